@@ -154,7 +154,7 @@ enum REPLCodePtr {
     ConcludeLoad,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$declare_module")))]
     DeclareModule,
-    #[strum_discriminants(strum(props(Arity = "3", Name = "$load_compiled_library")))]
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$load_compiled_library")))]
     LoadCompiledLibrary,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$prolog_lc_source")))]
     LoadContextSource,
@@ -172,20 +172,44 @@ enum REPLCodePtr {
     PopLoadStatePayload,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$push_load_state_payload")))]
     PushLoadStatePayload,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$push_module_load_state_payload")))]
+    PushModuleLoadStatePayload,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$load_state_module")))]
+    LoadStateModule,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$push_load_context")))]
     PushLoadContext,
-    #[strum_discriminants(strum(props(Arity = "3", Name = "$use_module")))]
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$set_load_context_position")))]
+    SetLoadContextPosition,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$use_module")))]
     UseModule,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$built_in_property")))]
     BuiltInProperty,
     #[strum_discriminants(strum(props(Arity = "4", Name = "$meta_predicate_property")))]
     MetaPredicateProperty,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$indexed_property")))]
+    IndexedProperty,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$fact_table_property")))]
+    FactTableProperty,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$multifile_property")))]
     MultifileProperty,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$discontiguous_property")))]
     DiscontiguousProperty,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$dynamic_property")))]
     DynamicProperty,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$clause_locs")))]
+    ClauseLocs,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$clause_source")))]
+    ClauseSource,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$modified_loaded_files")))]
+    ModifiedLoadedFiles,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$current_modules")))]
+    CurrentModules,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$module_class")))]
+    ModuleClass,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$module_file")))]
+    ModuleFile,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$module_exports")))]
+    ModuleExports,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$abolish_clause")))]
     AbolishClause,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$asserta")))]
@@ -202,6 +226,8 @@ enum REPLCodePtr {
     RemoveModuleExports,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$add_non_counted_backtracking")))]
     AddNonCountedBacktracking,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$add_fact_table")))]
+    AddFactTable,
 }
 
 #[allow(dead_code)]
@@ -410,6 +436,8 @@ enum SystemClauseType {
     GetDoubleQuotes,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$get_unknown")))]
     GetUnknown,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$get_backtrace")))]
+    GetBacktrace,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$install_new_block")))]
     InstallNewBlock,
     #[strum_discriminants(strum(props(Arity = "0", Name = "$maybe")))]
@@ -442,6 +470,8 @@ enum SystemClauseType {
     SetDoubleQuotes,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$set_unknown")))]
     SetUnknown,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$set_backtrace")))]
+    SetBacktrace,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$set_seed")))]
     SetSeed,
     #[strum_discriminants(strum(props(Arity = "4", Name = "$skip_max_list")))]
@@ -542,6 +572,32 @@ enum SystemClauseType {
     HomeDirectory,
     #[strum_discriminants(strum(props(Arity = "0", Name = "$debug_hook")))]
     DebugHook,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$profiler_start")))]
+    ProfilerStart,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$profiler_stop")))]
+    ProfilerStop,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$profiler_report")))]
+    ProfilerReport,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$profiler_write_folded")))]
+    ProfilerWriteFolded,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$profile_at_halt")))]
+    ProfileAtHalt,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$coverage_start")))]
+    CoverageStart,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$coverage_stop")))]
+    CoverageStop,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coverage_report")))]
+    CoverageReport,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coverage_write_lcov")))]
+    CoverageWriteLcov,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coverage_at_halt")))]
+    CoverageAtHalt,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$debugger_tracing")))]
+    DebuggerTracing,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$debug_call")))]
+    DebugCall,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$caught_backtrace")))]
+    CaughtBacktrace,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$popcount")))]
     PopCount,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$cpu_now")))]
@@ -562,6 +618,96 @@ enum SystemClauseType {
     ForeignCall,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$define_foreign_struct")))]
     DefineForeignStruct,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$call_native")))]
+    NativeCall,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$redo_native")))]
+    NativeRedo,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$native_choicepoint")))]
+    NativeChoicepoint,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$garbage_collect")))]
+    GarbageCollect,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$garbage_collect_atoms")))]
+    GarbageCollectAtoms,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$statistics")))]
+    Statistics,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$coroutine_create")))]
+    CoroutineCreate,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coroutine_finish")))]
+    CoroutineFinish,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$coroutine_join")))]
+    CoroutineJoin,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coroutine_self")))]
+    CoroutineSelf,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$coroutine_send_message")))]
+    CoroutineSendMessage,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coroutine_wait_message")))]
+    CoroutineWaitMessage,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$coroutine_peek_message")))]
+    CoroutinePeekMessage,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$coroutine_remove_message")))]
+    CoroutineRemoveMessage,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$engine_create")))]
+    EngineCreate,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$engine_next")))]
+    EngineNext,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$engine_yield")))]
+    EngineYield,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$engine_exception")))]
+    EngineException,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$engine_exhausted")))]
+    EngineExhausted,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$engine_destroy")))]
+    EngineDestroy,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$engine_self")))]
+    EngineSelf,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$save_program")))]
+    SaveProgram,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$save_executable")))]
+    SaveExecutable,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$add_tabled_predicate")))]
+    AddTabledPredicate,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tabled_predicate")))]
+    TabledPredicate,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$module_has_tabled_predicates")))]
+    ModuleHasTabledPredicates,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_variant_table")))]
+    TblVariantTable,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$tbl_set_active")))]
+    TblSetActive,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_add_answer")))]
+    TblAddAnswer,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_moded_answer")))]
+    TblModedAnswer,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_set_moded_answer")))]
+    TblSetModedAnswer,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_answer_count")))]
+    TblAnswerCount,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$tbl_answer")))]
+    TblAnswer,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_answer_conditions")))]
+    TblAnswerConditions,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_negation")))]
+    TblNegation,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_add_dependency")))]
+    TblAddDependency,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_pop_work")))]
+    TblPopWork,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$tbl_create_scc")))]
+    TblCreateScc,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$tbl_complete_scc")))]
+    TblCompleteScc,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$tbl_abandon_scc")))]
+    TblAbandonScc,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$tbl_abolish_all_tables")))]
+    TblAbolishAllTables,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_variant")))]
+    TblVariant,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_statistics")))]
+    TblStatistics,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$resource_limit")))]
+    ResourceLimit,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$set_resource_limit")))]
+    SetResourceLimit,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$predicate_defined")))]
     PredicateDefined,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$strip_module")))]
@@ -790,6 +936,15 @@ enum InstructionTemplate {
     // call verify_attrs.
     #[strum_discriminants(strum(props(Arity = "0", Name = "verify_attr_interrupt")))]
     VerifyAttrInterrupt,
+    // retry the next clause selected by a just-in-time index.
+    #[strum_discriminants(strum(props(Arity = "0", Name = "jit_retry")))]
+    JitRetry,
+    // call a fact table.
+    #[strum_discriminants(strum(props(Arity = "1", Name = "fact_table")))]
+    FactTable(usize),
+    // retry the next row of a fact table.
+    #[strum_discriminants(strum(props(Arity = "1", Name = "fact_table_retry")))]
+    FactTableRetry(usize),
     // procedures
     CallClause(ClauseType, usize, usize, bool, bool), // ClauseType,
                                                       // arity,
@@ -869,6 +1024,7 @@ fn generate_instruction_preface() -> TokenStream {
         use crate::machine::heap::*;
         use crate::machine::machine_errors::MachineStub;
         use crate::machine::machine_indices::CodeIndex;
+        use crate::machine::saved_state::*;
         use crate::parser::ast::*;
         use crate::types::*;
 
@@ -1239,6 +1395,15 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::VerifyAttrInterrupt => {
                         functor!(atom!("verify_attr_interrupt"))
                     }
+                    &Instruction::JitRetry => {
+                        functor!(atom!("jit_retry"))
+                    }
+                    &Instruction::FactTable(id) => {
+                        functor!(atom!("fact_table"), [fixnum(id)])
+                    }
+                    &Instruction::FactTableRetry(id) => {
+                        functor!(atom!("fact_table_retry"), [fixnum(id)])
+                    }
                     &Instruction::DynamicElse(birth, death, next_or_fail) => {
                         match (death, next_or_fail) {
                             (Death::Infinity, NextOrFail::Next(i)) => {
@@ -1769,6 +1934,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallGetCutPoint |
                     &Instruction::CallGetDoubleQuotes |
                     &Instruction::CallGetUnknown |
+                    &Instruction::CallGetBacktrace |
                     &Instruction::CallInstallNewBlock |
                     &Instruction::CallMaybe |
                     &Instruction::CallCpuNow |
@@ -1780,6 +1946,51 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
 		            &Instruction::CallLoadForeignLib |
 		            &Instruction::CallForeignCall |
 		            &Instruction::CallDefineForeignStruct |
+                    &Instruction::CallNativeCall |
+                    &Instruction::CallNativeRedo |
+                    &Instruction::CallNativeChoicepoint |
+                    &Instruction::CallGarbageCollect |
+                    &Instruction::CallGarbageCollectAtoms |
+                    &Instruction::CallStatistics |
+                    &Instruction::CallCoroutineCreate |
+                    &Instruction::CallCoroutineFinish |
+                    &Instruction::CallCoroutineJoin |
+                    &Instruction::CallCoroutineSelf |
+                    &Instruction::CallCoroutineSendMessage |
+                    &Instruction::CallCoroutineWaitMessage |
+                    &Instruction::CallCoroutinePeekMessage |
+                    &Instruction::CallCoroutineRemoveMessage |
+                    &Instruction::CallEngineCreate |
+                    &Instruction::CallEngineNext |
+                    &Instruction::CallEngineYield |
+                    &Instruction::CallEngineException |
+                    &Instruction::CallEngineExhausted |
+                    &Instruction::CallEngineDestroy |
+                    &Instruction::CallEngineSelf |
+                    &Instruction::CallSaveProgram |
+                    &Instruction::CallSaveExecutable |
+                    &Instruction::CallAddTabledPredicate |
+                    &Instruction::CallTabledPredicate |
+                    &Instruction::CallModuleHasTabledPredicates |
+                    &Instruction::CallTblVariantTable |
+                    &Instruction::CallTblSetActive |
+                    &Instruction::CallTblAddAnswer |
+                    &Instruction::CallTblModedAnswer |
+                    &Instruction::CallTblSetModedAnswer |
+                    &Instruction::CallTblAnswerCount |
+                    &Instruction::CallTblAnswer |
+                    &Instruction::CallTblAnswerConditions |
+                    &Instruction::CallTblNegation |
+                    &Instruction::CallTblAddDependency |
+                    &Instruction::CallTblPopWork |
+                    &Instruction::CallTblCreateScc |
+                    &Instruction::CallTblCompleteScc |
+                    &Instruction::CallTblAbandonScc |
+                    &Instruction::CallTblAbolishAllTables |
+                    &Instruction::CallTblVariant |
+                    &Instruction::CallTblStatistics |
+                    &Instruction::CallResourceLimit |
+                    &Instruction::CallSetResourceLimit |
                     &Instruction::CallPredicateDefined |
                     &Instruction::CallStripModule |
                     &Instruction::CallCurrentTime |
@@ -1796,6 +2007,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallSetCutPointByDefault(..) |
                     &Instruction::CallSetDoubleQuotes |
                     &Instruction::CallSetUnknown |
+                    &Instruction::CallSetBacktrace |
                     &Instruction::CallSetSeed |
                     &Instruction::CallSkipMaxList |
                     &Instruction::CallSleep |
@@ -1846,6 +2058,19 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallSetSTOWithErrorAsUnify |
                     &Instruction::CallHomeDirectory |
                     &Instruction::CallDebugHook |
+                    &Instruction::CallProfilerStart |
+                    &Instruction::CallProfilerStop |
+                    &Instruction::CallProfilerReport |
+                    &Instruction::CallProfilerWriteFolded |
+                    &Instruction::CallProfileAtHalt |
+                    &Instruction::CallCoverageStart |
+                    &Instruction::CallCoverageStop |
+                    &Instruction::CallCoverageReport |
+                    &Instruction::CallCoverageWriteLcov |
+                    &Instruction::CallCoverageAtHalt |
+                    &Instruction::CallDebuggerTracing |
+                    &Instruction::CallDebugCall |
+                    &Instruction::CallCaughtBacktrace |
                     &Instruction::CallAddDiscontiguousPredicate |
                     &Instruction::CallAddDynamicPredicate |
                     &Instruction::CallAddMultifilePredicate |
@@ -1865,13 +2090,25 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallPopLoadContext |
                     &Instruction::CallPopLoadStatePayload |
                     &Instruction::CallPushLoadContext |
+                    &Instruction::CallSetLoadContextPosition |
                     &Instruction::CallPushLoadStatePayload |
+                    &Instruction::CallPushModuleLoadStatePayload |
+                    &Instruction::CallLoadStateModule |
                     &Instruction::CallUseModule |
                     &Instruction::CallBuiltInProperty |
                     &Instruction::CallMetaPredicateProperty |
+                    &Instruction::CallIndexedProperty |
+                    &Instruction::CallFactTableProperty |
                     &Instruction::CallMultifileProperty |
                     &Instruction::CallDiscontiguousProperty |
                     &Instruction::CallDynamicProperty |
+                    &Instruction::CallClauseLocs |
+                    &Instruction::CallClauseSource |
+                    &Instruction::CallModifiedLoadedFiles |
+                    &Instruction::CallCurrentModules |
+                    &Instruction::CallModuleClass |
+                    &Instruction::CallModuleFile |
+                    &Instruction::CallModuleExports |
                     &Instruction::CallAbolishClause |
                     &Instruction::CallAsserta |
                     &Instruction::CallAssertz |
@@ -1880,6 +2117,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallFlushTermQueue |
                     &Instruction::CallRemoveModuleExports |
                     &Instruction::CallAddNonCountedBacktracking |
+                    &Instruction::CallAddFactTable |
                     &Instruction::CallPopCount => {
                         let (name, arity) = self.to_name_and_arity();
                         functor!(atom!("call"), [atom(name), fixnum(arity)])
@@ -1997,6 +2235,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteGetCutPoint |
                     &Instruction::ExecuteGetDoubleQuotes |
                     &Instruction::ExecuteGetUnknown |
+                    &Instruction::ExecuteGetBacktrace |
                     &Instruction::ExecuteInstallNewBlock |
                     &Instruction::ExecuteMaybe |
                     &Instruction::ExecuteCpuNow |
@@ -2008,6 +2247,51 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
 		            &Instruction::ExecuteLoadForeignLib |
 		            &Instruction::ExecuteForeignCall |
 		            &Instruction::ExecuteDefineForeignStruct |
+                    &Instruction::ExecuteNativeCall |
+                    &Instruction::ExecuteNativeRedo |
+                    &Instruction::ExecuteNativeChoicepoint |
+                    &Instruction::ExecuteGarbageCollect |
+                    &Instruction::ExecuteGarbageCollectAtoms |
+                    &Instruction::ExecuteStatistics |
+                    &Instruction::ExecuteCoroutineCreate |
+                    &Instruction::ExecuteCoroutineFinish |
+                    &Instruction::ExecuteCoroutineJoin |
+                    &Instruction::ExecuteCoroutineSelf |
+                    &Instruction::ExecuteCoroutineSendMessage |
+                    &Instruction::ExecuteCoroutineWaitMessage |
+                    &Instruction::ExecuteCoroutinePeekMessage |
+                    &Instruction::ExecuteCoroutineRemoveMessage |
+                    &Instruction::ExecuteEngineCreate |
+                    &Instruction::ExecuteEngineNext |
+                    &Instruction::ExecuteEngineYield |
+                    &Instruction::ExecuteEngineException |
+                    &Instruction::ExecuteEngineExhausted |
+                    &Instruction::ExecuteEngineDestroy |
+                    &Instruction::ExecuteEngineSelf |
+                    &Instruction::ExecuteSaveProgram |
+                    &Instruction::ExecuteSaveExecutable |
+                    &Instruction::ExecuteAddTabledPredicate |
+                    &Instruction::ExecuteTabledPredicate |
+                    &Instruction::ExecuteModuleHasTabledPredicates |
+                    &Instruction::ExecuteTblVariantTable |
+                    &Instruction::ExecuteTblSetActive |
+                    &Instruction::ExecuteTblAddAnswer |
+                    &Instruction::ExecuteTblModedAnswer |
+                    &Instruction::ExecuteTblSetModedAnswer |
+                    &Instruction::ExecuteTblAnswerCount |
+                    &Instruction::ExecuteTblAnswer |
+                    &Instruction::ExecuteTblAnswerConditions |
+                    &Instruction::ExecuteTblNegation |
+                    &Instruction::ExecuteTblAddDependency |
+                    &Instruction::ExecuteTblPopWork |
+                    &Instruction::ExecuteTblCreateScc |
+                    &Instruction::ExecuteTblCompleteScc |
+                    &Instruction::ExecuteTblAbandonScc |
+                    &Instruction::ExecuteTblAbolishAllTables |
+                    &Instruction::ExecuteTblVariant |
+                    &Instruction::ExecuteTblStatistics |
+                    &Instruction::ExecuteResourceLimit |
+                    &Instruction::ExecuteSetResourceLimit |
                     &Instruction::ExecutePredicateDefined |
                     &Instruction::ExecuteStripModule |
                     &Instruction::ExecuteCurrentTime |
@@ -2024,6 +2308,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteSetCutPointByDefault(_) |
                     &Instruction::ExecuteSetDoubleQuotes |
                     &Instruction::ExecuteSetUnknown |
+                    &Instruction::ExecuteSetBacktrace |
                     &Instruction::ExecuteSetSeed |
                     &Instruction::ExecuteSkipMaxList |
                     &Instruction::ExecuteSleep |
@@ -2074,6 +2359,19 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteSetSTOWithErrorAsUnify |
                     &Instruction::ExecuteHomeDirectory |
                     &Instruction::ExecuteDebugHook |
+                    &Instruction::ExecuteProfilerStart |
+                    &Instruction::ExecuteProfilerStop |
+                    &Instruction::ExecuteProfilerReport |
+                    &Instruction::ExecuteProfilerWriteFolded |
+                    &Instruction::ExecuteProfileAtHalt |
+                    &Instruction::ExecuteCoverageStart |
+                    &Instruction::ExecuteCoverageStop |
+                    &Instruction::ExecuteCoverageReport |
+                    &Instruction::ExecuteCoverageWriteLcov |
+                    &Instruction::ExecuteCoverageAtHalt |
+                    &Instruction::ExecuteDebuggerTracing |
+                    &Instruction::ExecuteDebugCall |
+                    &Instruction::ExecuteCaughtBacktrace |
                     &Instruction::ExecuteAddDiscontiguousPredicate |
                     &Instruction::ExecuteAddDynamicPredicate |
                     &Instruction::ExecuteAddMultifilePredicate |
@@ -2093,13 +2391,25 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecutePopLoadContext |
                     &Instruction::ExecutePopLoadStatePayload |
                     &Instruction::ExecutePushLoadContext |
+                    &Instruction::ExecuteSetLoadContextPosition |
                     &Instruction::ExecutePushLoadStatePayload |
+                    &Instruction::ExecutePushModuleLoadStatePayload |
+                    &Instruction::ExecuteLoadStateModule |
                     &Instruction::ExecuteUseModule |
                     &Instruction::ExecuteBuiltInProperty |
                     &Instruction::ExecuteMetaPredicateProperty |
+                    &Instruction::ExecuteIndexedProperty |
+                    &Instruction::ExecuteFactTableProperty |
                     &Instruction::ExecuteMultifileProperty |
                     &Instruction::ExecuteDiscontiguousProperty |
                     &Instruction::ExecuteDynamicProperty |
+                    &Instruction::ExecuteClauseLocs |
+                    &Instruction::ExecuteClauseSource |
+                    &Instruction::ExecuteModifiedLoadedFiles |
+                    &Instruction::ExecuteCurrentModules |
+                    &Instruction::ExecuteModuleClass |
+                    &Instruction::ExecuteModuleFile |
+                    &Instruction::ExecuteModuleExports |
                     &Instruction::ExecuteAbolishClause |
                     &Instruction::ExecuteAsserta |
                     &Instruction::ExecuteAssertz |
@@ -2108,6 +2418,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteFlushTermQueue |
                     &Instruction::ExecuteRemoveModuleExports |
                     &Instruction::ExecuteAddNonCountedBacktracking |
+                    &Instruction::ExecuteAddFactTable |
                     &Instruction::ExecutePopCount => {
                         let (name, arity) = self.to_name_and_arity();
                         functor!(atom!("execute"), [atom(name), fixnum(arity)])
@@ -3055,6 +3366,113 @@ fn attributeless_enum<T: ToDeriveInput>() -> Vec<Variant> {
         })
         .collect();
 
+    let mut save_arms = vec![];
+    let mut restore_arms = vec![];
+
+    for (idx, (_, _, _, variant)) in instr_data.instr_variants.iter().enumerate() {
+        let variant_ident = variant.ident.clone();
+
+        let enum_arity = if let Fields::Unnamed(fields) = &variant.fields {
+            fields.unnamed.len()
+        } else {
+            0
+        };
+
+        let placeholder_ids: Vec<_> = (0 .. enum_arity)
+            .map(|n| format_ident!("f_{}", n))
+            .collect();
+
+        let restored_fields: Vec<_> = (0 .. enum_arity)
+            .map(|_| quote! { Persist::restore(reader)? })
+            .collect();
+
+        if enum_arity == 0 {
+            save_arms.push(quote! {
+                Instruction::#variant_ident => writer.write_usize(#idx)
+            });
+
+            restore_arms.push(quote! {
+                #idx => Ok(Instruction::#variant_ident)
+            });
+        } else {
+            save_arms.push(quote! {
+                Instruction::#variant_ident(#(#placeholder_ids),*) => {
+                    writer.write_usize(#idx)?;
+                    #(
+                        #placeholder_ids.save(writer)?;
+                    )*
+                    Ok(())
+                }
+            });
+
+            restore_arms.push(quote! {
+                #idx => Ok(Instruction::#variant_ident(#(#restored_fields),*))
+            });
+        }
+    }
+
+    let atom_type: Type = parse_quote! { Atom };
+    let cell_type: Type = parse_quote! { HeapCellValue };
+    let indexing_type: Type = parse_quote! { Vec<IndexingLine> };
+    let clause_type: Type = parse_quote! { ClauseType };
+
+    let mut visit_operands_arms = vec![];
+
+    for (_, _, _, variant) in &instr_data.instr_variants {
+        let variant_ident = variant.ident.clone();
+
+        let fields: Vec<_> = if let Fields::Unnamed(fields) = &variant.fields {
+            fields.unnamed.iter().collect()
+        } else {
+            vec![]
+        };
+
+        if fields.is_empty() {
+            visit_operands_arms.push(quote! {
+                Instruction::#variant_ident => {}
+            });
+
+            continue;
+        }
+
+        let mut patterns = vec![];
+        let mut visits = vec![];
+
+        for (n, field) in fields.iter().enumerate() {
+            let id = format_ident!("f_{}", n);
+
+            if field.ty == atom_type {
+                visits.push(quote! { visitor.visit_atom(*#id); });
+            } else if field.ty == cell_type {
+                visits.push(quote! { visitor.visit_cell(*#id); });
+            } else if field.ty == indexing_type {
+                visits.push(quote! { visitor.visit_indexing_lines(#id); });
+            } else if field.ty == clause_type {
+                visits.push(quote! { visitor.visit_atom(#id.name()); });
+            } else {
+                patterns.push(quote! { _ });
+                continue;
+            }
+
+            patterns.push(quote! { #id });
+        }
+
+        visit_operands_arms.push(quote! {
+            Instruction::#variant_ident(#(#patterns),*) => {
+                #(#visits)*
+            }
+        });
+    }
+
+    // saved states record this digest of the instruction set, so a
+    // state saved by a build with different instructions is refused.
+    let instructions_digest = quote! { #(#instr_variants,)* }
+        .to_string()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+
     let name_and_arity_arms: Vec<_> = instr_data.instr_variants
         .into_iter()
         .map(|(name,arity,_,variant)| {
@@ -3233,6 +3651,10 @@ pub fn to_execute(self) -> Instruction {
 
             pub fn is_execute(&self) -> bool {
                 match self {
+                    // fact tables continue at cp once they're done,
+                    // like the execute of a builtin.
+                    &Instruction::FactTable(_) |
+                    &Instruction::FactTableRetry(_) => true,
                     #(
                         #is_execute_arms,
                     )*
@@ -3273,6 +3695,45 @@ pub fn is_query_instr(&self) -> bool {
             }
         }
 
+        /// Receives the atoms, constants and indexing code held in the
+        /// operands of an instruction.
+        pub(crate) trait OperandVisitor {
+            fn visit_atom(&mut self, atom: Atom);
+            fn visit_cell(&mut self, cell: HeapCellValue);
+            fn visit_indexing_lines(&mut self, indexing_lines: &[IndexingLine]);
+        }
+
+        impl Instruction {
+            pub(crate) fn visit_operands(&self, visitor: &mut impl OperandVisitor) {
+                match self {
+                    #(
+                        #visit_operands_arms,
+                    )*
+                }
+            }
+        }
+
+        pub(crate) const INSTRUCTIONS_DIGEST: u64 = #instructions_digest;
+
+        impl Persist for Instruction {
+            fn save(&self, writer: &mut StateWriter) -> std::io::Result<()> {
+                match self {
+                    #(
+                        #save_arms,
+                    )*
+                }
+            }
+
+            fn restore(reader: &mut StateReader) -> std::io::Result<Self> {
+                match reader.read_usize()? {
+                    #(
+                        #restore_arms,
+                    )*
+                    _ => Err(invalid_state("unknown instruction")),
+                }
+            }
+        }
+
         #[macro_export]
         macro_rules! _instr {
             #(
@@ -4,6 +4,7 @@
 use crate::machine::streams::*;
 use crate::raw_block::*;
 use crate::read::*;
+use crate::types::UntypedArenaPtr;
 
 use ordered_float::OrderedFloat;
 use crate::parser::dashu::{Integer, Rational};
@@ -673,6 +674,41 @@ impl Arena {
     pub fn new() -> Self {
         Arena { base: ptr::null_mut(), f64_tbl: F64Table::new() }
     }
+
+    /// The number of allocations in the arena and the number of bytes
+    /// they occupy, including their headers.
+    pub(crate) fn usage(&self) -> (usize, usize) {
+        let mut ptr = self.base as *const AllocSlab;
+        let mut slabs = 0;
+        let mut bytes = 0;
+
+        while !ptr.is_null() {
+            let slab = unsafe { &*ptr };
+
+            slabs += 1;
+            bytes += slab.slab_size();
+
+            ptr = slab.next;
+        }
+
+        (slabs, bytes)
+    }
+
+    /// The allocations of the arena, most recent first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = UntypedArenaPtr> + '_ {
+        let mut ptr = self.base as *const AllocSlab;
+
+        std::iter::from_fn(move || {
+            if ptr.is_null() {
+                return None;
+            }
+
+            let slab = unsafe { &*ptr };
+            ptr = slab.next;
+
+            Some(UntypedArenaPtr::from(&slab.header as *const ArenaHeader))
+        })
+    }
 }
 
 unsafe fn drop_slab_in_place(value: &mut AllocSlab) {
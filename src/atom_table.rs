@@ -4,12 +4,14 @@
 
 use std::borrow::Borrow;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::mem;
 use std::ptr;
 use std::slice;
 use std::str;
 
+use bit_set::BitSet;
 use indexmap::IndexSet;
 
 use modular_bitfield::prelude::*;
@@ -94,6 +96,28 @@ fn atomtable_is_not_concurrency_safe() {
     let _table_b = AtomTable::new();
 }
 
+#[test]
+fn collected_atoms_are_reused() {
+    let mut atom_tbl = AtomTable::new();
+
+    let a = atom_tbl.build_with("a dynamic atom");
+    let b = atom_tbl.build_with("another dynamic atom");
+
+    let mut marks = AtomMarks::default();
+    marks.mark(b);
+
+    assert_eq!(atom_tbl.sweep(&marks), 1);
+    assert_eq!(atom_tbl.table.len(), 1);
+
+    // an atom of the same length takes the place of the collected one.
+    let c = atom_tbl.build_with("a dynamic mota");
+
+    assert_eq!(c.index, a.index);
+    assert_eq!(c.as_str(), "a dynamic mota");
+    assert_eq!(b.as_str(), "another dynamic atom");
+    assert_eq!(atom_tbl.build_with("another dynamic atom"), b);
+}
+
 impl RawBlockTraits for AtomTable {
     #[inline]
     fn init_size() -> usize {
@@ -232,6 +256,16 @@ pub fn defrock_brackets(&self, atom_tbl: &mut AtomTable) -> Self {
     }
 }
 
+// the number of bytes allocated in the atom table for a string of len
+// bytes. Allocations are multiples of the alignment of the atom
+// indices, i.e. of 8 bytes.
+#[inline(always)]
+fn atom_alloc_size(len: usize) -> usize {
+    let size = mem::size_of::<AtomHeader>() + len;
+    let align_offset = 8 * mem::align_of::<AtomHeader>();
+    (size & !(align_offset - 1)) + align_offset
+}
+
 unsafe fn write_to_ptr(string: &str, ptr: *mut u8) {
     ptr::write(ptr as *mut _, AtomHeader::build_with(string.len() as u64));
     let str_ptr = (ptr as usize + mem::size_of::<AtomHeader>()) as *mut u8;
@@ -256,6 +290,34 @@ fn cmp(&self, other: &Atom) -> Ordering {
 pub struct AtomTable {
     block: RawBlock<AtomTable>,
     pub table: IndexSet<Atom>,
+    // the offsets of the allocations of collected atoms, keyed by
+    // their size. Atoms are identified by their offsets, so they can
+    // be reused but never moved.
+    free_slots: BTreeMap<usize, Vec<usize>>,
+    free_bytes: usize,
+}
+
+/// The dynamic atoms found to be live by the atom collector. Each
+/// allocation in the atom table is identified by its offset in units
+/// of 8 bytes, so that marking an index that doesn't belong to a live
+/// atom is harmless.
+#[derive(Debug, Default)]
+pub(crate) struct AtomMarks {
+    marks: BitSet<usize>,
+}
+
+impl AtomMarks {
+    #[inline]
+    pub(crate) fn mark(&mut self, atom: Atom) {
+        if !atom.is_static() {
+            self.marks.insert((atom.index - (STRINGS.len() << 3)) >> 3);
+        }
+    }
+
+    #[inline]
+    fn is_marked(&self, atom: Atom) -> bool {
+        atom.is_static() || self.marks.contains((atom.index - (STRINGS.len() << 3)) >> 3)
+    }
 }
 
 #[cold]
@@ -286,6 +348,8 @@ pub fn new() -> Self {
         Self {
             block,
             table: IndexSet::new(),
+            free_slots: BTreeMap::new(),
+            free_bytes: 0,
         }
     }
 
@@ -299,6 +363,52 @@ pub fn top(&self) -> *const u8 {
         self.block.top
     }
 
+    /// The number of bytes held by the dynamic atoms of the table.
+    #[inline]
+    pub(crate) fn bytes_used(&self) -> usize {
+        self.block.ptr as usize - self.block.base as usize - self.free_bytes
+    }
+
+    /// The total number of atoms, static and dynamic.
+    #[inline]
+    pub(crate) fn atom_count(&self) -> usize {
+        STRINGS.len() + self.table.len()
+    }
+
+    /// Removes the dynamic atoms not in `marks` from the table, making
+    /// their storage available to `build_with`. Returns the number of
+    /// atoms collected.
+    pub(crate) fn sweep(&mut self, marks: &AtomMarks) -> usize {
+        let base = STRINGS.len() << 3;
+        let len = self.table.len();
+
+        let free_slots = &mut self.free_slots;
+        let free_bytes = &mut self.free_bytes;
+
+        self.table.retain(|&atom| {
+            if marks.is_marked(atom) {
+                return true;
+            }
+
+            let size = atom_alloc_size(atom.len());
+
+            free_slots.entry(size).or_default().push(atom.index - base);
+            *free_bytes += size;
+
+            false
+        });
+
+        len - self.table.len()
+    }
+
+    // an allocation of size bytes left by a collected atom.
+    #[inline]
+    fn take_free_slot(&mut self, size: usize) -> Option<*mut u8> {
+        let offset = self.free_slots.get_mut(&size)?.pop()?;
+        self.free_bytes -= size;
+        Some((self.block.base as usize + offset) as *mut u8)
+    }
+
     #[inline(always)]
     fn lookup_str(&self, string: &str) -> Option<Atom> {
         STATIC_ATOMS_MAP.get(string).or_else(|| self.table.get(string)).cloned()
@@ -310,11 +420,11 @@ pub fn build_with(&mut self, string: &str) -> Atom {
         }
 
         unsafe {
-            let size = mem::size_of::<AtomHeader>() + string.len();
-            let align_offset = 8 * mem::align_of::<AtomHeader>();
-            let size = (size & !(align_offset - 1)) + align_offset;
+            let size = atom_alloc_size(string.len());
 
-            let len_ptr = {
+            let len_ptr = if let Some(ptr) = self.take_free_slot(size) {
+                ptr
+            } else {
                 let mut ptr;
 
                 loop {
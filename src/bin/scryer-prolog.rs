@@ -6,6 +6,28 @@ fn main() -> std::process::ExitCode {
         scryer_prolog::machine::INTERRUPT.store(true, Ordering::Relaxed);
     }).unwrap();
 
-    let mut wam = machine::Machine::new();
+    // an executable written by qsave_program/2 starts from the state
+    // embedded in it, and leaves all of its arguments to the program.
+    let embedded = machine::config::MachineBuilder::default()
+        .with_add_history(false)
+        .restore_embedded();
+
+    let restored = match embedded {
+        Ok(Some(wam)) => Ok(wam),
+        Ok(None) => machine::Machine::from_args(),
+        Err(err) => {
+            eprintln!("scryer-prolog: can't restore the embedded state: {}", err);
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+
+    let mut wam = match restored {
+        Ok(wam) => wam,
+        Err(err) => {
+            eprintln!("scryer-prolog: {}", err);
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+
     wam.run_top_level()
 }
@@ -1229,6 +1229,20 @@ pub(crate) fn compile_predicate(
                         clause_start_offset + 2 * (segment_is_indexed as usize);
                     clause_index_info.opt_arg_index_key += clause_start_offset + 1;
                 }
+            } else {
+                // the clauses of static predicates start past the
+                // choice instruction of their segment and its indexing
+                // code, if any.
+                let segment_is_indexed = code_segment[0].to_indexing_line().is_some();
+
+                for clause_index_info in self.skeleton.clauses
+                                             .make_contiguous()[skel_lower_bound..]
+                                             .iter_mut()
+                {
+                    clause_index_info.clause_start += clause_start_offset
+                        + multi_seq as usize
+                        + segment_is_indexed as usize;
+                }
             }
 
             code.extend(code_segment.into_iter());
@@ -13,7 +13,7 @@
 
 use fxhash::FxBuildHasher;
 
-use indexmap::{IndexMap, IndexSet};
+use indexmap::IndexMap;
 use ordered_float::OrderedFloat;
 
 use std::cell::Cell;
@@ -386,12 +386,13 @@ pub enum MetaSpec {
 #[derive(Debug, Clone)]
 pub enum Declaration {
     Dynamic(Atom, usize),
+    FactTable(Atom, usize), // name, arity
     MetaPredicate(Atom, Atom, Vec<MetaSpec>), // module name, name, meta-specs
     Module(ModuleDecl),
     NonCountedBacktracking(Atom, usize), // name, arity
     Op(OpDecl),
     UseModule(ModuleSource),
-    UseQualifiedModule(ModuleSource, IndexSet<ModuleExport>),
+    UseQualifiedModule(ModuleSource, ImportList),
 }
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Ord, PartialOrd)]
@@ -576,6 +577,34 @@ pub enum ModuleExport {
     PredicateKey(PredicateKey),
 }
 
+// the import list of use_module/2 and reexport/2, mapping the exports
+// it lists to the names they're renamed to by `as`, if any.
+#[derive(Debug, Clone)]
+pub enum ImportList {
+    Only(IndexMap<ModuleExport, Option<Atom>>),
+    Except(IndexMap<ModuleExport, Option<Atom>>),
+}
+
+impl ImportList {
+    /// The export as it's imported, renamed if the import list renames
+    /// it, or `None` if it isn't imported.
+    pub(crate) fn import(&self, export: &ModuleExport) -> Option<ModuleExport> {
+        let (listed, except) = match self {
+            ImportList::Only(exports) => (exports.get(export), false),
+            ImportList::Except(exports) => (exports.get(export), true),
+        };
+
+        match (listed, export) {
+            (Some(Some(name)), ModuleExport::PredicateKey((_, arity))) => {
+                Some(ModuleExport::PredicateKey((*name, *arity)))
+            }
+            (Some(_), _) if except => None,
+            (None, _) if !except => None,
+            _ => Some(export.clone()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ModuleDecl {
     pub(crate) name: Atom,
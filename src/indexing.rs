@@ -3,6 +3,7 @@
 
 use crate::forms::*;
 use crate::instructions::*;
+use crate::types::*;
 
 use fxhash::FxBuildHasher;
 use indexmap::IndexMap;
@@ -485,6 +486,9 @@ fn add_dynamic_indexed_choice_for_structure(
         }
     }
 
+    // switches on the principal functor only. The first argument of a
+    // compound term is looked into by the just-in-time index alone, see
+    // JitIndex.
     fn index_structure(&mut self, key: PredicateKey, index: usize) {
         loop {
             let indexing_code_len = self.indexing_code.len();
@@ -1556,3 +1560,586 @@ pub(crate) fn compute_indices(mut self, skip_stub_try_me_else: bool) -> Vec<Inde
         prelude.into_iter().collect()
     }
 }
+
+/// The fewest clauses a predicate must have before it's indexed just
+/// in time.
+pub(crate) const JIT_INDEX_MIN_CLAUSES: usize = 8;
+
+/// The number of calls made to a predicate since it was last changed
+/// before a just-in-time index is built for it. Predicates modified
+/// between most of their calls are never worth indexing.
+pub(crate) const JIT_INDEX_MIN_CALLS: usize = 4;
+
+/// The principal functor of an argument, as hashed by a just-in-time
+/// index. Strings and lists share a key, as do one-character atoms and
+/// characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum JitKey {
+    Atom(Atom),
+    Char(char),
+    Fixnum(i64),
+    List,
+    Structure(Atom, usize),
+}
+
+impl JitKey {
+    pub(crate) fn from_atom(name: Atom) -> Self {
+        match name.as_char() {
+            Some(c) => JitKey::Char(c),
+            None => JitKey::Atom(name),
+        }
+    }
+
+    pub(crate) fn from_functor(name: Atom, arity: usize) -> Self {
+        if arity == 0 {
+            JitKey::from_atom(name)
+        } else if name == atom!(".") && arity == 2 {
+            JitKey::List
+        } else {
+            JitKey::Structure(name, arity)
+        }
+    }
+
+    /// The principal functor of `addr`, a dereferenced cell of `heap`,
+    /// or None if `addr` is unbound or can't be keyed.
+    pub(crate) fn from_cell(heap: &[HeapCellValue], addr: HeapCellValue) -> Option<Self> {
+        read_heap_cell!(addr,
+            (HeapCellValueTag::Lis | HeapCellValueTag::PStrLoc) => {
+                Some(JitKey::List)
+            }
+            (HeapCellValueTag::Str, s) => {
+                let (name, arity) = cell_as_atom_cell!(heap[s]).get_name_and_arity();
+                Some(JitKey::from_functor(name, arity))
+            }
+            _ => {
+                JitKey::from_constant(addr)
+            }
+        )
+    }
+
+    // floats, big integers and rationals aren't keyed.
+    fn from_constant(value: HeapCellValue) -> Option<Self> {
+        read_heap_cell!(value,
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                Some(JitKey::from_atom(name))
+            }
+            (HeapCellValueTag::Char, c) => {
+                Some(JitKey::Char(c))
+            }
+            (HeapCellValueTag::Fixnum, n) => {
+                Some(JitKey::Fixnum(n.get_num()))
+            }
+            (HeapCellValueTag::CStr) => {
+                Some(JitKey::List)
+            }
+            _ => {
+                None
+            }
+        )
+    }
+}
+
+/// The keys of an instantiated argument of a call or a clause head: its
+/// principal functor and, if it's a compound term or a list, the
+/// principal functor of its first argument, if that's instantiated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct JitArgKey {
+    pub(crate) key: JitKey,
+    pub(crate) deep: Option<JitKey>,
+}
+
+impl JitArgKey {
+    #[inline]
+    pub(crate) fn new(key: JitKey, deep: Option<JitKey>) -> Self {
+        JitArgKey { key, deep }
+    }
+
+    /// The keys of `addr`, a dereferenced cell of `heap`. `deref` is
+    /// used to dereference the first argument of a compound term or the
+    /// head of a list.
+    pub(crate) fn from_cell(
+        heap: &[HeapCellValue],
+        addr: HeapCellValue,
+        deref: impl Fn(HeapCellValue) -> HeapCellValue,
+    ) -> Option<Self> {
+        let key = JitKey::from_cell(heap, addr)?;
+
+        let deep = read_heap_cell!(addr,
+            (HeapCellValueTag::Lis, l) => {
+                JitKey::from_cell(heap, deref(heap[l]))
+            }
+            (HeapCellValueTag::Str, s) => {
+                let arity = cell_as_atom_cell!(heap[s]).get_arity();
+
+                if arity > 0 {
+                    JitKey::from_cell(heap, deref(heap[s+1]))
+                } else {
+                    None
+                }
+            }
+            _ => {
+                None
+            }
+        );
+
+        Some(JitArgKey::new(key, deep))
+    }
+
+    // true if a call argument keyed by self may unify with a head
+    // argument keyed by other.
+    pub(crate) fn matches(&self, other: &JitArgKey) -> bool {
+        self.key == other.key
+            && match (self.deep, other.deep) {
+                (Some(deep), Some(other_deep)) => deep == other_deep,
+                _ => true,
+            }
+    }
+}
+
+// the key of the first argument of the compound head argument whose
+// first Unify instruction is at `p`. Subterms bound to registers are
+// keyed by the first Get instruction of the register that follows.
+fn head_subterm_key(code: &Code, mut p: usize) -> Option<JitKey> {
+    let r = match &code[p] {
+        &Instruction::UnifyConstant(value) => return JitKey::from_constant(value),
+        &Instruction::UnifyVariable(RegType::Temp(r)) => r,
+        _ => return None,
+    };
+
+    p += 1;
+
+    loop {
+        match &code[p] {
+            &Instruction::GetConstant(_, value, RegType::Temp(t)) if t == r => {
+                return JitKey::from_constant(value);
+            }
+            &Instruction::GetStructure(_, name, arity, RegType::Temp(t)) if t == r => {
+                return Some(JitKey::from_functor(name, arity));
+            }
+            &Instruction::GetList(_, RegType::Temp(t)) |
+            &Instruction::GetPartialString(_, _, RegType::Temp(t), _) if t == r => {
+                return Some(JitKey::List);
+            }
+            &Instruction::UnifyVariable(RegType::Temp(t)) |
+            &Instruction::GetVariable(RegType::Temp(t), _) if t == r => {
+                return None;
+            }
+            Instruction::GetConstant(..) |
+            Instruction::GetList(..) |
+            Instruction::GetStructure(..) |
+            Instruction::GetPartialString(..) |
+            &Instruction::UnifyVoid(..) |
+            &Instruction::UnifyConstant(..) |
+            &Instruction::GetVariable(..) |
+            &Instruction::GetValue(..) |
+            &Instruction::UnifyVariable(..) |
+            &Instruction::UnifyValue(..) |
+            &Instruction::UnifyLocalValue(..) => {
+                p += 1;
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+}
+
+// the keys of argument `arg` in the head of the clause at `p`, or None
+// if the argument is a variable or can't be keyed.
+fn clause_head_key(code: &Code, mut p: usize, arg: usize) -> Option<JitArgKey> {
+    if let Instruction::Allocate(_) = &code[p] {
+        p += 1;
+    }
+
+    loop {
+        match &code[p] {
+            &Instruction::GetConstant(Level::Shallow, value, RegType::Temp(t)) if t == arg => {
+                return JitKey::from_constant(value).map(|key| JitArgKey::new(key, None));
+            }
+            &Instruction::GetStructure(Level::Shallow, name, arity, RegType::Temp(t)) if t == arg => {
+                let key = JitKey::from_functor(name, arity);
+                let deep = if arity > 0 { head_subterm_key(code, p + 1) } else { None };
+
+                return Some(JitArgKey::new(key, deep));
+            }
+            &Instruction::GetList(Level::Shallow, RegType::Temp(t)) if t == arg => {
+                return Some(JitArgKey::new(JitKey::List, head_subterm_key(code, p + 1)));
+            }
+            &Instruction::GetPartialString(Level::Shallow, _, RegType::Temp(t), _) if t == arg => {
+                return Some(JitArgKey::new(JitKey::List, None));
+            }
+            Instruction::GetConstant(..) |
+            Instruction::GetList(..) |
+            Instruction::GetStructure(..) |
+            Instruction::GetPartialString(..) |
+            &Instruction::UnifyVoid(..) |
+            &Instruction::UnifyConstant(..) |
+            &Instruction::GetVariable(..) |
+            &Instruction::GetValue(..) |
+            &Instruction::UnifyVariable(..) |
+            &Instruction::UnifyValue(..) |
+            &Instruction::UnifyLocalValue(..) => {
+                p += 1;
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct JitClause {
+    // the location of the clause code.
+    p: usize,
+    // the locations of the dynamic clause headers guarding the clause,
+    // which must all be alive for the clause to be.
+    guards: Vec<usize>,
+    // the keys of each argument of the clause head, read from its code
+    // once as the index is built.
+    keys: Vec<Option<JitArgKey>>,
+}
+
+impl JitClause {
+    fn is_alive(&self, code: &Code, cc: usize) -> bool {
+        self.guards.iter().all(|&g| match &code[g] {
+            &Instruction::DynamicElse(birth, death, _) |
+            &Instruction::DynamicInternalElse(birth, death, _) => {
+                birth < cc && Death::Finite(cc) <= death
+            }
+            _ => true,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct JitArgIndex {
+    // the ordinals of the clauses keyed by each principal functor, in
+    // clause order.
+    keyed: IndexMap<JitKey, Vec<usize>, FxBuildHasher>,
+    // the ordinals of the clauses keyed by each principal functor and
+    // the principal functor of its first argument.
+    deep: IndexMap<(JitKey, JitKey), Vec<usize>, FxBuildHasher>,
+    // the ordinals of the clauses keyed by each principal functor
+    // whose first argument is a variable or can't be keyed.
+    shallow: IndexMap<JitKey, Vec<usize>, FxBuildHasher>,
+    // the ordinals of the clauses whose argument is a variable or
+    // can't be keyed. They match every call.
+    unkeyed: Vec<usize>,
+}
+
+impl JitArgIndex {
+    pub(crate) fn insert(&mut self, i: usize, key: Option<JitArgKey>) {
+        let key = match key {
+            Some(key) => key,
+            None => {
+                self.unkeyed.push(i);
+                return;
+            }
+        };
+
+        self.keyed.entry(key.key).or_default().push(i);
+
+        match key.deep {
+            Some(deep) => self.deep.entry((key.key, deep)).or_default().push(i),
+            None => self.shallow.entry(key.key).or_default().push(i),
+        }
+    }
+
+    // the ordinal lists whose union is the set of clauses matching a
+    // call argument keyed by `key`.
+    pub(crate) fn buckets(&self, key: JitArgKey) -> [&[usize]; 3] {
+        let bucket = |ordinals: Option<&Vec<usize>>| ordinals.map(Vec::as_slice).unwrap_or(&[]);
+
+        match key.deep {
+            Some(deep) => [
+                bucket(self.deep.get(&(key.key, deep))),
+                bucket(self.shallow.get(&key.key)),
+                &self.unkeyed,
+            ],
+            None => [bucket(self.keyed.get(&key.key)), &[], &self.unkeyed],
+        }
+    }
+}
+
+/// A just-in-time index of the clauses of a predicate. The hash index
+/// of an argument is built the first time the argument is instantiated
+/// in a call to the predicate. Compound terms and lists are also hashed
+/// on the principal functor of their first argument, so that clauses
+/// like `eval(op(add, X, Y), ...)` and `eval(op(mul, X, Y), ...)` fall
+/// into different buckets. The index is discarded whenever the clauses
+/// of the predicate change.
+///
+/// This is the only index that looks into compound arguments: the
+/// compiled first argument index switches on principal functors alone.
+/// A predicate with fewer than `JIT_INDEX_MIN_CLAUSES` clauses, or
+/// called fewer than `JIT_INDEX_MIN_CALLS` times since it last
+/// changed, tries the clauses sharing a principal functor in order.
+#[derive(Debug)]
+pub(crate) enum JitIndex {
+    // the predicate has been called this many times since it was last
+    // changed.
+    Pending(usize),
+    // the predicate has too few clauses, or clauses laid out in a way
+    // the index doesn't follow.
+    Unindexable,
+    Built {
+        clauses: Vec<JitClause>,
+        args: Vec<Option<JitArgIndex>>,
+        // the ordinals of the clauses selected by the hash index of an
+        // argument for a key, kept for the calls that follow.
+        selections: IndexMap<(usize, JitArgKey), Vec<usize>, FxBuildHasher>,
+    },
+}
+
+impl Default for JitIndex {
+    #[inline]
+    fn default() -> Self {
+        JitIndex::Pending(0)
+    }
+}
+
+impl JitIndex {
+    /// Counts a call to the predicate whose code starts at `p`, building
+    /// its index once it has been called often enough. Returns true if
+    /// the index is built.
+    pub(crate) fn prepare(&mut self, code: &Code, p: usize, arity: usize) -> bool {
+        match self {
+            JitIndex::Pending(calls) if *calls + 1 < JIT_INDEX_MIN_CALLS => {
+                *calls += 1;
+                false
+            }
+            JitIndex::Pending(_) => {
+                let mut clauses = vec![];
+                let mut steps = 0;
+
+                *self = match collect_jit_clauses(code, p, &mut vec![], &mut clauses, &mut steps) {
+                    Some(()) if clauses.len() >= JIT_INDEX_MIN_CLAUSES => {
+                        for clause in &mut clauses {
+                            clause.keys = (1 ..= arity)
+                                .map(|arg| clause_head_key(code, clause.p, arg))
+                                .collect();
+                        }
+
+                        JitIndex::Built {
+                            clauses,
+                            args: (0..arity).map(|_| None).collect(),
+                            selections: IndexMap::with_hasher(FxBuildHasher::default()),
+                        }
+                    }
+                    _ => JitIndex::Unindexable,
+                };
+
+                self.is_built()
+            }
+            JitIndex::Unindexable => false,
+            JitIndex::Built { .. } => true,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_built(&self) -> bool {
+        matches!(self, JitIndex::Built { .. })
+    }
+
+    /// True if the predicate was found not to be indexable. Its index
+    /// is never built unless its clauses change.
+    #[inline]
+    pub(crate) fn is_unindexable(&self) -> bool {
+        matches!(self, JitIndex::Unindexable)
+    }
+
+    /// The number of clauses indexed.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            JitIndex::Built { clauses, .. } => clauses.len(),
+            _ => 0,
+        }
+    }
+
+    /// The 1-based positions of the arguments with a hash index.
+    pub(crate) fn indexed_args(&self) -> Vec<usize> {
+        match self {
+            JitIndex::Built { args, .. } => args
+                .iter()
+                .enumerate()
+                .filter_map(|(i, arg)| arg.as_ref().map(|_| i + 1))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // the clauses and the ordinals of those matching a call whose
+    // argument `arg` is keyed by `key`, in clause order. The hash index
+    // of the argument is built on first use. Selections of keys
+    // appearing in the clauses are kept for the calls that follow.
+    fn selection(&mut self, arg: usize, key: JitArgKey) -> Option<(&[JitClause], &[usize])> {
+        let (clauses, args, selections) = match self {
+            JitIndex::Built { clauses, args, selections } => (clauses.as_slice(), args, selections),
+            _ => return None,
+        };
+
+        if !selections.contains_key(&(arg, key)) {
+            let arg_index = args[arg - 1].get_or_insert_with(|| {
+                let mut arg_index = JitArgIndex::default();
+
+                for (i, clause) in clauses.iter().enumerate() {
+                    arg_index.insert(i, clause.keys[arg - 1]);
+                }
+
+                arg_index
+            });
+
+            let [deep, shallow, unkeyed] = arg_index.buckets(key);
+
+            if deep.is_empty() && shallow.is_empty() {
+                return Some((clauses, unkeyed));
+            }
+
+            let selection = merge_ordinals(&merge_ordinals(deep, shallow), unkeyed);
+            selections.insert((arg, key), selection);
+        }
+
+        Some((clauses, selections[&(arg, key)].as_slice()))
+    }
+
+    /// An upper bound on the number of clauses matching a call whose
+    /// argument `arg` is keyed by `key`.
+    pub(crate) fn count(&mut self, arg: usize, key: JitArgKey) -> usize {
+        self.selection(arg, key).map(|(_, selection)| selection.len()).unwrap_or(0)
+    }
+
+    /// The locations of the clauses alive at clock `cc` that match a
+    /// call whose arguments are keyed by `keys`, in clause order. The
+    /// clauses are selected through the hash index of argument `arg`,
+    /// which must be keyed, and filtered by the keys of the others.
+    pub(crate) fn matching_clauses<'a>(
+        &'a mut self,
+        code: &'a Code,
+        arg: usize,
+        keys: &'a [Option<JitArgKey>],
+        cc: usize,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let (clauses, selection) = match keys[arg - 1] {
+            Some(key) => self.selection(arg, key).unwrap_or_default(),
+            None => Default::default(),
+        };
+
+        selection
+            .iter()
+            .map(move |&i| &clauses[i])
+            .filter(move |clause| {
+                keys.iter().zip(&clause.keys).enumerate().all(|(i, keys)| match keys {
+                    (Some(key), Some(head_key)) if i + 1 != arg => key.matches(head_key),
+                    _ => true,
+                })
+            })
+            .filter(move |clause| clause.is_alive(code, cc))
+            .map(|clause| clause.p)
+    }
+}
+
+pub(crate) fn merge_ordinals(xs: &[usize], ys: &[usize]) -> Vec<usize> {
+    let mut merged = Vec::with_capacity(xs.len() + ys.len());
+    let (mut i, mut j) = (0, 0);
+
+    while i < xs.len() && j < ys.len() {
+        if xs[i] < ys[j] {
+            merged.push(xs[i]);
+            i += 1;
+        } else {
+            merged.push(ys[j]);
+            j += 1;
+        }
+    }
+
+    merged.extend_from_slice(&xs[i..]);
+    merged.extend_from_slice(&ys[j..]);
+
+    merged
+}
+
+// walks the clauses of the predicate at `p` in the order they're tried
+// by a call whose first argument is unbound. Returns None on code the
+// walk doesn't follow. Alternatives are followed iteratively, so only
+// nested clause blocks deepen the recursion.
+fn collect_jit_clauses(
+    code: &Code,
+    mut p: usize,
+    guards: &mut Vec<usize>,
+    clauses: &mut Vec<JitClause>,
+    steps: &mut usize,
+) -> Option<()> {
+    loop {
+        *steps += 1;
+
+        if *steps > code.len() {
+            return None;
+        }
+
+        match &code[p] {
+            Instruction::IndexingCode(indexing_lines) => match &indexing_lines[0] {
+                &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(_, v, ..)) => match v {
+                    IndexingCodePtr::External(o) => {
+                        p += o;
+                    }
+                    IndexingCodePtr::DynamicExternal(o) => match (&code[p + o], &code[p + o - 1]) {
+                        (Instruction::DynamicInternalElse(..), _) => {
+                            p += o;
+                        }
+                        (_, Instruction::DynamicInternalElse(..)) => {
+                            guards.push(p + o - 1);
+                            let result = collect_jit_clauses(code, p + o, guards, clauses, steps);
+                            guards.pop();
+
+                            return result;
+                        }
+                        _ => {
+                            p += o;
+                        }
+                    },
+                    IndexingCodePtr::Fail => return Some(()),
+                    IndexingCodePtr::Internal(_) => return None,
+                },
+                _ => return None,
+            },
+            &Instruction::TryMeElse(o) | &Instruction::RetryMeElse(o) => {
+                collect_jit_clauses(code, p + 1, guards, clauses, steps)?;
+
+                if o > 0 {
+                    p += o;
+                } else {
+                    return Some(());
+                }
+            }
+            &Instruction::TrustMe(_) => {
+                p += 1;
+            }
+            &Instruction::DynamicElse(_, _, next_or_fail) |
+            &Instruction::DynamicInternalElse(_, _, next_or_fail) => {
+                guards.push(p);
+                let result = collect_jit_clauses(code, p + 1, guards, clauses, steps);
+                guards.pop();
+
+                result?;
+
+                match next_or_fail {
+                    NextOrFail::Next(i) if i > 0 => p += i,
+                    _ => return Some(()),
+                }
+            }
+            &Instruction::RevJmpBy(i) if i > 0 => {
+                p -= i;
+            }
+            Instruction::RevJmpBy(_) |
+            Instruction::DefaultRetryMeElse(_) |
+            Instruction::DefaultTrustMe(_) => {
+                return None;
+            }
+            _ => {
+                clauses.push(JitClause { p, guards: guards.clone(), keys: vec![] });
+                return Some(());
+            }
+        }
+    }
+}
@@ -9,7 +9,9 @@
                      current_output/1, current_op/3,
                      current_predicate/1, current_prolog_flag/2,
                      error/2, fail/0, false/0, findall/3, findall/4,
-                     flush_output/0, flush_output/1, get_byte/1,
+                     flush_output/0, flush_output/1,
+                     garbage_collect/0, garbage_collect_atoms/0,
+                     get_byte/1,
                      get_byte/2, get_char/1, get_char/2, get_code/1,
                      get_code/2, halt/0, halt/1, nl/0, nl/1,
                      number_chars/2, number_codes/2, once/1, op/3,
@@ -20,7 +22,8 @@
                      read/2, read_term/2, read_term/3, repeat/0,
                      retract/1, retractall/1, set_prolog_flag/2,
                      set_input/1, set_stream_position/2, set_output/1,
-                     setof/3, stream_property/2, sub_atom/5,
+                     setof/3, statistics/0, statistics/2,
+                     stream_property/2, sub_atom/5,
                      subsumes_term/2, term_variables/2, throw/1,
                      true/0, unify_with_occurs_check/2, write/1,
                      write/2, write_canonical/1, write_canonical/2,
@@ -143,6 +146,12 @@ call(_, _, _, _, _, _, _, _, _).
 %    enabled) and `error` which throws an exception when a cylic term is created. Read and write.
 %  * `unknown`: How undefined predicates are handled when called. Possible values are `error` (the default, an error is thrown),
 %    `fail` (the call silently fails) and `warn` (the call fails and a warning about the undefined predicate is printed).
+%  * `max_heap_size`, `max_stack_size`, `max_trail_size`: The maximum size in bytes of the heap, the stack and the trail,
+%    or `infinite` (the default). A call made once one of them is exceeded throws `resource_error(memory)`. Read and write.
+%  * `max_atoms`: The maximum number of atoms in the atom table, or `infinite` (the default). Read and write.
+%  * `backtrace`: If `true`, exceptions record the call stack they're thrown from, which the toplevel prints
+%    for the errors it catches, and `catch_with_backtrace/3` adds to the balls it catches. `false` by default.
+%    Read and write.
 %
 current_prolog_flag(Flag, Value) :- Flag == max_arity, !, Value = 1023.
 current_prolog_flag(max_arity, 1023).
@@ -154,6 +163,10 @@ current_prolog_flag(Flag, Value) :- Flag == double_quotes, !, '$get_double_quote
 current_prolog_flag(double_quotes, Value) :- '$get_double_quotes'(Value).
 current_prolog_flag(Flag, Value) :- Flag == unknown, !, '$get_unknown'(Value).
 current_prolog_flag(unknown, Value) :- '$get_unknown'(Value).
+current_prolog_flag(Flag, Value) :- Flag == backtrace, !, '$get_backtrace'(Value).
+current_prolog_flag(backtrace, Value) :- '$get_backtrace'(Value).
+current_prolog_flag(Flag, Value) :- atom(Flag), resource_limit_flag(Flag, Resource), !, '$resource_limit'(Resource, Value).
+current_prolog_flag(Flag, Value) :- var(Flag), resource_limit_flag(Flag, Resource), '$resource_limit'(Resource, Value).
 current_prolog_flag(Flag, _) :- Flag == max_integer, !, '$fail'.
 current_prolog_flag(Flag, _) :- Flag == min_integer, !, '$fail'.
 current_prolog_flag(Flag, OccursCheckEnabled) :-
@@ -167,6 +180,11 @@ current_prolog_flag(Flag, _) :-
     nonvar(Flag),
     throw(error(type_error(atom, Flag), current_prolog_flag/2)). % 8.17.2.3 a
 
+resource_limit_flag(max_heap_size, heap).
+resource_limit_flag(max_stack_size, stack).
+resource_limit_flag(max_trail_size, trail).
+resource_limit_flag(max_atoms, atoms).
+
 %% set_prolog_flag(Flag, Value).
 %
 % Sets the internal value of the flag. To see the list of flags supported by Scryer Prolog,
@@ -200,6 +218,13 @@ set_prolog_flag(unknown, warning) :-
     !, '$set_unknown'(warning).
 set_prolog_flag(unknown, fail) :-
     !, '$set_unknown'(fail).
+set_prolog_flag(backtrace, true) :-
+    !, '$set_backtrace'(true).
+set_prolog_flag(backtrace, false) :-
+    !, '$set_backtrace'(false).
+set_prolog_flag(backtrace, Value) :-
+    throw(error(domain_error(flag_value, backtrace + Value),
+                set_prolog_flag/2)).
 set_prolog_flag(occurs_check, true) :-
     !, '$set_sto_as_unify'.
 set_prolog_flag(occurs_check, false) :-
@@ -209,6 +234,17 @@ set_prolog_flag(occurs_check, error) :-
 set_prolog_flag(double_quotes, Value) :-
     throw(error(domain_error(flag_value, double_quotes + Value),
                 set_prolog_flag/2)). % 8.17.1.3 e
+set_prolog_flag(Flag, Value) :-
+    resource_limit_flag(Flag, Resource),
+    !,
+    (  (  Value == infinite
+       ;  integer(Value),
+          Value >= 0
+       ),
+       '$set_resource_limit'(Resource, Value) ->
+       true
+    ;  throw(error(domain_error(flag_value, Flag + Value), set_prolog_flag/2))
+    ).
 set_prolog_flag(Flag, _) :-
     atom(Flag),
     throw(error(domain_error(prolog_flag, Flag), set_prolog_flag/2)). % 8.17.1.3 d
@@ -1104,6 +1140,10 @@ setof(Template, Goal, Solution) :-
        ;  '$head_is_dynamic'(Module, H) ->
           '$clause_body_is_valid'(B),
           Module:'$clause'(H, B)
+       ;  '$fact_table_property'(Module, Name, Arity) ->
+          '$clause_body_is_valid'(B),
+          B = true,
+          Module:H
        ;  throw(error(permission_error(access, private_procedure, Name/Arity),
                       clause/2))
        )
@@ -1113,6 +1153,7 @@ setof(Template, Goal, Solution) :-
 %% clause(Head, Body).
 %
 % True iff Head can be unified with a clause head and Body with its corresponding clause body.
+% Head must be of a dynamic predicate, or of one declared with `fact_table/1`.
 clause(H, B) :-
     (  var(H) ->
        throw(error(instantiation_error, clause/2))
@@ -1127,6 +1168,10 @@ clause(H, B) :-
        ;  '$head_is_dynamic'(user, H) ->
           '$clause_body_is_valid'(B),
           '$clause'(H, B)
+       ;  '$fact_table_property'(user, Name, Arity) ->
+          '$clause_body_is_valid'(B),
+          B = true,
+          user:H
        ;  throw(error(permission_error(access, private_procedure, Name/Arity),
                       clause/2))
        )
@@ -1457,6 +1502,119 @@ halt(N) :-
     ;   throw(error(domain_error(exit_code, N), halt/1))
     ).
 
+%% garbage_collect.
+%
+% Reclaims the heap cells that are no longer reachable. The heap is also
+% collected automatically once it has grown to twice the size it had after
+% the previous collection, or to a million cells, whichever is larger.
+garbage_collect :- '$garbage_collect'.
+
+%% garbage_collect_atoms.
+%
+% Reclaims the atoms that are no longer referenced by any term, clause, flag
+% or stream. The storage of collected atoms is reused by atoms created later.
+% Atoms are also collected automatically once the number of atoms created at
+% runtime has doubled since the previous collection. No atoms are collected
+% while a file is being loaded.
+garbage_collect_atoms :- '$garbage_collect_atoms'.
+
+
+%% statistics(+Key, -Value)
+%
+% Unifies Value with the current value of the statistic named by Key.
+% Sizes are in bytes and times in milliseconds unless noted otherwise.
+% The supported keys are:
+%
+%  * `runtime`: `[CPUTime, SinceLast]`, the CPU time used by the process and the
+%    CPU time used since `runtime` was last read.
+%  * `walltime`: `[WallTime, SinceLast]`, the real time elapsed since the machine
+%    was started and since `walltime` was last read.
+%  * `cputime`: the CPU time used by the process, in seconds, as a float.
+%  * `inferences`: the number of predicate calls made so far.
+%  * `heapused`: the size of the heap.
+%  * `lifted_heap`: the size of the heap holding the solutions of `findall/3` and
+%    similar predicates while they are collected.
+%  * `stack`: the size of the environment and choicepoint stack.
+%  * `trail`: the size of the trail.
+%  * `code`: `[Instructions, Bytes]`, the size of the code area.
+%  * `arena`: `[Allocations, Bytes]`, the size of the arena holding streams,
+%    big numbers and other boxed values.
+%  * `garbage_collection`: `[Collections, ReclaimedCells, Milliseconds]`, the number
+%    of heap collections so far, the number of heap cells they reclaimed and the
+%    time they took.
+%  * `atoms`: `[Atoms, Bytes]`, the number of atoms and the number of bytes used
+%    by the atoms created at runtime.
+%  * `atom_garbage_collection`: `[Collections, CollectedAtoms, Milliseconds]`,
+%    the number of atom table collections so far, the number of atoms they
+%    reclaimed and the time they took.
+statistics(Key, Value) :-
+    (  var(Key) ->
+       throw(error(instantiation_error, statistics/2))
+    ;  \+ atom(Key) ->
+       throw(error(type_error(atom, Key), statistics/2))
+    ;  '$statistics'(Key, Value0) ->
+       Value = Value0
+    ;  throw(error(domain_error(statistics_key, Key), statistics/2))
+    ).
+
+%% statistics.
+%
+% Writes a summary of the statistics reported by `statistics/2` to the
+% current output stream.
+statistics :-
+    statistics(runtime, [CPUTime, _]),
+    statistics(walltime, [WallTime, _]),
+    statistics(inferences, Inferences),
+    statistics(heapused, Heap),
+    statistics(lifted_heap, LiftedHeap),
+    statistics(stack, Stack),
+    statistics(trail, Trail),
+    statistics(atoms, [Atoms, AtomBytes]),
+    statistics(code, [Instructions, CodeBytes]),
+    statistics(arena, [Allocations, ArenaBytes]),
+    statistics(garbage_collection, [GCs, ReclaimedCells, GCTime]),
+    statistics(atom_garbage_collection, [AtomGCs, CollectedAtoms, AtomGCTime]),
+    '$write_statistics'([
+        'Runtime' - [CPUTime, ' ms CPU, ', WallTime, ' ms wall'],
+        'Inferences' - [Inferences],
+        'Heap' - [Heap, ' bytes'],
+        'Lifted heap' - [LiftedHeap, ' bytes'],
+        'Stack' - [Stack, ' bytes'],
+        'Trail' - [Trail, ' bytes'],
+        'Atoms' - [Atoms, ' atoms, ', AtomBytes, ' bytes'],
+        'Code' - [Instructions, ' instructions, ', CodeBytes, ' bytes'],
+        'Arena' - [Allocations, ' allocations, ', ArenaBytes, ' bytes'],
+        'Heap GC' - [GCs, ' collections, ', ReclaimedCells, ' cells reclaimed in ',
+                     GCTime, ' ms'],
+        'Atom GC' - [AtomGCs, ' collections, ', CollectedAtoms, ' atoms reclaimed in ',
+                     AtomGCTime, ' ms']
+    ]).
+
+'$write_statistics'([]).
+'$write_statistics'([Label-Items | Lines]) :-
+    write('% '),
+    write(Label),
+    write(':'),
+    atom_length(Label, N),
+    Padding is 14 - N,
+    '$write_spaces'(Padding),
+    '$write_statistic_items'(Items),
+    nl,
+    '$write_statistics'(Lines).
+
+'$write_spaces'(N) :-
+    (  N > 0 ->
+       write(' '),
+       N1 is N - 1,
+       '$write_spaces'(N1)
+    ;  true
+    ).
+
+'$write_statistic_items'([]).
+'$write_statistic_items'([Item | Items]) :-
+    write(Item),
+    '$write_statistic_items'(Items).
+
 %% atom_length(+Atom, -Length).
 %
 % True iff Atom is an atom of Length characters. Example:
@@ -0,0 +1,128 @@
+/** Coroutines communicating through message queues.
+
+Each coroutine runs its goal on a heap, stack and trail of its own,
+while the predicates of the program are shared by all coroutines.
+Coroutines aren't threads: they all run on the OS thread of the
+machine, one at a time, so they don't make use of more than one core.
+The running coroutine is switched out after a fixed number of
+predicate calls, or when it waits in `coroutine_join/2` or
+`coroutine_get_message/1`. Other coroutines make progress only while
+some coroutine runs Prolog code, so a coroutine blocked in I/O holds
+up all of them.
+
+The main coroutine, which runs the toplevel, has the alias `main`.
+`library(threads)` offers the same predicates under the `thread_`
+names of other Prolog systems.
+
+```
+?- coroutine_create(coroutine_get_message(M), Id, []),
+   coroutine_send_message(Id, hello),
+   coroutine_join(Id, Status).
+   Id = 1, Status = true.
+```
+*/
+
+:- module(coroutines, [coroutine_create/3,
+                       coroutine_join/2,
+                       coroutine_self/1,
+                       coroutine_send_message/2,
+                       coroutine_get_message/1]).
+
+:- use_module(library(error)).
+
+:- meta_predicate coroutine_create(0, ?, +).
+
+%% coroutine_create(:Goal, -Id, +Options).
+%
+% Creates a coroutine running a copy of Goal and unifies Id with its
+% identifier, an integer unless an alias is given. Options are:
+%
+%  * `alias(Alias)`: the coroutine is known by the atom Alias, which is
+%    also its identifier.
+%  * `detached(Bool)`: if `true`, the coroutine is discarded as soon as
+%    it finishes, and can't be joined. The default is `false`.
+coroutine_create(Goal, Id, Options) :-
+    must_be(var, Id),
+    must_be(list, Options),
+    coroutine_options(Options, Alias, Detached),
+    '$coroutine_create'(Goal, Alias, Detached, Id).
+
+coroutine_options([], _, Detached) :-
+    (  var(Detached) ->
+       Detached = false
+    ;  true
+    ).
+coroutine_options([Option|Options], Alias, Detached) :-
+    (  var(Option) ->
+       instantiation_error(coroutine_create/3)
+    ;  Option = alias(Alias0) ->
+       must_be(atom, Alias0),
+       Alias = Alias0
+    ;  Option = detached(Detached0) ->
+       must_be(boolean, Detached0),
+       Detached = Detached0
+    ;  domain_error(coroutine_option, Option, coroutine_create/3)
+    ),
+    coroutine_options(Options, Alias, Detached).
+
+'$coroutine_run'(Goal) :-
+    (  catch(Goal, E, true) ->
+       (  var(E) ->
+          Status = true
+       ;  Status = exception(E)
+       )
+    ;  Status = false
+    ),
+    '$coroutine_finish'(Status).
+
+'$coroutine_failed' :-
+    '$coroutine_finish'(false).
+
+%% coroutine_join(+Id, -Status).
+%
+% Waits for the coroutine Id to finish, then discards it. Status is
+% `true` if its goal succeeded, `false` if it failed and `exception(E)`
+% if it threw E.
+coroutine_join(Id, Status) :-
+    must_be_coroutine(Id, coroutine_join/2),
+    '$coroutine_join'(Id, Status0),
+    Status = Status0.
+
+%% coroutine_self(-Id).
+%
+% Id is the identifier of the running coroutine.
+coroutine_self(Id) :-
+    '$coroutine_self'(Id).
+
+%% coroutine_send_message(+Id, +Message).
+%
+% Adds a copy of Message to the queue of the coroutine Id.
+coroutine_send_message(Id, Message) :-
+    must_be_coroutine(Id, coroutine_send_message/2),
+    '$coroutine_send_message'(Id, Message).
+
+%% coroutine_get_message(?Message).
+%
+% Removes the first message that unifies with Message from the queue
+% of the running coroutine, waiting for one to arrive if there is none.
+coroutine_get_message(Message) :-
+    coroutine_get_message(0, Message).
+
+coroutine_get_message(N, Message) :-
+    '$coroutine_wait_message'(N),
+    '$coroutine_peek_message'(N, Message0),
+    (  Message0 = Message ->
+       '$coroutine_remove_message'(N)
+    ;  N1 is N + 1,
+       coroutine_get_message(N1, Message)
+    ).
+
+must_be_coroutine(Id, Context) :-
+    (  var(Id) ->
+       instantiation_error(Context)
+    ;  integer(Id) ->
+       true
+    ;  atom(Id) ->
+       true
+    ;  type_error(coroutine, Id, Context)
+    ).
@@ -0,0 +1,93 @@
+/** Clause coverage.
+
+    coverage/1 runs a goal while the machine records which clauses are
+    entered, and reports on standard error, for each file clauses are
+    loaded from, the number of its clauses and the share of them that:
+
+     * `Entered`, were entered at least once;
+     * `Exited`, ran through to their last goal at least once;
+     * `Failed`, were backtracked out of into a later clause of their
+       predicate at least once.
+
+    The clauses of libraries are left out. The coverage can be written
+    to a file in the LCOV format, read by CI services and tools such as
+    `genhtml`, as the lines the clauses start on, hit by the entries of
+    the clauses, the predicates, hit by the entries of their clauses,
+    and two branches a clause, taken by its exits and failures:
+
+```
+?- coverage(run_tests, [lcov("coverage.info")]).
+```
+
+    The whole run of a program is covered with the `--coverage FILE`
+    option of `scryer-prolog`, which reports on standard error as the
+    program halts and writes the LCOV coverage to FILE.
+*/
+
+:- module(coverage, [coverage/1, coverage/2]).
+
+:- use_module(library(error)).
+:- use_module(library(format), [format/3]).
+:- use_module(library(lists), [member/2]).
+
+:- meta_predicate coverage(0).
+:- meta_predicate coverage(0, ?).
+
+%% coverage(Goal)
+%
+%  Call Goal once while recording clause coverage, and report the
+%  coverage.
+
+coverage(Goal) :-
+    coverage(Goal, []).
+
+%% coverage(Goal, Options)
+%
+%  Call Goal once while recording clause coverage, and report the
+%  coverage. Options is a list of:
+%
+%   * `lcov(File)`, to write the coverage to File in the LCOV format.
+%
+%  Fails if Goal fails, and throws the exceptions Goal throws, after
+%  the coverage is reported in either case.
+
+coverage(Goal, Options) :-
+    must_be(list, Options),
+    (  member(Option, Options),
+       \+ coverage_option(Option) ->
+       domain_error(coverage_option, Option, coverage/2)
+    ;  true
+    ),
+    '$coverage_start',
+    catch(Goal, E, true),
+    !,
+    '$coverage_stop',
+    report_coverage(Options),
+    (  nonvar(E) ->
+       throw(E)
+    ;  true
+    ).
+coverage(_, Options) :-
+    '$coverage_stop',
+    report_coverage(Options),
+    false.
+
+coverage_option(Option) :-
+    nonvar(Option),
+    Option = lcov(File),
+    (  atom(File) ->
+       true
+    ;  catch(atom_chars(_, File), _, false)
+    ).
+
+report_coverage(Options) :-
+    '$coverage_report'(Report),
+    format(user_error, "~s", [Report]),
+    (  member(lcov(File0), Options) ->
+       (  atom(File0) ->
+          File = File0
+       ;  atom_chars(File, File0)
+       ),
+       '$coverage_write_lcov'(File)
+    ;  true
+    ).
@@ -1,4 +1,4 @@
-/** Declarative debugging.
+/** Declarative debugging, and a tracer.
 
   This library provides three predicates with associated operators.
   The operators can be placed in front of goals to debug Prolog
@@ -14,6 +14,47 @@
 
   Source: [https://stackoverflow.com/a/30791637](https://stackoverflow.com/a/30791637)
 
+  The library also provides a tracer in the box model of Byrd. Each
+  call of a predicate is a box with four ports: the `call` port is
+  passed as the box is entered, the `exit` port as it succeeds, the
+  `redo` port as it's entered again on backtracking, and the `fail`
+  port as it fails. A fifth port, `exception`, is passed as an
+  exception is thrown out of the box. trace/0 reports each port on
+  standard error, with the depth of the box and its goal, and waits
+  for one of these commands:
+
+   * `c`, space or return: _creep_ on to the next port.
+   * `s`: _skip_ to the exit or the failure of the goal.
+   * `l`: _leap_ to the next spy point.
+   * `r`: _retry_ the goal from its call port.
+   * `f`: _fail_ the goal.
+   * `a`: _abort_ the query, and turn off the tracer.
+   * `p`: _print_ the goal with its bindings in full.
+   * `n`: turn off the tracer, and continue.
+   * `h` or `?`: list the commands.
+
+```
+len([], 0).
+len([_|Ls], N) :- len(Ls, N0), N is N0 + 1.
+
+?- trace, len([a], N).
+   Call: (1) len([a],_1) ? creep
+   Call: (2) len([],_2) ? creep
+   Exit: (2) len([],0) ? creep
+   Exit: (1) len([a],1) ? creep
+   N = 1.
+```
+
+  Only the predicates of user code are traced. The predicates of
+  libraries run as notrace regions, but the goals they call in turn,
+  such as the goals of maplist/2, are traced again. notrace/1 runs a
+  goal as a notrace region.
+
+  spy/1 sets a spy point on a predicate, and the tracer leaps to the
+  next spy point without stopping at other ports. leash/1 sets the
+  ports the tracer waits for a command at, and visible/1 those it
+  reports at all.
+
 */
 
 
@@ -24,14 +65,26 @@
     op(950, fy, *),
     (*)/1,
     ($)/1,
-    ($-)/1
+    ($-)/1,
+    trace/0,
+    notrace/0,
+    notrace/1,
+    spy/1,
+    nospy/1,
+    leash/1,
+    visible/1
 ]).
 
-:- use_module(library(format), [portray_clause/1]).
+:- use_module(library(charsio), [get_single_char/1]).
+:- use_module(library(error)).
+:- use_module(library(format), [format/3, portray_clause/1]).
+:- use_module(library(iso_ext), [bb_b_put/2, bb_get/2, bb_put/2]).
+:- use_module(library(lists), [append/3, member/2, memberchk/2]).
 
 :- meta_predicate *(0).
 :- meta_predicate $(0).
 :- meta_predicate $-(0).
+:- meta_predicate notrace(0).
 
 %% $-(Goal)
 %
@@ -55,3 +108,373 @@ $(G_0) :-
 
 
 *(_).
+
+%% trace.
+%
+%  Turn on the tracer, which stops at each port of the calls of user
+%  predicates.
+
+trace :-
+    set_mode(creep).
+
+%% notrace.
+%
+%  Turn off the tracer. The spy points are kept.
+
+notrace :-
+    set_mode(off).
+
+%% notrace(Goal)
+%
+%  Call Goal once, without tracing it.
+
+notrace(G_0) :-
+    '$debugger_tracing'(Tracing, false),
+    (  catch(G_0, E, ('$debugger_tracing'(_, Tracing), throw(E))) ->
+       '$debugger_tracing'(_, Tracing)
+    ;  '$debugger_tracing'(_, Tracing),
+       false
+    ).
+
+%% spy(+PI)
+%
+%  Set a spy point on the predicates indicated by PI, `Name/Arity` or
+%  `Name` for each arity of Name. Turns on the tracer, leaping to the
+%  next spy point, if it's off.
+
+spy(Spec) :-
+    spy_point_spec(Spec, spy/1, PI),
+    spy_points(PIs),
+    (  memberchk(PI, PIs) ->
+       true
+    ;  bb_put('$debug_spy_points', [PI|PIs])
+    ),
+    format(user_error, "% Spy point on ~q~n", [PI]),
+    (  debug_mode(off) ->
+       set_mode(leap)
+    ;  true
+    ).
+
+%% nospy(+PI)
+%
+%  Remove the spy point on the predicates indicated by PI.
+
+nospy(Spec) :-
+    spy_point_spec(Spec, nospy/1, PI),
+    spy_points(PIs0),
+    (  select_spy_point(PIs0, PI, PIs) ->
+       bb_put('$debug_spy_points', PIs),
+       format(user_error, "% Spy point removed from ~q~n", [PI])
+    ;  true
+    ).
+
+select_spy_point([PI0|PIs0], PI, PIs) :-
+    (  PI0 == PI ->
+       PIs = PIs0
+    ;  PIs = [PI0|PIs1],
+       select_spy_point(PIs0, PI, PIs1)
+    ).
+
+spy_point_spec(Spec, Context, PI) :-
+    (  var(Spec) ->
+       instantiation_error(Context)
+    ;  Spec = _:Spec0 ->
+       spy_point_spec(Spec0, Context, PI)
+    ;  Spec = Name/Arity ->
+       must_be(atom, Name),
+       must_be(integer, Arity),
+       PI = Spec
+    ;  atom(Spec) ->
+       PI = Spec
+    ;  type_error(predicate_indicator, Spec, Context)
+    ).
+
+spy_points(PIs) :-
+    (  bb_get('$debug_spy_points', PIs0) ->
+       PIs = PIs0
+    ;  PIs = []
+    ).
+
+spy_point(Goal) :-
+    functor(Goal, Name, Arity),
+    spy_points(PIs),
+    (  memberchk(Name/Arity, PIs) ->
+       true
+    ;  memberchk(Name, PIs)
+    ).
+
+%% leash(+Ports)
+%
+%  Set the ports the tracer waits for a command at. Ports is a port,
+%  one of `call`, `exit`, `redo`, `fail` and `exception`, a set of
+%  them, one of `all`, `full`, `half` (`call` and `redo`), `loose`
+%  (`call`) and `none`, or a list of those. A port or a set prefixed
+%  by `+` is added to the ports, and one prefixed by `-` is removed.
+%  All ports are leashed to begin with.
+
+leash(Spec) :-
+    set_ports('$debug_leash', Spec, leash/1).
+
+%% visible(+Ports)
+%
+%  Set the ports the tracer reports, as leash/1 sets the ports it waits
+%  at. All ports are visible to begin with.
+
+visible(Spec) :-
+    set_ports('$debug_visible', Spec, visible/1).
+
+set_ports(Key, Spec, Context) :-
+    (  var(Spec) ->
+       instantiation_error(Context)
+    ;  Spec = [_|_] ->
+       Specs = Spec
+    ;  Spec == [] ->
+       Specs = []
+    ;  Specs = [Spec]
+    ),
+    (  member(Spec0, Specs),
+       \+ Spec0 = +_,
+       \+ Spec0 = -_ ->
+       Ports0 = []
+    ;  ports(Key, Ports0)
+    ),
+    update_ports(Specs, Context, Ports0, Ports),
+    bb_put(Key, Ports).
+
+update_ports([], _, Ports, Ports).
+update_ports([Spec|Specs], Context, Ports0, Ports) :-
+    (  var(Spec) ->
+       instantiation_error(Context)
+    ;  Spec = -Spec0 ->
+       port_set(Spec0, Context, Set),
+       exclude_ports(Ports0, Set, Ports1)
+    ;  (  Spec = +Spec0 ->
+          true
+       ;  Spec0 = Spec
+       ),
+       port_set(Spec0, Context, Set),
+       exclude_ports(Ports0, Set, Ports2),
+       append(Ports2, Set, Ports1)
+    ),
+    update_ports(Specs, Context, Ports1, Ports).
+
+exclude_ports([], _, []).
+exclude_ports([Port|Ports0], Set, Ports) :-
+    (  memberchk(Port, Set) ->
+       Ports = Ports1
+    ;  Ports = [Port|Ports1]
+    ),
+    exclude_ports(Ports0, Set, Ports1).
+
+port_set(Spec, Context, Set) :-
+    must_be(atom, Spec),
+    (  port_set(Spec, Set) ->
+       true
+    ;  all_ports(Ports),
+       memberchk(Spec, Ports) ->
+       Set = [Spec]
+    ;  domain_error(port, Spec, Context)
+    ).
+
+port_set(all, Ports) :-
+    all_ports(Ports).
+port_set(full, Ports) :-
+    all_ports(Ports).
+port_set(half, [call, redo]).
+port_set(loose, [call]).
+port_set(none, []).
+
+all_ports([call, exit, redo, fail, exception]).
+
+ports(Key, Ports) :-
+    (  bb_get(Key, Ports0) ->
+       Ports = Ports0
+    ;  all_ports(Ports)
+    ).
+
+% The mode of the tracer is one of off, creep, leap, and skip(Id)
+% while the call Id is skipped. The machine stops redirecting calls
+% to trace_goal/3 while the tracer is off or skipping.
+
+debug_mode(Mode) :-
+    (  bb_get('$debug_mode', Mode0) ->
+       Mode = Mode0
+    ;  Mode = off
+    ).
+
+set_mode(Mode) :-
+    bb_put('$debug_mode', Mode),
+    (  ( Mode == off ; Mode = skip(_) ) ->
+       '$debugger_tracing'(_, false)
+    ;  '$debugger_tracing'(_, true)
+    ).
+
+depth(Depth) :-
+    (  bb_get('$debug_depth', Depth0) ->
+       Depth = Depth0
+    ;  Depth = 0
+    ).
+
+next_invocation(Id) :-
+    (  bb_get('$debug_invocation', Id0) ->
+       Id is Id0 + 1
+    ;  Id = 1
+    ),
+    bb_put('$debug_invocation', Id).
+
+% The machine calls trace_goal/3 in place of Goal while the tracer is
+% on, with the code index P of its predicate, and Kind as the
+% predicate is static or dynamic.
+
+trace_goal(Goal, P, Kind) :-
+    depth(Depth0),
+    Depth is Depth0 + 1,
+    next_invocation(Id),
+    trace_box(call, Goal, P, Kind, Depth0, Depth, Id).
+
+% The retry and fail commands throw '$debug'(Id, Entry) to the box of
+% the call Id, which is entered again at its call or its fail port.
+
+trace_box(Entry, Goal, P, Kind, Depth0, Depth, Id) :-
+    catch(box(Entry, Goal, P, Kind, Depth0, Depth, Id),
+          '$debug'(Id, Entry0),
+          trace_box(Entry0, Goal, P, Kind, Depth0, Depth, Id)).
+
+box(call, Goal, P, Kind, Depth0, Depth, Id) :-
+    bb_b_put('$debug_depth', Depth),
+    (  port(call, Goal, Depth, Id),
+       catch(call_goal(Goal, P, Kind),
+             E,
+             exception_port(E, Goal, Depth, Id)),
+       bb_b_put('$debug_depth', Depth0),
+       (  port(exit, Goal, Depth, Id)
+       ;  port(redo, Goal, Depth, Id),
+          false
+       )
+    ;  port(fail, Goal, Depth, Id),
+       false
+    ).
+box(fail, Goal, _, _, _, Depth, Id) :-
+    port(fail, Goal, Depth, Id),
+    false.
+
+call_goal(Goal, P, Kind) :-
+    '$debug_call'(Goal, P, Kind).
+
+exception_port(E, Goal, Depth, Id) :-
+    (  (  E = '$debug'(_, _)
+       ;  E == '$aborted'
+       ) ->
+       true
+    ;  port(exception(E), Goal, Depth, Id)
+    ),
+    throw(E).
+
+port(Port, Goal, Depth, Id) :-
+    debug_mode(Mode),
+    (  Mode = skip(Id),
+       Port \== call,
+       Port \== redo ->
+       set_mode(creep),
+       show_port(Port, Goal, Depth, Id)
+    ;  Mode == creep ->
+       show_port(Port, Goal, Depth, Id)
+    ;  Mode == leap,
+       spy_point(Goal) ->
+       show_port(Port, Goal, Depth, Id)
+    ;  true
+    ).
+
+port_name(exception(_), exception) :- !.
+port_name(Port, Port).
+
+show_port(Port, Goal, Depth, Id) :-
+    port_name(Port, Name),
+    ports('$debug_visible', Visible),
+    (  memberchk(Name, Visible) ->
+       print_port(Port, Goal, Depth, 10),
+       ports('$debug_leash', Leashed),
+       (  memberchk(Name, Leashed) ->
+          read_command(Port, Goal, Depth, Id)
+       ;  nl(user_error)
+       )
+    ;  true
+    ).
+
+print_port(Port, Goal, Depth, MaxDepth) :-
+    port_label(Port, Label),
+    format(user_error, "   ~a: (~d) ", [Label, Depth]),
+    write_term(user_error, Goal, [quoted(true), max_depth(MaxDepth)]),
+    (  Port = exception(E) ->
+       write(user_error, ' raised '),
+       write_term(user_error, E, [quoted(true), max_depth(MaxDepth)])
+    ;  true
+    ).
+
+port_label(call, 'Call').
+port_label(exit, 'Exit').
+port_label(redo, 'Redo').
+port_label(fail, 'Fail').
+port_label(exception(_), 'Exception').
+
+read_command(Port, Goal, Depth, Id) :-
+    write(user_error, ' ? '),
+    get_single_char(C),
+    (  command(C, Command) ->
+       true
+    ;  Command = unknown
+    ),
+    write(user_error, Command),
+    nl(user_error),
+    command(Command, Port, Goal, Depth, Id).
+
+command(c, creep).
+command(' ', creep).
+command('\n', creep).
+command(s, skip).
+command(l, leap).
+command(r, retry).
+command(f, fail).
+command(a, abort).
+command(p, print).
+command(n, nodebug).
+command(h, help).
+command('?', help).
+
+command(creep, _, _, _, _) :-
+    set_mode(creep).
+command(skip, Port, _, _, Id) :-
+    (  ( Port == call ; Port == redo ) ->
+       set_mode(skip(Id))
+    ;  set_mode(creep)
+    ).
+command(leap, _, _, _, _) :-
+    set_mode(leap).
+command(retry, _, _, _, Id) :-
+    set_mode(creep),
+    throw('$debug'(Id, call)).
+command(fail, Port, _, _, Id) :-
+    set_mode(creep),
+    (  Port == fail ->
+       true
+    ;  throw('$debug'(Id, fail))
+    ).
+command(abort, _, _, _, _) :-
+    set_mode(off),
+    throw('$aborted').
+command(print, Port, Goal, Depth, Id) :-
+    print_port(Port, Goal, Depth, 0),
+    read_command(Port, Goal, Depth, Id).
+command(nodebug, _, _, _, _) :-
+    set_mode(off).
+command(help, Port, Goal, Depth, Id) :-
+    help,
+    print_port(Port, Goal, Depth, 10),
+    read_command(Port, Goal, Depth, Id).
+command(unknown, Port, Goal, Depth, Id) :-
+    command(help, Port, Goal, Depth, Id).
+
+help :-
+    format(user_error, "   c, space, return: creep   s: skip    l: leap~n", []),
+    format(user_error, "   r: retry                  f: fail    a: abort~n", []),
+    format(user_error, "   p: print                  n: nodebug h, ?: help~n", []).
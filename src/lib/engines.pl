@@ -0,0 +1,108 @@
+/** Engines: goals whose answers are computed one at a time, on demand.
+
+An engine runs its goal on a heap, stack and trail of its own. Each
+call to `engine_next/2` resumes the engine until it finds its next
+answer, or until it yields a term with `engine_yield/1`, and then
+returns to the caller. Any number of engines can be kept alive at once,
+and each can be resumed independently of the others.
+
+```
+?- engine_create(X, member(X, [a,b]), E),
+   engine_next(E, A), engine_next(E, B),
+   engine_destroy(E).
+   E = 0, A = a, B = b.
+```
+*/
+
+:- module(engines, [engine_create/3,
+                    engine_create/4,
+                    engine_next/2,
+                    engine_yield/1,
+                    engine_destroy/1,
+                    engine_self/1]).
+
+:- use_module(library(error)).
+
+:- meta_predicate engine_create(?, 0, -).
+:- meta_predicate engine_create(?, 0, -, +).
+
+%% engine_create(+Template, :Goal, -Engine).
+%
+% Equivalent to `engine_create(Template, Goal, Engine, [])`.
+engine_create(Template, Goal, Engine) :-
+    engine_create(Template, Goal, Engine, []).
+
+%% engine_create(+Template, :Goal, -Engine, +Options).
+%
+% Creates an engine whose answers are the instances of Template for
+% which a copy of Goal is true. The goal doesn't start running until
+% the first call to `engine_next/2`. Engine is an integer unless the
+% option `alias(Alias)` is given, in which case it is the atom Alias.
+engine_create(Template, Goal, Engine, Options) :-
+    must_be(var, Engine),
+    must_be(list, Options),
+    engine_options(Options, Alias),
+    '$engine_create'(Template-Goal, Alias, Engine).
+
+engine_options([], _).
+engine_options([Option|Options], Alias) :-
+    (  var(Option) ->
+       instantiation_error(engine_create/4)
+    ;  Option = alias(Alias0) ->
+       must_be(atom, Alias0),
+       Alias = Alias0
+    ;  domain_error(engine_option, Option, engine_create/4)
+    ),
+    engine_options(Options, Alias).
+
+'$engine_run'(Template-Goal) :-
+    catch(Goal, E, '$engine_exception'(E)),
+    '$engine_yield'(Template),
+    false.
+'$engine_run'(_) :-
+    '$engine_exhausted'.
+
+%% engine_next(+Engine, -Term).
+%
+% Runs Engine until it finds its next answer or yields a term, and
+% unifies Term with it. Fails once the goal of the engine has no more
+% answers. If the goal throws an exception, so does `engine_next/2`,
+% after which the engine has no more answers.
+engine_next(Engine, Term) :-
+    must_be_engine(Engine, engine_next/2),
+    '$engine_next'(Engine, Term0),
+    Term = Term0.
+
+%% engine_yield(+Term).
+%
+% Makes the current call to `engine_next/2` of the running engine
+% return Term. The engine continues after the call to `engine_yield/1`
+% when it is next resumed.
+engine_yield(Term) :-
+    (  '$engine_self'(_) ->
+       '$engine_yield'(Term)
+    ;  throw(error(permission_error(yield, engine, Term), engine_yield/1))
+    ).
+
+%% engine_destroy(+Engine).
+%
+% Discards Engine, which can't be used again.
+engine_destroy(Engine) :-
+    must_be_engine(Engine, engine_destroy/1),
+    '$engine_destroy'(Engine).
+
+%% engine_self(-Engine).
+%
+% Engine is the innermost running engine. Fails if no engine is running.
+engine_self(Engine) :-
+    '$engine_self'(Engine).
+
+must_be_engine(Engine, Context) :-
+    (  var(Engine) ->
+       instantiation_error(Context)
+    ;  integer(Engine) ->
+       true
+    ;  atom(Engine) ->
+       true
+    ;  type_error(engine, Engine, Context)
+    ).
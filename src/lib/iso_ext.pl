@@ -9,6 +9,7 @@ but they're not part of the ISO Prolog standard at the moment.
                     bb_put/2,
                     call_cleanup/2,
                     call_with_inference_limit/3,
+                    catch_with_backtrace/3,
                     forall/2,
                     partial_string/1,
                     partial_string/3,
@@ -194,6 +195,63 @@ run_cleaners_without_handling(Cp) :-
     '$set_cp_by_default'(Cp),
     '$restore_cut_policy'.
 
+% catch_with_backtrace
+
+:- meta_predicate(catch_with_backtrace(0, ?, 0)).
+
+%% catch_with_backtrace(Goal, Catcher, Recovery).
+%
+% Like `catch/3`, but Goal is called with the `backtrace` flag on, and an error `error(E, Context)`
+% it throws is unified with Catcher as `error(E, backtrace(Context, Frames))`. Frames lists the
+% clauses the error was thrown from, from the innermost out, as `frame(Module:Name/Arity, N, File, Line)`
+% terms, where N is the number of the clause, and File and Line the file and the line it was loaded
+% from, or `[]`.
+% Clauses of library and system modules are left out.
+%
+% ```
+% ?- catch_with_backtrace(atom_length(_, _), error(E, backtrace(_, Frames)), true).
+% ```
+catch_with_backtrace(G, C, R) :-
+    current_prolog_flag(backtrace, Backtrace),
+    catch(call_with_backtrace(G, Backtrace),
+          Ball,
+          (  set_prolog_flag(backtrace, Backtrace),
+             handle_backtrace_ball(Ball, C, R)
+          )).
+
+% the backtrace flag is on only while G runs: it's restored when G
+% exits, fails or throws, and set again on backtracking into G.
+call_with_backtrace(G, Backtrace) :-
+    set_prolog_flag(backtrace, true),
+    (  '$get_cp'(Cp),
+       call(G),
+       (  '$check_cp'(Cp) ->
+          !,
+          set_prolog_flag(backtrace, Backtrace)
+       ;  backtrace_flag(Backtrace, true)
+       )
+    ;  set_prolog_flag(backtrace, Backtrace),
+       false
+    ).
+
+backtrace_flag(Value, _) :-
+    set_prolog_flag(backtrace, Value).
+backtrace_flag(_, Value) :-
+    set_prolog_flag(backtrace, Value),
+    false.
+
+handle_backtrace_ball(Ball, C, R) :-
+    (  Ball = error(E, Context),
+       \+ subsumes_term(backtrace(_, _), Context),
+       '$caught_backtrace'(Frames) ->
+       BacktraceBall = error(E, backtrace(Context, Frames))
+    ;  BacktraceBall = Ball
+    ),
+    (  BacktraceBall = C ->
+       call(R)
+    ;  throw(Ball)
+    ).
+
 % call_with_inference_limit
 
 :- non_counted_backtracking end_block/4.
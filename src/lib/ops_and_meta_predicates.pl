@@ -59,6 +59,9 @@
 
 :- op(1200, xfx, -->).
 
+% renaming imports, as in use_module(Module, [Name/Arity as NewName]).
+:- op(700, xfx, as).
+
 % meta_predicate declarations for call/{1, 66}.
 :- meta_predicate call(0).
 :- meta_predicate call(1, ?).
@@ -0,0 +1,105 @@
+/** Execution profiling.
+
+    profile/1 runs a goal under the profiler of the machine, and
+    reports on standard error, for each predicate that's run:
+
+     * `Calls`, the number of times the predicate is called;
+     * `Redos`, the number of times a choice point of the predicate is
+       resumed on backtracking;
+     * `Inferences`, the number of calls made by its clauses;
+     * `Self%`, the share of the sampled CPU time spent in its clauses;
+     * `Total%`, the share of the sampled CPU time spent in its clauses
+       and in the predicates they call, directly or indirectly.
+
+    The predicates are listed by `Self%`, from the highest. The CPU time
+    is sampled once in so many calls, together with the call stack of
+    the moment. The sampled call stacks can be written to a file in the
+    folded format read by flamegraph tools such as
+    [inferno](https://github.com/jonhoo/inferno):
+
+```
+?- profile(nrev(L), [top(10), folded("nrev.folded")]).
+```
+
+    The whole run of a program is profiled with the `--profile FILE`
+    option of `scryer-prolog`, which reports on standard error as the
+    program halts and writes the folded call stacks to FILE.
+*/
+
+:- module(profile, [profile/1, profile/2]).
+
+:- use_module(library(error)).
+:- use_module(library(format), [format/3]).
+:- use_module(library(lists), [member/2]).
+
+:- meta_predicate profile(0).
+:- meta_predicate profile(0, ?).
+
+%% profile(Goal)
+%
+%  Call Goal once under the profiler, and report the profile.
+
+profile(Goal) :-
+    profile(Goal, []).
+
+%% profile(Goal, Options)
+%
+%  Call Goal once under the profiler, and report the profile. Options
+%  is a list of:
+%
+%   * `top(N)`, to report only the N predicates with the highest
+%     `Self%`, instead of all of them;
+%   * `folded(File)`, to write the sampled call stacks to File in the
+%     folded format of flamegraph tools.
+%
+%  Fails if Goal fails, and throws the exceptions Goal throws, after
+%  the profile is reported in either case.
+
+profile(Goal, Options) :-
+    must_be(list, Options),
+    (  member(Option, Options),
+       \+ profile_option(Option) ->
+       domain_error(profile_option, Option, profile/2)
+    ;  true
+    ),
+    '$profiler_start',
+    catch(Goal, E, true),
+    !,
+    '$profiler_stop',
+    report_profile(Options),
+    (  nonvar(E) ->
+       throw(E)
+    ;  true
+    ).
+profile(_, Options) :-
+    '$profiler_stop',
+    report_profile(Options),
+    false.
+
+profile_option(Option) :-
+    nonvar(Option),
+    (  Option = top(N) ->
+       integer(N),
+       N >= 0
+    ;  Option = folded(File) ->
+       (  atom(File) ->
+          true
+       ;  catch(atom_chars(_, File), _, false)
+       )
+    ).
+
+report_profile(Options) :-
+    (  member(top(Top), Options) ->
+       true
+    ;  Top = all
+    ),
+    '$profiler_report'(Top, Report),
+    format(user_error, "~s", [Report]),
+    (  member(folded(File0), Options) ->
+       (  atom(File0) ->
+          File = File0
+       ;  atom_chars(File, File0)
+       ),
+       '$profiler_write_folded'(File)
+    ;  true
+    ).
@@ -0,0 +1,89 @@
+/** Saved states: programs that start without loading their sources.
+
+`save_program/2` writes the compiled code of every loaded module, the
+dynamic database, the operator tables and the flags of the running
+machine to a file. Starting Scryer Prolog with `--restore File` then
+builds the machine from that file, without reading or compiling any
+Prolog source, and continues with the toplevel as usual.
+
+```
+$ scryer-prolog -g "use_module(library(saved_states)), consult(app), save_program('app.qls', []), halt"
+$ scryer-prolog --restore app.qls
+```
+
+`qsave_program/2` instead writes an executable holding both the
+runtime and the saved state. The executable runs the goal the program
+declares with `:- initialization(Goal, main)`, passes all of its
+command line arguments to the program through `argv/1`, and exits with
+the status given to `halt/1`, or 0 if the goal succeeds and 1 if it
+fails or throws an exception. Without a main goal, it starts the
+toplevel. `scryer-prolog --compile File -o Executable` consults File
+and writes Executable with `qsave_program/2`.
+
+```
+:- initialization(main, main).
+
+main :-
+    argv(Args),
+    length(Args, N),
+    write(N), nl,
+    halt(N).
+```
+
+A saved state can only be restored by the version of Scryer Prolog that
+wrote it. Streams, global variables, threads, engines and the answer
+tables of tabled predicates aren't saved. The files loaded from source
+are, so `make/0` reloads them in the restored machine once they change.
+A machine with native predicates registered by the program embedding it
+can't be saved, and both predicates throw a permission error for it.
+*/
+
+:- module(saved_states, [save_program/2, qsave_program/2]).
+
+:- use_module(library(error)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+%% save_program(+File, +Options).
+%
+% Saves the state of the machine to File, an atom. No options are
+% supported yet, so Options must be `[]`.
+save_program(File, Options) :-
+    must_be(atom, File),
+    must_be(list, Options),
+    (  Options = [Option|_] ->
+       domain_error(save_program_option, Option, save_program/2)
+    ;  true
+    ),
+    without_session_facts('$save_program'(File)).
+
+%% qsave_program(+File, +Options).
+%
+% Writes an executable to File, an atom, that starts from the state of
+% the machine and runs its main goal. No options are supported yet, so
+% Options must be `[]`.
+qsave_program(File, Options) :-
+    must_be(atom, File),
+    must_be(list, Options),
+    (  Options = [Option|_] ->
+       domain_error(qsave_program_option, Option, qsave_program/2)
+    ;  true
+    ),
+    without_session_facts(
+        setup_call_cleanup(asserta('$toplevel':stand_alone),
+                           '$save_executable'(File),
+                           once(retract('$toplevel':stand_alone)))
+    ).
+
+% the restored toplevel handles the arguments it's started with, not
+% those of the session that saved it.
+without_session_facts(Goal) :-
+    findall(Fact, session_fact(Fact), Facts),
+    setup_call_cleanup(maplist(retract, Facts),
+                       Goal,
+                       maplist(assertz, Facts)).
+
+session_fact('$toplevel':argv(Args)) :-
+    '$toplevel':argv(Args).
+session_fact('$toplevel':disabled_init_file) :-
+    '$toplevel':disabled_init_file.
@@ -17,235 +17,393 @@
 ...
 ```
 
+    A tabled predicate can instead keep only the best answer for each
+    variant of some of its arguments, with a `(table)/1` directive that
+    gives the mode of each argument. For example, with
+
+```
+:- table path(_,_,min).
+```
+
+    the third argument of `path/3` is the least, in the standard order
+    of terms, of the answers found for each variant of the first two.
+    The arguments given as `_` or `index` are those the answers are
+    kept for, and at most one argument is moded as one of:
+
+     * `first`, the first answer found;
+     * `last`, the last answer found;
+     * `min`, the least answer in the standard order of terms;
+     * `max`, the greatest answer in the standard order of terms;
+     * `lattice(PI)`, the join of the answers, where `PI` is `Name/3`
+       or `Name` and `Name(Old, New, Join)` computes the join;
+     * `po(PI)`, the answer that is greatest in the partial order `PI`,
+       `Name/2` or `Name`, where `Name(Old, New)` succeeds if `New`
+       is greater than `Old`.
+
+    Tabled predicates are evaluated under the well-founded semantics
+    if they're negated with tnot/1 in place of `(\+)/1`, even through
+    recursion. An answer whose truth depends on such a negation is
+    true, false or *undefined*. For example, with
+
+```
+:- table win/1.
+
+win(X) :- move(X, Y), tnot(win(Y)).
+
+move(a, b).
+move(b, a).
+move(b, c).
+move(c, d).
+```
+
+    `win(c)` is true and `win(d)` is false, whereas `win(a)` and
+    `win(b)` are undefined. The toplevel writes an undefined answer with the goals
+    it's conditional on, after the residual program of the answers they
+    depend on in turn:
+
+```
+?- win(a).
+% WFS residual program
+    win(a) :- tnot(win(b)).
+    win(b) :- tnot(win(a)).
+   win(a).
+```
+
+    call_delays/2 tells the goals an answer is conditional on.
+
     The possibility to apply different execution strategies is one of
     the greatest attractions of pure Prolog code, and one of the
     strongest arguments for keeping to the pure core of Prolog as far
     as possible.
 
     Scryer Prolog implements tabling as described by Desouter et al. in [*Tabling as a Library with Delimited Control*](https://www.ijcai.org/Proceedings/16/Papers/619.pdf).
+    The loader compiles the clauses of a tabled predicate as those of
+    its implementation, and the predicate itself as a wrapper calling
+    start_tabling/2. The tables of answers, the tries by which calls
+    and answers are found in them, and the completion of the tables
+    under evaluation are native to the machine.
 */
 
 :- module(tabling,
 	  [ start_tabling/2,		% +Wrapper, :Worker.
-
+	    start_tabling/3,		% +Wrapper, :Worker, +Mode.
+	    tnot/1,
+	    call_delays/2,
 	    abolish_all_tables/0,
-
-%	    (table)/1,			% +PI ...
+	    current_table/2,
+	    table_statistics/2,
 	    op(1150, fx, table)
 	  ]).
 
-:- use_module(library(tabling/double_linked_list)).
-:- use_module(library(tabling/table_data_structure)).
-:- use_module(library(tabling/batched_worklist)).
-:- use_module(library(tabling/global_worklist)).
-:- use_module(library(tabling/table_link_manager)).
-:- use_module(library(tabling/wrapper)).
-
+:- use_module(library(between)).
 :- use_module(library(cont)).
-:- use_module(library(lists)).
-%:- use_module(library(debug)).
+:- use_module(library(error)).
 :- use_module(library(iso_ext)).
+:- use_module(library(lists)).
 
-%% :- meta_predicate
-%% 	start_tabling(+, 0).
-
-%%	user:exception(+Exception, +Var, -Action)
+%% abolish_all_tables.
 %
-%	Realises lazy initialization of table variables.
-
-%% user:exception(undefined_global_variable, Var, retry) :-
-%%   (   table_gvar(Var)
-%%   ->  true
-%%   ;   format('Creating global var ~q~n', [Var]),
-%%       nb_setval(Var, [])
-%%   ).
-/*
-table_gvar(trie_table_link) :-
-  table_datastructure_initialize.
-table_gvar(newly_created_table_identifiers) :-
-  table_datastructure_initialize.
-table_gvar(table_global_worklist) :-
-  bb_put(table_global_worklist, []).
-table_gvar(table_leader) :-
-  bb_put(table_leader, []).
-*/
+% Removes all tables. Tables can't be removed while tabled goals are
+% being evaluated.
+abolish_all_tables :-
+    (  '$tbl_abolish_all_tables' ->
+       true
+    ;  throw(error(permission_error(modify, tables, all), abolish_all_tables/0))
+    ).
 
-%%	abolish_all_tables
+%% tnot(:Goal).
 %
-%	Remove all tables.  Should not be called when tabling is in
-%	progress.
+% The negation of Goal under the well-founded semantics, where Goal is
+% a ground call of a predicate tabled without modes. Succeeds if Goal
+% is false, and fails if it's true. If Goal is undefined, tnot(Goal)
+% is undefined too, and succeeds conditional on it.
+:- meta_predicate tnot(0).
+
+tnot(Goal0) :-
+    loader:strip_module(Goal0, M, Goal),
+    (  ground(Goal) ->
+       true
+    ;  instantiation_error(tnot/1)
+    ),
+    functor(Goal, Name, Arity),
+    (  '$tabled_predicate'(M, Name, Arity) ->
+       true
+    ;  permission_error(tnot, non_tabled_procedure, M:Name/Arity)
+    ),
+    Goal =.. [Name | Args],
+    loader:tabled_implementation_name(Name, ImplName),
+    Worker =.. [ImplName | Args],
+    '$tbl_variant_table'(M:Goal, T, S),
+    (  S == complete ->
+       true
+    ;  '$tbl_create_scc' ->
+       catch(run_leader(M:Goal, M:Worker, variant, T),
+             E,
+             ( '$tbl_abandon_scc', throw(E) ))
+    ;  S == fresh ->
+       activate(M:Goal, M:Worker, variant, T)
+    ;  true
+    ),
+    '$tbl_negation'(T, Truth),
+    (  Truth == true ->
+       true
+    ;  Truth == undefined ->
+       % the truth of the negation is delayed until the table is
+       % complete, or left undefined if it's complete already.
+       add_delay(neg(T))
+    ).
+
+%% call_delays(:Goal, -Delays).
 %
-%	@bug	Check whether tabling is in progress
-
-
-abolish_all_tables :-
-  bb_put(trie_table_link, []),
-  bb_put(newly_created_table_identifiers, []),
-  bb_put(table_global_worklist,[]),
-  bb_put(table_leader, []).
+% Calls Goal, where Delays is `true` if the answer of Goal is true, and
+% the conjunction of the goals it's conditional on if it's undefined.
+:- meta_predicate call_delays(0, ?).
+
+call_delays(Goal, Delays) :-
+    delays(Delays0),
+    bb_b_put('$tbl_delays', []),
+    call(Goal),
+    delays(Delays1),
+    append(Delays1, Delays0, Delays2),
+    bb_b_put('$tbl_delays', Delays2),
+    maplist(delay_goal, Delays1, Goals),
+    conjunction(Goals, Delays).
+
+%% current_table(:Variant, -Table).
+%
+% Table is the table of a tabled call that unifies with Variant. Tables
+% are numbered from 0 in the order they're created.
+:- meta_predicate current_table(:, ?).
 
+current_table(M:Variant, Table) :-
+    '$tbl_statistics'(tables, N),
+    N1 is N - 1,
+    between(0, N1, Table),
+    '$tbl_variant'(Table, M:Variant).
 
-% Find table and status for the given call variant.
+%% table_statistics(?Key, ?Value).
 %
-table_and_status_for_variant(V,T,S) :-
-  % Order of the two calls really important: first create, then get status
-  table_for_variant(V,T),
-  tbd_table_status(T,S).
-
+% Value is the statistic Key of the tables, which is one of:
+%
+%  * `tables`, the number of tables;
+%  * `complete_tables`, the number of complete tables;
+%  * `answers`, the number of answers in all tables;
+%  * `conditional_answers`, the number of answers in all tables that
+%    are conditional, which are undefined in complete tables;
+%  * `trie_nodes`, the number of nodes of the call and answer tries;
+%  * `calls`, the number of calls of tabled predicates;
+%  * `completions`, the number of times the tables under evaluation
+%    were completed.
+table_statistics(Key, Value) :-
+    member(Key, [tables, complete_tables, answers, conditional_answers, trie_nodes,
+                 calls, completions]),
+    '$tbl_statistics'(Key, Value).
 
 :- meta_predicate start_tabling(?, :).
 
-start_tabling(Wrapper,Worker) :-
-  put_new_trie_table_link,
-  put_new_global_worklist,
-  put_new_table_identifiers,
-  table_and_status_for_variant(Wrapper,T,S),
-  ( S == complete ->
-    get_answer(T,Wrapper)
-  ;
-    ( exists_scheduling_component ->
-      run_leader(Wrapper,Worker,T),
-      % Now answer the original query!
-      get_answer(T,Wrapper)
-    ;
-      run_follower(S,Wrapper,Worker,T)
-    )
-  ).
-
-run_follower(fresh,Wrapper,Worker,T) :-
-  activate(Wrapper,Worker,T),
-  shift(call_info(Wrapper,T)).
-
-run_follower(active,Wrapper,_Worker,T) :-
-  shift(call_info(Wrapper,T)).
-
-run_leader(Wrapper,Worker,T) :-
-  create_scheduling_component,
-  activate(Wrapper,Worker,T),
-  completion,
-  unset_scheduling_component.
-
-exists_scheduling_component :-
-  bb_get(table_leader, Leader),
-  Leader == [].
-
-create_scheduling_component :-
-  bb_b_put(table_leader, leaderCreated).
-
-unset_scheduling_component :-
-  bb_put(table_leader, []).
-
-set_all_complete :-
-  get_newly_created_table_identifiers(Ts, _),
-  set_all_complete_(Ts).
-
-set_all_complete_([]).
-set_all_complete_([T|Ts]) :-
-  set_complete_status(T),
-  set_all_complete_(Ts).
-
-cleanup_all_complete :-
-  get_newly_created_table_identifiers(Ts,_),
-  cleanup_all_complete_(Ts).
-
-cleanup_all_complete_([]).
-cleanup_all_complete_([T|Ts]) :-
-  cleanup_after_complete(T),
-  cleanup_all_complete_(Ts).
-
-activate(Wrapper,Worker,T) :-
-    set_active_status(T),
-  (
-    delim(Wrapper,Worker,T),
-    fail
-  ;
-    true
-  ).
-
-delim(Wrapper,Worker,Table) :-
-%   debug(tabling, 'ACT: ~p on ~p', [Wrapper, Table]),
-    catch(reset(Worker,SourceCall,Continuation),
-          _,
-          fail),
-   ( Continuation = none ->
-     (	 add_answer(Table,Wrapper)
-     ->	 true %debug(tabling, 'ADD: ~p', [Wrapper])
-     ;	 %debug(tabling, 'DUP: ~p', [Wrapper]),
-	 fail
-     )
-   ;
-     Continuation = cont(Cont),
-     SourceCall = call_info(_,SourceTable),
-     TargetCall = call_info(Wrapper,Table),
-     Dependency = dependency(SourceCall,Cont,TargetCall),
-     %debug(tabling, 'DEP: ~p: ~p', [SourceTable,Dependency]),
-     store_dependency(SourceTable,Dependency)
-   ).
+%% start_tabling(+Wrapper, :Worker).
+%
+% Calls Worker, the implementation of the tabled call Wrapper, through
+% the table of Wrapper. The first tabled call made while no tables are
+% under evaluation leads the evaluation of the tables it depends on,
+% and returns its answers once they're all complete.
+start_tabling(Wrapper, Worker) :-
+    start_tabling(Wrapper, Worker, variant).
+
+:- meta_predicate start_tabling(?, :, +).
 
+%% start_tabling(+Wrapper, :Worker, +Mode).
+%
+% Like start_tabling/2, where Mode is `variant` if every answer is
+% tabled, and `moded(Args, Value, ValueMode)` if only the best Value
+% for each variant of Args is, as chosen by ValueMode. Args and Value
+% share their variables with Wrapper.
+start_tabling(Wrapper, Worker, Mode) :-
+    answer_term(Mode, Wrapper, Answer),
+    '$tbl_variant_table'(Wrapper, T, S),
+    (  S == complete ->
+       get_answer(T, Answer)
+    ;  '$tbl_create_scc' ->
+       catch(run_leader(Wrapper, Worker, Mode, T),
+             E,
+             ( '$tbl_abandon_scc', throw(E) )),
+       get_answer(T, Answer)
+    ;  run_follower(S, Wrapper, Worker, Mode, T)
+    ).
+
+answer_term(variant, Wrapper, Wrapper).
+answer_term(moded(Args, Value, _), _, Args-Value).
+
+get_answer(T, Answer) :-
+    '$tbl_answer_count'(T, N),
+    N1 is N - 1,
+    between(0, N1, I),
+    '$tbl_answer'(T, I, Answer, Condition),
+    add_delay(Condition).
+
+run_follower(fresh, Wrapper, Worker, Mode, T) :-
+    activate(Wrapper, Worker, Mode, T),
+    answer_term(Mode, Wrapper, Answer),
+    shift(call_info(Answer, T)).
+run_follower(active, Wrapper, _Worker, Mode, T) :-
+    answer_term(Mode, Wrapper, Answer),
+    shift(call_info(Answer, T)).
+
+run_leader(Wrapper, Worker, Mode, T) :-
+    activate(Wrapper, Worker, Mode, T),
+    completion.
+
+activate(Wrapper, Worker, Mode, T) :-
+    '$tbl_set_active'(T),
+    (  delim(Wrapper, Worker, Mode, T, []),
+       fail
+    ;  true
+    ).
+
+% runs Worker, with the delay list Delays0, until it either succeeds,
+% adding an answer to Table, or calls an incomplete table, whose
+% answers it's suspended on.
+delim(Wrapper, Worker, Mode, Table, Delays0) :-
+    bb_b_put('$tbl_delays', Delays0),
+    reset(Worker, SourceCall, Continuation),
+    delays(Delays),
+    (  Continuation = none ->
+       add_answer(Mode, Table, Wrapper, Delays)
+    ;  Continuation = cont(Cont),
+       SourceCall = call_info(SourceAnswer, SourceTable),
+       '$tbl_add_dependency'(SourceTable,
+                             dependency(SourceAnswer, Cont, Wrapper, Mode, Table, Delays))
+    ).
+
+% fails if the answer is already in Table, or if it's no better than
+% the answer there for the same arguments.
+add_answer(variant, Table, Wrapper, Delays) :-
+    '$tbl_add_answer'(Table, Wrapper, Delays).
+add_answer(moded(Args, Value, ValueMode), Table, _, Delays) :-
+    (  '$tbl_moded_answer'(Table, Args, Value0) ->
+       once(moded_value(ValueMode, Value0, Value, Value1))
+    ;  Value1 = Value
+    ),
+    '$tbl_set_moded_answer'(Table, Args-Value1, Delays).
+
+% Value1 is the answer kept in place of Value0 once Value is found.
+% There's no clause for `first`, which always keeps Value0.
+moded_value(last, _, Value, Value).
+moded_value(min, Value0, Value, Value) :-
+    Value @< Value0.
+moded_value(max, Value0, Value, Value) :-
+    Value @> Value0.
+moded_value(lattice(PI), Value0, Value, Value1) :-
+    call(PI, Value0, Value, Value1).
+moded_value(po(PI), Value0, Value, Value) :-
+    call(PI, Value0, Value).
+
+% runs the suspended continuations with the answers they haven't
+% consumed until there are none left, at which point the tables under
+% evaluation are complete.
 completion :-
-  ( worklist_empty ->
-    set_all_complete,
-    cleanup_all_complete,
-    % The place of the call to reset is really important: it must happen after the completion. If you do it before, you will wrongly remove yourself from the list of newly created table identifiers. On starting hProlog there are no newly created table identifiers, and nb_getval gives [] which is the perfect value.
-    reset_newly_created_table_identifiers
-  ;
-    pop_worklist(Table),
-    completion_step(Table),
-    completion
-  ).
-
-completion_step(SourceTableID) :-
-  bb_get(SourceTableID, Table),
-  get_nb_identifiers(Table, NBWorklistID, _),
-  (
-    table_get_work(NBWorklistID,Answer,dependency(Source,Continuation,Target)),
-    Source = call_info(Answer,_),
-    Target = call_info(Wrapper,TargetTable),
-    delim(Wrapper,Continuation,TargetTable),
-    fail
-  ;
-    true
-  ).
-
-table_get_work(NBWorklistID,Answer,Dependency) :-
-  % get_worklist(Table, Worklist),
-  % NOT IN PAPER (could be part of the definition of pop_worklist):
-  bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-  unset_global_worklist_presence_flag(Worklist),
-  set_flag_executing_all_work(Worklist),
-  bb_put(NBWorklistID, table_nb_worklist(Worklist)),
-  table_get_work_(NBWorklistID,Answer,Dependency).
-
-table_get_work_(NBWorklistID,Answer,Dependency) :-
-  worklist_do_all_work(NBWorklistID,Answer,Dependency0), % This will eventually fail
-  copy_term(Dependency0,Dependency).
-
-table_get_work_(NBWorklistID,_Answer,_Dependency) :-
-  bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-  unset_flag_executing_all_work(Worklist),
-  bb_put(NBWorklistID, table_nb_worklist(Worklist)),
-  fail.
-
-worklist_do_all_work(NBWorklistID,Answer,Dependency) :-
-  ( bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-    wkl_worklist_work_done(Worklist) ->
-    fail
-  ;
-    worklist_do_step(NBWorklistID,Answer,Dependency)
-  ;
-    worklist_do_all_work(NBWorklistID,Answer,Dependency)
-  ).
-
-worklist_do_step(NBWorklistID,Answer,Dependency) :-
-  bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-  wkl_p_get_rightmost_inner_answer_cluster_pointer(Worklist,ACP),
-  wkl_p_swap_answer_continuation(Worklist,ACP,SCP),
-  dll_get_data(ACP,wkl_answer_cluster(AListFlag)),
-  dll_get_data(SCP,wkl_suspension_cluster(SListFlag)),
-  get_atts(AListFlag, batched_worklist, wkl_answer_cluster(AList)),
-  get_atts(SListFlag, batched_worklist, wkl_suspension_cluster(SList)),
-  bb_put(NBWorklistID, table_nb_worklist(Worklist)),
-  member(Answer,AList),
-  member(Dependency,SList).
-
-:- initialization(bb_put(table_leader, [])).
+    (  '$tbl_pop_work'(Answer, Dependency, Condition) ->
+       (  Dependency = dependency(Answer, Cont, Wrapper, Mode, Table, Delays0),
+          add_condition(Condition, Delays0, Delays),
+          delim(Wrapper, Cont, Mode, Table, Delays),
+          fail
+       ;  true
+       ),
+       completion
+    ;  '$tbl_complete_scc'
+    ).
+
+% the delay list of the running goal, the literals pos(Table, Answer)
+% and neg(Table) it's conditional on.
+delays(Delays) :-
+    (  bb_get('$tbl_delays', Delays0) ->
+       Delays = Delays0
+    ;  Delays = []
+    ).
+
+add_delay(Condition) :-
+    delays(Delays0),
+    add_condition(Condition, Delays0, Delays),
+    bb_b_put('$tbl_delays', Delays).
+
+add_condition(true, Delays, Delays) :-
+    !.
+add_condition(Delay, Delays, [Delay | Delays]).
+
+delay_goal(pos(T, I), Goal) :-
+    answer_goal(T, I, Goal).
+delay_goal(neg(T), tnot(Goal)) :-
+    '$tbl_variant'(T, Variant),
+    unqualified_goal(Variant, Goal).
+
+% the answers of moded tables aren't calls, so they're written as the
+% calls of their tables.
+answer_goal(T, I, Goal) :-
+    '$tbl_answer'(T, I, Answer, _),
+    (  Answer = _:_ ->
+       unqualified_goal(Answer, Goal)
+    ;  '$tbl_variant'(T, Variant),
+       unqualified_goal(Variant, Goal)
+    ).
+
+unqualified_goal(M:Goal0, Goal) :-
+    (  M == user ->
+       Goal = Goal0
+    ;  Goal = M:Goal0
+    ).
+
+conjunction([], true).
+conjunction([Goal | Goals], Conjunction) :-
+    foldl(conjoin, Goals, Goal, Conjunction).
+
+conjoin(Goal, Conjunction, (Conjunction, Goal)).
+
+%% residual_program(-Goals, -Program).
+%
+% Goals are the goals the answer of the toplevel query is conditional
+% on, and Program the clauses of the conditional answers they depend
+% on. Fails if the answer is unconditional.
+residual_program(Goals, Program) :-
+    delays(Delays),
+    Delays \== [],
+    maplist(delay_goal, Delays, Goals),
+    residual_answers(Delays, [], Answers),
+    findall(Clause,
+            ( member(pos(T, I), Answers),
+              answer_clause(T, I, Clause)
+            ),
+            Program).
+
+residual_answers([], Answers, Answers).
+residual_answers([Delay | Delays0], Answers0, Answers) :-
+    findall(pos(T, I),
+            ( delay_answer(Delay, T, I),
+              \+ memberchk(pos(T, I), Answers0)
+            ),
+            NewAnswers),
+    append(Answers0, NewAnswers, Answers1),
+    findall(Delay1,
+            ( member(pos(T, I), NewAnswers),
+              '$tbl_answer_conditions'(T, I, Conditions),
+              member(Condition, Conditions),
+              member(Delay1, Condition)
+            ),
+            Delays1),
+    append(Delays0, Delays1, Delays),
+    residual_answers(Delays, Answers1, Answers).
+
+% the conditional answers a delayed literal depends on.
+delay_answer(pos(T, I), T, I) :-
+    '$tbl_answer_conditions'(T, I, [_|_]).
+delay_answer(neg(T), T, I) :-
+    '$tbl_answer_count'(T, N),
+    N1 is N - 1,
+    between(0, N1, I),
+    '$tbl_answer_conditions'(T, I, [_|_]).
+
+answer_clause(T, I, (Head :- Body)) :-
+    answer_goal(T, I, Head),
+    '$tbl_answer_conditions'(T, I, Conditions),
+    member(Condition, Conditions),
+    maplist(delay_goal, Condition, Goals),
+    conjunction(Goals, Body).
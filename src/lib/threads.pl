@@ -0,0 +1,70 @@
+/** Prolog threads communicating through message queues.
+
+Each thread runs its goal with registers, a heap, a stack and a trail
+of its own, while the compiled predicates of the program are shared by
+all threads. Threads are the coroutines of `library(coroutines)` under
+the names other Prolog systems give them: they are scheduled on the OS
+thread of the machine, one at a time, and switched after a fixed number
+of predicate calls or when they wait in `thread_join/2` or
+`thread_get_message/1`. They don't run in parallel, as the atom table
+and the arena of a machine can't be shared between OS threads, and a
+thread blocked in I/O holds up all of them.
+
+The main thread, which runs the toplevel, has the alias `main`.
+
+```
+?- thread_create(thread_get_message(M), Id, []),
+   thread_send_message(Id, hello),
+   thread_join(Id, Status).
+   Id = 1, Status = true.
+```
+*/
+
+:- module(threads, [thread_create/3,
+                    thread_join/2,
+                    thread_self/1,
+                    thread_send_message/2,
+                    thread_get_message/1]).
+
+:- use_module(library(coroutines)).
+
+:- meta_predicate thread_create(0, ?, +).
+
+%% thread_create(:Goal, -Id, +Options).
+%
+% Creates a thread running a copy of Goal and unifies Id with its
+% identifier, an integer unless an alias is given. Options are:
+%
+%  * `alias(Alias)`: the thread is known by the atom Alias, which is
+%    also its identifier.
+%  * `detached(Bool)`: if `true`, the thread is discarded as soon as it
+%    finishes, and can't be joined. The default is `false`.
+thread_create(Goal, Id, Options) :-
+    coroutine_create(Goal, Id, Options).
+
+%% thread_join(+Id, -Status).
+%
+% Waits for the thread Id to finish, then discards it. Status is
+% `true` if its goal succeeded, `false` if it failed and `exception(E)`
+% if it threw E.
+thread_join(Id, Status) :-
+    coroutine_join(Id, Status).
+
+%% thread_self(-Id).
+%
+% Id is the identifier of the running thread.
+thread_self(Id) :-
+    coroutine_self(Id).
+
+%% thread_send_message(+Id, +Message).
+%
+% Adds a copy of Message to the queue of the thread Id.
+thread_send_message(Id, Message) :-
+    coroutine_send_message(Id, Message).
+
+%% thread_get_message(?Message).
+%
+% Removes the first message that unifies with Message from the queue
+% of the running thread, waiting for one to arrive if there is none.
+thread_get_message(Message) :-
+    coroutine_get_message(Message).
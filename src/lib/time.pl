@@ -91,8 +91,6 @@ sleep(T) :-
     ).
 
 
-% '$cpu_now' can be replaced by statistics/2 once that is implemented.
-
 :- meta_predicate time(0).
 
 :- dynamic(time_id/1).
@@ -111,7 +109,7 @@ time_next_id(N) :-
 %  Reports the execution time of Goal.
 
 time(Goal) :-
-        '$cpu_now'(T0),
+        statistics(cputime, T0),
         time_next_id(ID),
         setup_call_cleanup(asserta(time_state(ID, T0)),
                            (   call_cleanup(catch(Goal, E, (report_time(ID),throw(E))),
@@ -130,13 +128,13 @@ time_true(ID) :-
 time_true(ID)  :-
         % on backtracking, update the stored CPU time for this ID
         retract(time_state(ID, _)),
-        '$cpu_now'(T0),
+        statistics(cputime, T0),
         asserta(time_state(ID, T0)),
         false.
 
 report_time(ID) :-
         time_state(ID, T0),
-        '$cpu_now'(T),
+        statistics(cputime, T),
         Time is T - T0,
         (   bb_get('$answer_count', 0) ->
             Pre = "   ", Post = ""
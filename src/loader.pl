@@ -1,10 +1,16 @@
-:- module(loader, [consult/1,
+:- module(loader, [clause_property/2,
+                   consult/1,
                    expand_goal/3,
                    expand_term/2,
                    file_load/2,
                    load/1,
+                   make/0,
+                   module_property/2,
+                   nth_clause/3,
                    predicate_property/2,
                    prolog_load_context/2,
+                   reexport/1,
+                   reexport/2,
                    strip_module/3,
                    use_module/1,
                    use_module/2,
@@ -15,6 +21,12 @@
 :- use_module(library(lists)).
 :- use_module(library(pairs)).
 
+%% file_import(File, Parent, Module, Imports, Reexport) records that
+%% File was imported into Module by a load from the file Parent, or []
+%% from the toplevel, with the arguments of use_module/4 make/0 repeats
+%% as it reloads File.
+:- dynamic(file_import/5).
+
 write_error(Error) :-
     % '$fetch_global_var' is the core system call of bb_get/2, but
     % bb_get may not exist when write_error is first called, so fall
@@ -139,6 +151,9 @@ file_load_cleanup(Evacuable, Error) :-
 	throw(Error).
 
 file_load(Stream, Path, Evacuable) :-
+    % the imports of a previous load of the file at Path are recorded
+    % again as they're repeated.
+    retractall(file_import(_, Path, _, _, _)),
     create_file_load_context(Stream, Path, Evacuable),
     % '$add_in_situ_filename_module' removes user level predicates,
     % local predicate clauses, etc. from a previous load of the file
@@ -196,6 +211,7 @@ warn_about_singletons([Singleton|Singletons], LinesRead) :-
 load_loop(Stream, Evacuable) :-
     (  '$devour_whitespace'(Stream) ->
        stream_property(Stream, position(position_and_lines_read(_, LinesRead))),
+       '$set_load_context_position'(Stream),
        read_term(Stream, Term, [singletons(Singletons)])
     ;  Term = end_of_file
     ),
@@ -331,10 +347,40 @@ compile_dispatch_or_clause(Term, Evacuable) :-
        instantiation_error(load/1)
     ;  compile_dispatch(Term, Evacuable) ->
        '$flush_term_queue'(Evacuable)
+    ;  tabled_clause(Term, Clause) ->
+       compile_clause(Clause, Evacuable)
     ;  compile_clause(Term, Evacuable)
     ).
 
 
+%% The clauses of a tabled predicate are compiled as those of its
+%% implementation, which is called by library(tabling) on behalf of
+%% the predicate's wrapper.
+
+tabled_clause((Head0 :- Body), (Head :- Body)) :-
+    !,
+    tabled_clause(Head0, Head).
+tabled_clause(Module:Head0, Module:Head) :-
+    !,
+    atom(Module),
+    tabled_head(Module, Head0, Head).
+tabled_clause(Head0, Head) :-
+    prolog_load_context(module, Module),
+    tabled_head(Module, Head0, Head).
+
+tabled_head(Module, Head0, Head) :-
+    '$module_has_tabled_predicates'(Module),
+    callable(Head0),
+    functor(Head0, Name, Arity),
+    '$tabled_predicate'(Module, Name, Arity),
+    Head0 =.. [Name | Args],
+    tabled_implementation_name(Name, ImplName),
+    Head =.. [ImplName | Args].
+
+tabled_implementation_name(Name, ImplName) :-
+    atom_concat(Name, ' tabled', ImplName).
+
+
 compile_dispatch((:- Declaration), Evacuable) :-
     (  var(Declaration) ->
        instantiation_error(load/1)
@@ -380,6 +426,10 @@ compile_declaration(use_module(Module, Exports), Evacuable) :-
        remove_module(Module, Evacuable)
     ;  use_module(Module, Exports, Evacuable)
     ).
+compile_declaration(reexport(Module), Evacuable) :-
+    use_module(Module, [], true, Evacuable).
+compile_declaration(reexport(Module, Imports), Evacuable) :-
+    use_module(Module, Imports, true, Evacuable).
 compile_declaration(module(Module, Exports), Evacuable) :-
     (  atom(Module) ->
        '$declare_module'(Module, Exports, Evacuable)
@@ -421,8 +471,36 @@ compile_declaration(discontiguous(Name/Arity), Evacuable) :-
 compile_declaration(initialization(Goal), Evacuable) :-
     prolog_load_context(module, Module),
     assertz(Module:'$initialization_goals'(Goal)).
+compile_declaration(initialization(Goal, When), Evacuable) :-
+    must_be(atom, When),
+    prolog_load_context(module, Module),
+    (  When == now ->
+       success_or_warning(Module:Goal)
+    ;  When == after_load ->
+       assertz(Module:'$initialization_goals'(Goal))
+    ;  When == main ->
+       %% the toplevel runs the main goal in place of the REPL.
+       retractall('$toplevel':main_goal(_)),
+       assertz('$toplevel':main_goal(Module:Goal))
+    ;  domain_error(initialization_type, When, initialization/2)
+    ).
+compile_declaration(table(Specs), Evacuable) :-
+    prolog_load_context(module, Module),
+    (  '$module_exists'(tabling) ->
+       true
+    ;  use_module(library(tabling), [start_tabling/2, start_tabling/3], Evacuable)
+    ),
+    '$flush_term_queue'(Evacuable),
+    add_tabled_predicates(Specs, Module, Evacuable).
 compile_declaration(set_prolog_flag(Flag, Value), _) :-
     set_prolog_flag(Flag, Value).
+compile_declaration(fact_table(Name/Arity), Evacuable) :-
+    must_be(atom, Name),
+    must_be(integer, Arity),
+    (  Arity >= 0 ->
+       '$add_fact_table'(Name, Arity, Evacuable)
+    ;  domain_error(not_less_than_zero, Arity, load/1)
+    ).
 compile_declaration(non_counted_backtracking(Name/Arity), Evacuable) :-
     must_be(atom, Name),
     must_be(integer, Arity),
@@ -432,6 +510,101 @@ compile_declaration(non_counted_backtracking(Name/Arity), Evacuable) :-
     ).
 
 
+add_tabled_predicates(Specs, Module, Evacuable) :-
+    (  var(Specs) ->
+       instantiation_error(load/1)
+    ;  Specs = (Spec, Specs1) ->
+       add_tabled_predicates(Spec, Module, Evacuable),
+       add_tabled_predicates(Specs1, Module, Evacuable)
+    ;  Specs = Name//Arity0 ->
+       must_be(integer, Arity0),
+       Arity is Arity0 + 2,
+       add_tabled_predicate(Name, Arity, Module, Evacuable)
+    ;  Specs = Name/Arity ->
+       add_tabled_predicate(Name, Arity, Module, Evacuable)
+    ;  compound(Specs) ->
+       add_moded_tabled_predicate(Specs, Module, Evacuable)
+    ;  type_error(predicate_indicator, Specs, load/1)
+    ).
+
+%% The wrapper of a tabled predicate, compiled in place of its clauses,
+%% calls its implementation through library(tabling).
+
+add_tabled_predicate(Name, Arity, Module, Evacuable) :-
+    must_be(atom, Name),
+    must_be(integer, Arity),
+    (  Arity >= 0 ->
+       true
+    ;  domain_error(not_less_than_zero, Arity, load/1)
+    ),
+    functor(Head, Name, Arity),
+    Head =.. [Name | Args],
+    tabled_implementation_name(Name, ImplName),
+    ImplHead =.. [ImplName | Args],
+    Wrapper = (Head :- tabling:start_tabling(Module:Head, Module:ImplHead)),
+    add_tabled_wrapper(Wrapper, Name, Arity, Module, Evacuable).
+
+%% A moded table spec such as path(_,_,min) gives the mode of each
+%% argument. The wrapper tables the answers of the implementation
+%% with the moded argument left unbound, and unifies it with the
+%% best answer.
+
+add_moded_tabled_predicate(Spec, Module, Evacuable) :-
+    functor(Spec, Name, Arity),
+    functor(Head, Name, Arity),
+    Spec =.. [Name | Modes],
+    Head =.. [Name | Args],
+    table_modes(Modes, Args, Module, IndexArgs, WorkerArgs, ModedArgs),
+    (  ModedArgs == [] ->
+       add_tabled_predicate(Name, Arity, Module, Evacuable)
+    ;  ModedArgs = [Arg-Value-Mode] ->
+       WorkerHead =.. [Name | WorkerArgs],
+       tabled_implementation_name(Name, ImplName),
+       ImplHead =.. [ImplName | WorkerArgs],
+       Wrapper = (Head :- tabling:start_tabling(Module:WorkerHead, Module:ImplHead,
+                                                moded(IndexArgs, Value, Mode)),
+                          Arg = Value),
+       add_tabled_wrapper(Wrapper, Name, Arity, Module, Evacuable)
+    ;  domain_error(table_spec, Spec, load/1)
+    ).
+
+table_modes([], [], _, [], [], []).
+table_modes([Mode0 | Modes], [Arg | Args], Module, IndexArgs, [WorkerArg | WorkerArgs], ModedArgs) :-
+    (  (  var(Mode0)
+       ;  Mode0 == index
+       ) ->
+       IndexArgs = [Arg | IndexArgs1],
+       WorkerArg = Arg,
+       ModedArgs = ModedArgs1
+    ;  table_mode(Mode0, Module, Mode) ->
+       IndexArgs = IndexArgs1,
+       ModedArgs = [Arg-WorkerArg-Mode | ModedArgs1]
+    ;  domain_error(table_mode, Mode0, load/1)
+    ),
+    table_modes(Modes, Args, Module, IndexArgs1, WorkerArgs, ModedArgs1).
+
+table_mode(first, _, first).
+table_mode(last, _, last).
+table_mode(min, _, min).
+table_mode(max, _, max).
+table_mode(lattice(PI), Module, lattice(Module:Name)) :-
+    table_mode_predicate(PI, 3, Name).
+table_mode(po(PI), Module, po(Module:Name)) :-
+    table_mode_predicate(PI, 2, Name).
+
+table_mode_predicate(PI, Arity, Name) :-
+    (  PI = Name/Arity ->
+       atom(Name)
+    ;  atom(PI),
+       Name = PI
+    ).
+
+add_tabled_wrapper(Wrapper, Name, Arity, Module, Evacuable) :-
+    compile_clause(Wrapper, Evacuable),
+    '$flush_term_queue'(Evacuable),
+    '$add_tabled_predicate'(Module, Name, Arity).
+
+
 compile_clause((Target:Head :- Body), Evacuable) :-
     !,
     functor(Head, Name, Arity),
@@ -513,14 +686,121 @@ use_module(Module, Exports) :-
     ;  use_module(Module, Exports, Evacuable)
     ).
 
+%% reexport(Module)
+%
+%  Import the exports of Module, as use_module/1 does, and, in a module,
+%  add them to the exports of the module.
+
+reexport(Module) :-
+    '$push_load_state_payload'(Evacuable),
+    use_module(Module, [], true, Evacuable).
+
+%% reexport(Module, Imports)
+%
+%  Import the exports of Module listed by Imports, as use_module/2 does,
+%  and, in a module, add them to the exports of the module under the
+%  names they're imported by.
+
+reexport(Module, Imports) :-
+    '$push_load_state_payload'(Evacuable),
+    use_module(Module, Imports, true, Evacuable).
+
+%% make
+%
+%  Reload the files modified since they were loaded, in the order their
+%  loads concluded, so that the files a file loads are reloaded before
+%  it. A file loaded from a file that's also modified is reloaded with
+%  it. The others are reloaded in place, replacing the modules and
+%  clauses they define, and imported again into each module they were
+%  imported into, as they were: with the same import lists, and
+%  reexported if they were. Each file reloaded is reported on standard
+%  error.
+
+make :-
+    '$modified_loaded_files'(Files),
+    maplist(loader:reload_file, Files).
+
+reload_file(File) :-
+    open(File, read, Stream),
+    file_load(Stream, File, Subevacuable),
+    (  file_import(File, _, Module, Imports, Reexport),
+       '$push_module_load_state_payload'(Module, Evacuable),
+       '$use_module'(Evacuable, Subevacuable, Imports, Reexport),
+       false
+    ;  true
+    ),
+    write(user_error, '% Reloaded '),
+    write(user_error, File),
+    nl(user_error).
+
+%% current_module(?Module)
+%
+%  Module is a loaded module. Enumerates the loaded modules on
+%  backtracking if Module is unbound.
+
 current_module(Module) :-
     (  var(Module) ->
-       instantiation_error(current_module/1)
+       '$current_modules'(Modules),
+       member(Module, Modules)
     ;  \+ atom(Module) ->
        type_error(atom, Module, current_module/1)
     ;  '$module_exists'(Module)
     ).
 
+%% module_property(?Module, ?Property)
+%
+%  Property is a property of the loaded module Module. The properties
+%  are:
+%
+%   * `class(Class)`, `system` for the modules of the system itself,
+%     `library` for the modules of libraries, and `user` for the rest;
+%   * `file(File)`, the path of the file the module was loaded from;
+%   * `exports(PIs)`, the predicates the module exports, including
+%     those it reexports;
+%   * `exported_operators(Ops)`, the operators the module exports, as
+%     `op/3` terms, if there are any.
+%
+%  Enumerates the modules and their properties on backtracking.
+
+module_property(Module, Property) :-
+    current_module(Module),
+    (  var(Property) ->
+       true
+    ;  functor(Property, Name, 1),
+       member(Name, [class, file, exports, exported_operators]) ->
+       true
+    ;  domain_error(module_property, Property, module_property/2)
+    ),
+    module_property_(Property, Module).
+
+module_property_(class(Class), Module) :-
+    '$module_class'(Module, Class).
+module_property_(file(File), Module) :-
+    '$module_file'(Module, File).
+module_property_(exports(PIs), Module) :-
+    '$module_exports'(Module, Exports),
+    exported_predicates(Exports, PIs).
+module_property_(exported_operators(Ops), Module) :-
+    '$module_exports'(Module, Exports),
+    exported_operators(Exports, Ops),
+    Ops \== [].
+
+exported_predicates([], []).
+exported_predicates([Export | Exports], PIs) :-
+    (  Export = op(_, _, _) ->
+       exported_predicates(Exports, PIs)
+    ;  PIs = [Export | PIs1],
+       exported_predicates(Exports, PIs1)
+    ).
+
+exported_operators([], []).
+exported_operators([Export | Exports], Ops) :-
+    (  Export = op(_, _, _) ->
+       Ops = [Export | Ops1],
+       exported_operators(Exports, Ops1)
+    ;  exported_operators(Exports, Ops)
+    ).
+
 
 %% If use_module is invoked in an existing load context, use its
 %% directory. Otherwise, use the relative path of Path.
@@ -562,15 +842,21 @@ open_file(Path, Stream) :-
 
 
 use_module(Module, Exports, Evacuable) :-
+    use_module(Module, Exports, false, Evacuable).
+
+%% Reexport is true if the imports are also to be added to the exports
+%% of the module being loaded, for reexport/1,2.
+
+use_module(Module, Exports, Reexport, Evacuable) :-
     (  var(Module) ->
        instantiation_error(load/1)
     ;  Module = library(Library) ->
        (  path_atom(Library, LibraryPath) ->
-          (  '$load_compiled_library'(LibraryPath, Exports, Evacuable) ->
+          (  '$load_compiled_library'(LibraryPath, Exports, Evacuable, Reexport) ->
              true
           ;  '$load_library_as_stream'(LibraryPath, Stream, Path),
              file_load(Stream, Path, Subevacuable),
-             '$use_module'(Evacuable, Subevacuable, Exports)
+             '$use_module'(Evacuable, Subevacuable, Exports, Reexport)
           )
        ;  var(Library) ->
           instantiation_error(load/1)
@@ -581,11 +867,21 @@ use_module(Module, Exports, Evacuable) :-
           open_file(Path, Stream),
           stream_property(Stream, file_name(PathFileName)),
           file_load(Stream, PathFileName, Subevacuable),
-          '$use_module'(Evacuable, Subevacuable, Exports)
+          '$use_module'(Evacuable, Subevacuable, Exports, Reexport),
+          record_file_import(PathFileName, Evacuable, Exports, Reexport)
        ;  type_error(atom, Module, load/1)
        )
     ).
 
+record_file_import(File, Evacuable, Imports, Reexport) :-
+    (  prolog_load_context(source, Parent) ->
+       true
+    ;  Parent = []
+    ),
+    '$load_state_module'(Evacuable, Module),
+    retractall(file_import(File, Parent, Module, _, _)),
+    assertz(file_import(File, Parent, Module, Imports, Reexport)).
+
 
 check_predicate_property(meta_predicate, Module, Name, Arity, MetaPredicateTerm) :-
     '$meta_predicate_property'(Module, Name, Arity, MetaPredicateTerm).
@@ -597,6 +893,20 @@ check_predicate_property(multifile, Module, Name, Arity, multifile) :-
     '$multifile_property'(Module, Name, Arity).
 check_predicate_property(discontiguous, Module, Name, Arity, discontiguous) :-
     '$discontiguous_property'(Module, Name, Arity).
+check_predicate_property(indexed, Module, Name, Arity, indexed(Args)) :-
+    '$indexed_property'(Module, Name, Arity, Args).
+check_predicate_property(fact_table, Module, Name, Arity, fact_table) :-
+    '$fact_table_property'(Module, Name, Arity).
+check_predicate_property(tabled, Module, Name, Arity, tabled) :-
+    '$tabled_predicate'(Module, Name, Arity).
+check_predicate_property(file, Module, Name, Arity, file(File)) :-
+    '$clause_locs'(Module, Name, Arity, [Loc|_]),
+    '$clause_source'(Loc, _, File, _),
+    atom(File).
+check_predicate_property(line_count, Module, Name, Arity, line_count(Line)) :-
+    '$clause_locs'(Module, Name, Arity, [Loc|_]),
+    '$clause_source'(Loc, _, _, Line),
+    integer(Line).
 
 
 
@@ -635,6 +945,62 @@ predicate_property(Callable, Property) :-
        )
     ).
 
+%% nth_clause(+Head, ?N, -Ref)
+%
+% Ref is a reference to the Nth clause of the predicate of Head, which
+% may be qualified by a module. Enumerates the clauses on backtracking
+% if N is unbound. The properties of the clause are given by
+% clause_property/2.
+
+nth_clause(Head, N, Ref) :-
+    (  var(Head) ->
+       instantiation_error(nth_clause/3)
+    ;  functor(Head, (:), 2),
+       arg(1, Head, Module),
+       arg(2, Head, Head0),
+       atom(Module),
+       nonvar(Head0) ->
+       nth_clause(Module, Head0, N, Ref)
+    ;  load_context(Module),
+       nth_clause(Module, Head, N, Ref)
+    ).
+
+nth_clause(Module, Head, N, '$clause_ref'(Loc)) :-
+    must_be(callable, Head),
+    can_be(integer, N),
+    functor(Head, Name, Arity),
+    '$clause_locs'(Module, Name, Arity, Locs),
+    nth1(N, Locs, Loc).
+
+%% clause_property(+Ref, ?Property)
+%
+% Property is a property of the clause referred to by Ref, as given by
+% nth_clause/3. The properties are:
+%
+%  * `file(File)`, the absolute path of the file the clause was loaded
+%    from;
+%  * `line_count(Line)`, the line of that file on which the clause
+%    starts;
+%  * `predicate(Module:Name/Arity)`, the predicate of the clause.
+%
+% Clauses asserted rather than loaded have neither a file nor a line.
+
+clause_property(Ref, Property) :-
+    (  var(Ref) ->
+       instantiation_error(clause_property/2)
+    ;  Ref = '$clause_ref'(Loc),
+       integer(Loc) ->
+       '$clause_source'(Loc, PI, File, Line),
+       clause_property_(Property, PI, File, Line)
+    ;  type_error(clause_reference, Ref, clause_property/2)
+    ).
+
+clause_property_(file(File), _, File, _) :-
+    atom(File).
+clause_property_(line_count(Line), _, _, Line) :-
+    integer(Line).
+clause_property_(predicate(PI), PI, _, _).
+
 strip_module(Goal, M, G) :-
     '$strip_module'(Goal, M, G).
 
@@ -4,13 +4,22 @@
 #[derive(Debug)]
 pub struct MachineArgs {
     pub add_history: bool,
+    pub restore: Option<String>,
 }
 
 impl MachineArgs {
     pub fn new() -> Self {
-        let args: BTreeSet<String> = env::args().collect();
+        // the arguments after "--" are the program's own.
+        let args: Vec<String> = env::args().take_while(|arg| arg != "--").collect();
+        let flags: BTreeSet<&str> = args.iter().map(String::as_str).collect();
+
         Self {
-            add_history: !args.contains("--no-add-history"),
+            add_history: !flags.contains("--no-add-history"),
+            restore: args
+                .iter()
+                .position(|arg| arg == "--restore")
+                .and_then(|idx| args.get(idx + 1))
+                .cloned(),
         }
     }
 }
@@ -0,0 +1,466 @@
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::loader::CompilationTarget;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::streams::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::types::*;
+
+use std::time::Instant;
+
+pub(crate) const GC_INITIAL_ATOM_THRESHOLD: usize = 1 << 16;
+
+// finds the atoms reachable from the machine. A load in progress
+// holds atoms in its arena-allocated load state, which the marker
+// can't see into, so finding one cancels the collection.
+#[derive(Default)]
+struct AtomMarker {
+    marks: AtomMarks,
+    loading: bool,
+}
+
+impl AtomMarker {
+    #[inline]
+    fn mark(&mut self, atom: Atom) {
+        self.marks.mark(atom);
+    }
+
+    fn mark_cell(&mut self, cell: HeapCellValue) {
+        read_heap_cell!(cell,
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                self.mark(name);
+            }
+            (HeapCellValueTag::PStr | HeapCellValueTag::CStr, pstr_atom) => {
+                self.mark(pstr_atom);
+            }
+            (HeapCellValueTag::Cons, ptr) => {
+                match ptr.get_tag() {
+                    ArenaHeaderTag::LiveLoadState | ArenaHeaderTag::InactiveLoadState => {
+                        self.loading = true;
+                    }
+                    _ => {
+                    }
+                }
+            }
+            _ => {
+            }
+        );
+    }
+
+    #[inline]
+    fn mark_cells<'a>(&mut self, cells: impl IntoIterator<Item = &'a HeapCellValue>) {
+        for &cell in cells {
+            self.mark_cell(cell);
+        }
+    }
+
+    fn mark_literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Atom(atom) | Literal::String(atom) => {
+                self.mark(*atom);
+            }
+            _ => {
+            }
+        }
+    }
+
+    fn mark_stream(&mut self, stream: &Stream) {
+        if let Some(file_name) = stream.file_name() {
+            self.mark(file_name);
+        }
+
+        if let Some(alias) = stream.options().get_alias() {
+            self.mark(alias);
+        }
+    }
+
+    fn mark_clause_index_info(&mut self, clause_index_info: &ClauseIndexInfo) {
+        match &clause_index_info.opt_arg_index_key {
+            OptArgIndexKey::Literal(_, _, literal, alternatives) => {
+                self.mark_literal(literal);
+
+                for literal in alternatives {
+                    self.mark_literal(literal);
+                }
+            }
+            &OptArgIndexKey::Structure(_, _, name, _) => {
+                self.mark(name);
+            }
+            OptArgIndexKey::List(..) | OptArgIndexKey::None => {
+            }
+        }
+    }
+
+    fn mark_local_skeleton(&mut self, skeleton: &LocalPredicateSkeleton) {
+        if let Some(clauses) = &skeleton.retracted_dynamic_clauses {
+            for clause_index_info in clauses {
+                self.mark_clause_index_info(clause_index_info);
+            }
+        }
+    }
+
+    #[inline]
+    fn mark_compilation_target(&mut self, compilation_target: &CompilationTarget) {
+        if let &CompilationTarget::Module(module_name) = compilation_target {
+            self.mark(module_name);
+        }
+    }
+
+    fn mark_code_dir(&mut self, code_dir: &CodeDir) {
+        for &(name, _) in code_dir.keys() {
+            self.mark(name);
+        }
+    }
+
+    fn mark_op_dir(&mut self, op_dir: &OpDir) {
+        for &(name, _) in op_dir.keys() {
+            self.mark(name);
+        }
+    }
+
+    fn mark_meta_predicates(&mut self, meta_predicates: &MetaPredicateDir) {
+        for &(name, _) in meta_predicates.keys() {
+            self.mark(name);
+        }
+    }
+
+    fn mark_extensible_predicates(&mut self, extensible_predicates: &ExtensiblePredicates) {
+        for (&(name, _), skeleton) in extensible_predicates {
+            self.mark(name);
+            self.mark_local_skeleton(&skeleton.core);
+
+            for clause_index_info in &skeleton.clauses {
+                self.mark_clause_index_info(clause_index_info);
+            }
+        }
+    }
+
+    fn mark_local_extensible_predicates(
+        &mut self,
+        local_extensible_predicates: &LocalExtensiblePredicates,
+    ) {
+        for ((compilation_target, (name, _)), skeleton) in local_extensible_predicates {
+            self.mark_compilation_target(compilation_target);
+            self.mark(*name);
+            self.mark_local_skeleton(skeleton);
+        }
+    }
+
+    fn mark_module(&mut self, module: &Module) {
+        self.mark(module.module_decl.name);
+
+        for export in &module.module_decl.exports {
+            match export {
+                ModuleExport::OpDecl(op_decl) => {
+                    self.mark(op_decl.name);
+                }
+                &ModuleExport::PredicateKey((name, _)) => {
+                    self.mark(name);
+                }
+            }
+        }
+
+        self.mark_code_dir(&module.code_dir);
+        self.mark_op_dir(&module.op_dir);
+        self.mark_meta_predicates(&module.meta_predicates);
+        self.mark_extensible_predicates(&module.extensible_predicates);
+        self.mark_local_extensible_predicates(&module.local_extensible_predicates);
+
+        if let ListingSource::File(file_name, _) = &module.listing_src {
+            self.mark(*file_name);
+        }
+    }
+
+    fn mark_indices(&mut self, indices: &IndexStore) {
+        self.mark_code_dir(&indices.code_dir);
+
+        for source in indices.clause_sources.values() {
+            self.mark(source.module_name);
+            self.mark(source.key.0);
+
+            if let Some(file) = source.file {
+                self.mark(file);
+            }
+        }
+
+        self.mark_extensible_predicates(&indices.extensible_predicates);
+        self.mark_local_extensible_predicates(&indices.local_extensible_predicates);
+
+        for (&key, (ball, cell)) in &indices.global_variables {
+            self.mark(key);
+            self.mark_cells(&ball.stub);
+
+            if let Some(cell) = cell {
+                self.mark_cell(*cell);
+            }
+        }
+
+        for table in &indices.fact_tables {
+            self.mark_cells(table.cells());
+        }
+
+        for &(name, _) in &indices.goal_expansion_indices {
+            self.mark(name);
+        }
+
+        self.mark_meta_predicates(&indices.meta_predicates);
+
+        for (&module_name, module) in &indices.modules {
+            self.mark(module_name);
+            self.mark_module(module);
+        }
+
+        self.mark_op_dir(&indices.op_dir);
+
+        for stream in &indices.streams {
+            self.mark_stream(stream);
+        }
+
+        for (&alias, stream) in &indices.stream_aliases {
+            self.mark(alias);
+            self.mark_stream(stream);
+        }
+    }
+
+    fn mark_arena(&mut self, arena: &Arena) {
+        for ptr in arena.iter() {
+            match_untyped_arena_ptr!(ptr,
+                (ArenaHeaderTag::Stream, stream) => {
+                    self.mark_stream(&stream);
+                }
+                (ArenaHeaderTag::OssifiedOpDir, op_dir) => {
+                    for &(name, _) in op_dir.keys() {
+                        self.mark(name);
+                    }
+                }
+                (ArenaHeaderTag::LiveLoadState, _load_state) => {
+                    self.loading = true;
+                }
+                _ => {
+                }
+            );
+        }
+    }
+}
+
+impl OperandVisitor for AtomMarker {
+    #[inline]
+    fn visit_atom(&mut self, atom: Atom) {
+        self.mark(atom);
+    }
+
+    #[inline]
+    fn visit_cell(&mut self, cell: HeapCellValue) {
+        self.mark_cell(cell);
+    }
+
+    fn visit_indexing_lines(&mut self, indexing_lines: &[IndexingLine]) {
+        for indexing_line in indexing_lines {
+            match indexing_line {
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnConstant(constants)) => {
+                    for literal in constants.keys() {
+                        self.mark_literal(literal);
+                    }
+                }
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnStructure(structures)) => {
+                    for &(name, _) in structures.keys() {
+                        self.mark(name);
+                    }
+                }
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(..)) |
+                IndexingLine::IndexedChoice(_) |
+                IndexingLine::DynamicIndexedChoice(_) => {
+                }
+            }
+        }
+    }
+}
+
+// marks the atoms held by the context installed in machine_st.
+fn mark_execution_context(machine_st: &MachineState, marker: &mut AtomMarker) {
+    marker.mark_cells(&machine_st.heap);
+    marker.mark_cells(&machine_st.lifted_heap);
+    marker.mark_cells(machine_st.registers.iter());
+
+    let (_or_frames, stack_cells) = machine_st.live_stack_frames();
+
+    for s in stack_cells {
+        marker.mark_cell(machine_st.stack[s]);
+    }
+
+    let mut i = 0;
+
+    while i < machine_st.trail.len() {
+        let value = machine_st.trail[i].get_value() as usize;
+
+        match machine_st.trail[i].get_tag() {
+            TrailEntryTag::TrailedBlackboardEntry => {
+                marker.mark(Atom::from(value));
+            }
+            TrailEntryTag::TrailedBlackboardOffset => {
+                marker.mark(Atom::from(value));
+                marker.mark_cell(HeapCellValue::from(u64::from(machine_st.trail[i + 1])));
+                i += 1;
+            }
+            TrailEntryTag::TrailedAttrVarListLink => {
+                i += 1;
+            }
+            TrailEntryTag::TrailedHeapVar | TrailEntryTag::TrailedStackVar |
+            TrailEntryTag::TrailedAttrVar | TrailEntryTag::TrailedAttachedValue => {
+            }
+        }
+
+        i += 1;
+    }
+
+    marker.mark_cells(&machine_st.ball.stub);
+
+    for ball in &machine_st.ball_stack {
+        marker.mark_cells(&ball.stub);
+    }
+
+    if let Some(ball) = &machine_st.caught_ball {
+        marker.mark_cells(&ball.stub);
+    }
+
+    for &(_, cell) in &machine_st.attr_var_init.bindings {
+        marker.mark_cell(cell);
+    }
+
+    marker.mark_cells(machine_st.cont_pts.iter().map(|(cell, ..)| cell));
+    marker.mark_cells(&machine_st.gc.roots);
+}
+
+impl Machine {
+    fn mark_atoms(&mut self, marker: &mut AtomMarker) {
+        mark_execution_context(&self.machine_st, marker);
+
+        // the contexts of the other coroutines and engines are swapped in
+        // one at a time to be marked like the running one.
+        let parked_contexts = self.coroutines
+            .parked_contexts_mut()
+            .chain(self.engines.parked_contexts_mut());
+
+        for context in parked_contexts {
+            context.swap(&mut self.machine_st);
+            mark_execution_context(&self.machine_st, marker);
+            context.swap(&mut self.machine_st);
+        }
+
+        for instr in &self.code {
+            instr.visit_operands(marker);
+        }
+
+        marker.mark_indices(&self.indices);
+        marker.mark_arena(&self.machine_st.arena);
+
+        self.mark_atom_roots(marker);
+    }
+
+    /// Marks the atoms held outside of the execution contexts, the code
+    /// area, the indices and the arena, i.e. by the state that features
+    /// keep in their own fields of `Machine` and `MachineState`. Those
+    /// atoms are invisible to the collector otherwise, so a field added
+    /// to either that holds atoms must be marked here, or its atoms may
+    /// be swept and their slots reused by unrelated atoms.
+    fn mark_atom_roots(&self, marker: &mut AtomMarker) {
+        for name in self.coroutines.aliases().chain(self.engines.aliases()) {
+            marker.mark(name);
+        }
+
+        for ball in self.coroutines.balls().chain(self.engines.balls()) {
+            marker.mark_cells(&ball.stub);
+        }
+
+        for &(module_name, (name, _)) in &self.tables.tabled {
+            marker.mark(module_name);
+            marker.mark(name);
+        }
+
+        for name in self.tables.trie_atoms() {
+            marker.mark(name);
+        }
+
+        for ball in self.tables.balls() {
+            marker.mark_cells(&ball.stub);
+        }
+
+        for load_context in &self.load_contexts {
+            marker.mark(load_context.module);
+            marker.mark_stream(&load_context.stream);
+        }
+
+        for (&file, loaded_file) in &self.loaded_files {
+            marker.mark(file);
+
+            if let Some(parent) = loaded_file.parent {
+                marker.mark(parent);
+            }
+        }
+
+        for name in self.native_predicates.names() {
+            marker.mark(name);
+        }
+
+        for name in self.foreign_function_table.atoms() {
+            marker.mark(name);
+        }
+
+        if let Some(output) = self.machine_st.profiler.output {
+            marker.mark(output);
+        }
+
+        if let Some(output) = self.machine_st.coverage.output {
+            marker.mark(output);
+        }
+    }
+
+    /// Reclaims the dynamic atoms that are no longer referenced by the
+    /// heap, the stacks, the code area or the indices. The heap is
+    /// collected first so that unreachable terms don't keep atoms
+    /// alive. Collected atoms keep their place in the atom table, whose
+    /// storage is reused by atoms created later.
+    ///
+    /// The contexts of suspended coroutines and engines are scanned along
+    /// with the running one, but only the running one's heap is
+    /// collected. Nothing is collected while a file is being loaded.
+    /// Like `garbage_collect`, this must only be called between calls.
+    pub(crate) fn garbage_collect_atoms(&mut self) {
+        let start = Instant::now();
+
+        if self.load_contexts.is_empty() {
+            self.garbage_collect();
+
+            let mut marker = AtomMarker::default();
+            self.mark_atoms(&mut marker);
+
+            if !marker.loading {
+                let collected = self.machine_st.atom_tbl.sweep(&marker.marks);
+
+                let gc = &mut self.machine_st.gc;
+
+                gc.atom_collections += 1;
+                gc.collected_atoms += collected;
+            }
+        }
+
+        let live_atoms = self.machine_st.atom_tbl.table.len();
+        let gc = &mut self.machine_st.gc;
+
+        gc.atom_threshold = std::cmp::max(GC_INITIAL_ATOM_THRESHOLD, 2 * live_atoms);
+        gc.atom_time += start.elapsed();
+    }
+
+    /// Collects the atom table if the number of dynamic atoms has grown
+    /// past the threshold set by the previous collection.
+    #[inline(always)]
+    pub(crate) fn maybe_garbage_collect_atoms(&mut self) {
+        if self.machine_st.atom_tbl.table.len() >= self.machine_st.gc.atom_threshold {
+            self.garbage_collect_atoms();
+        }
+    }
+}
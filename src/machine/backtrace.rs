@@ -0,0 +1,160 @@
+//! Exception backtraces.
+//!
+//! While the `backtrace` flag is on, a thrown ball records the call
+//! stack it was thrown from, read off the continuation pointers of the
+//! environment frames. A ball caught and thrown again keeps the
+//! backtrace it was first thrown with. The backtrace of the ball last
+//! caught is resolved to the predicates and clauses whose code holds
+//! its locations by '$caught_backtrace'/1, on which the toplevel and
+//! catch_with_backtrace/3 are built.
+
+use crate::atom_table::*;
+use crate::machine::heap::*;
+use crate::machine::loader::is_system_module;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::types::*;
+
+// the most code locations of a backtrace.
+const MAX_BACKTRACE_DEPTH: usize = 256;
+
+impl MachineState {
+    /// The code locations of the call stack, from P out through the
+    /// continuation pointers of the environment frames, up to
+    /// `max_depth` of them.
+    pub(crate) fn call_stack_locs(&self, max_depth: usize) -> Vec<usize> {
+        let mut locs = vec![self.p, self.cp];
+        let mut e = self.e;
+
+        while e < self.stack.len() && locs.len() < max_depth {
+            let prelude = &self.stack.index_and_frame(e).prelude;
+            locs.push(prelude.cp);
+
+            if prelude.e == e {
+                break;
+            }
+
+            e = prelude.e;
+        }
+
+        locs
+    }
+
+    /// The backtrace of the ball at `addr` about to be thrown: that of
+    /// the ball last caught if it's thrown again, or else the call
+    /// stack if the backtrace flag is on.
+    pub(super) fn ball_backtrace(&mut self, addr: HeapCellValue) -> Vec<usize> {
+        if let Some(caught_ball) = self.caught_ball.take() {
+            let h = self.heap.len();
+
+            self.heap.extend(caught_ball.copy_and_align(h));
+
+            let rethrown = !self.eq_test(self.heap[h], addr);
+            self.heap.truncate(h);
+
+            if rethrown {
+                return caught_ball.backtrace;
+            }
+        }
+
+        if self.flags.backtrace {
+            self.call_stack_locs(MAX_BACKTRACE_DEPTH)
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Machine {
+    // the clauses of the backtrace from the innermost out, with the
+    // code locations at which they start, leaving out those of system
+    // modules.
+    fn backtrace_clauses(&self, backtrace: &[usize]) -> Vec<(usize, CodeLoc)> {
+        let code_locs = self.indices.code_locs();
+        let resolve = |p: usize| {
+            code_locs
+                .range(..=p)
+                .next_back()
+                .map(|(&clause_loc, &code_loc)| (clause_loc, code_loc))
+        };
+
+        let mut clauses = vec![];
+
+        for (i, &p) in backtrace.iter().enumerate() {
+            // the continuation pointer is left over from a call made
+            // by the clause at P, or saved in its environment, if it
+            // isn't that of a clause without one.
+            if i == 1 && (backtrace.get(2) == Some(&p) || resolve(p) == resolve(backtrace[0])) {
+                continue;
+            }
+
+            match resolve(p) {
+                Some((clause_loc, code_loc)) if !is_system_module(code_loc.module_name) => {
+                    clauses.push((clause_loc, code_loc));
+                }
+                _ => {}
+            }
+        }
+
+        clauses
+    }
+
+    /// Unifies the first register with the backtrace of the ball last
+    /// caught, as a list of `frame(Module:Name/Arity, Clause, File,
+    /// Line)` terms from the innermost out, where `Clause` is the number
+    /// of the clause, and `File` and `Line` the file and the line it
+    /// was loaded from, or `[]`.
+    /// Fails if the ball was thrown without a backtrace.
+    pub(crate) fn caught_backtrace(&mut self) {
+        let backtrace = match &self.machine_st.caught_ball {
+            Some(ball) => ball.backtrace.clone(),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let mut frames = vec![];
+
+        for (clause_loc, code_loc) in self.backtrace_clauses(&backtrace) {
+            let src = self.indices.clause_sources.get(&clause_loc);
+
+            let file = match src.and_then(|src| src.file) {
+                Some(file) => atom_as_cell!(file),
+                None => empty_list_as_cell!(),
+            };
+
+            let line = match src {
+                Some(src) => fixnum_as_cell!(Fixnum::build_with(src.line as i64)),
+                None => empty_list_as_cell!(),
+            };
+
+            let (name, arity) = code_loc.key;
+            let heap = &mut self.machine_st.heap;
+            let h = heap.len();
+
+            heap.push(atom_as_cell!(atom!("/"), 2));
+            heap.push(atom_as_cell!(name));
+            heap.push(fixnum_as_cell!(Fixnum::build_with(arity as i64)));
+            heap.push(atom_as_cell!(atom!(":"), 2));
+            heap.push(atom_as_cell!(code_loc.module_name));
+            heap.push(str_loc_as_cell!(h));
+            heap.push(atom_as_cell!(atom!("frame"), 4));
+            heap.push(str_loc_as_cell!(h + 3));
+            heap.push(fixnum_as_cell!(Fixnum::build_with(code_loc.clause as i64)));
+            heap.push(file);
+            heap.push(line);
+
+            frames.push(str_loc_as_cell!(h + 6));
+        }
+
+        let frames = heap_loc_as_cell!(iter_to_heap_list(
+            &mut self.machine_st.heap,
+            frames.into_iter(),
+        ));
+
+        unify!(self.machine_st, self.machine_st.registers[1], frames);
+    }
+}
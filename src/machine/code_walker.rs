@@ -41,6 +41,13 @@ fn capture_offset(line: &Instruction, index: usize, stack: &mut Vec<usize>) -> b
  * walker function.
  */
 pub(crate) fn walk_code(code: &Code, p: usize, mut walker: impl FnMut(&Instruction)) {
+    walk_code_locs(code, p, |_, instr| walker(instr));
+}
+
+/* Like walk_code, but each instruction is passed to the walker
+ * function together with its offset in code.
+ */
+pub(crate) fn walk_code_locs(code: &Code, p: usize, mut walker: impl FnMut(usize, &Instruction)) {
     let mut stack = vec![p];
     let mut visited_indices = IndexSet::with_hasher(FxBuildHasher::default());
 
@@ -52,7 +59,7 @@ pub(crate) fn walk_code(code: &Code, p: usize, mut walker: impl FnMut(&Instructi
         }
 
         for (index, instr) in code[first_index..].iter().enumerate() {
-            walker(instr);
+            walker(first_index + index, instr);
 
             if capture_offset(instr, first_index + index, &mut stack) {
                 break;
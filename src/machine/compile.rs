@@ -3,6 +3,7 @@
 use crate::forms::*;
 use crate::indexing::{merge_clause_index, remove_index};
 use crate::instructions::*;
+use crate::machine::fact_table::*;
 use crate::machine::load_state::*;
 use crate::machine::loader::*;
 use crate::machine::machine_errors::*;
@@ -1280,6 +1281,38 @@ pub(super) fn listing_src_file_name(&mut self) -> Option<Atom> {
         None
     }
 
+    fn add_clause_source(
+        &mut self,
+        clause_loc: usize,
+        compilation_target: CompilationTarget,
+        key: PredicateKey,
+        file: Option<Atom>,
+        position: TermPosition,
+    ) {
+        self.wam_prelude.indices.clause_sources.insert(
+            clause_loc,
+            ClauseSource {
+                module_name: compilation_target.module_name(),
+                key,
+                file,
+                line: position.line,
+                col: position.col,
+            },
+        );
+
+        self.payload
+            .retraction_info
+            .push_record(RetractionRecord::AddedClauseSource(clause_loc));
+    }
+
+    pub(super) fn remove_clause_source(&mut self, clause_loc: usize) {
+        if let Some(source) = self.wam_prelude.indices.clause_sources.remove(&clause_loc) {
+            self.payload
+                .retraction_info
+                .push_record(RetractionRecord::RemovedClauseSource(clause_loc, source));
+        }
+    }
+
     fn compile_standalone_clause(
         &mut self,
         term: Term,
@@ -1323,6 +1356,8 @@ fn compile(
             clauses.push(self.try_term_to_tl(term, &mut preprocessor)?);
         }
 
+        let file = self.listing_src_file_name();
+
         let mut cg = CodeGenerator::new(
             &mut LS::machine_st(&mut self.payload).atom_tbl,
             settings,
@@ -1330,6 +1365,16 @@ fn compile(
 
         let mut code = cg.compile_predicate(clauses)?;
 
+        let clause_positions: Vec<_> = cg
+            .skeleton
+            .clauses
+            .iter()
+            .zip(&predicates.positions)
+            .filter_map(|(clause_index_info, &position)| {
+                position.map(|position| (code_len + clause_index_info.clause_start, position))
+            })
+            .collect();
+
         if settings.is_extensible {
             let mut clause_clause_locs = VecDeque::new();
 
@@ -1391,6 +1436,16 @@ fn compile(
             );
         }
 
+        for (clause_loc, position) in clause_positions {
+            self.add_clause_source(
+                clause_loc,
+                predicates.compilation_target,
+                key,
+                file,
+                position,
+            );
+        }
+
         print_overwrite_warning(
             &predicates.compilation_target,
             code_index.get(),
@@ -1416,6 +1471,64 @@ fn compile(
         Ok(code_index)
     }
 
+    // stores the ground facts of `predicates` in a new fact table, which
+    // becomes the code of `key`.
+    fn compile_fact_table(
+        &mut self,
+        key: PredicateKey,
+        mut predicates: PredicateQueue,
+    ) -> Result<CodeIndex, SessionError> {
+        let code_index = self.get_or_insert_code_index(key, predicates.compilation_target);
+
+        LS::err_on_builtin_overwrite(self, key)?;
+
+        let declared = self.payload.fact_table_preds.contains(&key);
+        let mut table = FactTable::new(key.1, declared);
+
+        for term in predicates.predicates.drain(0..) {
+            table.push_row(&term, &mut LS::machine_st(&mut self.payload).atom_tbl)?;
+        }
+
+        table.set_loc(self.wam_prelude.code.len());
+
+        let fact_tables = &mut self.wam_prelude.indices.fact_tables;
+
+        let id = match fact_tables.iter().position(FactTable::is_free) {
+            Some(id) => {
+                fact_tables[id] = table;
+                id
+            }
+            None => {
+                fact_tables.push(table);
+                fact_tables.len() - 1
+            }
+        };
+
+        self.payload
+            .retraction_info
+            .push_record(RetractionRecord::AddedFactTable(id));
+
+        print_overwrite_warning(
+            &predicates.compilation_target,
+            code_index.get(),
+            key,
+            false,
+        );
+
+        set_code_index(
+            &mut self.payload.retraction_info,
+            &predicates.compilation_target,
+            key,
+            code_index,
+            IndexPtr::index(self.wam_prelude.code.len()),
+        );
+
+        self.wam_prelude.code.push(Instruction::FactTable(id));
+        self.wam_prelude.code.push(Instruction::FactTableRetry(id));
+
+        Ok(code_index)
+    }
+
     fn extend_local_predicate_skeleton(
         &mut self,
         compilation_target: &CompilationTarget,
@@ -1539,14 +1652,31 @@ fn push_back_to_local_predicate_skeleton(
         }
     }
 
+    // discards the just-in-time index of a predicate whose clauses are
+    // about to change.
+    pub(super) fn invalidate_jit_index(&mut self, key: PredicateKey, compilation_target: CompilationTarget) {
+        let (name, arity) = key;
+        let module_name = compilation_target.module_name();
+
+        let code_index = self.wam_prelude.indices.get_predicate_code_index(name, arity, module_name);
+
+        if let Some(p) = code_index.and_then(|code_index| code_index.local()) {
+            self.wam_prelude.indices.jit_indices.remove(&p);
+            self.wam_prelude.indices.jit_unindexable.remove(p);
+        }
+    }
+
     pub(super) fn incremental_compile_clause(
         &mut self,
         key: PredicateKey,
         clause: Term,
+        position: Option<TermPosition>,
         compilation_target: CompilationTarget,
         non_counted_bt: bool,
         append_or_prepend: AppendOrPrepend,
     ) -> Result<CodeIndex, SessionError> {
+        self.invalidate_jit_index(key, compilation_target);
+
         let settings = match self
             .wam_prelude
             .indices
@@ -1576,7 +1706,9 @@ pub(super) fn incremental_compile_clause(
                     non_counted_bt,
                 };
 
-                let mut predicate_queue = predicate_queue![clause];
+                let mut predicate_queue = predicate_queue![];
+
+                predicate_queue.push(clause, position);
                 predicate_queue.compilation_target = compilation_target;
 
                 return self.compile(key, predicate_queue, settings);
@@ -1589,6 +1721,11 @@ pub(super) fn incremental_compile_clause(
         } = self.compile_standalone_clause(clause, settings)?;
 
         let code_len = self.wam_prelude.code.len();
+        let file = self.listing_src_file_name();
+
+        if let Some(position) = position {
+            self.add_clause_source(code_len, compilation_target, key, file, position);
+        }
 
         let skeleton = match self
             .wam_prelude
@@ -1682,7 +1819,24 @@ pub(super) fn incremental_compile_clause(
         }
     }
 
+    // forgets the source of the clause at `target_pos` in the skeleton
+    // of `key`, which is about to be retracted.
+    fn remove_target_clause_source(&mut self, key: PredicateKey, target_pos: usize) {
+        let clause_loc = self
+            .wam_prelude
+            .indices
+            .get_predicate_skeleton(&self.payload.compilation_target, &key)
+            .and_then(|skeleton| skeleton.core.clause_clause_locs.get(target_pos).cloned());
+
+        if let Some(clause_loc) = clause_loc {
+            self.remove_clause_source(clause_loc);
+        }
+    }
+
     pub(super) fn retract_dynamic_clause(&mut self, key: PredicateKey, target_pos: usize) -> usize {
+        self.invalidate_jit_index(key, self.payload.compilation_target);
+        self.remove_target_clause_source(key, target_pos);
+
         let skeleton = match self
             .wam_prelude
             .indices
@@ -1727,6 +1881,9 @@ pub(super) fn retract_dynamic_clause(&mut self, key: PredicateKey, target_pos: u
 
     pub(super) fn retract_clause(&mut self, key: PredicateKey, target_pos: usize) -> usize {
         let payload_compilation_target = self.payload.compilation_target;
+        self.invalidate_jit_index(key, payload_compilation_target);
+        self.remove_target_clause_source(key, target_pos);
+
         let code_index = self.get_or_insert_code_index(key, payload_compilation_target);
 
         let skeleton = self
@@ -2112,6 +2269,7 @@ pub(super) fn compile_clause_clauses<ClauseIter: Iterator<Item = (Term, Term)>>(
             self.incremental_compile_clause(
                 (atom!("$clause"), 2),
                 clause_term,
+                None,
                 clause_clause_compilation_target,
                 false, // non_counted_bt is false.
                 append_or_prepend,
@@ -2252,10 +2410,11 @@ pub(super) fn compile_and_submit(&mut self) -> Result<(), SessionError> {
         if predicate_info.compile_incrementally() {
             let predicates = self.payload.predicates.take();
 
-            for term in predicates.predicates {
+            for (term, position) in predicates.predicates.into_iter().zip(predicates.positions) {
                 self.incremental_compile_clause(
                     key,
                     term,
+                    position,
                     self.payload.predicates.compilation_target,
                     non_counted_bt,
                     AppendOrPrepend::Append,
@@ -2320,7 +2479,17 @@ pub(super) fn compile_and_submit(&mut self) -> Result<(), SessionError> {
             };
 
             let predicates = self.payload.predicates.take();
-            let code_index = self.compile(key, predicates, settings)?;
+
+            let is_fact_table = !predicate_info.is_extensible
+                && (predicates_len >= FACT_TABLE_MIN_ROWS
+                    || self.payload.fact_table_preds.contains(&key))
+                && are_ground_facts(&predicates.predicates);
+
+            let code_index = if is_fact_table {
+                self.compile_fact_table(key, predicates)?
+            } else {
+                self.compile(key, predicates, settings)?
+            };
 
             if let Some(filename) = self.listing_src_file_name() {
                 match self.wam_prelude.indices.modules.get_mut(&filename) {
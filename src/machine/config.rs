@@ -0,0 +1,320 @@
+use crate::arena::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::*;
+use crate::machine::saved_state::*;
+use crate::machine::streams::*;
+use crate::machine::Machine;
+
+use tokio::runtime::Runtime;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Describes how to construct one of the standard streams of a
+/// `Machine` once its arena exists.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    inner: StreamConfigInner,
+}
+
+#[derive(Debug, Clone)]
+enum StreamConfigInner {
+    Stdio,
+    InMemory(String),
+    Null,
+}
+
+impl StreamConfig {
+    /// stdin, stdout or stderr, depending on which stream is configured.
+    #[inline]
+    pub fn stdio() -> Self {
+        StreamConfig { inner: StreamConfigInner::Stdio }
+    }
+
+    /// An empty in-memory buffer. Output written to it can be drained
+    /// with `Machine::read_user_output` and `Machine::read_user_error`.
+    #[inline]
+    pub fn in_memory() -> Self {
+        StreamConfig { inner: StreamConfigInner::InMemory(String::new()) }
+    }
+
+    /// An in-memory buffer holding `contents`, typically used as
+    /// `user_input`.
+    #[inline]
+    pub fn from_string<S: Into<String>>(contents: S) -> Self {
+        StreamConfig { inner: StreamConfigInner::InMemory(contents.into()) }
+    }
+
+    /// A stream that is always at end of file and discards output.
+    #[inline]
+    pub fn null() -> Self {
+        StreamConfig { inner: StreamConfigInner::Null }
+    }
+
+    fn into_stream(self, arena: &mut Arena, stdio: impl FnOnce(&mut Arena) -> Stream) -> Stream {
+        match self.inner {
+            StreamConfigInner::Stdio => stdio(arena),
+            StreamConfigInner::InMemory(contents) => Stream::from_owned_string(contents, arena),
+            StreamConfigInner::Null => Stream::Null(StreamOptions::default()),
+        }
+    }
+}
+
+/// Configures and bootstraps a `Machine`.
+///
+/// ```ignore
+/// let mut machine = MachineBuilder::default()
+///     .with_user_output(StreamConfig::in_memory())
+///     .with_preloaded_library("assoc")
+///     .with_use_module("lists")
+///     .with_prolog_flag("double_quotes", Value::atom("codes"))
+///     .build()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MachineBuilder {
+    user_input: StreamConfig,
+    user_output: StreamConfig,
+    user_error: StreamConfig,
+    add_history: bool,
+    current_thread_runtime: bool,
+    preloaded_libraries: Vec<String>,
+    libraries: Vec<String>,
+    flags: Vec<(String, Value)>,
+}
+
+impl Default for MachineBuilder {
+    fn default() -> Self {
+        MachineBuilder {
+            user_input: StreamConfig::stdio(),
+            user_output: StreamConfig::stdio(),
+            user_error: StreamConfig::stdio(),
+            add_history: true,
+            current_thread_runtime: false,
+            preloaded_libraries: vec![],
+            libraries: vec![],
+            flags: vec![],
+        }
+    }
+}
+
+impl MachineBuilder {
+    #[inline]
+    pub fn with_user_input(mut self, config: StreamConfig) -> Self {
+        self.user_input = config;
+        self
+    }
+
+    #[inline]
+    pub fn with_user_output(mut self, config: StreamConfig) -> Self {
+        self.user_output = config;
+        self
+    }
+
+    #[inline]
+    pub fn with_user_error(mut self, config: StreamConfig) -> Self {
+        self.user_error = config;
+        self
+    }
+
+    /// Whether lines read from a stdio `user_input` are added to the
+    /// readline history.
+    #[inline]
+    pub fn with_add_history(mut self, add_history: bool) -> Self {
+        self.add_history = add_history;
+        self
+    }
+
+    /// Runs the machine's async I/O on the calling thread rather than
+    /// on a thread pool of its own.
+    #[inline]
+    pub fn with_current_thread_runtime(mut self) -> Self {
+        self.current_thread_runtime = true;
+        self
+    }
+
+    /// Compiles `library(name)` as part of bootstrapping without
+    /// importing any of its predicates into the user module. Libraries
+    /// that aren't preloaded are compiled when they're first used, so
+    /// preloading moves that cost out of the first query that needs
+    /// them and lets module-qualified calls like `assoc:list_to_assoc/2`
+    /// reach the library.
+    #[inline]
+    pub fn with_preloaded_library<S: Into<String>>(mut self, name: S) -> Self {
+        self.preloaded_libraries.push(name.into());
+        self
+    }
+
+    /// Runs `use_module(library(name))` in the user module once the
+    /// machine is bootstrapped and its libraries are preloaded, so the
+    /// library is both compiled and imported before the first query.
+    #[inline]
+    pub fn with_use_module<S: Into<String>>(mut self, name: S) -> Self {
+        self.libraries.push(name.into());
+        self
+    }
+
+    /// Sets the Prolog flag `flag` to `value` as if by
+    /// `set_prolog_flag/2`, after the `use_module/1` calls.
+    #[inline]
+    pub fn with_prolog_flag<S: Into<String>>(mut self, flag: S, value: Value) -> Self {
+        self.flags.push((flag.into(), value));
+        self
+    }
+
+    /// Bootstraps the machine, preloads its libraries, runs its
+    /// `use_module/1` calls and sets its flags. The error term of the
+    /// first library or flag that can't be loaded or set is returned in
+    /// `Err`.
+    pub fn build(self) -> Result<Machine, Value> {
+        let preloaded_libraries = self.preloaded_libraries.clone();
+        let libraries = self.libraries.clone();
+        let flags = self.flags.clone();
+
+        let mut wam = self.bootstrap();
+
+        for library in preloaded_libraries {
+            load_library(&mut wam, "use_module(library(L), [])", library, 2)?;
+        }
+
+        for library in libraries {
+            load_library(&mut wam, "use_module(library(L))", library, 1)?;
+        }
+
+        for (flag, value) in flags {
+            let bindings = [("F", Value::Atom(flag.clone())), ("V", value.clone())];
+
+            let succeeded = match wam.run_query_with_bindings("set_prolog_flag(F, V)", bindings)?.next() {
+                Some(Ok(_)) => true,
+                Some(Err(err)) => return Err(err),
+                None => false,
+            };
+
+            if !succeeded {
+                return Err(iso_error(
+                    Value::structure(
+                        "domain_error",
+                        vec![
+                            Value::atom("flag_value"),
+                            Value::structure("+", vec![Value::Atom(flag), value]),
+                        ],
+                    ),
+                    "set_prolog_flag",
+                    2,
+                ));
+            }
+        }
+
+        Ok(wam)
+    }
+
+    /// Bootstraps the machine with the configured streams only.
+    pub(crate) fn bootstrap(self) -> Machine {
+        let (machine_st, user_input, user_output, user_error, runtime) = self.into_parts();
+        Machine::bootstrap(machine_st, user_input, user_output, user_error, runtime)
+    }
+
+    /// Restores the machine from the state saved to `path` by
+    /// `save_program/2`, with the configured streams. The libraries and
+    /// flags of the builder aren't applied, as they are part of the
+    /// saved state.
+    pub fn restore<P: AsRef<Path>>(self, path: P) -> io::Result<Machine> {
+        let mut input = BufReader::new(File::open(path)?);
+        let (machine_st, user_input, user_output, user_error, runtime) = self.into_parts();
+
+        Machine::restore(machine_st, user_input, user_output, user_error, runtime, &mut input)
+    }
+
+    /// Restores the machine from the state embedded in the running
+    /// executable by `qsave_program/2`, or returns `None` if it has
+    /// none. An executable that can't be opened has none.
+    pub fn restore_embedded(self) -> io::Result<Option<Machine>> {
+        let mut exe = match env::current_exe().and_then(File::open) {
+            Ok(exe) => exe,
+            Err(_) => return Ok(None),
+        };
+
+        let (offset, len) = match embedded_state(&mut exe)? {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        exe.seek(SeekFrom::Start(offset))?;
+
+        let mut input = BufReader::new(exe.take(len));
+        let (machine_st, user_input, user_output, user_error, runtime) = self.into_parts();
+
+        Machine::restore(machine_st, user_input, user_output, user_error, runtime, &mut input)
+            .map(Some)
+    }
+
+    fn into_parts(self) -> (MachineState, Stream, Stream, Stream, Runtime) {
+        let mut machine_st = MachineState::new();
+        let add_history = self.add_history;
+
+        let user_input = self.user_input.into_stream(
+            &mut machine_st.arena,
+            |arena| Stream::stdin(arena, add_history),
+        );
+
+        let user_output = self.user_output.into_stream(&mut machine_st.arena, Stream::stdout);
+        let user_error = self.user_error.into_stream(&mut machine_st.arena, Stream::stderr);
+
+        let runtime = if self.current_thread_runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+        } else {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+        };
+
+        (machine_st, user_input, user_output, user_error, runtime)
+    }
+}
+
+fn load_library(wam: &mut Machine, query: &str, library: String, arity: i64) -> Result<(), Value> {
+    let bindings = [("L", Value::Atom(library.clone()))];
+
+    match wam.run_query_with_bindings(query, bindings)?.next() {
+        Some(Ok(_)) => Ok(()),
+        Some(Err(err)) => Err(err),
+        None => Err(iso_error(
+            Value::structure(
+                "existence_error",
+                vec![
+                    Value::atom("source_sink"),
+                    Value::structure("library", vec![Value::Atom(library)]),
+                ],
+            ),
+            "use_module",
+            arity,
+        )),
+    }
+}
+
+fn iso_error(err: Value, name: &str, arity: i64) -> Value {
+    Value::structure(
+        "error",
+        vec![
+            err,
+            Value::structure("/", vec![Value::atom(name), Value::from(arity)]),
+        ],
+    )
+}
+
+impl Machine {
+    /// Drains the output written to an in-memory `user_output` since
+    /// it was last read.
+    pub fn read_user_output(&mut self) -> Vec<u8> {
+        self.user_output.bytes().map_while(Result::ok).collect()
+    }
+
+    /// Drains the output written to an in-memory `user_error` since
+    /// it was last read.
+    pub fn read_user_error(&mut self) -> Vec<u8> {
+        self.user_error.bytes().map_while(Result::ok).collect()
+    }
+}
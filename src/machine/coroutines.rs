@@ -0,0 +1,596 @@
+use crate::atom_table::*;
+use crate::machine::attributed_variables::*;
+use crate::machine::copier::*;
+use crate::machine::dispatch::CALLS_PER_POLL;
+use crate::machine::heap::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::stack::*;
+use crate::machine::{Machine, BREAK_FROM_DISPATCH_LOOP_LOC};
+use crate::types::*;
+
+use std::collections::VecDeque;
+use std::mem;
+
+// the number of predicate calls a coroutine makes before it yields to
+// the other runnable coroutines.
+const COROUTINE_TIME_SLICE: usize = 1024;
+
+/// The registers and memory areas of a `MachineState` that belong to
+/// a single coroutine or engine. The running one keeps its context in
+/// the `MachineState` itself, the others keep theirs here until
+/// they're switched back in.
+#[derive(Debug)]
+pub(crate) struct ExecutionContext {
+    s: HeapPtr,
+    s_offset: usize,
+    p: usize,
+    oip: u32,
+    iip: u32,
+    b: usize,
+    b0: usize,
+    e: usize,
+    num_of_args: usize,
+    cp: usize,
+    attr_var_init: AttrVarInitializer,
+    fail: bool,
+    heap: Heap,
+    mode: MachineMode,
+    stack: Stack,
+    registers: Box<Registers>,
+    trail: Vec<TrailEntry>,
+    tr: usize,
+    hb: usize,
+    block: usize,
+    scc_block: usize,
+    ball: Ball,
+    ball_stack: Vec<Ball>,
+    caught_ball: Option<Ball>,
+    lifted_heap: Heap,
+    cont_pts: Vec<(HeapCellValue, usize, usize)>,
+    cwil: CWIL,
+    cc: usize,
+    dynamic_mode: FirstOrNext,
+    gc_roots: Vec<HeapCellValue>,
+}
+
+impl ExecutionContext {
+    /// A context with empty stacks, about to call the predicate at `p`
+    /// with `goal` as its only argument. Backtracking out of the
+    /// predicate continues at `bp`.
+    pub(crate) fn new(machine_st: &MachineState, goal: &Ball, p: usize, bp: usize) -> Self {
+        let mut heap = Heap::new();
+        heap.extend(goal.copy_and_align(0));
+
+        let mut stack = Stack::new();
+        let b = stack.allocate_or_frame(0);
+        let or_frame = stack.index_or_frame_mut(b);
+
+        or_frame.prelude.num_cells = 0;
+        or_frame.prelude.e = 0;
+        or_frame.prelude.cp = BREAK_FROM_DISPATCH_LOOP_LOC;
+        or_frame.prelude.b = b;
+        or_frame.prelude.bp = bp;
+        or_frame.prelude.boip = 0;
+        or_frame.prelude.biip = 0;
+        or_frame.prelude.tr = 0;
+        or_frame.prelude.h = heap.len();
+        or_frame.prelude.b0 = b;
+        or_frame.prelude.attr_var_queue_len = 0;
+
+        let mut registers = Box::new([heap_loc_as_cell!(0); MAX_ARITY + 1]);
+        registers[1] = heap_loc_as_cell!(0);
+
+        ExecutionContext {
+            s: HeapPtr::default(),
+            s_offset: 0,
+            p,
+            oip: 0,
+            iip: 0,
+            b,
+            b0: b,
+            e: 0,
+            num_of_args: 1,
+            cp: BREAK_FROM_DISPATCH_LOOP_LOC,
+            attr_var_init: AttrVarInitializer::new(machine_st.attr_var_init.verify_attrs_loc),
+            fail: false,
+            hb: heap.len(),
+            heap,
+            mode: MachineMode::Write,
+            stack,
+            registers,
+            trail: vec![],
+            tr: 0,
+            block: b,
+            scc_block: 0,
+            ball: Ball::new(),
+            ball_stack: vec![],
+            caught_ball: None,
+            lifted_heap: Heap::new(),
+            cont_pts: vec![],
+            cwil: CWIL::new(),
+            cc: 0,
+            dynamic_mode: FirstOrNext::First,
+            gc_roots: vec![],
+        }
+    }
+
+    /// Installs the context in `machine_st` and returns the context it
+    /// replaced.
+    #[inline]
+    pub(crate) fn exchange(mut self, machine_st: &mut MachineState) -> Self {
+        self.swap(machine_st);
+        self
+    }
+
+    /// Swaps the context with the one installed in `machine_st`.
+    pub(crate) fn swap(&mut self, machine_st: &mut MachineState) {
+        mem::swap(&mut self.s, &mut machine_st.s);
+        mem::swap(&mut self.s_offset, &mut machine_st.s_offset);
+        mem::swap(&mut self.p, &mut machine_st.p);
+        mem::swap(&mut self.oip, &mut machine_st.oip);
+        mem::swap(&mut self.iip, &mut machine_st.iip);
+        mem::swap(&mut self.b, &mut machine_st.b);
+        mem::swap(&mut self.b0, &mut machine_st.b0);
+        mem::swap(&mut self.e, &mut machine_st.e);
+        mem::swap(&mut self.num_of_args, &mut machine_st.num_of_args);
+        mem::swap(&mut self.cp, &mut machine_st.cp);
+        mem::swap(&mut self.attr_var_init, &mut machine_st.attr_var_init);
+        mem::swap(&mut self.fail, &mut machine_st.fail);
+        mem::swap(&mut self.heap, &mut machine_st.heap);
+        mem::swap(&mut self.mode, &mut machine_st.mode);
+        mem::swap(&mut self.stack, &mut machine_st.stack);
+        mem::swap(&mut *self.registers, &mut machine_st.registers);
+        mem::swap(&mut self.trail, &mut machine_st.trail);
+        mem::swap(&mut self.tr, &mut machine_st.tr);
+        mem::swap(&mut self.hb, &mut machine_st.hb);
+        mem::swap(&mut self.block, &mut machine_st.block);
+        mem::swap(&mut self.scc_block, &mut machine_st.scc_block);
+        mem::swap(&mut self.ball, &mut machine_st.ball);
+        mem::swap(&mut self.ball_stack, &mut machine_st.ball_stack);
+        mem::swap(&mut self.caught_ball, &mut machine_st.caught_ball);
+        mem::swap(&mut self.lifted_heap, &mut machine_st.lifted_heap);
+        mem::swap(&mut self.cont_pts, &mut machine_st.cont_pts);
+        mem::swap(&mut self.cwil, &mut machine_st.cwil);
+        mem::swap(&mut self.cc, &mut machine_st.cc);
+        mem::swap(&mut self.dynamic_mode, &mut machine_st.dynamic_mode);
+        mem::swap(&mut self.gc_roots, &mut machine_st.gc.roots);
+    }
+}
+
+impl MachineState {
+    /// Copies the term at `cell` out of the heap, e.g. to move it to
+    /// the heap of another coroutine.
+    pub(crate) fn copy_to_ball(&mut self, cell: HeapCellValue) -> Ball {
+        let mut ball = Ball::new();
+        ball.boundary = self.heap.len();
+
+        copy_term(
+            CopyBallTerm::new(&mut self.stack, &mut self.heap, &mut ball.stub),
+            cell,
+            AttrVarPolicy::DeepCopy,
+        );
+
+        ball
+    }
+
+    /// Writes a copy of the term held by `ball` to the heap.
+    pub(crate) fn ball_to_heap(&mut self, ball: &Ball) -> HeapCellValue {
+        let h = self.heap.len();
+        let stub = ball.copy_and_align(h);
+
+        self.heap.extend(stub);
+        heap_loc_as_cell!(h)
+    }
+}
+
+#[derive(Debug)]
+enum CoroutineState {
+    Runnable,
+    // waiting in coroutine_join/2 for the coroutine with the given id.
+    Joining(usize),
+    // waiting in coroutine_get_message/1 for more than the given number
+    // of messages to be queued.
+    Receiving(usize),
+    Finished(Ball),
+}
+
+#[derive(Debug)]
+struct Coroutine {
+    id: usize,
+    alias: Option<Atom>,
+    detached: bool,
+    state: CoroutineState,
+    queue: VecDeque<Ball>,
+    // `None` while the coroutine is running.
+    context: Option<ExecutionContext>,
+    // the ids of the engines the coroutine is running, innermost last.
+    engines: Vec<usize>,
+}
+
+impl Coroutine {
+    fn new(id: usize, alias: Option<Atom>, detached: bool, context: Option<ExecutionContext>) -> Self {
+        Coroutine {
+            id,
+            alias,
+            detached,
+            state: CoroutineState::Runnable,
+            queue: VecDeque::new(),
+            context,
+            engines: vec![],
+        }
+    }
+
+    #[inline]
+    fn is_finished(&self) -> bool {
+        matches!(self.state, CoroutineState::Finished(_))
+    }
+
+    fn handle(&self) -> HeapCellValue {
+        match self.alias {
+            Some(alias) => atom_as_cell!(alias),
+            None => fixnum_as_cell!(Fixnum::build_with(self.id as i64)),
+        }
+    }
+}
+
+/// The coroutines of a machine. The main coroutine, which runs the toplevel
+/// and the queries made from Rust, is always first.
+#[derive(Debug)]
+pub(crate) struct Coroutines {
+    coroutines: Vec<Coroutine>,
+    current: usize,
+    next_id: usize,
+    slice: usize,
+}
+
+impl Coroutines {
+    pub(crate) fn new() -> Self {
+        Coroutines {
+            coroutines: vec![Coroutine::new(0, Some(atom!("main")), false, None)],
+            current: 0,
+            next_id: 1,
+            slice: COROUTINE_TIME_SLICE,
+        }
+    }
+
+    /// True if only the main coroutine exists.
+    #[inline]
+    pub(crate) fn is_single(&self) -> bool {
+        self.coroutines.len() == 1
+    }
+
+    /// The contexts of the coroutines that aren't running.
+    pub(crate) fn parked_contexts_mut(&mut self) -> impl Iterator<Item = &mut ExecutionContext> {
+        self.coroutines.iter_mut().filter_map(|coroutine| coroutine.context.as_mut())
+    }
+
+    #[inline]
+    pub(crate) fn aliases(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.coroutines.iter().filter_map(|coroutine| coroutine.alias)
+    }
+
+    /// The terms queued for the coroutines, and those left by the
+    /// finished ones.
+    pub(crate) fn balls(&self) -> impl Iterator<Item = &Ball> {
+        self.coroutines.iter().flat_map(|coroutine| {
+            let result = match &coroutine.state {
+                CoroutineState::Finished(ball) => Some(ball),
+                _ => None,
+            };
+
+            coroutine.queue.iter().chain(result)
+        })
+    }
+
+    /// The ids of the engines run by the current coroutine, innermost
+    /// last.
+    #[inline]
+    pub(crate) fn running_engines(&mut self) -> &mut Vec<usize> {
+        &mut self.coroutines[self.current].engines
+    }
+
+    fn find(&self, id: HeapCellValue) -> Option<usize> {
+        read_heap_cell!(id,
+            (HeapCellValueTag::Fixnum, n) => {
+                let n = n.get_num();
+                self.coroutines.iter().position(|coroutine| coroutine.id as i64 == n)
+            }
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                self.coroutines.iter().position(|coroutine| coroutine.alias == Some(name))
+            }
+            _ => {
+                None
+            }
+        )
+    }
+
+    fn is_ready(&self, idx: usize) -> bool {
+        match &self.coroutines[idx].state {
+            CoroutineState::Runnable => true,
+            &CoroutineState::Joining(id) => {
+                self.coroutines
+                    .iter()
+                    .find(|coroutine| coroutine.id == id)
+                    .map(Coroutine::is_finished)
+                    .unwrap_or(true)
+            }
+            &CoroutineState::Receiving(n) => self.coroutines[idx].queue.len() > n,
+            CoroutineState::Finished(_) => false,
+        }
+    }
+
+    // the first coroutine after the current one, in round-robin order,
+    // that is ready to run.
+    fn next_ready(&self) -> Option<usize> {
+        let len = self.coroutines.len();
+
+        (1 .. len)
+            .map(|offset| (self.current + offset) % len)
+            .find(|&idx| self.is_ready(idx))
+    }
+
+    fn remove(&mut self, idx: usize) {
+        debug_assert_ne!(idx, self.current);
+
+        self.coroutines.remove(idx);
+
+        if idx < self.current {
+            self.current -= 1;
+        }
+    }
+}
+
+impl Machine {
+    fn switch_coroutine(&mut self, to: usize) {
+        let from = self.coroutines.current;
+        let context = self.coroutines.coroutines[to].context.take().unwrap();
+
+        self.coroutines.coroutines[from].context = Some(context.exchange(&mut self.machine_st));
+        self.coroutines.coroutines[to].state = CoroutineState::Runnable;
+        self.coroutines.current = to;
+        self.coroutines.slice = COROUTINE_TIME_SLICE;
+    }
+
+    /// Switches to the next runnable coroutine once the current one has
+    /// used up its time slice. Returns true if the switch was made,
+    /// in which case the instruction at `p` belongs to the new coroutine.
+    ///
+    /// Like `maybe_garbage_collect`, this must only be called between
+    /// calls. It's called once every `CALLS_PER_POLL` calls, which are
+    /// all charged to the slice.
+    #[inline(always)]
+    pub(crate) fn maybe_switch_coroutine(&mut self) -> bool {
+        if self.coroutines.is_single() {
+            return false;
+        }
+
+        self.coroutines.slice = self.coroutines.slice.saturating_sub(CALLS_PER_POLL);
+
+        if self.coroutines.slice > 0 {
+            return false;
+        }
+
+        match self.coroutines.next_ready() {
+            Some(idx) => {
+                self.switch_coroutine(idx);
+                true
+            }
+            None => {
+                self.coroutines.slice = COROUTINE_TIME_SLICE;
+                false
+            }
+        }
+    }
+
+    // parks the current coroutine until it's ready according to `state`.
+    // Its blocking call is made again once it's switched back in.
+    fn block_current_coroutine(
+        &mut self,
+        state: CoroutineState,
+        name: Atom,
+        arity: usize,
+    ) -> CallResult {
+        let current = self.coroutines.current;
+        self.coroutines.coroutines[current].state = state;
+
+        match self.coroutines.next_ready() {
+            Some(idx) => {
+                self.switch_coroutine(idx);
+                Ok(())
+            }
+            None => {
+                self.coroutines.coroutines[current].state = CoroutineState::Runnable;
+
+                let err = self.machine_st.deadlock_error();
+                let stub = functor_stub(name, arity);
+
+                Err(self.machine_st.error_form(err, stub))
+            }
+        }
+    }
+
+    fn coroutine_index(
+        &mut self,
+        id: HeapCellValue,
+        name: Atom,
+        arity: usize,
+    ) -> Result<usize, MachineStub> {
+        match self.coroutines.find(id) {
+            Some(idx) => Ok(idx),
+            None => {
+                let err = self.machine_st.existence_error(ExistenceError::Coroutine(id));
+                let stub = functor_stub(name, arity);
+
+                Err(self.machine_st.error_form(err, stub))
+            }
+        }
+    }
+
+    /// The code of the predicate `module:name/arity`, which must be
+    /// defined by a loaded library.
+    pub(crate) fn library_code_index(&self, module: Atom, name: Atom, arity: usize) -> usize {
+        self.indices.modules
+            .get(&module)
+            .and_then(|module| module.code_dir.get(&(name, arity)))
+            .and_then(|code_index| code_index.local())
+            .unwrap()
+    }
+
+    pub(crate) fn coroutine_create(&mut self) -> CallResult {
+        let goal = self.machine_st.registers[1];
+        let alias = self.deref_register(2);
+        let detached = cell_as_atom!(self.deref_register(3)) == atom!("true");
+
+        let alias = if alias.is_var() {
+            None
+        } else if self.coroutines.find(alias).is_some() {
+            let err = self.machine_st.permission_error(
+                Permission::Create,
+                atom!("coroutine"),
+                alias,
+            );
+            let stub = functor_stub(atom!("coroutine_create"), 3);
+
+            return Err(self.machine_st.error_form(err, stub));
+        } else {
+            Some(cell_as_atom!(alias))
+        };
+
+        let goal = self.machine_st.copy_to_ball(goal);
+        let p = self.library_code_index(atom!("coroutines"), atom!("$coroutine_run"), 1);
+        let bp = self.library_code_index(atom!("coroutines"), atom!("$coroutine_failed"), 0);
+        let context = ExecutionContext::new(&self.machine_st, &goal, p, bp);
+
+        let id = self.coroutines.next_id;
+        self.coroutines.next_id += 1;
+
+        let coroutine = Coroutine::new(id, alias, detached, Some(context));
+        let handle = coroutine.handle();
+
+        self.coroutines.coroutines.push(coroutine);
+
+        let a4 = self.machine_st.registers[4];
+        unify!(self.machine_st, handle, a4);
+
+        Ok(())
+    }
+
+    /// Records the status of the current coroutine, which has run its
+    /// goal, and switches to another. Detached coroutines are removed
+    /// right away, the others once they're joined.
+    pub(crate) fn coroutine_finish(&mut self) {
+        let status = self.machine_st.registers[1];
+        let status = self.machine_st.copy_to_ball(status);
+
+        let current = self.coroutines.current;
+        let coroutine = &mut self.coroutines.coroutines[current];
+
+        coroutine.state = CoroutineState::Finished(status);
+
+        let detached = coroutine.detached;
+
+        // the main coroutine never finishes, so is always there to take
+        // over, if only to find that it's deadlocked.
+        let next = self.coroutines.next_ready().unwrap_or(0);
+        self.switch_coroutine(next);
+
+        if detached {
+            self.coroutines.remove(current);
+        } else {
+            self.coroutines.coroutines[current].context = None;
+        }
+    }
+
+    /// Unifies the second argument with the status of the coroutine named
+    /// by the first once it finishes. Returns false if the current
+    /// coroutine was switched out to wait for it.
+    pub(crate) fn coroutine_join(&mut self) -> Result<bool, MachineStub> {
+        let id = self.deref_register(1);
+        let idx = self.coroutine_index(id, atom!("coroutine_join"), 2)?;
+
+        if idx == self.coroutines.current || self.coroutines.coroutines[idx].detached {
+            let err = self.machine_st.permission_error(
+                Permission::Join,
+                atom!("coroutine"),
+                id,
+            );
+            let stub = functor_stub(atom!("coroutine_join"), 2);
+
+            return Err(self.machine_st.error_form(err, stub));
+        }
+
+        if let CoroutineState::Finished(status) = &self.coroutines.coroutines[idx].state {
+            let status = self.machine_st.ball_to_heap(status);
+            self.coroutines.remove(idx);
+
+            let a2 = self.machine_st.registers[2];
+            unify!(self.machine_st, status, a2);
+
+            return Ok(true);
+        }
+
+        let state = CoroutineState::Joining(self.coroutines.coroutines[idx].id);
+        self.block_current_coroutine(state, atom!("coroutine_join"), 2)?;
+
+        Ok(false)
+    }
+
+    pub(crate) fn coroutine_self(&mut self) {
+        let handle = self.coroutines.coroutines[self.coroutines.current].handle();
+        let a1 = self.machine_st.registers[1];
+
+        unify!(self.machine_st, handle, a1);
+    }
+
+    pub(crate) fn coroutine_send_message(&mut self) -> CallResult {
+        let id = self.deref_register(1);
+        let idx = self.coroutine_index(id, atom!("coroutine_send_message"), 2)?;
+
+        let message = self.machine_st.registers[2];
+        let message = self.machine_st.copy_to_ball(message);
+
+        self.coroutines.coroutines[idx].queue.push_back(message);
+
+        Ok(())
+    }
+
+    /// Waits until more than the given number of messages are queued
+    /// for the current coroutine. Returns false if the current coroutine was
+    /// switched out to wait for them.
+    pub(crate) fn coroutine_wait_message(&mut self) -> Result<bool, MachineStub> {
+        let n = cell_as_fixnum!(self.deref_register(1)).get_num() as usize;
+
+        if self.coroutines.coroutines[self.coroutines.current].queue.len() > n {
+            return Ok(true);
+        }
+
+        let state = CoroutineState::Receiving(n);
+        self.block_current_coroutine(state, atom!("coroutine_get_message"), 1)?;
+
+        Ok(false)
+    }
+
+    pub(crate) fn coroutine_peek_message(&mut self) {
+        let n = cell_as_fixnum!(self.deref_register(1)).get_num() as usize;
+        let coroutine = &self.coroutines.coroutines[self.coroutines.current];
+
+        let message = match coroutine.queue.get(n) {
+            Some(message) => self.machine_st.ball_to_heap(message),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let a2 = self.machine_st.registers[2];
+        unify!(self.machine_st, message, a2);
+    }
+
+    pub(crate) fn coroutine_remove_message(&mut self) {
+        let n = cell_as_fixnum!(self.deref_register(1)).get_num() as usize;
+        let current = self.coroutines.current;
+
+        self.coroutines.coroutines[current].queue.remove(n);
+    }
+}
@@ -0,0 +1,358 @@
+//! The clause coverage of library(coverage).
+//!
+//! While coverage is on, the machine counts the clauses it enters at
+//! the code locations their bodies start on, as calls, indexing and
+//! the `try`, `retry` and `trust` instructions jump to them, and the
+//! `proceed` instructions and last calls it runs. Code locations are
+//! resolved to the clauses whose code holds them only as the coverage
+//! is reported. A clause exits each time it runs through to its last
+//! goal, by way of `proceed` or a last call to a predicate. It fails
+//! each time its choice point is resumed at a later clause, so the
+//! failures of the last clause to be tried aren't counted.
+//!
+//! Only the clauses loaded from files by modules other than the
+//! system modules are covered. The coverage is written in the LCOV
+//! format as lines hit by the entries of the clauses starting on
+//! them, functions hit by the entries of the clauses of predicates,
+//! and branches taken by the exits and failures of clauses.
+
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::code_walker::walk_code_locs;
+use crate::machine::loader::is_system_module;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::partial_string::*;
+use crate::machine::Machine;
+use crate::types::*;
+
+use fxhash::FxHashMap;
+use indexmap::IndexMap;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+
+#[derive(Debug, Default)]
+pub(crate) struct Coverage {
+    active: bool,
+    // the entries of the clauses whose bodies start at each code
+    // location.
+    entries: FxHashMap<usize, u64>,
+    // the runs of the proceed or last call at each code location.
+    exits: FxHashMap<usize, u64>,
+    // the failures of the clauses whose bodies start at each code
+    // location.
+    fails: FxHashMap<usize, u64>,
+    // the body of the clause last entered from each choice point.
+    alternatives: FxHashMap<usize, usize>,
+    /// The file the coverage is written to as the machine halts, set
+    /// by the `--coverage` option.
+    pub(crate) output: Option<Atom>,
+}
+
+impl Coverage {
+    #[inline(always)]
+    pub(crate) fn active(&self) -> bool {
+        self.active
+    }
+
+    fn start(&mut self) {
+        *self = Coverage {
+            active: true,
+            output: self.output.take(),
+            ..Coverage::default()
+        };
+    }
+
+    /// Counts the run of the `proceed` or last call at `p`.
+    pub(crate) fn exit(&mut self, p: usize) {
+        *self.exits.entry(p).or_insert(0) += 1;
+    }
+}
+
+#[derive(Debug)]
+struct CoveredClause {
+    module_name: Atom,
+    key: PredicateKey,
+    line: usize,
+    entries: u64,
+    exits: u64,
+    fails: u64,
+}
+
+impl CoveredClause {
+    fn predicate_name(&self) -> String {
+        let (name, arity) = self.key;
+        format!("{}:{}/{}", self.module_name.as_str(), name.as_str(), arity)
+    }
+}
+
+// the start of the body of the clause at p, past the choice and
+// indexing instructions leading up to it.
+fn clause_body(code: &Code, mut p: usize) -> usize {
+    loop {
+        match &code[p] {
+            Instruction::TryMeElse(_)
+            | Instruction::RetryMeElse(_)
+            | Instruction::TrustMe(_)
+            | Instruction::DefaultRetryMeElse(_)
+            | Instruction::DefaultTrustMe(_)
+            | Instruction::DynamicElse(..)
+            | Instruction::DynamicInternalElse(..)
+            | Instruction::IndexingCode(_) => {
+                p += 1;
+            }
+            _ => {
+                return p;
+            }
+        }
+    }
+}
+
+fn count_at(counts: &FxHashMap<usize, u64>, p: usize) -> u64 {
+    counts.get(&p).cloned().unwrap_or(0)
+}
+
+fn percentage(n: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * n as f64 / total as f64
+    }
+}
+
+impl Machine {
+    /// Counts the entry of the clause whose body starts at P, jumped to
+    /// from a call or a choice instruction run with the choice point
+    /// `b`. If the instruction `resumed` the choice point, the clause
+    /// last entered from it has failed.
+    pub(super) fn cover_clause_entry(&mut self, b: usize, resumed: bool) {
+        let coverage = &mut self.machine_st.coverage;
+
+        if resumed {
+            if let Some(body) = coverage.alternatives.remove(&b) {
+                *coverage.fails.entry(body).or_insert(0) += 1;
+            }
+        }
+
+        let p = self.machine_st.p;
+
+        if self.machine_st.fail || clause_body(&self.code, p) != p {
+            return;
+        }
+
+        *coverage.entries.entry(p).or_insert(0) += 1;
+
+        // the choice point is kept by a retry and pushed by a try.
+        if resumed == (self.machine_st.b == b) {
+            coverage.alternatives.insert(self.machine_st.b, p);
+        }
+    }
+
+    // the covered clauses of each file, by their lines.
+    fn covered_clauses(&self) -> IndexMap<Atom, Vec<CoveredClause>> {
+        let coverage = &self.machine_st.coverage;
+        let mut files: IndexMap<Atom, Vec<CoveredClause>> = IndexMap::new();
+
+        for (clause_loc, code_loc) in self.indices.code_locs() {
+            if code_loc.clause == 0 || is_system_module(code_loc.module_name) {
+                continue;
+            }
+
+            let (file, line) = match self.indices.clause_sources.get(&clause_loc) {
+                Some(&ClauseSource {
+                    file: Some(file),
+                    line,
+                    ..
+                }) => (file, line),
+                _ => continue,
+            };
+
+            let body = clause_body(&self.code, clause_loc);
+
+            let mut exits = 0;
+
+            walk_code_locs(&self.code, body, |p, instr| {
+                if matches!(instr, Instruction::Proceed) || instr.is_execute() {
+                    exits += count_at(&coverage.exits, p);
+                }
+            });
+
+            files.entry(file).or_default().push(CoveredClause {
+                module_name: code_loc.module_name,
+                key: code_loc.key,
+                line,
+                entries: count_at(&coverage.entries, body),
+                exits,
+                fails: count_at(&coverage.fails, body),
+            });
+        }
+
+        files.sort_by(|file1, _, file2, _| file1.as_str().cmp(file2.as_str()));
+
+        for clauses in files.values_mut() {
+            clauses.sort_by_key(|clause| clause.line);
+        }
+
+        files
+    }
+
+    fn coverage_summary(&self) -> String {
+        let files = self.covered_clauses();
+        let mut report = String::new();
+
+        let _ = writeln!(
+            report,
+            "{:>8} {:>8} {:>8} {:>8}  {}",
+            "Clauses", "Entered", "Exited", "Failed", "File",
+        );
+
+        for (file, clauses) in &files {
+            let count = |port: fn(&CoveredClause) -> u64| {
+                clauses.iter().filter(|clause| port(clause) > 0).count()
+            };
+
+            let _ = writeln!(
+                report,
+                "{:>8} {:>7.1}% {:>7.1}% {:>7.1}%  {}",
+                clauses.len(),
+                percentage(count(|clause| clause.entries), clauses.len()),
+                percentage(count(|clause| clause.exits), clauses.len()),
+                percentage(count(|clause| clause.fails), clauses.len()),
+                file.as_str(),
+            );
+        }
+
+        report
+    }
+
+    // the coverage in the LCOV tracefile format.
+    fn lcov_records(&self) -> String {
+        let mut lcov = String::new();
+
+        for (file, clauses) in self.covered_clauses() {
+            let _ = writeln!(lcov, "TN:");
+            let _ = writeln!(lcov, "SF:{}", file.as_str());
+
+            let mut functions: IndexMap<String, (usize, u64)> = IndexMap::new();
+
+            for clause in &clauses {
+                functions
+                    .entry(clause.predicate_name())
+                    .or_insert((clause.line, 0))
+                    .1 += clause.entries;
+            }
+
+            for (name, &(line, _)) in &functions {
+                let _ = writeln!(lcov, "FN:{},{}", line, name);
+            }
+
+            for (name, &(_, entries)) in &functions {
+                let _ = writeln!(lcov, "FNDA:{},{}", entries, name);
+            }
+
+            let _ = writeln!(lcov, "FNF:{}", functions.len());
+            let _ = writeln!(
+                lcov,
+                "FNH:{}",
+                functions.values().filter(|(_, entries)| *entries > 0).count(),
+            );
+
+            let mut branches_hit = 0;
+
+            for (block, clause) in clauses.iter().enumerate() {
+                for (branch, taken) in [clause.exits, clause.fails].into_iter().enumerate() {
+                    if clause.entries == 0 {
+                        let _ = writeln!(lcov, "BRDA:{},{},{},-", clause.line, block, branch);
+                    } else {
+                        let _ = writeln!(lcov, "BRDA:{},{},{},{}", clause.line, block, branch, taken);
+                    }
+
+                    branches_hit += (taken > 0) as usize;
+                }
+            }
+
+            let _ = writeln!(lcov, "BRF:{}", 2 * clauses.len());
+            let _ = writeln!(lcov, "BRH:{}", branches_hit);
+
+            let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+
+            for clause in &clauses {
+                *lines.entry(clause.line).or_insert(0) += clause.entries;
+            }
+
+            for (line, entries) in &lines {
+                let _ = writeln!(lcov, "DA:{},{}", line, entries);
+            }
+
+            let _ = writeln!(lcov, "LF:{}", lines.len());
+            let _ = writeln!(
+                lcov,
+                "LH:{}",
+                lines.values().filter(|&&entries| entries > 0).count(),
+            );
+            let _ = writeln!(lcov, "end_of_record");
+        }
+
+        lcov
+    }
+
+    pub(crate) fn coverage_start(&mut self) {
+        self.machine_st.coverage.start();
+    }
+
+    pub(crate) fn coverage_stop(&mut self) {
+        self.machine_st.coverage.active = false;
+    }
+
+    /// Unifies the first register with the report of the last coverage
+    /// as a string.
+    pub(crate) fn coverage_report(&mut self) {
+        let report = self.coverage_summary();
+        let report = put_complete_string(
+            &mut self.machine_st.heap,
+            &report,
+            &mut self.machine_st.atom_tbl,
+        );
+
+        unify!(self.machine_st, self.machine_st.registers[1], report);
+    }
+
+    /// Writes the last coverage in the LCOV format to the file named by
+    /// the atom in the first register.
+    pub(crate) fn coverage_write_lcov(&mut self) -> CallResult {
+        let file = self.deref_register(1);
+        let path = cell_as_atom!(file);
+
+        match fs::write(path.as_str(), self.lcov_records()) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.machine_st.open_permission_error(file, atom!("coverage"), 2)),
+        }
+    }
+
+    /// Covers the rest of the run, for the `--coverage` option, whose
+    /// file is named by the atom in the first register.
+    pub(crate) fn coverage_at_halt(&mut self) {
+        let file = cell_as_atom!(self.deref_register(1));
+
+        self.machine_st.coverage.start();
+        self.machine_st.coverage.output = Some(file);
+    }
+
+    /// Reports the coverage begun by the `--coverage` option on
+    /// standard error as the machine halts, and writes it in the LCOV
+    /// format.
+    pub(crate) fn write_coverage_at_halt(&mut self) {
+        if let Some(file) = self.machine_st.coverage.output.take() {
+            self.coverage_stop();
+
+            eprint!("{}", self.coverage_summary());
+
+            if let Err(err) = fs::write(file.as_str(), self.lcov_records()) {
+                eprintln!("% could not write the coverage to {}: {}", file.as_str(), err);
+            }
+        }
+    }
+}
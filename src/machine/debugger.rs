@@ -0,0 +1,184 @@
+//! The hook of the box-model debugger of library(debug) into the
+//! calls of the machine.
+//!
+//! While tracing is on, each call of a visible predicate is redirected
+//! to the tracer of library(debug), which reports the ports of the
+//! call and runs it by way of '$debug_call'/3. Visible predicates are
+//! those not defined by a library or system module, whose code runs
+//! untraced; calls made back to user predicates from library code,
+//! such as those of call/N, are traced again.
+
+use crate::atom_table::*;
+use crate::machine::loader::is_system_module;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::types::*;
+
+use fxhash::FxHashMap;
+
+#[derive(Debug, Default)]
+pub(crate) struct Debugger {
+    tracing: bool,
+    // the code index of debug:trace_goal/3, set as tracing is turned on.
+    tracer_loc: Option<usize>,
+    // whether the predicates at each code index are visible.
+    visible: FxHashMap<usize, bool>,
+}
+
+impl Debugger {
+    #[inline(always)]
+    pub(crate) fn tracing(&self) -> bool {
+        self.tracing
+    }
+}
+
+impl Machine {
+    fn is_visible_predicate(&mut self, name: Atom, arity: usize, p: usize) -> bool {
+        if let Some(&visible) = self.debugger.visible.get(&p) {
+            return visible;
+        }
+
+        let visible = !self.indices.modules.iter().any(|(&module_name, module)| {
+            is_system_module(module_name)
+                && module
+                    .code_dir
+                    .get(&(name, arity))
+                    .and_then(|idx| idx.local())
+                    == Some(p)
+        });
+
+        self.debugger.visible.insert(p, visible);
+        visible
+    }
+
+    /// Redirects the call of `name/arity` at `idx` to the tracer if
+    /// tracing is on and the predicate is visible, in which case the
+    /// goal and its code index replace the arguments in the registers.
+    /// Returns whether the call was redirected.
+    pub(super) fn trace_call(
+        &mut self,
+        name: Atom,
+        arity: usize,
+        idx: IndexPtr,
+        last_call: bool,
+    ) -> bool {
+        let tracer_loc = match self.debugger.tracer_loc {
+            Some(tracer_loc) => tracer_loc,
+            None => return false,
+        };
+
+        let p = idx.p() as usize;
+
+        if !self.is_visible_predicate(name, arity, p) {
+            return false;
+        }
+
+        let goal = if arity == 0 {
+            atom_as_cell!(name)
+        } else {
+            let h = self.machine_st.heap.len();
+            self.machine_st.heap.push(atom_as_cell!(name, arity));
+
+            for i in 1..=arity {
+                let addr = self.machine_st.deref(self.machine_st.registers[i]);
+                let value = self.machine_st.store(addr);
+
+                if value.is_stack_var() {
+                    let h = self.machine_st.heap.len();
+                    self.machine_st.heap.push(heap_loc_as_cell!(h));
+                    (self.machine_st.bind_fn)(&mut self.machine_st, Ref::heap_cell(h), value);
+                } else {
+                    self.machine_st.heap.push(value);
+                }
+            }
+
+            str_loc_as_cell!(h)
+        };
+
+        let kind = match idx.tag() {
+            IndexPtrTag::DynamicIndex => atom!("dynamic"),
+            _ => atom!("static"),
+        };
+
+        self.machine_st.registers[1] = goal;
+        self.machine_st.registers[2] = fixnum_as_cell!(Fixnum::build_with(p as i64));
+        self.machine_st.registers[3] = atom_as_cell!(kind);
+
+        if last_call {
+            self.machine_st.execute_at_index(3, tracer_loc);
+        } else {
+            self.machine_st.call_at_index(3, tracer_loc);
+        }
+
+        true
+    }
+
+    /// Unifies the first register with whether tracing is on, and turns
+    /// it on or off as the second is `true` or `false`.
+    pub(crate) fn debugger_tracing(&mut self) {
+        let tracing = if self.debugger.tracing {
+            atom!("true")
+        } else {
+            atom!("false")
+        };
+
+        self.machine_st.unify_atom(tracing, self.machine_st.registers[1]);
+
+        if self.machine_st.fail {
+            return;
+        }
+
+        let tracing = cell_as_atom!(self.deref_register(2)) == atom!("true");
+
+        if tracing && !self.debugger.tracing {
+            self.debugger.tracer_loc = self
+                .indices
+                .get_predicate_code_index(atom!("trace_goal"), 3, atom!("debug"))
+                .and_then(|idx| idx.local());
+
+            // the code indices of predicates redefined since tracing
+            // was last on may have been taken by others.
+            self.debugger.visible.clear();
+        }
+
+        self.debugger.tracing = tracing && self.debugger.tracer_loc.is_some();
+    }
+
+    /// Calls the goal in the first register at the code index in the
+    /// second, bypassing the tracer. The third register is `dynamic`
+    /// if the predicate is dynamic.
+    pub(crate) fn debug_call(&mut self, last_call: bool) {
+        let goal = self.deref_register(1);
+        let p = cell_as_fixnum!(self.deref_register(2)).get_num() as usize;
+        let dynamic = cell_as_atom!(self.deref_register(3)) == atom!("dynamic");
+
+        let arity = read_heap_cell!(goal,
+            (HeapCellValueTag::Str, s) => {
+                let arity = cell_as_atom_cell!(self.machine_st.heap[s]).get_arity();
+
+                for i in 1..=arity {
+                    self.machine_st.registers[i] = self.machine_st.heap[s + i];
+                }
+
+                arity
+            }
+            _ => {
+                0
+            }
+        );
+
+        if dynamic {
+            self.machine_st.dynamic_mode = FirstOrNext::First;
+        }
+
+        if last_call {
+            self.machine_st.execute_at_index(arity, p);
+        } else {
+            self.machine_st.call_at_index(arity, p);
+        }
+
+        self.jit_index_call(arity);
+    }
+}
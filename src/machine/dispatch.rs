@@ -16,6 +16,7 @@ macro_rules! step_or_fail {
         if $self.machine_st.fail {
             $self.machine_st.backtrack();
         } else {
+            try_or_throw!($self.machine_st, $self.check_growth_limits());
             $step_e;
         }
     };
@@ -50,6 +51,35 @@ macro_rules! try_or_throw_gen {
 
 static INSTRUCTIONS_PER_INTERRUPT_POLL: usize = 256;
 
+/// The number of calls between polls of the heap and atom collectors,
+/// the resource limits and the coroutine scheduler, which would
+/// otherwise each be checked at every call.
+pub(crate) const CALLS_PER_POLL: usize = 32;
+
+// polls the collectors, limits and scheduler once every CALLS_PER_POLL
+// calls, so that the other calls only count down. Continues the
+// dispatch loop if a coroutine was switched in.
+macro_rules! poll_between_calls {
+    ($self:expr, $name:expr, $arity:expr) => {
+        if $self.machine_st.calls_until_poll == 0 {
+            $self.machine_st.calls_until_poll = CALLS_PER_POLL;
+
+            $self.maybe_garbage_collect();
+
+            try_or_throw!(
+                $self.machine_st,
+                $self.check_resource_limits($name, $arity)
+            );
+
+            if $self.maybe_switch_coroutine() {
+                continue;
+            }
+        } else {
+            $self.machine_st.calls_until_poll -= 1;
+        }
+    };
+}
+
 impl MachineState {
     #[inline(always)]
     fn compare(&mut self) -> CallResult {
@@ -279,6 +309,12 @@ pub(crate) fn constant_to_literal(&self, addr: HeapCellValue) -> Literal {
         )
     }
 
+    /// The keys of `addr` in a just-in-time index, looking into the
+    /// first argument of compound terms and the head of lists.
+    pub(crate) fn jit_arg_key(&self, addr: HeapCellValue) -> Option<JitArgKey> {
+        JitArgKey::from_cell(&self.heap, addr, |cell| self.store(self.deref(cell)))
+    }
+
     #[inline(always)]
     pub(crate) fn select_switch_on_structure_index(
         &self,
@@ -594,6 +630,32 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     let arity = std::cmp::max(arity, self.machine_st.num_of_args);
                     self.run_verify_attr_interrupt(arity);
                 }
+                &Instruction::JitRetry => {
+                    let b = self.machine_st.b;
+                    self.machine_st.p = self.retry_alternatives();
+
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, true);
+                    }
+
+                    try_or_throw!(
+                        self.machine_st,
+                        (self.machine_st.increment_call_count_fn)(&mut self.machine_st)
+                    );
+                }
+                &Instruction::FactTable(id) => {
+                    self.fact_table_call(id);
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::FactTableRetry(id) => {
+                    self.fact_table_retry(id);
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+
+                    try_or_throw!(
+                        self.machine_st,
+                        (self.machine_st.increment_call_count_fn)(&mut self.machine_st)
+                    );
+                }
                 &Instruction::Add(ref a1, ref a2, t) => {
                     let stub_gen = || functor_stub(atom!("is"), 2);
 
@@ -1053,6 +1115,9 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.machine_st.p += 1;
                 }
                 &Instruction::DynamicElse(..) => {
+                    let b = self.machine_st.b;
+                    let resumed = matches!(self.machine_st.dynamic_mode, FirstOrNext::Next);
+
                     if let FirstOrNext::First = self.machine_st.dynamic_mode {
                         self.machine_st.cc = self.machine_st.global_clock;
                     }
@@ -1122,6 +1187,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         }
                     }
 
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, resumed);
+                    }
+
                     self.machine_st.dynamic_mode = FirstOrNext::Next;
 
                     if self.machine_st.fail {
@@ -1129,6 +1198,8 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     }
                 }
                 &Instruction::DynamicInternalElse(..) => {
+                    let b = self.machine_st.b;
+                    let resumed = matches!(self.machine_st.dynamic_mode, FirstOrNext::Next);
                     let p = self.machine_st.p;
 
                     match self.find_living_dynamic_else(p) {
@@ -1192,6 +1263,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         }
                     }
 
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, resumed);
+                    }
+
                     self.machine_st.dynamic_mode = FirstOrNext::Next;
 
                     if self.machine_st.fail {
@@ -1199,25 +1274,52 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     }
                 }
                 &Instruction::TryMeElse(offset) => {
+                    let b = self.machine_st.b;
                     self.try_me_else(offset);
+
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, false);
+                    }
+
+                    try_or_throw!(self.machine_st, self.check_growth_limits());
                 }
                 &Instruction::DefaultRetryMeElse(offset) => {
+                    let b = self.machine_st.b;
                     self.retry_me_else(offset);
+
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, true);
+                    }
                 }
                 &Instruction::DefaultTrustMe(_) => {
+                    let b = self.machine_st.b;
                     self.trust_me();
+
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, true);
+                    }
                 }
                 &Instruction::RetryMeElse(offset) => {
+                    let b = self.machine_st.b;
                     self.retry_me_else(offset);
 
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, true);
+                    }
+
                     try_or_throw!(
                         self.machine_st,
                         (self.machine_st.increment_call_count_fn)(&mut self.machine_st)
                     );
                 }
                 &Instruction::TrustMe(_) => {
+                    let b = self.machine_st.b;
                     self.trust_me();
 
+                    if self.machine_st.coverage.active() {
+                        self.cover_clause_entry(b, true);
+                    }
+
                     try_or_throw!(
                         self.machine_st,
                         (self.machine_st.increment_call_count_fn)(&mut self.machine_st)
@@ -1260,6 +1362,7 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                 }
                 &Instruction::Allocate(num_cells) => {
                     self.machine_st.allocate(num_cells);
+                    try_or_throw!(self.machine_st, self.check_growth_limits());
                 }
                 &Instruction::DefaultCallAcyclicTerm => {
                     let addr = self.machine_st.registers[1];
@@ -2789,6 +2892,8 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                 &Instruction::CallNamed(arity, name, ref idx) => {
                     let idx = idx.get();
 
+                    poll_between_calls!(self, name, arity);
+
                     try_or_throw!(
                         self.machine_st,
                         self.try_call(name, arity, idx)
@@ -2806,6 +2911,8 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                 &Instruction::ExecuteNamed(arity, name, ref idx) => {
                     let idx = idx.get();
 
+                    poll_between_calls!(self, name, arity);
+
                     try_or_throw!(
                         self.machine_st,
                         self.try_execute(name, arity, idx)
@@ -2823,6 +2930,8 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                 &Instruction::DefaultCallNamed(arity, name, ref idx) => {
                     let idx = idx.get();
 
+                    poll_between_calls!(self, name, arity);
+
                     try_or_throw!(
                         self.machine_st,
                         self.try_call(name, arity, idx)
@@ -2835,6 +2944,8 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                 &Instruction::DefaultExecuteNamed(arity, name, ref idx) => {
                     let idx = idx.get();
 
+                    poll_between_calls!(self, name, arity);
+
                     try_or_throw!(
                         self.machine_st,
                         self.try_execute(name, arity, idx)
@@ -2854,6 +2965,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.machine_st.p -= offset;
                 }
                 &Instruction::Proceed => {
+                    if self.machine_st.coverage.active() {
+                        self.machine_st.coverage.exit(self.machine_st.p);
+                    }
+
                     self.machine_st.p = self.machine_st.cp;
                 }
                 &Instruction::GetConstant(_, c, reg) => {
@@ -3137,6 +3252,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         IndexingLine::Indexing(_) => {
                             self.execute_switch_on_term();
 
+                            if self.machine_st.coverage.active() {
+                                self.cover_clause_entry(self.machine_st.b, false);
+                            }
+
                             if self.machine_st.fail {
                                 self.machine_st.backtrack();
                             }
@@ -3144,19 +3263,36 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         IndexingLine::IndexedChoice(ref indexed_choice) => {
                             match &indexed_choice[self.machine_st.iip as usize] {
                                 &IndexedChoiceInstruction::Try(offset) => {
+                                    let b = self.machine_st.b;
                                     self.indexed_try(offset);
+
+                                    if self.machine_st.coverage.active() {
+                                        self.cover_clause_entry(b, false);
+                                    }
+
+                                    try_or_throw!(self.machine_st, self.check_growth_limits());
                                 }
                                 &IndexedChoiceInstruction::Retry(l) => {
+                                    let b = self.machine_st.b;
                                     self.retry(l);
 
+                                    if self.machine_st.coverage.active() {
+                                        self.cover_clause_entry(b, true);
+                                    }
+
                                     try_or_throw!(
                                         self.machine_st,
                                         (self.machine_st.increment_call_count_fn)(&mut self.machine_st)
                                     );
                                 }
                                 &IndexedChoiceInstruction::Trust(l) => {
+                                    let b = self.machine_st.b;
                                     self.trust(l);
 
+                                    if self.machine_st.coverage.active() {
+                                        self.cover_clause_entry(b, true);
+                                    }
+
                                     try_or_throw!(
                                         self.machine_st,
                                         (self.machine_st.increment_call_count_fn)(&mut self.machine_st)
@@ -3165,6 +3301,8 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                             }
                         }
                         IndexingLine::DynamicIndexedChoice(_) => {
+                            let b = self.machine_st.b;
+                            let resumed = matches!(self.machine_st.dynamic_mode, FirstOrNext::Next);
                             let p = self.machine_st.p;
 
                             match self.find_living_dynamic(self.machine_st.oip, self.machine_st.iip) {
@@ -3251,6 +3389,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                                 }
                             }
 
+                            if self.machine_st.coverage.active() {
+                                self.cover_clause_entry(b, resumed);
+                            }
+
                             self.machine_st.dynamic_mode = FirstOrNext::Next;
 
                             if self.machine_st.fail {
@@ -4209,6 +4351,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.get_unknown();
                     step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                 }
+                &Instruction::CallGetBacktrace => {
+                    self.get_backtrace();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteGetBacktrace => {
+                    self.get_backtrace();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallInstallNewBlock => {
                     self.machine_st.install_new_block(self.machine_st.registers[1]);
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -4297,6 +4447,370 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
 		    try_or_throw!(self.machine_st, self.define_foreign_struct());
 		    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
 		}
+                &Instruction::CallNativeCall => {
+                    try_or_throw!(self.machine_st, self.call_native());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteNativeCall => {
+                    try_or_throw!(self.machine_st, self.call_native());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallNativeRedo => {
+                    try_or_throw!(self.machine_st, self.redo_native());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteNativeRedo => {
+                    try_or_throw!(self.machine_st, self.redo_native());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallNativeChoicepoint => {
+                    self.native_choicepoint();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteNativeChoicepoint => {
+                    self.native_choicepoint();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallGarbageCollect => {
+                    self.garbage_collect();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteGarbageCollect => {
+                    self.garbage_collect();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallGarbageCollectAtoms => {
+                    self.garbage_collect_atoms();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteGarbageCollectAtoms => {
+                    self.garbage_collect_atoms();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallStatistics => {
+                    self.statistics();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteStatistics => {
+                    self.statistics();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoroutineCreate => {
+                    try_or_throw!(self.machine_st, self.coroutine_create());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoroutineCreate => {
+                    try_or_throw!(self.machine_st, self.coroutine_create());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoroutineFinish => {
+                    self.coroutine_finish();
+                }
+                &Instruction::ExecuteCoroutineFinish => {
+                    self.coroutine_finish();
+                }
+                &Instruction::CallCoroutineJoin => {
+                    if try_or_throw!(self.machine_st, self.coroutine_join()) {
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                }
+                &Instruction::ExecuteCoroutineJoin => {
+                    if try_or_throw!(self.machine_st, self.coroutine_join()) {
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                }
+                &Instruction::CallCoroutineSelf => {
+                    self.coroutine_self();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoroutineSelf => {
+                    self.coroutine_self();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoroutineSendMessage => {
+                    try_or_throw!(self.machine_st, self.coroutine_send_message());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoroutineSendMessage => {
+                    try_or_throw!(self.machine_st, self.coroutine_send_message());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoroutineWaitMessage => {
+                    if try_or_throw!(self.machine_st, self.coroutine_wait_message()) {
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                }
+                &Instruction::ExecuteCoroutineWaitMessage => {
+                    if try_or_throw!(self.machine_st, self.coroutine_wait_message()) {
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                }
+                &Instruction::CallCoroutinePeekMessage => {
+                    self.coroutine_peek_message();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoroutinePeekMessage => {
+                    self.coroutine_peek_message();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoroutineRemoveMessage => {
+                    self.coroutine_remove_message();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoroutineRemoveMessage => {
+                    self.coroutine_remove_message();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallEngineCreate => {
+                    try_or_throw!(self.machine_st, self.engine_create());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteEngineCreate => {
+                    try_or_throw!(self.machine_st, self.engine_create());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallEngineNext => {
+                    if try_or_throw!(self.machine_st, self.engine_next()) {
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                }
+                &Instruction::ExecuteEngineNext => {
+                    if try_or_throw!(self.machine_st, self.engine_next()) {
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                }
+                &Instruction::CallEngineYield => {
+                    self.machine_st.p += 1;
+                    self.engine_yield();
+                }
+                &Instruction::ExecuteEngineYield => {
+                    self.machine_st.p = self.machine_st.cp;
+                    self.engine_yield();
+                }
+                &Instruction::CallEngineException => {
+                    self.machine_st.p += 1;
+                    self.engine_exception();
+                }
+                &Instruction::ExecuteEngineException => {
+                    self.machine_st.p = self.machine_st.cp;
+                    self.engine_exception();
+                }
+                &Instruction::CallEngineExhausted => {
+                    self.machine_st.p += 1;
+                    self.engine_exhausted();
+                }
+                &Instruction::ExecuteEngineExhausted => {
+                    self.machine_st.p = self.machine_st.cp;
+                    self.engine_exhausted();
+                }
+                &Instruction::CallEngineDestroy => {
+                    try_or_throw!(self.machine_st, self.engine_destroy());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteEngineDestroy => {
+                    try_or_throw!(self.machine_st, self.engine_destroy());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallEngineSelf => {
+                    self.engine_self();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteEngineSelf => {
+                    self.engine_self();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallSaveProgram => {
+                    try_or_throw!(self.machine_st, self.save_program());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteSaveProgram => {
+                    try_or_throw!(self.machine_st, self.save_program());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallSaveExecutable => {
+                    try_or_throw!(self.machine_st, self.save_executable());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteSaveExecutable => {
+                    try_or_throw!(self.machine_st, self.save_executable());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallAddTabledPredicate => {
+                    self.add_tabled_predicate();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteAddTabledPredicate => {
+                    self.add_tabled_predicate();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTabledPredicate => {
+                    self.tabled_predicate();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTabledPredicate => {
+                    self.tabled_predicate();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallModuleHasTabledPredicates => {
+                    self.module_has_tabled_predicates();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteModuleHasTabledPredicates => {
+                    self.module_has_tabled_predicates();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblVariantTable => {
+                    try_or_throw!(self.machine_st, self.tbl_variant_table());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblVariantTable => {
+                    try_or_throw!(self.machine_st, self.tbl_variant_table());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblSetActive => {
+                    self.tbl_set_active();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblSetActive => {
+                    self.tbl_set_active();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAddAnswer => {
+                    try_or_throw!(self.machine_st, self.tbl_add_answer());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAddAnswer => {
+                    try_or_throw!(self.machine_st, self.tbl_add_answer());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblModedAnswer => {
+                    try_or_throw!(self.machine_st, self.tbl_moded_answer());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblModedAnswer => {
+                    try_or_throw!(self.machine_st, self.tbl_moded_answer());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblSetModedAnswer => {
+                    try_or_throw!(self.machine_st, self.tbl_set_moded_answer());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblSetModedAnswer => {
+                    try_or_throw!(self.machine_st, self.tbl_set_moded_answer());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAnswerCount => {
+                    self.tbl_answer_count();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAnswerCount => {
+                    self.tbl_answer_count();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAnswer => {
+                    self.tbl_answer();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAnswer => {
+                    self.tbl_answer();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAnswerConditions => {
+                    self.tbl_answer_conditions();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAnswerConditions => {
+                    self.tbl_answer_conditions();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblNegation => {
+                    self.tbl_negation();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblNegation => {
+                    self.tbl_negation();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAddDependency => {
+                    self.tbl_add_dependency();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAddDependency => {
+                    self.tbl_add_dependency();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblPopWork => {
+                    self.tbl_pop_work();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblPopWork => {
+                    self.tbl_pop_work();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblCreateScc => {
+                    self.tbl_create_scc();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblCreateScc => {
+                    self.tbl_create_scc();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblCompleteScc => {
+                    self.tbl_complete_scc();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblCompleteScc => {
+                    self.tbl_complete_scc();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAbandonScc => {
+                    self.tbl_abandon_scc();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAbandonScc => {
+                    self.tbl_abandon_scc();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblAbolishAllTables => {
+                    self.tbl_abolish_all_tables();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblAbolishAllTables => {
+                    self.tbl_abolish_all_tables();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblVariant => {
+                    self.tbl_variant();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblVariant => {
+                    self.tbl_variant();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallTblStatistics => {
+                    self.tbl_statistics();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteTblStatistics => {
+                    self.tbl_statistics();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallResourceLimit => {
+                    self.resource_limit();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteResourceLimit => {
+                    self.resource_limit();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallSetResourceLimit => {
+                    self.set_resource_limit();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteSetResourceLimit => {
+                    self.set_resource_limit();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallCurrentTime => {
                     self.current_time();
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -4405,6 +4919,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.set_unknown();
                     step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                 }
+                &Instruction::CallSetBacktrace => {
+                    self.set_backtrace();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteSetBacktrace => {
+                    self.set_backtrace();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallSetSeed => {
                     self.set_seed();
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -4801,6 +5323,108 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.debug_hook();
                     self.machine_st.p = self.machine_st.cp;
                 }
+                &Instruction::CallProfilerStart => {
+                    self.profiler_start();
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteProfilerStart => {
+                    self.profiler_start();
+                    self.machine_st.p = self.machine_st.cp;
+                }
+                &Instruction::CallProfilerStop => {
+                    self.profiler_stop();
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteProfilerStop => {
+                    self.profiler_stop();
+                    self.machine_st.p = self.machine_st.cp;
+                }
+                &Instruction::CallProfilerReport => {
+                    self.profiler_report();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteProfilerReport => {
+                    self.profiler_report();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallProfilerWriteFolded => {
+                    try_or_throw!(self.machine_st, self.profiler_write_folded());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteProfilerWriteFolded => {
+                    try_or_throw!(self.machine_st, self.profiler_write_folded());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallProfileAtHalt => {
+                    self.profile_at_halt();
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteProfileAtHalt => {
+                    self.profile_at_halt();
+                    self.machine_st.p = self.machine_st.cp;
+                }
+                &Instruction::CallCoverageStart => {
+                    self.coverage_start();
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteCoverageStart => {
+                    self.coverage_start();
+                    self.machine_st.p = self.machine_st.cp;
+                }
+                &Instruction::CallCoverageStop => {
+                    self.coverage_stop();
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteCoverageStop => {
+                    self.coverage_stop();
+                    self.machine_st.p = self.machine_st.cp;
+                }
+                &Instruction::CallCoverageReport => {
+                    self.coverage_report();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoverageReport => {
+                    self.coverage_report();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoverageWriteLcov => {
+                    try_or_throw!(self.machine_st, self.coverage_write_lcov());
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCoverageWriteLcov => {
+                    try_or_throw!(self.machine_st, self.coverage_write_lcov());
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCoverageAtHalt => {
+                    self.coverage_at_halt();
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteCoverageAtHalt => {
+                    self.coverage_at_halt();
+                    self.machine_st.p = self.machine_st.cp;
+                }
+                &Instruction::CallDebuggerTracing => {
+                    self.debugger_tracing();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteDebuggerTracing => {
+                    self.debugger_tracing();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallDebugCall => {
+                    self.debug_call(false);
+                }
+                &Instruction::ExecuteDebugCall => {
+                    self.debug_call(true);
+                }
+                &Instruction::CallCaughtBacktrace => {
+                    self.caught_backtrace();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCaughtBacktrace => {
+                    self.caught_backtrace();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallPopCount => {
                     self.pop_count();
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -4961,6 +5585,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     try_or_throw!(self.machine_st, self.push_load_context());
                     self.machine_st.p = self.machine_st.cp;
                 }
+                &Instruction::CallSetLoadContextPosition => {
+                    try_or_throw!(self.machine_st, self.set_load_context_position());
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteSetLoadContextPosition => {
+                    try_or_throw!(self.machine_st, self.set_load_context_position());
+                    self.machine_st.p = self.machine_st.cp;
+                }
                 &Instruction::CallPushLoadStatePayload => {
                     self.push_load_state_payload();
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -4969,6 +5601,22 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.push_load_state_payload();
                     step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                 }
+                &Instruction::CallPushModuleLoadStatePayload => {
+                    self.push_module_load_state_payload();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecutePushModuleLoadStatePayload => {
+                    self.push_module_load_state_payload();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallLoadStateModule => {
+                    self.load_state_module();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteLoadStateModule => {
+                    self.load_state_module();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallUseModule => {
                     try_or_throw!(self.machine_st, self.use_module());
                     self.machine_st.p += 1;
@@ -5001,6 +5649,22 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.meta_predicate_property();
                     step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                 }
+                &Instruction::CallIndexedProperty => {
+                    self.indexed_property();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteIndexedProperty => {
+                    self.indexed_property();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallFactTableProperty => {
+                    self.fact_table_property();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteFactTableProperty => {
+                    self.fact_table_property();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallMultifileProperty => {
                     self.multifile_property();
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -5025,6 +5689,62 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     self.dynamic_property();
                     step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                 }
+                &Instruction::CallClauseLocs => {
+                    self.clause_locs();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteClauseLocs => {
+                    self.clause_locs();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallClauseSource => {
+                    self.clause_source();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteClauseSource => {
+                    self.clause_source();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallModifiedLoadedFiles => {
+                    self.modified_loaded_files();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteModifiedLoadedFiles => {
+                    self.modified_loaded_files();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallCurrentModules => {
+                    self.current_modules();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteCurrentModules => {
+                    self.current_modules();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallModuleClass => {
+                    self.module_class();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteModuleClass => {
+                    self.module_class();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallModuleFile => {
+                    self.module_file();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteModuleFile => {
+                    self.module_file();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
+                &Instruction::CallModuleExports => {
+                    self.module_exports();
+                    step_or_fail!(self, self.machine_st.p += 1);
+                }
+                &Instruction::ExecuteModuleExports => {
+                    self.module_exports();
+                    step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                }
                 &Instruction::CallAbolishClause => {
                     try_or_throw!(self.machine_st, self.abolish_clause());
                     self.machine_st.p += 1;
@@ -5089,6 +5809,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     try_or_throw!(self.machine_st, self.add_non_counted_backtracking());
                     self.machine_st.p = self.machine_st.cp;
                 }
+                &Instruction::CallAddFactTable => {
+                    try_or_throw!(self.machine_st, self.add_fact_table());
+                    self.machine_st.p += 1;
+                }
+                &Instruction::ExecuteAddFactTable => {
+                    try_or_throw!(self.machine_st, self.add_fact_table());
+                    self.machine_st.p = self.machine_st.cp;
+                }
                 &Instruction::CallPredicateDefined => {
                     self.machine_st.fail = !self.predicate_defined();
                     step_or_fail!(self, self.machine_st.p += 1);
@@ -0,0 +1,255 @@
+use crate::atom_table::*;
+use crate::machine::coroutines::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::Machine;
+use crate::types::*;
+
+#[derive(Debug)]
+enum EngineResult {
+    Answer(Ball),
+    Exception(Ball),
+    Exhausted,
+}
+
+#[derive(Debug)]
+struct Engine {
+    id: usize,
+    alias: Option<Atom>,
+    // `None` while the engine is running, or once it's finished.
+    context: Option<ExecutionContext>,
+    // the context of the caller of engine_next/2, parked while the
+    // engine runs.
+    caller: Option<ExecutionContext>,
+    // the term delivered by the engine, to be taken by engine_next/2
+    // once the caller is switched back in.
+    result: Option<EngineResult>,
+    finished: bool,
+}
+
+impl Engine {
+    fn handle(&self) -> HeapCellValue {
+        match self.alias {
+            Some(alias) => atom_as_cell!(alias),
+            None => fixnum_as_cell!(Fixnum::build_with(self.id as i64)),
+        }
+    }
+}
+
+/// The engines of a machine. An engine runs a goal in a context of
+/// its own, one answer at a time, on behalf of the coroutine that calls
+/// engine_next/2.
+#[derive(Debug, Default)]
+pub(crate) struct Engines {
+    engines: Vec<Engine>,
+    next_id: usize,
+}
+
+impl Engines {
+    /// The contexts of the engines that aren't running, and of the
+    /// callers parked while their engines run.
+    pub(crate) fn parked_contexts_mut(&mut self) -> impl Iterator<Item = &mut ExecutionContext> {
+        self.engines
+            .iter_mut()
+            .flat_map(|engine| engine.context.iter_mut().chain(engine.caller.iter_mut()))
+    }
+
+    #[inline]
+    pub(crate) fn aliases(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.engines.iter().filter_map(|engine| engine.alias)
+    }
+
+    /// The answers and exceptions not yet taken by engine_next/2.
+    pub(crate) fn balls(&self) -> impl Iterator<Item = &Ball> {
+        self.engines.iter().filter_map(|engine| match &engine.result {
+            Some(EngineResult::Answer(ball) | EngineResult::Exception(ball)) => Some(ball),
+            Some(EngineResult::Exhausted) | None => None,
+        })
+    }
+
+    fn find(&self, id: HeapCellValue) -> Option<usize> {
+        read_heap_cell!(id,
+            (HeapCellValueTag::Fixnum, n) => {
+                let n = n.get_num();
+                self.engines.iter().position(|engine| engine.id as i64 == n)
+            }
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                self.engines.iter().position(|engine| engine.alias == Some(name))
+            }
+            _ => {
+                None
+            }
+        )
+    }
+}
+
+impl Machine {
+    fn engine_index(&mut self, id: HeapCellValue, name: Atom, arity: usize) -> Result<usize, MachineStub> {
+        let idx = match self.engines.find(id) {
+            Some(idx) => idx,
+            None => {
+                let err = self.machine_st.existence_error(ExistenceError::Engine(id));
+                let stub = functor_stub(name, arity);
+
+                return Err(self.machine_st.error_form(err, stub));
+            }
+        };
+
+        if self.coroutines.running_engines().contains(&self.engines.engines[idx].id) {
+            let err = self.machine_st.permission_error(Permission::Access, atom!("engine"), id);
+            let stub = functor_stub(name, arity);
+
+            return Err(self.machine_st.error_form(err, stub));
+        }
+
+        Ok(idx)
+    }
+
+    pub(crate) fn engine_create(&mut self) -> CallResult {
+        let run = self.machine_st.registers[1];
+        let alias = self.deref_register(2);
+
+        let alias = if alias.is_var() {
+            None
+        } else if self.engines.find(alias).is_some() {
+            let err = self.machine_st.permission_error(Permission::Create, atom!("engine"), alias);
+            let stub = functor_stub(atom!("engine_create"), 4);
+
+            return Err(self.machine_st.error_form(err, stub));
+        } else {
+            Some(cell_as_atom!(alias))
+        };
+
+        let run = self.machine_st.copy_to_ball(run);
+        let p = self.library_code_index(atom!("engines"), atom!("$engine_run"), 1);
+        let bp = self.library_code_index(atom!("engines"), atom!("$engine_exhausted"), 0);
+
+        let engine = Engine {
+            id: self.engines.next_id,
+            alias,
+            context: Some(ExecutionContext::new(&self.machine_st, &run, p, bp)),
+            caller: None,
+            result: None,
+            finished: false,
+        };
+
+        self.engines.next_id += 1;
+
+        let handle = engine.handle();
+        self.engines.engines.push(engine);
+
+        let a3 = self.machine_st.registers[3];
+        unify!(self.machine_st, handle, a3);
+
+        Ok(())
+    }
+
+    /// Runs the engine named by the first argument to its next answer,
+    /// which is unified with the second. The engine is switched in and
+    /// false is returned, after which the call is made again once the
+    /// engine delivers its answer.
+    pub(crate) fn engine_next(&mut self) -> Result<bool, MachineStub> {
+        let id = self.deref_register(1);
+        let idx = self.engine_index(id, atom!("engine_next"), 2)?;
+        let engine = &mut self.engines.engines[idx];
+
+        match engine.result.take() {
+            Some(EngineResult::Answer(answer)) => {
+                let answer = self.machine_st.ball_to_heap(&answer);
+                let a2 = self.machine_st.registers[2];
+
+                unify!(self.machine_st, answer, a2);
+                return Ok(true);
+            }
+            Some(EngineResult::Exception(ball)) => {
+                let ball = self.machine_st.ball_to_heap(&ball);
+
+                self.machine_st.registers[1] = ball;
+                self.machine_st.set_ball();
+                self.machine_st.unwind_stack();
+
+                return Ok(true);
+            }
+            Some(EngineResult::Exhausted) => {
+                self.machine_st.fail = true;
+                return Ok(true);
+            }
+            None if engine.finished => {
+                self.machine_st.fail = true;
+                return Ok(true);
+            }
+            None => {
+            }
+        }
+
+        let context = engine.context.take().unwrap();
+        engine.caller = Some(context.exchange(&mut self.machine_st));
+
+        let id = engine.id;
+        self.coroutines.running_engines().push(id);
+
+        Ok(false)
+    }
+
+    // hands `result` to the caller of the innermost running engine and
+    // switches the caller back in.
+    fn engine_deliver(&mut self, result: EngineResult) {
+        let id = self.coroutines.running_engines().pop().unwrap();
+        let idx = self.engines.engines.iter().position(|engine| engine.id == id).unwrap();
+        let engine = &mut self.engines.engines[idx];
+
+        let caller = engine.caller.take().unwrap();
+        let context = caller.exchange(&mut self.machine_st);
+
+        if let EngineResult::Answer(_) = &result {
+            engine.context = Some(context);
+        } else {
+            engine.finished = true;
+        }
+
+        engine.result = Some(result);
+    }
+
+    pub(crate) fn engine_yield(&mut self) {
+        let answer = self.machine_st.registers[1];
+        let answer = self.machine_st.copy_to_ball(answer);
+
+        self.engine_deliver(EngineResult::Answer(answer));
+    }
+
+    pub(crate) fn engine_exception(&mut self) {
+        let ball = self.machine_st.registers[1];
+        let ball = self.machine_st.copy_to_ball(ball);
+
+        self.engine_deliver(EngineResult::Exception(ball));
+    }
+
+    pub(crate) fn engine_exhausted(&mut self) {
+        self.engine_deliver(EngineResult::Exhausted);
+    }
+
+    pub(crate) fn engine_destroy(&mut self) -> CallResult {
+        let id = self.deref_register(1);
+        let idx = self.engine_index(id, atom!("engine_destroy"), 1)?;
+
+        self.engines.engines.remove(idx);
+        Ok(())
+    }
+
+    pub(crate) fn engine_self(&mut self) {
+        let id = self.coroutines.running_engines().last().cloned();
+        let handle = id.and_then(|id| {
+            self.engines.engines.iter().find(|engine| engine.id == id).map(Engine::handle)
+        });
+
+        match handle {
+            Some(handle) => {
+                let a1 = self.machine_st.registers[1];
+                unify!(self.machine_st, handle, a1);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+    }
+}
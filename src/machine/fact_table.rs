@@ -0,0 +1,311 @@
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::indexing::*;
+use crate::instructions::*;
+use crate::iterators::*;
+use crate::machine::heap::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::saved_state::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::read::*;
+use crate::types::*;
+
+use std::io;
+
+/// The fewest clauses a static predicate of ground facts must have to
+/// be stored as a fact table without being declared one with
+/// `:- fact_table(Name/Arity)`.
+pub(crate) const FACT_TABLE_MIN_ROWS: usize = 256;
+
+/// True if `clauses` are all ground facts of positive arity, and so can
+/// be stored in a fact table.
+pub(crate) fn are_ground_facts(clauses: &[Term]) -> bool {
+    clauses.iter().all(|clause| match clause {
+        Term::Clause(_, name, _) if *name != atom!(":-") => {
+            breadth_first_iter(clause, RootIterationPolicy::Iterated).all(|subterm| {
+                !matches!(subterm, TermRef::AnonVar(_) | TermRef::Var(..))
+            })
+        }
+        _ => false,
+    })
+}
+
+/// The ground facts of a static predicate, stored as rows of cells
+/// instead of being compiled to WAM code. A call selects the rows
+/// matching its instantiated arguments through the hash index of the
+/// most selective of them, and copies each row to the heap only when it
+/// is tried.
+#[derive(Debug)]
+pub(crate) struct FactTable {
+    arity: usize,
+    // true if the table was declared with `:- fact_table(Name/Arity)`,
+    // which makes its rows accessible to clause/2.
+    declared: bool,
+    // the location of the FactTable instruction calling the table, or
+    // None once the table is freed.
+    loc: Option<usize>,
+    // the rows, one after another, each written as the term
+    // `name(A1, ..., An)` with addresses relative to the start of cells.
+    cells: Heap,
+    // the offset of each row in cells.
+    rows: Vec<usize>,
+    // the hash index of each column, built the first time the column
+    // is instantiated in a call.
+    columns: Vec<Option<JitArgIndex>>,
+}
+
+impl FactTable {
+    pub(crate) fn new(arity: usize, declared: bool) -> Self {
+        FactTable {
+            arity,
+            declared,
+            loc: None,
+            cells: vec![],
+            rows: vec![],
+            columns: (0..arity).map(|_| None).collect(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn arity(&self) -> usize {
+        self.arity
+    }
+
+    #[inline]
+    pub(crate) fn is_declared(&self) -> bool {
+        self.declared
+    }
+
+    #[inline]
+    pub(crate) fn loc(&self) -> Option<usize> {
+        self.loc
+    }
+
+    #[inline]
+    pub(crate) fn set_loc(&mut self, loc: usize) {
+        self.loc = Some(loc);
+    }
+
+    #[inline]
+    pub(crate) fn is_free(&self) -> bool {
+        self.loc.is_none()
+    }
+
+    /// Drops the rows of the table, leaving its slot to be reused.
+    #[inline]
+    pub(crate) fn free(&mut self) {
+        *self = FactTable::new(0, false);
+    }
+
+    #[inline]
+    pub(crate) fn cells(&self) -> &Heap {
+        &self.cells
+    }
+
+    pub(crate) fn push_row(
+        &mut self,
+        fact: &Term,
+        atom_tbl: &mut AtomTable,
+    ) -> Result<(), CompilationError> {
+        let result = write_term_to_heap(fact, &mut self.cells, atom_tbl)?;
+        self.rows.push(result.heap_loc);
+        Ok(())
+    }
+
+    fn deref(&self, mut cell: HeapCellValue) -> HeapCellValue {
+        loop {
+            read_heap_cell!(cell,
+                (HeapCellValueTag::Var, h) => {
+                    let value = self.cells[h];
+
+                    if value == cell {
+                        return cell;
+                    }
+
+                    cell = value;
+                }
+                _ => {
+                    return cell;
+                }
+            )
+        }
+    }
+
+    fn arg_key(&self, row: usize, col: usize) -> Option<JitArgKey> {
+        let cell = self.deref(self.cells[self.rows[row] + 2 + col]);
+        JitArgKey::from_cell(&self.cells, cell, |cell| self.deref(cell))
+    }
+
+    fn column(&mut self, col: usize) -> &JitArgIndex {
+        if self.columns[col].is_none() {
+            let mut column = JitArgIndex::default();
+
+            for row in 0..self.rows.len() {
+                column.insert(row, self.arg_key(row, col));
+            }
+
+            self.columns[col] = Some(column);
+        }
+
+        self.columns[col].as_ref().unwrap()
+    }
+
+    fn count(&mut self, col: usize, key: JitArgKey) -> usize {
+        self.column(col).buckets(key).iter().map(|bucket| bucket.len()).sum()
+    }
+
+    /// The rows matching a call whose arguments are keyed by `keys`, in
+    /// order. The rows are selected through the index of the most
+    /// selective column, and filtered by the keys of the others.
+    pub(crate) fn matching_rows(&mut self, keys: &[Option<JitArgKey>]) -> Vec<usize> {
+        let selective_col = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(col, key)| key.map(|key| (col, self.count(col, key))))
+            .min_by_key(|&(_, count)| count);
+
+        let col = match selective_col {
+            Some((col, _)) => col,
+            None => return (0..self.rows.len()).collect(),
+        };
+
+        let candidates = {
+            let [deep, shallow, unkeyed] = self.column(col).buckets(keys[col].unwrap());
+            merge_ordinals(&merge_ordinals(deep, shallow), unkeyed)
+        };
+
+        candidates
+            .into_iter()
+            .filter(|&row| {
+                keys.iter().enumerate().all(|(i, key)| match key {
+                    Some(key) if i != col => match self.arg_key(row, i) {
+                        Some(row_key) => key.matches(&row_key),
+                        None => true,
+                    },
+                    _ => true,
+                })
+            })
+            .collect()
+    }
+
+    /// Copies `row` to the end of `heap`.
+    pub(crate) fn copy_row(&self, row: usize, heap: &mut Heap) {
+        let start = self.rows[row];
+        let end = self.rows.get(row + 1).cloned().unwrap_or(self.cells.len());
+        let diff = start as i64 - heap.len() as i64;
+
+        heap.extend(self.cells[start..end].iter().map(|&cell| cell - diff));
+    }
+}
+
+impl Persist for FactTable {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        self.arity.save(writer)?;
+        self.declared.save(writer)?;
+        self.loc.save(writer)?;
+        self.cells.save(writer)?;
+        self.rows.save(writer)
+    }
+
+    // the column indices are rebuilt as they're needed.
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let arity = Persist::restore(reader)?;
+        let mut table = FactTable::new(arity, Persist::restore(reader)?);
+
+        table.loc = Persist::restore(reader)?;
+        table.cells = Persist::restore(reader)?;
+        table.rows = Persist::restore(reader)?;
+
+        Ok(table)
+    }
+}
+
+impl Machine {
+    /// Calls the fact table `id` with the arguments in the registers.
+    /// The choicepoint over the other matching rows is resumed at the
+    /// FactTableRetry instruction following the FactTable instruction.
+    pub(crate) fn fact_table_call(&mut self, id: usize) {
+        let arity = self.indices.fact_tables[id].arity();
+
+        let keys: Vec<Option<JitArgKey>> = (1 ..= arity)
+            .map(|i| {
+                let cell = self.machine_st.store(self.machine_st.deref(self.machine_st.registers[i]));
+                self.machine_st.jit_arg_key(cell)
+            })
+            .collect();
+
+        let rows = self.indices.fact_tables[id].matching_rows(&keys);
+
+        match rows.split_first() {
+            None => {
+                self.machine_st.fail = true;
+            }
+            Some((&first, rest)) => {
+                if !rest.is_empty() {
+                    self.machine_st.try_alternatives(rest.iter().copied(), self.machine_st.p + 1);
+                }
+
+                self.unify_fact_table_row(id, first);
+            }
+        }
+    }
+
+    /// Resumes the choicepoint pushed by the call to the fact table
+    /// `id`. The rows the choicepoint holds are dropped if the table
+    /// has been freed, or its slot reused, since.
+    pub(crate) fn fact_table_retry(&mut self, id: usize) {
+        let row = self.retry_alternatives();
+
+        if self.indices.fact_tables[id].loc() != Some(self.machine_st.p - 1) {
+            self.machine_st.fail = true;
+            return;
+        }
+
+        self.unify_fact_table_row(id, row);
+    }
+
+    /// Frees the fact tables no predicate is compiled to any longer,
+    /// as after the predicates are redefined or their module reloaded.
+    pub(crate) fn free_unreachable_fact_tables(&mut self) {
+        if self.indices.fact_tables.iter().all(FactTable::is_free) {
+            return;
+        }
+
+        let mut reachable = vec![false; self.indices.fact_tables.len()];
+
+        let code_indices = self.indices.code_dir.values().chain(
+            self.indices.modules.values().flat_map(|module| module.code_dir.values()),
+        );
+
+        for code_index in code_indices {
+            if let Some(p) = code_index.local() {
+                if let Instruction::FactTable(id) = self.code[p] {
+                    reachable[id] = true;
+                }
+            }
+        }
+
+        for (table, reachable) in self.indices.fact_tables.iter_mut().zip(reachable) {
+            if !reachable {
+                table.free();
+            }
+        }
+    }
+
+    fn unify_fact_table_row(&mut self, id: usize, row: usize) {
+        let table = &self.indices.fact_tables[id];
+        let h = self.machine_st.heap.len();
+
+        table.copy_row(row, &mut self.machine_st.heap);
+
+        for i in 1 ..= table.arity() {
+            unify!(self.machine_st, heap_loc_as_cell!(h + 1 + i), self.machine_st.registers[i]);
+
+            if self.machine_st.fail {
+                return;
+            }
+        }
+    }
+}
@@ -1,5 +1,9 @@
 use crate::atom_table::*;
+use crate::machine::atom_gc::GC_INITIAL_ATOM_THRESHOLD;
 use crate::machine::heap::*;
+use crate::machine::machine_state::*;
+use crate::machine::stack::*;
+use crate::machine::Machine;
 use crate::types::*;
 
 #[cfg(test)]
@@ -7,6 +11,10 @@
 
 use core::marker::PhantomData;
 
+use indexmap::IndexSet;
+
+use std::time::{Duration, Instant};
+
 pub(crate) trait UnmarkPolicy {
     fn unmark(heap: &mut [HeapCellValue], current: usize);
     fn mark(heap: &mut [HeapCellValue], current: usize);
@@ -352,6 +360,411 @@ pub fn mark_cells(heap: &mut Heap, cell: HeapCellValue) {
     while let Some(_) = iter.forward() {}
 }
 
+/// The heap size, in cells, at which the first collection is
+/// triggered. After each collection the threshold is reset to twice
+/// the size of the surviving heap, but never below this.
+pub(crate) const GC_INITIAL_HEAP_THRESHOLD: usize = 1 << 20;
+
+/// The bookkeeping of the heap and atom collectors.
+#[derive(Debug)]
+pub(crate) struct GcState {
+    pub(crate) threshold: usize,
+    pub(crate) collections: usize,
+    pub(crate) reclaimed_cells: usize,
+    pub(crate) time: Duration,
+    pub(crate) atom_threshold: usize,
+    pub(crate) atom_collections: usize,
+    pub(crate) collected_atoms: usize,
+    pub(crate) atom_time: Duration,
+    // heap cells held outside of the registers and stacks, e.g. the
+    // variables of queries run from Rust. They are relocated in place
+    // when the heap is compacted.
+    pub(crate) roots: Vec<HeapCellValue>,
+}
+
+impl GcState {
+    pub(crate) fn new() -> Self {
+        GcState {
+            threshold: GC_INITIAL_HEAP_THRESHOLD,
+            collections: 0,
+            reclaimed_cells: 0,
+            time: Duration::ZERO,
+            atom_threshold: GC_INITIAL_ATOM_THRESHOLD,
+            atom_collections: 0,
+            collected_atoms: 0,
+            atom_time: Duration::ZERO,
+            roots: vec![],
+        }
+    }
+}
+
+// the cell from which the marker should start for a root, or None if
+// the root doesn't refer to a well-formed term on the heap. Unused
+// registers and permanent variables not initialized since the heap
+// was last truncated can hold such stale references.
+fn root_cell(heap: &[HeapCellValue], cell: HeapCellValue) -> Option<HeapCellValue> {
+    let h = cell.get_value();
+
+    match cell.get_tag() {
+        HeapCellValueTag::Var | HeapCellValueTag::AttrVar | HeapCellValueTag::PStrOffset
+            if h < heap.len() => {
+            // the marker expects to find the attribute list of an
+            // attributed variable after the cell it starts from, so
+            // roots are always entered through a Var cell.
+            Some(heap_loc_as_cell!(h))
+        }
+        HeapCellValueTag::Lis if h + 1 < heap.len() => {
+            Some(cell)
+        }
+        HeapCellValueTag::PStrLoc if h + 1 < heap.len() => {
+            match heap[h].get_tag() {
+                HeapCellValueTag::PStr | HeapCellValueTag::PStrOffset => Some(cell),
+                _ => None,
+            }
+        }
+        HeapCellValueTag::Str if h < heap.len() => {
+            read_heap_cell!(heap[h],
+                (HeapCellValueTag::Atom, (_name, arity)) => {
+                    if arity > 0 && h + arity < heap.len() {
+                        Some(cell)
+                    } else {
+                        None
+                    }
+                }
+                _ => {
+                    None
+                }
+            )
+        }
+        _ => None,
+    }
+}
+
+// the sliding compactor preserves the order of the marked cells, so
+// the cell at h moves to new_locs[h], the number of marked cells
+// below h. The same mapping carries heap boundaries like HB and the
+// saved heap tops of choicepoints to their new values.
+struct Relocation {
+    new_locs: Vec<usize>,
+}
+
+impl Relocation {
+    fn new(heap: &[HeapCellValue]) -> Self {
+        let mut new_locs = Vec::with_capacity(heap.len() + 1);
+        let mut live = 0;
+
+        for cell in heap {
+            new_locs.push(live);
+
+            if cell.get_mark_bit() {
+                live += 1;
+            }
+        }
+
+        new_locs.push(live);
+
+        Relocation { new_locs }
+    }
+
+    #[inline]
+    fn live_cells(&self) -> usize {
+        self.new_locs[self.new_locs.len() - 1]
+    }
+
+    #[inline]
+    fn loc(&self, h: usize) -> usize {
+        match self.new_locs.get(h) {
+            Some(&new_h) => new_h,
+            None => h,
+        }
+    }
+
+    #[inline]
+    fn cell(&self, mut cell: HeapCellValue) -> HeapCellValue {
+        match cell.get_tag() {
+            HeapCellValueTag::Var | HeapCellValueTag::AttrVar | HeapCellValueTag::Str |
+            HeapCellValueTag::Lis | HeapCellValueTag::PStrLoc | HeapCellValueTag::PStrOffset => {
+                let h = cell.get_value();
+                cell.set_value(self.loc(h));
+                cell
+            }
+            _ => {
+                cell
+            }
+        }
+    }
+}
+
+impl MachineState {
+    // the offsets of the choicepoints reachable from B and of the
+    // cells of every frame reachable from them or from E.
+    pub(super) fn live_stack_frames(&self) -> (IndexSet<usize>, Vec<usize>) {
+        let stack_len = self.stack.len();
+
+        let mut or_frames = IndexSet::new();
+        let mut b = self.b;
+
+        while b < stack_len && or_frames.insert(b) {
+            b = self.stack.index_or_frame(b).prelude.b;
+        }
+
+        let mut and_frames = IndexSet::new();
+        let env_chains = or_frames
+            .iter()
+            .map(|&b| self.stack.index_or_frame(b).prelude.e)
+            .chain(std::iter::once(self.e))
+            .collect::<Vec<_>>();
+
+        for mut e in env_chains {
+            while e < stack_len && !or_frames.contains(&e) && and_frames.insert(e) {
+                e = self.stack.index_and_frame(e).prelude.e;
+            }
+        }
+
+        let mut cells = vec![];
+
+        for &e in &and_frames {
+            let num_cells = self.stack.index_and_frame(e).prelude.num_cells;
+            cells.extend((1..num_cells + 1).map(|idx| stack_loc!(AndFrame, e, idx)));
+        }
+
+        for &b in &or_frames {
+            let num_cells = self.stack.index_or_frame(b).prelude.num_cells;
+            cells.extend((0..num_cells).map(|idx| stack_loc!(OrFrame, b, idx)));
+        }
+
+        cells.retain(|&s| s < stack_len);
+        cells.sort_unstable();
+        cells.dedup();
+
+        (or_frames, cells)
+    }
+}
+
+impl Machine {
+    fn heap_gc_roots(&self, stack_cells: &[usize]) -> Vec<HeapCellValue> {
+        let machine_st = &self.machine_st;
+        let mut roots = vec![];
+
+        roots.extend(machine_st.registers.iter().cloned());
+        roots.extend(stack_cells.iter().map(|&s| machine_st.stack[s]));
+
+        let mut i = 0;
+
+        while i < machine_st.trail.len() {
+            let h = machine_st.trail[i].get_value() as usize;
+
+            match machine_st.trail[i].get_tag() {
+                TrailEntryTag::TrailedHeapVar | TrailEntryTag::TrailedAttrVar => {
+                    roots.push(heap_loc_as_cell!(h));
+                }
+                TrailEntryTag::TrailedAttrVarListLink => {
+                    let l = machine_st.trail[i + 1].get_value() as usize;
+
+                    roots.push(heap_loc_as_cell!(h));
+                    roots.push(heap_loc_as_cell!(l));
+
+                    i += 1;
+                }
+                TrailEntryTag::TrailedBlackboardOffset => {
+                    roots.push(HeapCellValue::from(u64::from(machine_st.trail[i + 1])));
+                    i += 1;
+                }
+                TrailEntryTag::TrailedStackVar | TrailEntryTag::TrailedBlackboardEntry |
+                TrailEntryTag::TrailedAttachedValue => {
+                }
+            }
+
+            i += 1;
+        }
+
+        roots.extend(machine_st.attr_var_init.attr_var_queue.iter().map(|&h| heap_loc_as_cell!(h)));
+
+        for &(h, cell) in &machine_st.attr_var_init.bindings {
+            roots.push(heap_loc_as_cell!(h));
+            roots.push(cell);
+        }
+
+        roots.extend(machine_st.cont_pts.iter().map(|&(cell, ..)| cell));
+        roots.extend(self.indices.global_variables.values().filter_map(|(_, cell)| *cell));
+        roots.extend(machine_st.gc.roots.iter().cloned());
+
+        roots
+    }
+
+    fn relocate_heap_gc_roots(
+        &mut self,
+        relocation: &Relocation,
+        or_frames: &IndexSet<usize>,
+        stack_cells: &[usize],
+    ) {
+        let machine_st = &mut self.machine_st;
+
+        for cell in machine_st.registers.iter_mut() {
+            *cell = relocation.cell(*cell);
+        }
+
+        for &s in stack_cells {
+            machine_st.stack[s] = relocation.cell(machine_st.stack[s]);
+        }
+
+        for &b in or_frames {
+            let or_frame = machine_st.stack.index_or_frame_mut(b);
+            or_frame.prelude.h = relocation.loc(or_frame.prelude.h);
+        }
+
+        let mut i = 0;
+
+        while i < machine_st.trail.len() {
+            let entry = machine_st.trail[i];
+            let h = entry.get_value() as usize;
+
+            match entry.get_tag() {
+                tag @ (TrailEntryTag::TrailedHeapVar | TrailEntryTag::TrailedAttrVar) => {
+                    machine_st.trail[i] = TrailEntry::build_with(tag, relocation.loc(h) as u64);
+                }
+                TrailEntryTag::TrailedAttrVarListLink => {
+                    let l = machine_st.trail[i + 1].get_value() as usize;
+
+                    machine_st.trail[i] = TrailEntry::build_with(
+                        TrailEntryTag::TrailedAttrVarListLink,
+                        relocation.loc(h) as u64,
+                    );
+
+                    machine_st.trail[i + 1] = TrailEntry::build_with(
+                        TrailEntryTag::TrailedAttachedValue,
+                        relocation.loc(l) as u64,
+                    );
+
+                    i += 1;
+                }
+                TrailEntryTag::TrailedBlackboardOffset => {
+                    let cell = HeapCellValue::from(u64::from(machine_st.trail[i + 1]));
+                    let cell = relocation.cell(cell);
+
+                    machine_st.trail[i + 1] = TrailEntry::from_bytes(cell.into_bytes());
+                    i += 1;
+                }
+                TrailEntryTag::TrailedStackVar | TrailEntryTag::TrailedBlackboardEntry |
+                TrailEntryTag::TrailedAttachedValue => {
+                }
+            }
+
+            i += 1;
+        }
+
+        for h in machine_st.attr_var_init.attr_var_queue.iter_mut() {
+            *h = relocation.loc(*h);
+        }
+
+        for (h, cell) in machine_st.attr_var_init.bindings.iter_mut() {
+            *h = relocation.loc(*h);
+            *cell = relocation.cell(*cell);
+        }
+
+        for (cell, ..) in machine_st.cont_pts.iter_mut() {
+            *cell = relocation.cell(*cell);
+        }
+
+        for (_, cell) in self.indices.global_variables.values_mut() {
+            if let Some(cell) = cell {
+                *cell = relocation.cell(*cell);
+            }
+        }
+
+        for cell in machine_st.gc.roots.iter_mut() {
+            *cell = relocation.cell(*cell);
+        }
+
+        match &mut machine_st.s {
+            HeapPtr::HeapCell(h) | HeapPtr::PStrChar(h, _) | HeapPtr::PStrLocation(h, _) => {
+                *h = relocation.loc(*h);
+            }
+        }
+
+        machine_st.hb = relocation.loc(machine_st.hb);
+    }
+
+    /// Reclaims the heap cells unreachable from the machine's
+    /// registers, stacks, trail and attributed variable queues by
+    /// sliding the live cells toward the bottom of the heap. The
+    /// relative order of the live cells is preserved, so the standard
+    /// order of variables and the heap tops saved by choicepoints
+    /// remain valid.
+    ///
+    /// This must only be called between calls, when there are no
+    /// partially built structures on the heap.
+    pub(crate) fn garbage_collect(&mut self) {
+        let start = Instant::now();
+
+        let (or_frames, stack_cells) = self.machine_st.live_stack_frames();
+        let roots = self.heap_gc_roots(&stack_cells);
+
+        let heap = &mut self.machine_st.heap;
+        let heap_len = heap.len();
+
+        for root in roots {
+            if let Some(cell) = root_cell(heap, root) {
+                mark_cells(heap, cell);
+            }
+        }
+
+        // a partial string is always followed by its tail, even when
+        // the string was only reached through a reference to its
+        // first cell.
+        loop {
+            let mut marked_tail = false;
+
+            for h in 0 .. heap_len.saturating_sub(1) {
+                if heap[h].get_mark_bit() && heap[h].get_tag() == HeapCellValueTag::PStr &&
+                   !heap[h + 1].get_mark_bit() {
+                    mark_cells(heap, heap_loc_as_cell!(h + 1));
+                    marked_tail = true;
+                }
+            }
+
+            if !marked_tail {
+                break;
+            }
+        }
+
+        let relocation = Relocation::new(heap);
+        let mut dest = 0;
+
+        for h in 0 .. heap_len {
+            if heap[h].get_mark_bit() {
+                let mut cell = relocation.cell(heap[h]);
+                cell.set_mark_bit(false);
+
+                heap[dest] = cell;
+                dest += 1;
+            }
+        }
+
+        heap.truncate(dest);
+
+        self.relocate_heap_gc_roots(&relocation, &or_frames, &stack_cells);
+
+        let gc = &mut self.machine_st.gc;
+
+        gc.collections += 1;
+        gc.reclaimed_cells += heap_len - relocation.live_cells();
+        gc.threshold = std::cmp::max(GC_INITIAL_HEAP_THRESHOLD, 2 * relocation.live_cells());
+        gc.time += start.elapsed();
+    }
+
+    /// Collects the heap, and then the atom table, if either has grown
+    /// past the threshold set by its previous collection.
+    #[inline(always)]
+    pub(crate) fn maybe_garbage_collect(&mut self) {
+        if self.machine_st.heap.len() >= self.machine_st.gc.threshold {
+            self.garbage_collect();
+        }
+
+        self.maybe_garbage_collect_atoms();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
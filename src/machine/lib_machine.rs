@@ -0,0 +1,376 @@
+use crate::atom_table::*;
+use crate::machine::machine_errors::*;
+use crate::machine::parsed_results::*;
+use crate::machine::machine_state::*;
+use crate::machine::streams::*;
+use crate::machine::{Machine, BREAK_FROM_DISPATCH_LOOP_LOC, LIB_QUERY_SUCCESS};
+use crate::read::*;
+use crate::types::*;
+
+use indexmap::IndexMap;
+
+impl Machine {
+    /// Runs `query` against the machine's user module and returns a
+    /// lazy iterator over its solutions. Each call to `next` resumes
+    /// the query by backtracking into its most recent choicepoint.
+    ///
+    /// A terminating '.' is optional. Syntax errors are returned in the
+    /// `Err` variant as the error term that the toplevel would throw.
+    #[inline]
+    pub fn run_query(&mut self, query: &str) -> Result<QueryState<'_>, Value> {
+        self.run_query_with_bindings(query, std::iter::empty::<(&str, Value)>())
+    }
+
+    /// Like `run_query`, but binds the named variables of `query` to
+    /// the given values before it is run, e.g.
+    ///
+    /// ```ignore
+    /// machine.run_query_with_bindings("Y is X * 2", [("X", Value::from(21))])
+    /// ```
+    ///
+    /// Variables inside the values are fresh, i.e. they are never
+    /// shared with the variables of the query. Bindings for names that
+    /// don't occur in the query are ignored.
+    pub fn run_query_with_bindings<'b, I>(
+        &mut self,
+        query: &str,
+        bindings: I,
+    ) -> Result<QueryState<'_>, Value>
+    where
+        I: IntoIterator<Item = (&'b str, Value)>,
+    {
+        let mut query = query.trim_end().to_string();
+
+        if !query.ends_with('.') {
+            query.push('.');
+        }
+
+        // a stale ball left by an exception caught in a previous query
+        // would otherwise be mistaken for an uncaught one.
+        self.machine_st.ball.reset();
+
+        let stream = Stream::from_owned_string(query, &mut self.machine_st.arena);
+        let h = self.machine_st.heap.len();
+
+        let term_write_result = match self.machine_st.read(stream, &self.indices.op_dir) {
+            Ok(term_write_result) => term_write_result,
+            Err(err) => {
+                self.machine_st.heap.truncate(h);
+                return Err(self.compilation_error_to_value(err));
+            }
+        };
+
+        for (name, value) in bindings {
+            let var = term_write_result.var_dict.iter().find(|(var_key, _)| {
+                !var_key.is_anon() && var_key.to_string() == name
+            });
+
+            let var = match var {
+                Some((_, &var)) => self.machine_st.deref(var),
+                None => continue,
+            };
+
+            let term = value.to_term(&mut self.machine_st);
+
+            let value_loc = match write_term_to_heap(
+                &term,
+                &mut self.machine_st.heap,
+                &mut self.machine_st.atom_tbl,
+            ) {
+                Ok(value_write_result) => value_write_result.heap_loc,
+                Err(err) => {
+                    self.machine_st.heap.truncate(h);
+                    return Err(self.binding_error_to_value(err));
+                }
+            };
+
+            read_heap_cell!(var,
+                (HeapCellValueTag::Var | HeapCellValueTag::AttrVar, var_h) => {
+                    self.machine_st.heap[var_h] = heap_loc_as_cell!(value_loc);
+                }
+                _ => {
+                }
+            );
+        }
+
+        Ok(QueryState::new(self, term_write_result, h))
+    }
+
+    fn compilation_error_to_value(&mut self, err: CompilationError) -> Value {
+        let err = self.machine_st.syntax_error(err);
+        let stub = functor_stub(atom!("run_query"), 1);
+        let err = self.machine_st.error_form(err, stub);
+
+        self.stub_to_value(err)
+    }
+
+    // the error of a binding that couldn't be written to the heap, by
+    // its own kind rather than as a syntax error of the query.
+    fn binding_error_to_value(&mut self, err: CompilationError) -> Value {
+        let err = self.machine_st.session_error(SessionError::CompilationError(err));
+        let stub = functor_stub(atom!("run_query_with_bindings"), 2);
+        let err = self.machine_st.error_form(err, stub);
+
+        self.stub_to_value(err)
+    }
+
+    fn stub_to_value(&mut self, stub: MachineStub) -> Value {
+        let h = self.machine_st.heap.len();
+        let is_compound = stub.len() > 1;
+
+        self.machine_st.heap.extend(stub.into_iter());
+
+        let cell = if is_compound {
+            str_loc_as_cell!(h)
+        } else {
+            heap_loc_as_cell!(h)
+        };
+
+        let var_names = IndexMap::new();
+        let value = ValueReader::new(&self.machine_st, &var_names).read(cell);
+
+        self.machine_st.heap.truncate(h);
+        value
+    }
+}
+
+/// The saved registers of the machine from before a query was started,
+/// restored once the query is exhausted or dropped.
+#[derive(Debug, Clone, Copy)]
+struct QueryPrelude {
+    b: usize,
+    block: usize,
+    e: usize,
+    cp: usize,
+    p: usize,
+    b0: usize,
+    num_of_args: usize,
+}
+
+/// The solutions of a query run by `Machine::run_query`. The
+/// machine is borrowed until the `QueryState` is dropped, which
+/// discards the choicepoints and bindings made by the query.
+pub struct QueryState<'a> {
+    machine: &'a mut Machine,
+    term_write_result: TermWriteResult,
+    prelude: QueryPrelude,
+    // the address of the choicepoint separating the query's
+    // choicepoints from those of the caller.
+    stub_b: usize,
+    // the number of pending native predicate redo callbacks when the
+    // query was started.
+    redos_len: usize,
+    // the offset of the query's variables among the heap collector's
+    // roots, which keeps them up to date as the heap is compacted.
+    gc_roots_start: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl<'a> QueryState<'a> {
+    fn new(machine: &'a mut Machine, term_write_result: TermWriteResult, h: usize) -> Self {
+        let machine_st = &mut machine.machine_st;
+
+        let prelude = QueryPrelude {
+            b: machine_st.b,
+            block: machine_st.block,
+            e: machine_st.e,
+            cp: machine_st.cp,
+            p: machine_st.p,
+            b0: machine_st.b0,
+            num_of_args: machine_st.num_of_args,
+        };
+
+        // failing or throwing out of the query backtracks into this
+        // choicepoint, whose alternative is to leave the dispatch loop.
+        let stub_b = machine_st.stack.allocate_or_frame(0);
+        let or_frame = machine_st.stack.index_or_frame_mut(stub_b);
+
+        or_frame.prelude.num_cells = 0;
+        or_frame.prelude.e = machine_st.e;
+        or_frame.prelude.cp = machine_st.cp;
+        or_frame.prelude.b = machine_st.b;
+        or_frame.prelude.bp = BREAK_FROM_DISPATCH_LOOP_LOC;
+        or_frame.prelude.boip = 0;
+        or_frame.prelude.biip = 0;
+        or_frame.prelude.tr = machine_st.tr;
+        or_frame.prelude.h = h;
+        or_frame.prelude.b0 = machine_st.b0;
+        or_frame.prelude.attr_var_queue_len = machine_st.attr_var_init.attr_var_queue.len();
+
+        machine_st.b = stub_b;
+        machine_st.block = stub_b;
+        machine_st.hb = machine_st.heap.len();
+
+        let gc_roots_start = machine_st.gc.roots.len();
+        machine_st.gc.roots.extend(term_write_result.var_dict.values().cloned());
+
+        let redos_len = machine.native_predicates.redos_len();
+
+        QueryState {
+            machine,
+            term_write_result,
+            prelude,
+            stub_b,
+            redos_len,
+            gc_roots_start,
+            started: false,
+            finished: false,
+        }
+    }
+
+    fn start(&mut self) {
+        let code_index = self.machine.indices.modules
+            .get(&atom!("$toplevel"))
+            .and_then(|module| module.code_dir.get(&(atom!("$run_query"), 1)))
+            .and_then(|code_index| code_index.local())
+            .unwrap();
+
+        let machine_st = &mut self.machine.machine_st;
+
+        machine_st.registers[1] = heap_loc_as_cell!(self.term_write_result.heap_loc);
+        machine_st.cp = LIB_QUERY_SUCCESS;
+        machine_st.execute_at_index(1, code_index);
+    }
+
+    fn answer(&self) -> QueryAnswer {
+        let machine_st = &self.machine.machine_st;
+        let mut var_names = IndexMap::new();
+
+        let vars = || {
+            self.term_write_result.var_dict.keys()
+                .zip(machine_st.gc.roots[self.gc_roots_start ..].iter())
+        };
+
+        for (var_key, cell) in vars() {
+            if var_key.is_anon() {
+                continue;
+            }
+
+            let addr = machine_st.deref(*cell);
+
+            if machine_st.store(addr).is_var() {
+                var_names.entry(addr).or_insert_with(|| var_key.to_string());
+            }
+        }
+
+        let mut reader = ValueReader::new(machine_st, &var_names);
+        let mut answer = QueryAnswer::default();
+
+        for (var_key, cell) in vars() {
+            if !var_key.is_anon() {
+                answer.bindings.insert(var_key.to_string(), reader.read(*cell));
+            }
+        }
+
+        answer
+    }
+
+    fn exception(&mut self) -> Value {
+        let machine_st = &mut self.machine.machine_st;
+        let h = machine_st.heap.len();
+
+        let stub = machine_st.ball.copy_and_align(h);
+        machine_st.heap.extend(stub.into_iter());
+
+        let var_names = IndexMap::new();
+        let value = ValueReader::new(machine_st, &var_names).read(heap_loc_as_cell!(h));
+
+        machine_st.heap.truncate(h);
+        value
+    }
+
+    // removes the stub choicepoint and everything above it, undoing
+    // the bindings of the query and restoring the caller's registers.
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        self.finished = true;
+
+        let or_frame = self.machine.machine_st.stack.index_or_frame(self.stub_b);
+
+        let old_tr = or_frame.prelude.tr;
+        let target_h = or_frame.prelude.h;
+        let attr_var_queue_len = or_frame.prelude.attr_var_queue_len;
+
+        let curr_tr = self.machine.machine_st.tr;
+
+        self.machine.unwind_trail(old_tr, curr_tr);
+        self.machine.reset_attr_var_state(attr_var_queue_len);
+
+        let machine_st = &mut self.machine.machine_st;
+
+        machine_st.tr = old_tr;
+        machine_st.trail.truncate(old_tr);
+
+        machine_st.b = self.prelude.b;
+        machine_st.block = self.prelude.block;
+        machine_st.e = self.prelude.e;
+        machine_st.cp = self.prelude.cp;
+        machine_st.p = self.prelude.p;
+        machine_st.b0 = self.prelude.b0;
+        machine_st.num_of_args = self.prelude.num_of_args;
+
+        machine_st.stack.truncate(self.stub_b);
+        machine_st.heap.truncate(target_h);
+        machine_st.hb = machine_st.heap.len();
+
+        machine_st.fail = false;
+        machine_st.ball.reset();
+        machine_st.gc.roots.truncate(self.gc_roots_start);
+
+        self.machine.native_predicates.truncate_redos(self.redos_len);
+    }
+}
+
+impl<'a> Iterator for QueryState<'a> {
+    type Item = Result<QueryAnswer, Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if !self.started {
+            self.started = true;
+            self.start();
+        } else if self.machine.machine_st.b > self.stub_b {
+            // ask for the next solution by failing into the most
+            // recent choicepoint of the query.
+            self.machine.machine_st.backtrack();
+        } else {
+            self.finish();
+            return None;
+        }
+
+        self.machine.dispatch_loop();
+
+        let machine_st = &self.machine.machine_st;
+
+        if machine_st.p == LIB_QUERY_SUCCESS {
+            let answer = self.answer();
+
+            if self.machine.machine_st.b <= self.stub_b {
+                // the query succeeded deterministically.
+                self.finish();
+            }
+
+            Some(Ok(answer))
+        } else if machine_st.p == BREAK_FROM_DISPATCH_LOOP_LOC && !machine_st.ball.stub.is_empty() {
+            let exception = self.exception();
+            self.finish();
+            Some(Err(exception))
+        } else {
+            self.finish();
+            None
+        }
+    }
+}
+
+impl<'a> Drop for QueryState<'a> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
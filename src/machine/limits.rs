@@ -0,0 +1,204 @@
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::Machine;
+use crate::types::*;
+
+use std::mem;
+
+/// The memory areas whose size can be bounded by the `max_heap_size`,
+/// `max_stack_size`, `max_trail_size` and `max_atoms` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resource {
+    Heap,
+    Stack,
+    Trail,
+    Atoms,
+}
+
+impl Resource {
+    const ALL: [Resource; 4] = [Resource::Heap, Resource::Stack, Resource::Trail, Resource::Atoms];
+
+    pub(crate) fn from_atom(atom: Atom) -> Option<Self> {
+        match atom {
+            atom!("heap") => Some(Resource::Heap),
+            atom!("stack") => Some(Resource::Stack),
+            atom!("trail") => Some(Resource::Trail),
+            atom!("atoms") => Some(Resource::Atoms),
+            _ => None,
+        }
+    }
+}
+
+/// The size limits of the heap, stack and trail in bytes, and of the
+/// atom table in atoms. `None` is the `infinite` flag value.
+#[derive(Debug, Default)]
+pub(crate) struct ResourceLimits {
+    limits: [Option<usize>; 4],
+    // set when the limit of a resource is exceeded, and cleared once
+    // its usage falls back under the limit. The error is thrown once
+    // per excess, so a limit set below what the toplevel itself needs
+    // can't stop it from reporting the error.
+    exceeded: [bool; 4],
+}
+
+impl ResourceLimits {
+    #[inline]
+    pub(crate) fn get(&self, resource: Resource) -> Option<usize> {
+        self.limits[resource as usize]
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, resource: Resource, limit: Option<usize>) {
+        self.limits[resource as usize] = limit;
+        self.exceeded[resource as usize] = false;
+    }
+
+    // true if `usage` is past the limit of `resource` for the first
+    // time since it was last under it.
+    #[inline(always)]
+    fn newly_exceeded(&mut self, resource: Resource, usage: usize) -> bool {
+        let idx = resource as usize;
+
+        match self.limits[idx] {
+            Some(limit) if usage > limit => !mem::replace(&mut self.exceeded[idx], true),
+            Some(_) => {
+                self.exceeded[idx] = false;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+impl Machine {
+    pub(crate) fn resource_limit(&mut self) {
+        let resource = cell_as_atom!(self.deref_register(1));
+        let resource = Resource::from_atom(resource).unwrap();
+
+        let value = match self.machine_st.limits.get(resource) {
+            Some(limit) => fixnum_as_cell!(Fixnum::build_with(limit as i64)),
+            None => atom_as_cell!(atom!("infinite")),
+        };
+
+        let a2 = self.machine_st.registers[2];
+        unify!(self.machine_st, value, a2);
+    }
+
+    pub(crate) fn set_resource_limit(&mut self) {
+        let resource = cell_as_atom!(self.deref_register(1));
+        let resource = Resource::from_atom(resource).unwrap();
+
+        let limit = read_heap_cell!(self.deref_register(2),
+            (HeapCellValueTag::Fixnum, n) => {
+                Some(n.get_num() as usize)
+            }
+            (HeapCellValueTag::Atom, (name, _arity)) if name == atom!("infinite") => {
+                None
+            }
+            _ => {
+                self.machine_st.fail = true;
+                return;
+            }
+        );
+
+        self.machine_st.limits.set(resource, limit);
+
+        // the new limit is enforced from the next call on.
+        self.machine_st.calls_until_poll = 0;
+    }
+
+    fn resource_usage(&self, resource: Resource) -> usize {
+        let machine_st = &self.machine_st;
+
+        match resource {
+            Resource::Heap => machine_st.heap.len() * mem::size_of::<HeapCellValue>(),
+            Resource::Stack => machine_st.stack.len(),
+            Resource::Trail => machine_st.trail.len() * mem::size_of::<TrailEntry>(),
+            Resource::Atoms => machine_st.atom_tbl.atom_count(),
+        }
+    }
+
+    fn memory_resource_error(&mut self, name: Atom, arity: usize) -> MachineStub {
+        let err = self.machine_st.memory_resource_error();
+        let stub = functor_stub(name, arity);
+
+        self.machine_st.error_form(err, stub)
+    }
+
+    /// Throws `resource_error(memory)` from the call to `name/arity` if
+    /// one of the resource limits is exceeded. The heap and the atom
+    /// table are collected before their limits are enforced.
+    ///
+    /// Like `maybe_garbage_collect`, this must only be called between
+    /// calls.
+    pub(crate) fn check_resource_limits(&mut self, name: Atom, arity: usize) -> CallResult {
+        for resource in Resource::ALL {
+            let limit = match self.machine_st.limits.get(resource) {
+                Some(limit) => limit,
+                None => continue,
+            };
+
+            let idx = resource as usize;
+
+            if self.resource_usage(resource) <= limit {
+                self.machine_st.limits.exceeded[idx] = false;
+                continue;
+            }
+
+            if self.machine_st.limits.exceeded[idx] {
+                continue;
+            }
+
+            match resource {
+                Resource::Heap => self.garbage_collect(),
+                Resource::Atoms => self.garbage_collect_atoms(),
+                Resource::Stack | Resource::Trail => {}
+            }
+
+            if self.resource_usage(resource) > limit {
+                self.machine_st.limits.exceeded[idx] = true;
+                return Err(self.memory_resource_error(name, arity));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Throws `resource_error(memory)` if the heap or the stack has
+    /// grown past its limit. This is checked where they grow, after
+    /// builtins and frame allocations, so a builtin building a large
+    /// term or a deep recursion is stopped before it reaches the next
+    /// call. Nothing can be collected there, so the error is thrown
+    /// from the predicate whose code is running.
+    #[inline(always)]
+    pub(crate) fn check_growth_limits(&mut self) -> CallResult {
+        let heap_usage = self.resource_usage(Resource::Heap);
+        let stack_usage = self.resource_usage(Resource::Stack);
+
+        let limits = &mut self.machine_st.limits;
+
+        // both are checked so that each latch is cleared once its
+        // usage falls back under the limit.
+        let heap_exceeded = limits.newly_exceeded(Resource::Heap, heap_usage);
+        let stack_exceeded = limits.newly_exceeded(Resource::Stack, stack_usage);
+
+        if heap_exceeded || stack_exceeded {
+            let (name, arity) = self.running_predicate();
+            Err(self.memory_resource_error(name, arity))
+        } else {
+            Ok(())
+        }
+    }
+
+    // the predicate whose code holds P, or call/1 if it's a query.
+    fn running_predicate(&self) -> PredicateKey {
+        self.indices
+            .code_locs()
+            .range(..=self.machine_st.p)
+            .next_back()
+            .map(|(_, code_loc)| code_loc.key)
+            .unwrap_or((atom!("call"), 1))
+    }
+}
@@ -8,7 +8,6 @@
 use crate::parser::ast::*;
 
 use fxhash::FxBuildHasher;
-use indexmap::IndexSet;
 use ref_thread_local::RefThreadLocal;
 
 use std::collections::VecDeque;
@@ -226,45 +225,39 @@ fn import_qualified_module_exports<'a, LS: LoadState<'a>>(
     payload: &mut LS::LoaderFieldType,
     compilation_target: &CompilationTarget,
     imported_module: &Module,
-    exports: &IndexSet<ModuleExport>,
+    import_list: &ImportList,
     wam_prelude: &mut MachinePreludeView,
 ) -> Result<(), SessionError> {
     for export in imported_module.module_decl.exports.iter() {
-        if !exports.contains(export) {
-            continue;
-        }
-
-        match export {
-            ModuleExport::PredicateKey((name, arity)) => {
-                let key = (*name, *arity);
-
-                if let Some(meta_specs) = imported_module.meta_predicates.get(&key) {
-                    wam_prelude.indices.meta_predicates.insert(key.clone(), meta_specs.clone());
+        match (export, import_list.import(export)) {
+            (ModuleExport::PredicateKey(key), Some(ModuleExport::PredicateKey(import_key))) => {
+                if let Some(meta_specs) = imported_module.meta_predicates.get(key) {
+                    wam_prelude.indices.meta_predicates.insert(import_key, meta_specs.clone());
                 }
 
-                if let Some(src_code_index) = imported_module.code_dir.get(&key) {
+                if let Some(src_code_index) = imported_module.code_dir.get(key) {
                     let arena = &mut LS::machine_st(payload).arena;
 
                     let target_code_index = wam_prelude.indices.code_dir
-                        .entry(key.clone())
+                        .entry(import_key)
                         .or_insert_with(|| CodeIndex::new(IndexPtr::undefined(), arena))
                         .clone();
 
                     set_code_index(
                         &mut payload.retraction_info,
                         compilation_target,
-                        key,
+                        import_key,
                         target_code_index,
                         src_code_index.get(),
                     );
                 } else {
                     return Err(SessionError::ModuleDoesNotContainExport(
                         imported_module.module_decl.name.clone(),
-                        (*name, *arity),
+                        *key,
                     ));
                 }
             }
-            ModuleExport::OpDecl(ref op_decl) => {
+            (ModuleExport::OpDecl(ref op_decl), Some(_)) => {
                 add_op_decl(
                     &mut payload.retraction_info,
                     compilation_target,
@@ -272,6 +265,7 @@ fn import_qualified_module_exports<'a, LS: LoadState<'a>>(
                     op_decl,
                 );
             }
+            _ => {}
         }
     }
 
@@ -281,7 +275,7 @@ fn import_qualified_module_exports<'a, LS: LoadState<'a>>(
 fn import_qualified_module_exports_into_module<'a, LS: LoadState<'a>>(
     payload: &mut LS::LoaderFieldType,
     imported_module: &Module,
-    exports: &IndexSet<ModuleExport>,
+    import_list: &ImportList,
     code_dir: &mut CodeDir,
     op_dir: &mut OpDir,
     meta_predicates: &mut MetaPredicateDir,
@@ -290,41 +284,35 @@ fn import_qualified_module_exports_into_module<'a, LS: LoadState<'a>>(
     let payload_compilation_target = payload.compilation_target;
 
     for export in imported_module.module_decl.exports.iter() {
-        if !exports.contains(export) {
-            continue;
-        }
-
-        match export {
-            ModuleExport::PredicateKey((name, arity)) => {
-                let key = (*name, *arity);
-
-                if let Some(meta_specs) = imported_module.meta_predicates.get(&key) {
-                    meta_predicates.insert(key, meta_specs.clone());
+        match (export, import_list.import(export)) {
+            (ModuleExport::PredicateKey(key), Some(ModuleExport::PredicateKey(import_key))) => {
+                if let Some(meta_specs) = imported_module.meta_predicates.get(key) {
+                    meta_predicates.insert(import_key, meta_specs.clone());
                 }
 
-                if let Some(src_code_index) = imported_module.code_dir.get(&key) {
+                if let Some(src_code_index) = imported_module.code_dir.get(key) {
                     let arena = &mut LS::machine_st(payload).arena;
 
                     let target_code_index = code_dir
-                        .entry(key)
+                        .entry(import_key)
                         .or_insert_with(|| CodeIndex::new(IndexPtr::undefined(), arena))
                         .clone();
 
                     set_code_index(
                         &mut payload.retraction_info,
                         &payload_compilation_target,
-                        key,
+                        import_key,
                         target_code_index,
                         src_code_index.get(),
                     );
                 } else {
                     return Err(SessionError::ModuleDoesNotContainExport(
                         imported_module.module_decl.name.clone(),
-                        (*name, *arity),
+                        *key,
                     ));
                 }
             }
-            ModuleExport::OpDecl(ref op_decl) => {
+            (ModuleExport::OpDecl(ref op_decl), Some(_)) => {
                 add_op_decl_as_module_export::<LS>(
                     payload,
                     op_dir,
@@ -332,6 +320,7 @@ fn import_qualified_module_exports_into_module<'a, LS: LoadState<'a>>(
                     op_decl,
                 );
             }
+            _ => {}
         }
     }
 
@@ -1085,7 +1074,7 @@ pub(super) fn import_module(&mut self, module_name: Atom) -> Result<(), SessionE
     pub(super) fn import_qualified_module(
         &mut self,
         module_name: Atom,
-        exports: IndexSet<ModuleExport>,
+        import_list: &ImportList,
     ) -> Result<(), SessionError> {
         if let Some(module) = self.wam_prelude.indices.modules.remove(&module_name) {
             let payload_compilation_target = self.payload.compilation_target;
@@ -1096,7 +1085,7 @@ pub(super) fn import_qualified_module(
                         &mut self.payload,
                         &payload_compilation_target,
                         &module,
-                        &exports,
+                        import_list,
                         &mut self.wam_prelude,
                     )
                 }
@@ -1106,7 +1095,7 @@ pub(super) fn import_qualified_module(
                             import_qualified_module_exports_into_module::<LS>(
                                 &mut self.payload,
                                 &module,
-                                &exports,
+                                import_list,
                                 &mut target_module.code_dir,
                                 &mut target_module.op_dir,
                                 &mut target_module.meta_predicates,
@@ -1127,6 +1116,45 @@ pub(super) fn import_qualified_module(
         }
     }
 
+    /// Adds the exports of module_name imported by the import list, or
+    /// all of them if there's none, to the exports of the module being
+    /// loaded, for reexport/1,2.
+    pub(super) fn reexport_module(
+        &mut self,
+        module_name: Atom,
+        import_list: Option<&ImportList>,
+    ) -> Result<(), SessionError> {
+        let defining_module_name = match self.payload.compilation_target {
+            CompilationTarget::Module(defining_module_name) => defining_module_name,
+            CompilationTarget::User => return Ok(()),
+        };
+
+        let reexports: Vec<ModuleExport> = match self.wam_prelude.indices.modules.get(&module_name) {
+            Some(module) => module
+                .module_decl
+                .exports
+                .iter()
+                .filter_map(|export| match import_list {
+                    Some(import_list) => import_list.import(export),
+                    None => Some(export.clone()),
+                })
+                .collect(),
+            None => {
+                return Err(SessionError::ExistenceError(ExistenceError::Module(module_name)));
+            }
+        };
+
+        if let Some(module) = self.wam_prelude.indices.modules.get_mut(&defining_module_name) {
+            for export in reexports {
+                if !module.module_decl.exports.contains(&export) {
+                    module.module_decl.exports.push(export);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn use_module(&mut self, module_src: ModuleSource) -> Result<(), SessionError> {
         let (stream, listing_src) = match module_src {
             ModuleSource::File(filename) => {
@@ -1197,7 +1225,7 @@ pub(crate) fn use_module(&mut self, module_src: ModuleSource) -> Result<(), Sess
     pub(crate) fn use_qualified_module(
         &mut self,
         module_src: ModuleSource,
-        exports: IndexSet<ModuleExport>,
+        import_list: &ImportList,
     ) -> Result<(), SessionError> {
         let (stream, listing_src) = match module_src {
             ModuleSource::File(filename) => {
@@ -1213,7 +1241,7 @@ pub(crate) fn use_qualified_module(
             ModuleSource::Library(library) => match LIBRARIES.borrow().get(library.as_str()) {
                 Some(code) => {
                     if self.wam_prelude.indices.modules.contains_key(&library) {
-                        return self.import_qualified_module(library, exports);
+                        return self.import_qualified_module(library, import_list);
                     } else {
                         (
                             Stream::from_static_string(*code, &mut LS::machine_st(&mut self.payload).arena),
@@ -1222,7 +1250,7 @@ pub(crate) fn use_qualified_module(
                     }
                 }
                 None => {
-                    return self.import_qualified_module(library, exports);
+                    return self.import_qualified_module(library, import_list);
                 }
             },
         };
@@ -1254,7 +1282,7 @@ pub(crate) fn use_qualified_module(
                 Ok(())
             }
             CompilationTarget::Module(module_name) => {
-                self.import_qualified_module(module_name, exports)
+                self.import_qualified_module(module_name, import_list)
             }
         }
     }
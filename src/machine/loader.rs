@@ -19,6 +19,7 @@
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::fmt;
+use std::fs;
 use std::mem;
 use std::ops::{Deref, DerefMut};
 
@@ -91,6 +92,9 @@ pub(crate) enum RetractionRecord {
         usize,
     ),
     ReplacedIndexingLine(usize, Vec<IndexingLine>),
+    AddedFactTable(usize),
+    AddedClauseSource(usize),
+    RemovedClauseSource(usize, ClauseSource),
     RemovedLocalSkeletonClauseLocations(
         CompilationTarget,
         CompilationTarget,
@@ -184,15 +188,40 @@ pub(crate) fn module_name(&self) -> Atom {
     }
 }
 
+/// The class of the module named `module_name`: `system` for the
+/// modules of the system itself, `library` for the modules of
+/// libraries, and `user` for the rest.
+pub(crate) fn module_class(module_name: Atom) -> Atom {
+    if module_name == atom!("builtins")
+        || module_name == atom!("loader")
+        || module_name.as_str().starts_with('$')
+    {
+        atom!("system")
+    } else if LIBRARIES.borrow().contains_key(module_name.as_str()) {
+        atom!("library")
+    } else {
+        atom!("user")
+    }
+}
+
+/// True if the module named `module_name` is of the system or of a
+/// library rather than the user's.
+pub(crate) fn is_system_module(module_name: Atom) -> bool {
+    module_class(module_name) != atom!("user")
+}
+
 pub struct PredicateQueue {
     pub(super) predicates: Vec<Term>,
+    // the positions the clauses were read from, if they were loaded.
+    pub(super) positions: Vec<Option<TermPosition>>,
     pub(super) compilation_target: CompilationTarget,
 }
 
 impl PredicateQueue {
     #[inline]
-    pub(super) fn push(&mut self, clause: Term) {
+    pub(super) fn push(&mut self, clause: Term, position: Option<TermPosition>) {
         self.predicates.push(clause);
+        self.positions.push(position);
     }
 
     #[inline]
@@ -209,6 +238,7 @@ pub(crate) fn is_empty(&self) -> bool {
     pub(super) fn take(&mut self) -> Self {
         Self {
             predicates: mem::replace(&mut self.predicates, vec![]),
+            positions: mem::replace(&mut self.positions, vec![]),
             compilation_target: self.compilation_target.clone(),
         }
     }
@@ -224,6 +254,7 @@ macro_rules! predicate_queue {
     [$($v:expr),*] => (
         PredicateQueue {
             predicates: vec![$($v,)*],
+            positions: vec![None; <[&str]>::len(&[$(stringify!($v)),*])],
             compilation_target: CompilationTarget::default(),
         }
     )
@@ -490,6 +521,7 @@ fn dequeue_terms(&mut self) -> Result<Option<Declaration>, SessionError> {
             let composite_op_dir = self.wam_prelude.composite_op_dir(compilation_target);
 
             let term = load_state.term_stream.next(&composite_op_dir)?;
+            let position = load_state.term_stream.term_position();
 
             if !term.is_consistent(&load_state.predicates) {
                 self.compile_and_submit()?;
@@ -502,7 +534,7 @@ fn dequeue_terms(&mut self) -> Result<Option<Declaration>, SessionError> {
                 term => term,
             };
 
-            self.payload.predicates.push(term);
+            self.payload.predicates.push(term, position);
         }
 
         Ok(None)
@@ -514,6 +546,9 @@ pub(super) fn load_decl(&mut self, decl: Declaration) -> Result<(), SessionError
                 let compilation_target = self.payload.compilation_target;
                 self.add_dynamic_predicate(compilation_target, name, arity)?;
             }
+            Declaration::FactTable(name, arity) => {
+                self.payload.fact_table_preds.insert((name, arity));
+            }
             Declaration::MetaPredicate(module_name, name, meta_specs) => {
                 self.add_meta_predicate_record(module_name, name, meta_specs);
             }
@@ -521,7 +556,18 @@ pub(super) fn load_decl(&mut self, decl: Declaration) -> Result<(), SessionError
                 self.payload.compilation_target = CompilationTarget::Module(module_decl.name);
                 self.payload.predicates.compilation_target = self.payload.compilation_target;
 
-                let listing_src = self.payload.term_stream.listing_src().clone();
+                // modules loaded from files through the term queue have
+                // the file of their load context as their listing source.
+                let file_name = self.listing_src_file_name();
+
+                let listing_src = match (self.payload.term_stream.listing_src(), file_name) {
+                    (ListingSource::User, Some(file_name)) => ListingSource::from_file_and_path(
+                        file_name,
+                        PathBuf::from(file_name.as_str()),
+                    ),
+                    (listing_src, _) => listing_src.clone(),
+                };
+
                 self.add_module(module_decl, listing_src);
             }
             Declaration::NonCountedBacktracking(name, arity) => {
@@ -533,8 +579,8 @@ pub(super) fn load_decl(&mut self, decl: Declaration) -> Result<(), SessionError
             Declaration::UseModule(module_src) => {
                 self.use_module(module_src)?;
             }
-            Declaration::UseQualifiedModule(module_src, exports) => {
-                self.use_qualified_module(module_src, exports)?;
+            Declaration::UseQualifiedModule(module_src, import_list) => {
+                self.use_qualified_module(module_src, &import_list)?;
             }
         }
 
@@ -542,6 +588,12 @@ pub(super) fn load_decl(&mut self, decl: Declaration) -> Result<(), SessionError
     }
 
     fn reset_machine(&mut self) {
+        if !self.payload.retraction_info.records.is_empty() {
+            // the code of indexed predicates is about to be rolled back.
+            self.wam_prelude.indices.jit_indices.clear();
+            self.wam_prelude.indices.jit_unindexable.clear();
+        }
+
         while let Some(record) = self.payload.retraction_info.records.pop() {
             match record {
                 RetractionRecord::AddedMetaPredicate(target_module_name, key) => {
@@ -981,6 +1033,15 @@ fn reset_machine(&mut self) {
                 RetractionRecord::ReplacedIndexingLine(index_loc, indexing_code) => {
                     self.wam_prelude.code[index_loc] = Instruction::IndexingCode(indexing_code);
                 }
+                RetractionRecord::AddedFactTable(id) => {
+                    self.wam_prelude.indices.fact_tables[id].free();
+                }
+                RetractionRecord::AddedClauseSource(clause_loc) => {
+                    self.wam_prelude.indices.clause_sources.remove(&clause_loc);
+                }
+                RetractionRecord::RemovedClauseSource(clause_loc, source) => {
+                    self.wam_prelude.indices.clause_sources.insert(clause_loc, source);
+                }
                 RetractionRecord::RemovedLocalSkeletonClauseLocations(
                     compilation_target,
                     local_compilation_target,
@@ -1063,6 +1124,24 @@ fn extract_module_export_list_from_heap(
         Ok(export_list.into_iter().collect())
     }
 
+    // the import list in r, or None if it's [], to import every export.
+    fn extract_import_list_from_heap(
+        &mut self,
+        r: RegType,
+    ) -> Result<Option<ImportList>, SessionError> {
+        let machine_st = LS::machine_st(&mut self.payload);
+        let cell = machine_st[r];
+
+        let import_list = machine_st.read_term_from_heap(cell)?;
+
+        if let Term::Literal(_, Literal::Atom(atom!("[]"))) = import_list {
+            return Ok(None);
+        }
+
+        let atom_tbl = &mut LS::machine_st(&mut self.payload).atom_tbl;
+        Ok(Some(setup_import_list(import_list, atom_tbl)?))
+    }
+
     fn add_clause_clause(&mut self, term: Term) -> Result<(), CompilationError> {
         match term {
             Term::Clause(_, atom!(":-"), mut terms) if terms.len() == 2 =>
@@ -1510,7 +1589,7 @@ pub(crate) fn use_module(&mut self) -> CallResult {
             .machine_st
             .store(self.machine_st.deref(self.machine_st.registers[2]));
 
-        let module_src = ModuleSource::Library({
+        let module_name = {
             let payload = cell_as_load_state_payload!(subevacuable_addr);
 
             match payload.compilation_target {
@@ -1519,17 +1598,22 @@ pub(crate) fn use_module(&mut self) -> CallResult {
                     return Ok(());
                 }
             }
-        });
+        };
 
+        let reexport = cell_as_atom!(self.deref_register(4)) == atom!("true");
         let mut loader = self.loader_from_heap_evacuable(temp_v!(1));
 
         let use_module = || {
-            let export_list = loader.extract_module_export_list_from_heap(temp_v!(3))?;
+            let import_list = loader.extract_import_list_from_heap(temp_v!(3))?;
+            let module_src = ModuleSource::Library(module_name);
 
-            if export_list.is_empty() {
-                loader.use_module(module_src)?;
-            } else {
-                loader.use_qualified_module(module_src, export_list)?;
+            match &import_list {
+                Some(import_list) => loader.use_qualified_module(module_src, import_list)?,
+                None => loader.use_module(module_src)?,
+            }
+
+            if reexport {
+                loader.reexport_module(module_name, import_list.as_ref())?;
             }
 
             LiveLoadAndMachineState::evacuate(loader)
@@ -1550,15 +1634,19 @@ pub(crate) fn load_compiled_library(&mut self) -> CallResult {
                 return Ok(());
             }
 
+            let reexport = cell_as_atom!(self.deref_register(4)) == atom!("true");
             let mut loader = self.loader_from_heap_evacuable(temp_v!(3));
 
             let import_module = || {
-                let export_list = loader.extract_module_export_list_from_heap(temp_v!(2))?;
+                let import_list = loader.extract_import_list_from_heap(temp_v!(2))?;
 
-                if export_list.is_empty() {
-                    loader.import_module(library)?;
-                } else {
-                    loader.import_qualified_module(library, export_list)?;
+                match &import_list {
+                    Some(import_list) => loader.import_qualified_module(library, import_list)?,
+                    None => loader.import_module(library)?,
+                }
+
+                if reexport {
+                    loader.reexport_module(library, import_list.as_ref())?;
                 }
 
                 LiveLoadAndMachineState::evacuate(loader)
@@ -1666,9 +1754,16 @@ pub(crate) fn add_term_expansion_clause(&mut self) -> CallResult {
         let add_clause = || {
             let term = loader.read_term_from_heap(temp_v!(1))?;
 
+            let position = loader
+                .wam_prelude
+                .load_contexts
+                .last()
+                .and_then(|load_context| load_context.term_position);
+
             loader.incremental_compile_clause(
                 (atom!("term_expansion"), 2),
                 term,
+                position,
                 CompilationTarget::User,
                 false,
                 AppendOrPrepend::Append,
@@ -1711,9 +1806,16 @@ pub(crate) fn add_goal_expansion_clause(&mut self) -> CallResult {
                 }
             }
 
+            let position = loader
+                .wam_prelude
+                .load_contexts
+                .last()
+                .and_then(|load_context| load_context.term_position);
+
             loader.incremental_compile_clause(
                 (atom!("goal_expansion"), 2),
                 term,
+                position,
                 compilation_target,
                 false, // backtracking inferences are counted by call_with_inference_limit.
                 AppendOrPrepend::Append,
@@ -1795,15 +1897,30 @@ pub(crate) fn loader_from_heap_evacuable<'a>(
 
     #[inline]
     pub(crate) fn push_load_state_payload(&mut self) {
-        let payload = arena_alloc!(
-            LoadStatePayload::new(
-                self.code.len(),
-                LiveTermStream::new(ListingSource::User),
-            ),
-            &mut self.machine_st.arena
+        self.bind_load_state_payload(CompilationTarget::User, temp_v!(1));
+    }
+
+    /// Binds the second register to a load state payload importing
+    /// into the module named by the atom in the first register.
+    pub(crate) fn push_module_load_state_payload(&mut self) {
+        let compilation_target = match cell_as_atom!(self.deref_register(1)) {
+            atom!("user") => CompilationTarget::User,
+            module_name => CompilationTarget::Module(module_name),
+        };
+
+        self.bind_load_state_payload(compilation_target, temp_v!(2));
+    }
+
+    fn bind_load_state_payload(&mut self, compilation_target: CompilationTarget, r: RegType) {
+        let mut payload = LoadStatePayload::new(
+            self.code.len(),
+            LiveTermStream::new(ListingSource::User),
         );
 
-        let var = self.machine_st.deref(self.machine_st.registers[1]);
+        payload.compilation_target = compilation_target;
+
+        let payload = arena_alloc!(payload, &mut self.machine_st.arena);
+        let var = self.machine_st.deref(self.machine_st[r]);
 
         self.machine_st.bind(
             var.as_var().unwrap(),
@@ -1811,6 +1928,17 @@ pub(crate) fn push_load_state_payload(&mut self) {
         );
     }
 
+    /// Unifies the second register with the module the load state
+    /// payload in the first register imports into.
+    pub(crate) fn load_state_module(&mut self) {
+        let payload = cell_as_load_state_payload!(
+            self.machine_st.store(self.machine_st.deref(self.machine_st.registers[1]))
+        );
+
+        let module_name = payload.compilation_target.module_name();
+        self.machine_st.unify_atom(module_name, self.machine_st.registers[2]);
+    }
+
     #[inline]
     pub(crate) fn pop_load_state_payload(&mut self) {
         let load_state_payload = self.machine_st.store(
@@ -1915,9 +2043,95 @@ pub(crate) fn conclude_load(&mut self) -> CallResult {
         };
 
         let result = compile_final_terms();
+
+        if result.is_ok() {
+            self.add_loaded_file();
+
+            // the code replaced by the load can't be restored once no
+            // enclosing load is left to fail.
+            if self.load_contexts.len() <= 1 {
+                self.free_unreachable_fact_tables();
+                self.indices.forget_unreachable_clause_sources();
+            }
+        }
+
         self.restore_load_state_payload(result)
     }
 
+    // records the file of the load concluded, if it's loaded from one,
+    // together with the file it was loaded from.
+    fn add_loaded_file(&mut self) {
+        let mut paths = self.load_contexts.iter().rev().map(|load_context| &load_context.path);
+
+        let (file, modified) = match paths.next() {
+            Some(path) if path.is_file() => match path.to_str() {
+                Some(path_str) => (
+                    self.machine_st.atom_tbl.build_with(path_str),
+                    fs::metadata(path).and_then(|md| md.modified()).ok(),
+                ),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let parent = paths
+            .next()
+            .filter(|path| path.is_file())
+            .and_then(|path| path.to_str())
+            .map(|path_str| self.machine_st.atom_tbl.build_with(path_str));
+
+        // a file loaded again is moved past the files it loaded. if
+        // it's reloaded in place by make/0, it keeps the file it was
+        // loaded from.
+        let parent = match self.loaded_files.shift_remove(&file) {
+            Some(loaded_file) => parent.or(loaded_file.parent),
+            None => parent,
+        };
+
+        self.loaded_files.insert(file, LoadedFile { modified, parent });
+    }
+
+    /// Unifies the first register with the list of files modified
+    /// since they were loaded, leaving out those loaded from files
+    /// also modified, in the order their loads concluded. Files that
+    /// no longer exist are left out.
+    pub(crate) fn modified_loaded_files(&mut self) {
+        let modified_files = self
+            .loaded_files
+            .iter()
+            .filter(|(file, loaded_file)| {
+                match fs::metadata(file.as_str()).and_then(|md| md.modified()) {
+                    Ok(modified) => loaded_file.modified != Some(modified),
+                    Err(_) => false,
+                }
+            })
+            .map(|(&file, _)| file)
+            .collect::<IndexSet<_>>();
+
+        let loaded_by_modified_file = |mut file: Atom| {
+            // the parents of a file may loop back to it if it was
+            // loaded again from a file it loaded.
+            for _ in 0..self.loaded_files.len() {
+                match self.loaded_files.get(&file).and_then(|loaded_file| loaded_file.parent) {
+                    Some(parent) if modified_files.contains(&parent) => return true,
+                    Some(parent) => file = parent,
+                    None => break,
+                }
+            }
+
+            false
+        };
+
+        let files = modified_files
+            .iter()
+            .filter(|&&file| !loaded_by_modified_file(file))
+            .map(|&file| atom_as_cell!(file))
+            .collect::<Vec<_>>();
+
+        let h = iter_to_heap_list(&mut self.machine_st.heap, files.into_iter());
+        unify!(self.machine_st, heap_loc_as_cell!(h), self.machine_st.registers[1]);
+    }
+
     pub(crate) fn load_context_source(&mut self) {
         if let Some(load_context) = self.load_contexts.last() {
             let path_str = load_context.path.to_str().unwrap();
@@ -1981,6 +2195,27 @@ pub(crate) fn load_context_stream(&mut self) {
         }
     }
 
+    /// Records the position of the stream in the first register as
+    /// that at which the term about to be read from the file being
+    /// loaded starts.
+    pub(crate) fn set_load_context_position(&mut self) -> CallResult {
+        let stream = self.machine_st.get_stream_or_alias(
+            self.machine_st.registers[1],
+            &self.indices.stream_aliases,
+            atom!("$set_load_context_position"),
+            1,
+        )?;
+
+        if let Some(load_context) = self.load_contexts.last_mut() {
+            load_context.term_position = Some(TermPosition {
+                line: stream.lines_read() + 1,
+                col: stream.col_num() + 1,
+            });
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn compile_assert(&mut self, append_or_prepend: AppendOrPrepend) -> CallResult {
         let module_name = cell_as_atom!(self.deref_register(1));
 
@@ -2067,6 +2302,7 @@ pub(crate) fn compile_assert(&mut self, append_or_prepend: AppendOrPrepend) -> C
             loader.incremental_compile_clause(
                 (name, arity),
                 asserted_clause,
+                None,
                 compilation_target,
                 false,
                 append_or_prepend,
@@ -2140,7 +2376,7 @@ pub(crate) fn abolish_clause(&mut self) -> CallResult {
                 module => module,
             };
 
-            let mut clause_clause_target_poses: Vec<_> = loader
+            let (mut clause_clause_target_poses, clause_locs): (Vec<_>, Vec<_>) = loader
                 .wam_prelude
                 .indices
                 .remove_predicate_skeleton(&compilation_target, &key)
@@ -2163,6 +2399,8 @@ pub(crate) fn abolish_clause(&mut self) -> CallResult {
                         })
                         .collect();
 
+                    let clause_locs = skeleton.core.clause_clause_locs.iter().cloned().collect();
+
                     loader.add_extensible_predicate(
                         key,
                         skeleton,
@@ -2175,13 +2413,19 @@ pub(crate) fn abolish_clause(&mut self) -> CallResult {
                         clause_clause_compilation_target,
                     );
 
-                    result
+                    (result, clause_locs)
                 }).unwrap();
 
             loader.wam_prelude
                 .indices
                 .remove_predicate_skeleton(&compilation_target, &key);
 
+            for clause_loc in clause_locs {
+                loader.remove_clause_source(clause_loc);
+            }
+
+            loader.invalidate_jit_index(key, compilation_target);
+
             let mut code_index = loader
                 .get_or_insert_code_index(key, compilation_target);
 
@@ -2331,6 +2575,18 @@ pub(crate) fn remove_module_exports(&mut self) -> CallResult {
         self.restore_load_state_payload(result)
     }
 
+    pub(crate) fn add_fact_table(&mut self) -> CallResult {
+        let key = self
+            .machine_st
+            .read_predicate_key(self.machine_st[temp_v!(1)], self.machine_st[temp_v!(2)]);
+
+        let mut loader = self.loader_from_heap_evacuable(temp_v!(3));
+        loader.payload.fact_table_preds.insert(key);
+
+        let result = LiveLoadAndMachineState::evacuate(loader);
+        self.restore_load_state_payload(result)
+    }
+
     pub(crate) fn add_non_counted_backtracking(&mut self) -> CallResult {
         let key = self
             .machine_st
@@ -2390,6 +2646,253 @@ pub(crate) fn meta_predicate_property(&mut self) {
         }
     }
 
+    pub(crate) fn indexed_property(&mut self) {
+        let module_name = cell_as_atom!(
+            self.machine_st.store(self.machine_st.deref(self.machine_st.registers[1]))
+        );
+
+        let (predicate_name, arity) = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[2], self.machine_st.registers[3]);
+
+        let indexed_args = self
+            .indices
+            .get_predicate_code_index(predicate_name, arity, module_name)
+            .and_then(|code_index| code_index.local())
+            .and_then(|p| self.indices.jit_indices.get(&p))
+            .map(|jit_index| jit_index.indexed_args())
+            .unwrap_or_default();
+
+        if indexed_args.is_empty() {
+            self.machine_st.fail = true;
+            return;
+        }
+
+        let h = iter_to_heap_list(
+            &mut self.machine_st.heap,
+            indexed_args.into_iter().map(|arg| fixnum_as_cell!(Fixnum::build_with(arg as i64))),
+        );
+
+        unify!(self.machine_st, heap_loc_as_cell!(h), self.machine_st.registers[4]);
+    }
+
+    pub(crate) fn fact_table_property(&mut self) {
+        let module_name = cell_as_atom!(
+            self.machine_st.store(self.machine_st.deref(self.machine_st.registers[1]))
+        );
+
+        let (predicate_name, arity) = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[2], self.machine_st.registers[3]);
+
+        let is_fact_table = self
+            .indices
+            .get_predicate_code_index(predicate_name, arity, module_name)
+            .and_then(|code_index| code_index.local())
+            .and_then(|p| match self.code[p] {
+                Instruction::FactTable(id) => Some(self.indices.fact_tables[id].is_declared()),
+                _ => None,
+            })
+            .unwrap_or(false);
+
+        self.machine_st.fail = !is_fact_table;
+    }
+
+    /// Unifies the fourth register with the list of the code locations
+    /// at which the clauses of the predicate named by the second and
+    /// third registers start, as it's visible in the module of the
+    /// first.
+    pub(crate) fn clause_locs(&mut self) {
+        let module_name = cell_as_atom!(
+            self.machine_st.store(self.machine_st.deref(self.machine_st.registers[1]))
+        );
+
+        let key = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[2], self.machine_st.registers[3]);
+
+        let locs = self
+            .indices
+            .predicate_clause_locs(module_name, key)
+            .map(|(_, locs)| locs)
+            .unwrap_or_default();
+
+        let h = iter_to_heap_list(
+            &mut self.machine_st.heap,
+            locs.into_iter().map(|loc| fixnum_as_cell!(Fixnum::build_with(loc as i64))),
+        );
+
+        unify!(self.machine_st, heap_loc_as_cell!(h), self.machine_st.registers[4]);
+    }
+
+    /// Unifies the second, third and fourth registers with the
+    /// predicate of the clause starting at the code location in the
+    /// first, as `Module:Name/Arity`, and the file and line it was
+    /// loaded from, each `[]` if the clause was asserted. Fails if no
+    /// clause starts there.
+    pub(crate) fn clause_source(&mut self) {
+        let loc = match Number::try_from(self.deref_register(1)) {
+            Ok(Number::Fixnum(n)) => usize::try_from(n.get_num()).ok(),
+            _ => None,
+        };
+
+        // only the clauses loaded have sources, so the predicates of
+        // those asserted are looked up among the clauses of the
+        // predicates.
+        let source = loc.and_then(|loc| match self.indices.clause_sources.get(&loc) {
+            Some(source) => Some((source.module_name, source.key, source.file, Some(source.line))),
+            None => match self.indices.code_locs().get(&loc) {
+                Some(code_loc) if code_loc.clause > 0 => {
+                    Some((code_loc.module_name, code_loc.key, None, None))
+                }
+                _ => None,
+            },
+        });
+
+        let (module_name, (name, arity), file, line) = match source {
+            Some(source) => source,
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let heap = &mut self.machine_st.heap;
+        let h = heap.len();
+
+        heap.push(atom_as_cell!(atom!("/"), 2));
+        heap.push(atom_as_cell!(name));
+        heap.push(fixnum_as_cell!(Fixnum::build_with(arity as i64)));
+        heap.push(atom_as_cell!(atom!(":"), 2));
+        heap.push(atom_as_cell!(module_name));
+        heap.push(str_loc_as_cell!(h));
+
+        let file = match file {
+            Some(file) => atom_as_cell!(file),
+            None => empty_list_as_cell!(),
+        };
+
+        let line = match line {
+            Some(line) => fixnum_as_cell!(Fixnum::build_with(line as i64)),
+            None => empty_list_as_cell!(),
+        };
+
+        unify!(self.machine_st, str_loc_as_cell!(h + 3), self.machine_st.registers[2]);
+
+        if !self.machine_st.fail {
+            unify!(self.machine_st, file, self.machine_st.registers[3]);
+        }
+
+        if !self.machine_st.fail {
+            unify!(self.machine_st, line, self.machine_st.registers[4]);
+        }
+    }
+
+    /// Unifies the first register with the list of the names of the
+    /// modules loaded.
+    pub(crate) fn current_modules(&mut self) {
+        let module_names = self
+            .indices
+            .modules
+            .keys()
+            .map(|&module_name| atom_as_cell!(module_name))
+            .collect::<Vec<_>>();
+
+        let h = iter_to_heap_list(&mut self.machine_st.heap, module_names.into_iter());
+        unify!(self.machine_st, heap_loc_as_cell!(h), self.machine_st.registers[1]);
+    }
+
+    /// Unifies the second register with the class of the module named
+    /// by the first: `system` for the modules of the system itself,
+    /// `library` for the modules of libraries, and `user` for the rest.
+    pub(crate) fn module_class(&mut self) {
+        let module_name = cell_as_atom!(self.deref_register(1));
+
+        let class = module_class(module_name);
+        self.machine_st.unify_atom(class, self.machine_st.registers[2]);
+    }
+
+    /// Unifies the second register with the file the module named by
+    /// the first was loaded from. Fails if it wasn't loaded from a
+    /// file.
+    pub(crate) fn module_file(&mut self) {
+        let module_name = cell_as_atom!(self.deref_register(1));
+
+        let path_buf = match self.indices.modules.get(&module_name) {
+            Some(Module {
+                listing_src: ListingSource::File(_, path_buf),
+                ..
+            }) => path_buf.clone(),
+            _ => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        match path_buf.to_str() {
+            Some(path_str) => {
+                let file = self.machine_st.atom_tbl.build_with(path_str);
+                self.machine_st.unify_atom(file, self.machine_st.registers[2]);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+    }
+
+    /// Unifies the second register with the exports of the module named
+    /// by the first, as they're listed by its module declaration and
+    /// reexports: predicate indicators and `op/3` terms.
+    pub(crate) fn module_exports(&mut self) {
+        let module_name = cell_as_atom!(self.deref_register(1));
+
+        let exports = match self.indices.modules.get(&module_name) {
+            Some(module) => module.module_decl.exports.clone(),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let mut export_cells = vec![];
+
+        for export in exports {
+            let h = self.machine_st.heap.len();
+
+            match export {
+                ModuleExport::PredicateKey((name, arity)) => {
+                    self.machine_st.heap.push(atom_as_cell!(atom!("/"), 2));
+                    self.machine_st.heap.push(atom_as_cell!(name));
+                    self.machine_st.heap.push(fixnum_as_cell!(Fixnum::build_with(arity as i64)));
+                }
+                ModuleExport::OpDecl(op_decl) => {
+                    let spec = match op_decl.op_desc.get_spec() as u32 {
+                        XFX => atom!("xfx"),
+                        XFY => atom!("xfy"),
+                        YFX => atom!("yfx"),
+                        FX => atom!("fx"),
+                        FY => atom!("fy"),
+                        XF => atom!("xf"),
+                        YF => atom!("yf"),
+                        _ => unreachable!(),
+                    };
+
+                    let prec = op_decl.op_desc.get_prec() as i64;
+
+                    self.machine_st.heap.push(atom_as_cell!(atom!("op"), 3));
+                    self.machine_st.heap.push(fixnum_as_cell!(Fixnum::build_with(prec)));
+                    self.machine_st.heap.push(atom_as_cell!(spec));
+                    self.machine_st.heap.push(atom_as_cell!(op_decl.name));
+                }
+            }
+
+            export_cells.push(str_loc_as_cell!(h));
+        }
+
+        let h = iter_to_heap_list(&mut self.machine_st.heap, export_cells.into_iter());
+        unify!(self.machine_st, heap_loc_as_cell!(h), self.machine_st.registers[2]);
+    }
+
     pub(crate) fn dynamic_property(&mut self) {
         let module_name = cell_as_atom!(
             self.machine_st.store(self.machine_st.deref(self.machine_st.registers[1]))
@@ -2487,9 +2990,14 @@ fn read_and_enqueue_term(
         }
 
         let term = self.read_term_from_heap(term_reg)?;
+        let position = self
+            .wam_prelude
+            .load_contexts
+            .last()
+            .and_then(|load_context| load_context.term_position);
 
         self.add_clause_clause_if_dynamic(&term)?;
-        self.payload.term_stream.term_queue.push_back(term);
+        self.payload.term_stream.term_queue.push_back((term, position));
 
         self.load()
     }
@@ -293,6 +293,30 @@ pub(super) fn resource_error(&mut self, value: HeapCellValue) -> MachineError {
         }
     }
 
+    // the ISO resource error raised when a limit set by one of the
+    // max_*_size flags is exceeded.
+    pub(super) fn memory_resource_error(&mut self) -> MachineError {
+        let stub = functor!(atom!("resource_error"), [atom(atom!("memory"))]);
+
+        MachineError {
+            stub,
+            location: None,
+            from: ErrorProvenance::Received,
+        }
+    }
+
+    // thrown by a coroutine that would wait forever, e.g. in
+    // coroutine_get_message/1 when no other coroutine can run.
+    pub(super) fn deadlock_error(&mut self) -> MachineError {
+        let stub = functor!(atom!("system_error"), [atom(atom!("deadlock"))]);
+
+        MachineError {
+            stub,
+            location: None,
+            from: ErrorProvenance::Received,
+        }
+    }
+
     pub(super) fn type_error<T: TypeError>(
         &mut self,
         valid_type: ValidType,
@@ -383,6 +407,42 @@ pub(super) fn existence_error(&mut self, err: ExistenceError) -> MachineError {
                     [atom(atom!("stream")), cell(culprit)]
                 );
 
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::Engine(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("engine")), cell(culprit)]
+                );
+
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::Coroutine(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("coroutine")), cell(culprit)]
+                );
+
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::NativePredicate(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("native_predicate")), cell(culprit)]
+                );
+
                 MachineError {
                     stub,
                     location: None,
@@ -722,10 +782,12 @@ pub(crate) enum Permission {
     Access,
     Create,
     InputStream,
+    Join,
     Modify,
     Open,
     OutputStream,
     Reposition,
+    Save,
 }
 
 impl Permission {
@@ -735,10 +797,12 @@ pub(crate) fn as_atom(self) -> Atom {
             Permission::Access => atom!("access"),
             Permission::Create => atom!("create"),
             Permission::InputStream => atom!("input"),
+            Permission::Join => atom!("join"),
             Permission::Modify => atom!("modify"),
             Permission::Open => atom!("open"),
             Permission::OutputStream => atom!("output"),
             Permission::Reposition => atom!("reposition"),
+            Permission::Save => atom!("save"),
         }
     }
 }
@@ -942,6 +1006,9 @@ pub enum ExistenceError {
     Procedure(Atom, usize),
     SourceSink(HeapCellValue),
     Stream(HeapCellValue),
+    Coroutine(HeapCellValue),
+    Engine(HeapCellValue),
+    NativePredicate(HeapCellValue),
 }
 
 #[derive(Debug)]
@@ -3,18 +3,21 @@
 use crate::arena::*;
 use crate::atom_table::*;
 use crate::forms::*;
+use crate::indexing::JitIndex;
 use crate::machine::ClauseType;
+use crate::machine::fact_table::FactTable;
 use crate::machine::loader::*;
 use crate::machine::machine_state::*;
 use crate::machine::streams::Stream;
 
+use bit_set::BitSet;
 use fxhash::FxBuildHasher;
 use indexmap::{IndexMap, IndexSet};
 use modular_bitfield::{BitfieldSpecifier, bitfield};
 use modular_bitfield::specifiers::*;
 
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::{Deref, DerefMut};
 
 use crate::types::*;
@@ -270,13 +273,56 @@ pub(crate) fn is_anon(&self) -> bool {
 
 pub(crate) type GoalExpansionIndices = IndexSet<PredicateKey, FxBuildHasher>;
 
+// just-in-time indices, keyed by the code location of their predicates.
+pub(crate) type JitIndexDir = IndexMap<usize, JitIndex, FxBuildHasher>;
+
+/// The predicate whose code holds a code location, and the clause of
+/// the predicate holding it, numbered from 1, or 0 if the location is
+/// in the code indexing the clauses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct CodeLoc {
+    pub(crate) module_name: Atom,
+    pub(crate) key: PredicateKey,
+    pub(crate) clause: usize,
+}
+
+/// The line and the column, counted from 1, at which a term read
+/// from a stream starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TermPosition {
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+}
+
+/// The predicate of a clause loaded rather than asserted, the file it
+/// was loaded from, if any, and the line and the column at which the
+/// term it was compiled from starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClauseSource {
+    pub(crate) module_name: Atom,
+    pub(crate) key: PredicateKey,
+    pub(crate) file: Option<Atom>,
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+}
+
+// the sources of the clauses loaded, keyed by the code locations at
+// which the clauses start.
+pub(crate) type ClauseSourceDir = BTreeMap<usize, ClauseSource>;
+
 #[derive(Debug)]
 pub struct IndexStore {
     pub(super) code_dir: CodeDir,
+    pub(super) clause_sources: ClauseSourceDir,
     pub(super) extensible_predicates: ExtensiblePredicates,
+    pub(super) fact_tables: Vec<FactTable>,
     pub(super) local_extensible_predicates: LocalExtensiblePredicates,
     pub(super) global_variables: GlobalVarDir,
     pub(super) goal_expansion_indices: GoalExpansionIndices,
+    pub(super) jit_indices: JitIndexDir,
+    // the code locations of the predicates found not to be indexable
+    // just in time, which have no entry in jit_indices.
+    pub(super) jit_unindexable: BitSet<usize>,
     pub(super) meta_predicates: MetaPredicateDir,
     pub(super) modules: ModuleDir,
     pub(super) op_dir: OpDir,
@@ -336,6 +382,132 @@ pub(crate) fn get_predicate_skeleton(
         }
     }
 
+    // the module defining the predicate `key` at the code location `p`
+    // of the code directory of `module_name`, from which it may be
+    // imported.
+    fn predicate_owner(&self, module_name: Atom, key: PredicateKey, p: usize) -> Option<Atom> {
+        let compilation_target = match module_name {
+            atom!("user") => CompilationTarget::User,
+            _ => CompilationTarget::Module(module_name),
+        };
+
+        if self.get_predicate_skeleton(&compilation_target, &key).is_some() {
+            return Some(module_name);
+        }
+
+        // an extensible predicate is defined by the module holding its
+        // skeleton, whether or not any of its clauses were loaded.
+        let defines = |code_dir: &CodeDir| {
+            code_dir.get(&key).and_then(|idx| idx.local()) == Some(p)
+        };
+
+        if self.extensible_predicates.contains_key(&key) && defines(&self.code_dir) {
+            return Some(atom!("user"));
+        }
+
+        let owner = self.modules.iter().find(|(_, module)| {
+            module.extensible_predicates.contains_key(&key) && defines(&module.code_dir)
+        });
+
+        if let Some((&owner, _)) = owner {
+            return Some(owner);
+        }
+
+        let owns =|src: &ClauseSource| {
+            src.key == key
+                && self
+                    .get_predicate_code_index(key.0, key.1, src.module_name)
+                    .and_then(|idx| idx.local())
+                    == Some(p)
+        };
+
+        // the clauses of a static predicate follow its entry point.
+        self.clause_sources
+            .range(p..)
+            .next()
+            .filter(|(_, src)| owns(src))
+            .map(|(_, src)| src.module_name)
+    }
+
+    /// The module defining the predicate `key` visible in `module_name`,
+    /// and the code locations at which its clauses start, in order.
+    pub(crate) fn predicate_clause_locs(
+        &self,
+        module_name: Atom,
+        key: PredicateKey,
+    ) -> Option<(Atom, Vec<usize>)> {
+        let p = self
+            .get_predicate_code_index(key.0, key.1, module_name)
+            .and_then(|idx| idx.local())?;
+
+        let owner = self.predicate_owner(module_name, key, p)?;
+
+        let compilation_target = match owner {
+            atom!("user") => CompilationTarget::User,
+            _ => CompilationTarget::Module(owner),
+        };
+
+        let locs = match self.get_predicate_skeleton(&compilation_target, &key) {
+            Some(skeleton) => skeleton.core.clause_clause_locs.iter().cloned().collect(),
+            None => self
+                .clause_sources
+                .range(p..)
+                .take_while(|(_, src)| src.module_name == owner && src.key == key)
+                .map(|(&loc, _)| loc)
+                .collect(),
+        };
+
+        Some((owner, locs))
+    }
+
+    /// The code locations at which the predicates and their clauses
+    /// start. The predicate holding a code location is that of the
+    /// greatest start not after it.
+    pub(crate) fn code_locs(&self) -> BTreeMap<usize, CodeLoc> {
+        let mut locs = BTreeMap::new();
+
+        let predicates = self
+            .code_dir
+            .iter()
+            .map(|(&key, idx)| (atom!("user"), key, idx))
+            .chain(self.modules.iter().flat_map(|(&module_name, module)| {
+                module
+                    .code_dir
+                    .iter()
+                    .map(move |(&key, idx)| (module_name, key, idx))
+            }));
+
+        for (module_name, key, idx) in predicates {
+            let p = match idx.local() {
+                Some(p) => p,
+                None => continue,
+            };
+
+            match self.predicate_clause_locs(module_name, key) {
+                Some((owner, clause_locs)) if owner == module_name => {
+                    let code_loc = |clause| CodeLoc { module_name, key, clause };
+
+                    locs.insert(p, code_loc(0));
+
+                    for (i, loc) in clause_locs.into_iter().enumerate() {
+                        locs.insert(loc, code_loc(i + 1));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        locs
+    }
+
+    /// Forgets the sources of the clauses no predicate holds any
+    /// longer, as after the predicates are redefined or their module
+    /// reloaded.
+    pub(crate) fn forget_unreachable_clause_sources(&mut self) {
+        let code_locs = self.code_locs();
+        self.clause_sources.retain(|loc, _| code_locs.contains_key(loc));
+    }
+
     pub(crate) fn get_local_predicate_skeleton_mut(
         &mut self,
         mut src_compilation_target: CompilationTarget,
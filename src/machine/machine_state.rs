@@ -6,9 +6,13 @@
 use crate::machine::Machine;
 use crate::machine::attributed_variables::*;
 use crate::machine::copier::*;
+use crate::machine::coverage::Coverage;
+use crate::machine::gc::GcState;
 use crate::machine::heap::*;
+use crate::machine::limits::ResourceLimits;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_indices::*;
+use crate::machine::profiler::Profiler;
 use crate::machine::stack::*;
 use crate::machine::streams::*;
 use crate::parser::ast::*;
@@ -22,6 +26,7 @@
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::{Index, IndexMut};
+use std::time::{Duration, Instant};
 
 pub(crate) type Registers = [HeapCellValue; MAX_ARITY + 1];
 
@@ -56,6 +61,8 @@ pub enum OnEOF {
     Continue,
 }
 
+// fields that hold atoms outside of the heap, stacks and trail must be
+// marked as atom roots by Machine::mark_atom_roots in atom_gc.rs.
 pub struct MachineState {
     pub atom_tbl: AtomTable,
     pub arena: Arena,
@@ -83,6 +90,7 @@ pub struct MachineState {
     pub(super) scc_block: usize, // an offset into the OR stack for setup_call_cleanup/3.
     pub(super) ball: Ball,
     pub(super) ball_stack: Vec<Ball>, // save current ball before jumping via, e.g., verify_attr interrupt.
+    pub(super) caught_ball: Option<Ball>, // the last ball caught with a backtrace.
     pub(super) lifted_heap: Heap,
     pub(super) interms: Vec<Number>, // intermediate numbers.
     // locations of cleaners, cut points, the previous scc_block. for setup_call_cleanup/3.
@@ -96,6 +104,14 @@ pub struct MachineState {
     pub(crate) bind_fn: fn(&mut MachineState, Ref, HeapCellValue),
     pub(crate) run_cleaners_fn: fn(&mut Machine) -> bool,
     pub(crate) increment_call_count_fn: fn(&mut MachineState) -> CallResult,
+    pub(crate) gc: GcState,
+    pub(crate) stats: MachineStats,
+    pub(crate) profiler: Profiler,
+    pub(crate) coverage: Coverage,
+    pub(crate) limits: ResourceLimits,
+    // the number of calls left until the next poll of the collectors,
+    // limits and scheduler. Zero polls at the next call.
+    pub(crate) calls_until_poll: usize,
 }
 
 impl fmt::Debug for MachineState {
@@ -151,6 +167,9 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                     &"MachineState::bind_with_occurs_check_with_error_wrapper"
                 },
             )
+            .field("gc", &self.gc)
+            .field("stats", &self.stats)
+            .field("limits", &self.limits)
             .finish()
     }
 }
@@ -225,6 +244,9 @@ fn push_var_eq_functors<'a>(
 pub struct Ball {
     pub(super) boundary: usize,
     pub(super) stub: Heap,
+    // the code locations of the call stack the ball was thrown from,
+    // if the backtrace flag was on.
+    pub(super) backtrace: Vec<usize>,
 }
 
 impl Ball {
@@ -232,12 +254,14 @@ pub(super) fn new() -> Self {
         Ball {
             boundary: 0,
             stub: Heap::new(),
+            backtrace: vec![],
         }
     }
 
     pub(super) fn reset(&mut self) {
         self.boundary = 0;
         self.stub.clear();
+        self.backtrace.clear();
     }
 
     pub(super) fn copy_and_align(&self, h: usize) -> Heap {
@@ -407,6 +431,10 @@ pub(crate) fn backtrack(&mut self) {
 
         self.pdl.clear();
         self.fail = false;
+
+        if self.profiler.active() {
+            self.profile_redo();
+        }
     }
 
     pub(crate) fn increment_call_count(&mut self) -> CallResult {
@@ -922,6 +950,28 @@ pub(super) fn cut_body(&mut self, value: HeapCellValue) {
     }
 }
 
+/// The counters reported by `statistics/2` that aren't kept elsewhere.
+#[derive(Debug)]
+pub(crate) struct MachineStats {
+    pub(crate) inferences: u64,
+    pub(crate) start: Instant,
+    // the values of the runtime and walltime keys when they were last
+    // read, from which their "since last" components are computed.
+    pub(crate) last_runtime: Duration,
+    pub(crate) last_walltime: Duration,
+}
+
+impl MachineStats {
+    pub(crate) fn new() -> Self {
+        MachineStats {
+            inferences: 0,
+            start: Instant::now(),
+            last_runtime: Duration::ZERO,
+            last_walltime: Duration::ZERO,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct CWIL {
     count: Integer,
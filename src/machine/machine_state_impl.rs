@@ -5,11 +5,15 @@
 use crate::heap_iter::*;
 use crate::machine::attributed_variables::*;
 use crate::machine::copier::*;
+use crate::machine::coverage::Coverage;
+use crate::machine::gc::GcState;
 use crate::machine::heap::*;
+use crate::machine::limits::ResourceLimits;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_indices::*;
 use crate::machine::machine_state::*;
 use crate::machine::partial_string::*;
+use crate::machine::profiler::Profiler;
 use crate::machine::stack::*;
 use crate::machine::unify::*;
 use crate::parser::ast::*;
@@ -49,6 +53,7 @@ pub(crate) fn new() -> Self {
             scc_block: 0,
             ball: Ball::new(),
             ball_stack: vec![],
+            caught_ball: None,
             lifted_heap: Heap::new(),
             interms: vec![Number::default();256],
             cont_pts: Vec::with_capacity(256),
@@ -61,6 +66,12 @@ pub(crate) fn new() -> Self {
             bind_fn: MachineState::bind,
             run_cleaners_fn: |_| { false },
             increment_call_count_fn: |_| { Ok(()) },
+            gc: GcState::new(),
+            stats: MachineStats::new(),
+            profiler: Profiler::default(),
+            coverage: Coverage::default(),
+            limits: ResourceLimits::default(),
+            calls_until_poll: 0,
         }
     }
 
@@ -337,6 +348,8 @@ pub(super) fn set_ball(&mut self) {
         self.ball.reset();
 
         let addr = self.registers[1];
+        let backtrace = self.ball_backtrace(addr);
+
         self.ball.boundary = self.heap.len();
 
         copy_term(
@@ -344,6 +357,8 @@ pub(super) fn set_ball(&mut self) {
             addr,
             AttrVarPolicy::DeepCopy,
         );
+
+        self.ball.backtrace = backtrace;
     }
 
     #[inline(always)]
@@ -1739,6 +1754,43 @@ pub fn deallocate(&mut self) {
         self.p += 1;
     }
 
+    // pushes a choicepoint resumed at `bp` over `alternatives`, which
+    // are kept in a heap list saved after the arguments.
+    pub(crate) fn try_alternatives(&mut self, alternatives: impl Iterator<Item = usize>, bp: usize) {
+        let h = iter_to_heap_list(
+            &mut self.heap,
+            alternatives.map(|p| fixnum_as_cell!(Fixnum::build_with(p as i64))),
+        );
+
+        let alternatives = self.heap[h];
+
+        let n = self.num_of_args;
+        let b = self.stack.allocate_or_frame(n + 1);
+        let or_frame = self.stack.index_or_frame_mut(b);
+
+        or_frame.prelude.num_cells = n + 1;
+        or_frame.prelude.e = self.e;
+        or_frame.prelude.cp = self.cp;
+        or_frame.prelude.b = self.b;
+        or_frame.prelude.bp = bp;
+        or_frame.prelude.boip = 0;
+        or_frame.prelude.biip = 0;
+        or_frame.prelude.tr = self.tr;
+        or_frame.prelude.h = self.heap.len();
+        or_frame.prelude.b0 = self.b0;
+        or_frame.prelude.attr_var_queue_len = self.attr_var_init.attr_var_queue.len();
+
+        self.b = b;
+
+        for i in 0..n {
+            or_frame[i] = self.registers[i+1];
+        }
+
+        or_frame[n] = alternatives;
+
+        self.hb = self.heap.len();
+    }
+
     pub fn throw_interrupt_exception(&mut self) {
         let err = self.interrupt_error();
         let src = functor_stub(atom!("repl"), 0);
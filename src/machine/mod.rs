@@ -1,26 +1,41 @@
 pub mod args;
 pub mod arithmetic_ops;
+pub mod atom_gc;
 pub mod attributed_variables;
+pub mod backtrace;
 pub mod code_walker;
+pub mod coverage;
+pub mod config;
 #[macro_use]
 pub mod loader;
 pub mod compile;
 pub mod copier;
+pub mod coroutines;
+pub mod debugger;
 pub mod dispatch;
+pub mod engines;
+pub mod fact_table;
 pub mod gc;
 pub mod heap;
+pub mod lib_machine;
+pub mod limits;
 pub mod load_state;
 pub mod machine_errors;
 pub mod machine_indices;
 pub mod machine_state;
 pub mod machine_state_impl;
 pub mod mock_wam;
+pub mod native_predicates;
+pub mod parsed_results;
 pub mod partial_string;
 pub mod disjuncts;
 pub mod preprocessor;
+pub mod profiler;
+pub mod saved_state;
 pub mod stack;
 pub mod streams;
 pub mod system_calls;
+pub mod tabling;
 pub mod term_stream;
 pub mod unify;
 
@@ -29,35 +44,48 @@
 use crate::atom_table::*;
 use crate::forms::*;
 use crate::ffi::ForeignFunctionTable;
+use crate::indexing::JitArgKey;
 use crate::instructions::*;
 use crate::machine::args::*;
 use crate::machine::compile::*;
+use crate::machine::config::*;
 use crate::machine::copier::*;
+use crate::machine::coroutines::*;
+use crate::machine::debugger::*;
+use crate::machine::engines::*;
 use crate::machine::heap::*;
 use crate::machine::loader::*;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_indices::*;
 use crate::machine::machine_state::*;
+use crate::machine::native_predicates::*;
 use crate::machine::stack::*;
 use crate::machine::streams::*;
+use crate::machine::tabling::*;
 use crate::parser::ast::*;
 use crate::parser::dashu::{Integer, Rational};
 use crate::types::*;
 
+use fxhash::FxBuildHasher;
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use ordered_float::OrderedFloat;
+use smallvec::SmallVec;
 
 use std::cmp::Ordering;
 use std::env;
+use std::io;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
+use std::time::SystemTime;
 use tokio::runtime::Runtime;
 
 lazy_static! {
     pub static ref INTERRUPT: AtomicBool = AtomicBool::new(false);
 }
 
+// fields that hold atoms must be marked as atom roots by
+// Machine::mark_atom_roots in atom_gc.rs.
 #[derive(Debug)]
 pub struct Machine {
     pub(super) machine_st: MachineState,
@@ -67,8 +95,14 @@ pub struct Machine {
     pub(super) user_output: Stream,
     pub(super) user_error: Stream,
     pub(super) load_contexts: Vec<LoadContext>,
+    pub(super) loaded_files: IndexMap<Atom, LoadedFile, FxBuildHasher>,
     pub(super) runtime: Runtime,
     pub(super) foreign_function_table: ForeignFunctionTable,
+    pub(super) native_predicates: NativePredicates,
+    pub(super) coroutines: Coroutines,
+    pub(super) engines: Engines,
+    pub(super) tables: TableStore,
+    pub(super) debugger: Debugger,
 }
 
 #[derive(Debug)]
@@ -76,6 +110,18 @@ pub struct LoadContext {
     pub(super) path: PathBuf,
     pub(super) stream: Stream,
     pub(super) module: Atom,
+    // the position at which the term last read from the stream starts.
+    pub(super) term_position: Option<TermPosition>,
+}
+
+/// A file loaded from source, with the time it was last modified as
+/// it was loaded, and the file it was loaded from, if any. Files are
+/// kept in the order their loads concluded, the files a file loads
+/// before it.
+#[derive(Debug)]
+pub struct LoadedFile {
+    pub(super) modified: Option<SystemTime>,
+    pub(super) parent: Option<Atom>,
 }
 
 impl LoadContext {
@@ -93,6 +139,7 @@ fn new(path: &str, stream: Stream) -> Self {
             path: path_buf,
             stream,
             module: atom!("user"),
+            term_position: None,
         }
     }
 }
@@ -107,6 +154,8 @@ fn current_dir() -> PathBuf {
 pub static BREAK_FROM_DISPATCH_LOOP_LOC: usize = 0;
 pub static INSTALL_VERIFY_ATTR_INTERRUPT: usize = 1;
 pub static VERIFY_ATTR_INTERRUPT_LOC: usize = 2;
+pub static LIB_QUERY_SUCCESS: usize = 3;
+pub static JIT_RETRY_LOC: usize = 4;
 
 pub struct MachinePreludeView<'a> {
     pub indices: &'a mut IndexStore,
@@ -359,12 +408,14 @@ fn update_call_n_indices(loader: &Module, target_code_dir: &mut CodeDir, arena:
     }
 
     pub(crate) fn add_impls_to_indices(&mut self) {
-        let impls_offset = self.code.len() + 3;
+        let impls_offset = self.code.len() + 5;
 
         self.code.extend(vec![
             Instruction::BreakFromDispatchLoop,
             Instruction::InstallVerifyAttr,
             Instruction::VerifyAttrInterrupt,
+            Instruction::BreakFromDispatchLoop, // the location of LIB_QUERY_SUCCESS
+            Instruction::JitRetry, // the location of JIT_RETRY_LOC
             Instruction::ExecuteTermGreaterThan,
             Instruction::ExecuteTermLessThan,
             Instruction::ExecuteTermGreaterThanOrEqual,
@@ -416,19 +467,34 @@ pub(crate) fn add_impls_to_indices(&mut self) {
     }
 
     pub fn new() -> Self {
-        use ref_thread_local::RefThreadLocal;
-
         let args = MachineArgs::new();
-        let mut machine_st = MachineState::new();
+        MachineBuilder::default().with_add_history(args.add_history).bootstrap()
+    }
 
-        let user_input = Stream::stdin(&mut machine_st.arena, args.add_history);
-        let user_output = Stream::stdout(&mut machine_st.arena);
-        let user_error = Stream::stderr(&mut machine_st.arena);
+    /// Like `new`, but restores the machine from the state saved to the
+    /// file named by the `--restore` option, if it's given. The error
+    /// of a failed restore names the file.
+    pub fn from_args() -> io::Result<Self> {
+        let args = MachineArgs::new();
+        let builder = MachineBuilder::default().with_add_history(args.add_history);
 
-        let runtime = tokio::runtime::Runtime::new()
-            .unwrap();
+        match args.restore {
+            Some(path) => builder.restore(&path).map_err(|err| {
+                io::Error::new(err.kind(), format!("can't restore {}: {}", path, err))
+            }),
+            None => Ok(builder.bootstrap()),
+        }
+    }
 
-        let mut wam = Machine {
+    // a machine with no code, to be bootstrapped or restored.
+    pub(crate) fn unloaded(
+        machine_st: MachineState,
+        user_input: Stream,
+        user_output: Stream,
+        user_error: Stream,
+        runtime: Runtime,
+    ) -> Self {
+        Machine {
             machine_st,
             indices: IndexStore::new(),
             code: vec![],
@@ -436,9 +502,27 @@ pub fn new() -> Self {
             user_output,
             user_error,
             load_contexts: vec![],
+            loaded_files: IndexMap::with_hasher(FxBuildHasher::default()),
             runtime,
 	        foreign_function_table: Default::default(),
-        };
+            native_predicates: NativePredicates::default(),
+            coroutines: Coroutines::new(),
+            engines: Engines::default(),
+            tables: TableStore::default(),
+            debugger: Debugger::default(),
+        }
+    }
+
+    pub(crate) fn bootstrap(
+        machine_st: MachineState,
+        user_input: Stream,
+        user_output: Stream,
+        user_error: Stream,
+        runtime: Runtime,
+    ) -> Self {
+        use ref_thread_local::RefThreadLocal;
+
+        let mut wam = Machine::unloaded(machine_st, user_input, user_output, user_error, runtime);
 
         let mut lib_path = current_dir();
 
@@ -1047,6 +1131,19 @@ fn undefined_procedure(&mut self, name: Atom, arity: usize) -> CallResult {
     #[inline(always)]
     fn try_call(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
         let compiled_tl_index = idx.p() as usize;
+        let b = self.machine_st.b;
+        self.machine_st.stats.inferences += 1;
+
+        if self.machine_st.profiler.active() {
+            self.profile_call(idx);
+        }
+
+        if self.debugger.tracing()
+            && matches!(idx.tag(), IndexPtrTag::DynamicIndex | IndexPtrTag::Index)
+            && self.trace_call(name, arity, idx, false)
+        {
+            return Ok(());
+        }
 
         match idx.tag() {
             IndexPtrTag::DynamicUndefined => {
@@ -1058,18 +1155,41 @@ fn try_call(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
             IndexPtrTag::DynamicIndex => {
                 self.machine_st.dynamic_mode = FirstOrNext::First;
                 self.machine_st.call_at_index(arity, compiled_tl_index);
+                self.jit_index_call(arity);
             }
             IndexPtrTag::Index => {
                 self.machine_st.call_at_index(arity, compiled_tl_index);
+                self.jit_index_call(arity);
             }
         }
 
+        if self.machine_st.coverage.active() {
+            self.cover_clause_entry(b, false);
+        }
+
         Ok(())
     }
 
     #[inline(always)]
     fn try_execute(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
         let compiled_tl_index = idx.p() as usize;
+        let b = self.machine_st.b;
+        self.machine_st.stats.inferences += 1;
+
+        if self.machine_st.profiler.active() {
+            self.profile_call(idx);
+        }
+
+        if self.machine_st.coverage.active() {
+            self.machine_st.coverage.exit(self.machine_st.p);
+        }
+
+        if self.debugger.tracing()
+            && matches!(idx.tag(), IndexPtrTag::DynamicIndex | IndexPtrTag::Index)
+            && self.trace_call(name, arity, idx, true)
+        {
+            return Ok(());
+        }
 
         match idx.tag() {
             IndexPtrTag::DynamicUndefined => {
@@ -1081,15 +1201,201 @@ fn try_execute(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult
             IndexPtrTag::DynamicIndex => {
                 self.machine_st.dynamic_mode = FirstOrNext::First;
                 self.machine_st.execute_at_index(arity, compiled_tl_index);
+                self.jit_index_call(arity);
             }
             IndexPtrTag::Index => {
-                self.machine_st.execute_at_index(arity, compiled_tl_index)
+                self.machine_st.execute_at_index(arity, compiled_tl_index);
+                self.jit_index_call(arity);
             }
         }
 
+        if self.machine_st.coverage.active() {
+            self.cover_clause_entry(b, false);
+        }
+
         Ok(())
     }
 
+    /// Narrows the call just made to the predicate at `p` down to the
+    /// clauses its just-in-time index selects, if the index of one of
+    /// the instantiated arguments rules out some of the clauses. The
+    /// clauses of a dynamic predicate are selected as of the time of
+    /// the call. Calls the first argument index already narrows down
+    /// to one clause are left to it.
+    fn jit_index_call(&mut self, arity: usize) {
+        let p = self.machine_st.p;
+
+        if arity == 0 {
+            return;
+        }
+
+        match &self.code[p] {
+            Instruction::IndexingCode(indexing_lines) => {
+                if self.selects_one_clause(indexing_lines) {
+                    return;
+                }
+            }
+            Instruction::TryMeElse(_) |
+            Instruction::DynamicElse(..) => {}
+            _ => return,
+        }
+
+        // most predicates are never indexed, so they're kept out of
+        // the map once they're found not to be.
+        if self.indices.jit_unindexable.contains(p) {
+            return;
+        }
+
+        let jit_index = self.indices.jit_indices.entry(p).or_default();
+
+        if !jit_index.prepare(&self.code, p, arity) {
+            if jit_index.is_unindexable() {
+                self.indices.jit_indices.swap_remove(&p);
+                self.indices.jit_unindexable.insert(p);
+            }
+
+            return;
+        }
+
+        let keys: SmallVec<[Option<JitArgKey>; 8]> = (1 ..= arity)
+            .map(|i| {
+                let cell = self.machine_st.store(self.machine_st.deref(self.machine_st.registers[i]));
+                self.machine_st.jit_arg_key(cell)
+            })
+            .collect();
+
+        let selective_arg = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, key)| key.map(|key| (i + 1, jit_index.count(i + 1, key))))
+            .min_by_key(|&(_, count)| count);
+
+        let arg = match selective_arg {
+            Some((arg, count)) if count < jit_index.len() => arg,
+            _ => return,
+        };
+
+        let cc = self.machine_st.global_clock;
+        let mut clauses = jit_index.matching_clauses(&self.code, arg, &keys, cc).peekable();
+
+        self.machine_st.dynamic_mode = FirstOrNext::Next;
+
+        match clauses.next() {
+            None => {
+                self.machine_st.fail = true;
+            }
+            Some(first) => {
+                if clauses.peek().is_some() {
+                    self.machine_st.try_alternatives(clauses, JIT_RETRY_LOC);
+                }
+
+                self.machine_st.p = first;
+            }
+        }
+    }
+
+    // true if the first argument index of the predicate, whose
+    // indexing instructions are `indexing_lines`, narrows the call in
+    // the registers down to at most one clause.
+    fn selects_one_clause(&self, indexing_lines: &[IndexingLine]) -> bool {
+        let addr = match &indexing_lines[0] {
+            &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(arg, ..)) => {
+                self.machine_st.store(self.machine_st.deref(self.machine_st.registers[arg]))
+            }
+            _ => return false,
+        };
+
+        if addr.is_var() {
+            return false;
+        }
+
+        let mut index = 0;
+
+        loop {
+            let offset = match &indexing_lines[index] {
+                &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(_, v, c, l, s)) => {
+                    self.machine_st.select_switch_on_term_index(addr, v, c, l, s)
+                }
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnConstant(hm)) => {
+                    let lit = self.machine_st.constant_to_literal(addr);
+                    hm.get(&lit).copied().unwrap_or(IndexingCodePtr::Fail)
+                }
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnStructure(hm)) => {
+                    self.machine_st.select_switch_on_structure_index(addr, hm)
+                }
+                IndexingLine::IndexedChoice(_) | IndexingLine::DynamicIndexedChoice(_) => {
+                    return false;
+                }
+            };
+
+            match offset {
+                IndexingCodePtr::Internal(o) => index += o,
+                _ => return true,
+            }
+        }
+    }
+
+    // restores the state saved by the choicepoint pushed by
+    // try_alternatives and returns its next alternative, popping the
+    // choicepoint if it's the last.
+    pub(super) fn retry_alternatives(&mut self) -> usize {
+        let b = self.machine_st.b;
+        let or_frame = self.machine_st.stack.index_or_frame(b);
+        let n = or_frame.prelude.num_cells - 1;
+
+        let old_tr = or_frame.prelude.tr;
+        let curr_tr = self.machine_st.tr;
+
+        for i in 0..n {
+            self.machine_st.registers[i+1] = or_frame[i];
+        }
+
+        let l = read_heap_cell!(or_frame[n],
+            (HeapCellValueTag::Lis, l) => {
+                l
+            }
+            _ => {
+                unreachable!()
+            }
+        );
+
+        self.unwind_trail(old_tr, curr_tr);
+
+        let p = cell_as_fixnum!(self.machine_st.heap[l]).get_num() as usize;
+        let rest = self.machine_st.heap[l+1];
+
+        if rest.get_tag() != HeapCellValueTag::Lis {
+            self.trust_epilogue(0);
+            self.machine_st.num_of_args = n;
+
+            return p;
+        }
+
+        let or_frame = self.machine_st.stack.index_or_frame_mut(b);
+
+        or_frame[n] = rest;
+
+        self.machine_st.num_of_args = n;
+        self.machine_st.e = or_frame.prelude.e;
+        self.machine_st.cp = or_frame.prelude.cp;
+
+        let target_h = or_frame.prelude.h;
+        let attr_var_queue_len = or_frame.prelude.attr_var_queue_len;
+
+        self.machine_st.tr = or_frame.prelude.tr;
+        self.machine_st.trail.truncate(self.machine_st.tr);
+
+        self.reset_attr_var_state(attr_var_queue_len);
+
+        self.machine_st.hb = target_h;
+        self.machine_st.heap.truncate(target_h);
+
+        self.machine_st.oip = 0;
+        self.machine_st.iip = 0;
+
+        p
+    }
+
     #[inline(always)]
     fn call_clause(&mut self, module_name: Atom, key: PredicateKey) -> CallResult {
         let (name, arity) = key;
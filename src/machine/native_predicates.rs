@@ -0,0 +1,423 @@
+use crate::atom_table::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::read::*;
+use crate::types::*;
+
+use indexmap::IndexMap;
+
+use std::fmt;
+
+/// The outcome of a call to a native predicate.
+pub enum NativeResult {
+    /// Succeed deterministically.
+    True,
+    /// Fail.
+    False,
+    /// Throw the given term, usually one built by the ISO error
+    /// constructors of `NativeCall`.
+    Throw(Value),
+    /// Succeed, leaving a choicepoint. On backtracking, the bindings
+    /// made by the call are undone and the redo callback is called in
+    /// its place with the same arguments.
+    Redo(NativeFn),
+}
+
+pub type NativeFn = Box<dyn FnMut(&mut NativeCall) -> NativeResult>;
+
+/// The arguments of a call to a native predicate, as seen by the
+/// Rust closure implementing it.
+pub struct NativeCall<'a> {
+    machine_st: &'a mut MachineState,
+    name: Atom,
+    args: Vec<HeapCellValue>,
+    // the argument that a value too large for the heap was unified
+    // with, which turns the result of the call into a resource error.
+    write_error: Option<HeapCellValue>,
+}
+
+impl<'a> NativeCall<'a> {
+    #[inline]
+    pub fn arity(&self) -> usize {
+        self.args.len()
+    }
+
+    /// The `i`th argument, counting from 0, with its bindings resolved.
+    pub fn arg(&self, i: usize) -> Value {
+        let var_names = IndexMap::new();
+        ValueReader::new(self.machine_st, &var_names).read(self.args[i])
+    }
+
+    #[inline]
+    pub fn is_var(&self, i: usize) -> bool {
+        let addr = self.machine_st.deref(self.args[i]);
+        self.machine_st.store(addr).is_var()
+    }
+
+    /// Unifies the `i`th argument with `value`. Returns false if
+    /// unification fails, after which the closure should return
+    /// `NativeResult::False`. If `value` can't be written to the heap,
+    /// false is returned too, and the call throws a resource error
+    /// whatever the closure returns.
+    pub fn unify(&mut self, i: usize, value: &Value) -> bool {
+        let term = value.to_term(self.machine_st);
+
+        let value_loc = match write_term_to_heap(
+            &term,
+            &mut self.machine_st.heap,
+            &mut self.machine_st.atom_tbl,
+        ) {
+            Ok(term_write_result) => term_write_result.heap_loc,
+            Err(_) => {
+                self.write_error.get_or_insert(self.args[i]);
+                return false;
+            }
+        };
+
+        unify_fn!(*self.machine_st, heap_loc_as_cell!(value_loc), self.args[i]);
+
+        if self.machine_st.fail {
+            self.machine_st.fail = false;
+            false
+        } else {
+            true
+        }
+    }
+
+    fn error(&self, err: Value) -> NativeResult {
+        let context = Value::structure(
+            "/",
+            vec![
+                Value::atom(self.name.as_str()),
+                Value::from(self.args.len() as i64),
+            ],
+        );
+
+        NativeResult::Throw(Value::structure("error", vec![err, context]))
+    }
+
+    pub fn instantiation_error(&self) -> NativeResult {
+        self.error(Value::atom("instantiation_error"))
+    }
+
+    pub fn type_error(&self, valid_type: &str, culprit: Value) -> NativeResult {
+        self.error(Value::structure("type_error", vec![Value::atom(valid_type), culprit]))
+    }
+
+    pub fn domain_error(&self, domain: &str, culprit: Value) -> NativeResult {
+        self.error(Value::structure("domain_error", vec![Value::atom(domain), culprit]))
+    }
+
+    pub fn representation_error(&self, flag: &str) -> NativeResult {
+        self.error(Value::structure("representation_error", vec![Value::atom(flag)]))
+    }
+}
+
+struct NativePredicate {
+    name: Atom,
+    f: NativeFn,
+}
+
+struct NativeRedo {
+    id: usize,
+    f: NativeFn,
+    // the choicepoint that calls f on backtracking, once
+    // '$native_choicepoint'/1 has recorded it.
+    b: Option<usize>,
+}
+
+/// The native predicates registered on a machine, and the redo
+/// callbacks of their pending choicepoints.
+#[derive(Default)]
+pub(crate) struct NativePredicates {
+    preds: Vec<NativePredicate>,
+    redos: Vec<Option<NativeRedo>>,
+}
+
+impl fmt::Debug for NativePredicates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativePredicates")
+            .field("preds", &self.preds.len())
+            .field("redos", &self.redos.len())
+            .finish()
+    }
+}
+
+impl NativePredicates {
+    /// The names of the registered predicates.
+    #[inline]
+    pub(crate) fn names(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.preds.iter().map(|pred| pred.name)
+    }
+
+    #[inline]
+    pub(crate) fn redos_len(&self) -> usize {
+        self.redos.len()
+    }
+
+    /// Drops the redo callbacks of choicepoints created after the
+    /// table held `len` of them, which have since been cut or
+    /// discarded.
+    #[inline]
+    pub(crate) fn truncate_redos(&mut self, len: usize) {
+        self.redos.truncate(len);
+    }
+
+    /// Drops the redo callbacks whose choicepoints lie above `b`, and
+    /// at `b` too if `inclusive`. Those choicepoints were cut or
+    /// backtracked over, so their callbacks can never be called. The
+    /// callbacks never tied to a choicepoint are dropped as well, since
+    /// a callback is tied right after the call that returned it, before
+    /// any other native call or choicepoint.
+    fn reclaim_redos(&mut self, b: usize, inclusive: bool) {
+        for entry in self.redos.iter_mut() {
+            let dead = match entry {
+                Some(NativeRedo { b: Some(redo_b), .. }) => {
+                    *redo_b > b || (inclusive && *redo_b == b)
+                }
+                Some(NativeRedo { b: None, .. }) => true,
+                None => false,
+            };
+
+            if dead {
+                *entry = None;
+            }
+        }
+
+        while let Some(None) = self.redos.last() {
+            self.redos.pop();
+        }
+    }
+}
+
+impl Machine {
+    /// Defines `module:name/arity` as a predicate implemented by `f`.
+    /// The predicate is dynamic, like those defined by `ffi:use_foreign_module/2`.
+    ///
+    /// ```ignore
+    /// machine.register_predicate("user", "succ_or_zero", 2, |call| {
+    ///     match call.arg(0) {
+    ///         Value::Integer(n) => {
+    ///             let succ = Value::Integer(n + 1);
+    ///             if call.unify(1, &succ) { NativeResult::True } else { NativeResult::False }
+    ///         }
+    ///         Value::Var(_) => call.instantiation_error(),
+    ///         culprit => call.type_error("integer", culprit),
+    ///     }
+    /// })?;
+    /// ```
+    pub fn register_predicate<F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        arity: usize,
+        f: F,
+    ) -> Result<(), Value>
+    where
+        F: FnMut(&mut NativeCall) -> NativeResult + 'static,
+    {
+        let id = self.native_predicates.preds.len();
+        let name_atom = self.machine_st.atom_tbl.build_with(name);
+
+        self.native_predicates.preds.push(NativePredicate {
+            name: name_atom,
+            f: Box::new(f),
+        });
+
+        let bindings = [
+            ("M", Value::atom(module)),
+            ("N", Value::atom(name)),
+            ("A", Value::from(arity as i64)),
+            ("Id", Value::from(id as i64)),
+        ];
+
+        let result = match self.run_query_with_bindings(
+            "'$toplevel':'$register_native_predicate'(M, N, A, Id)",
+            bindings,
+        )?.next() {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(err)) => Err(err),
+            None => Err(Value::structure(
+                "error",
+                vec![
+                    Value::structure(
+                        "domain_error",
+                        vec![
+                            Value::atom("native_predicate"),
+                            Value::structure(
+                                ":",
+                                vec![
+                                    Value::atom(module),
+                                    Value::structure(
+                                        "/",
+                                        vec![Value::atom(name), Value::from(arity as i64)],
+                                    ),
+                                ],
+                            ),
+                        ],
+                    ),
+                    Value::structure(
+                        "/",
+                        vec![Value::atom("register_predicate"), Value::from(4i64)],
+                    ),
+                ],
+            )),
+        };
+
+        if result.is_err() {
+            self.native_predicates.preds.pop();
+        }
+
+        result
+    }
+
+    fn native_args(&mut self, name: Atom) -> Result<Vec<HeapCellValue>, MachineStub> {
+        let args = self.machine_st.registers[2];
+        self.machine_st.try_from_list(args, || functor_stub(name, 3))
+    }
+
+    /// The name of the predicate registered under the id in register
+    /// 1. Registrations aren't part of saved states, so the id of a
+    /// restored clause may name no predicate.
+    fn native_predicate_name(&mut self) -> Result<(usize, Atom), MachineStub> {
+        let id_cell = self.deref_register(1);
+        let id = cell_as_fixnum!(id_cell).get_num() as usize;
+
+        match self.native_predicates.preds.get(id) {
+            Some(pred) => Ok((id, pred.name)),
+            None => {
+                let err = self.machine_st.existence_error(ExistenceError::NativePredicate(id_cell));
+                let stub = functor_stub(atom!("$call_native"), 3);
+
+                Err(self.machine_st.error_form(err, stub))
+            }
+        }
+    }
+
+    fn native_result(
+        &mut self,
+        id: usize,
+        name: Atom,
+        arity: usize,
+        write_error: Option<HeapCellValue>,
+        result: NativeResult,
+    ) {
+        if let Some(culprit) = write_error {
+            let err = self.machine_st.resource_error(culprit);
+            let stub = functor_stub(name, arity);
+            let err = self.machine_st.error_form(err, stub);
+
+            self.machine_st.throw_exception(err);
+            return;
+        }
+
+        match result {
+            NativeResult::True => {
+                let result = self.deref_register(3);
+                self.machine_st.unify_atom(atom!("true"), result);
+            }
+            NativeResult::False => {
+                self.machine_st.fail = true;
+            }
+            NativeResult::Throw(ball) => {
+                let term = ball.to_term(&mut self.machine_st);
+
+                match write_term_to_heap(&term, &mut self.machine_st.heap, &mut self.machine_st.atom_tbl) {
+                    Ok(term_write_result) => {
+                        self.machine_st.registers[1] = heap_loc_as_cell!(term_write_result.heap_loc);
+                        self.machine_st.set_ball();
+                        self.machine_st.unwind_stack();
+                    }
+                    Err(err) => {
+                        let err = self.machine_st.syntax_error(err);
+                        let stub = functor_stub(atom!("$call_native"), 3);
+                        let err = self.machine_st.error_form(err, stub);
+
+                        self.machine_st.throw_exception(err);
+                    }
+                }
+            }
+            NativeResult::Redo(f) => {
+                let redo = self.native_predicates.redos.len();
+                self.native_predicates.redos.push(Some(NativeRedo { id, f, b: None }));
+
+                let h = self.machine_st.heap.len();
+
+                self.machine_st.heap.push(atom_as_cell!(atom!("redo"), 1));
+                self.machine_st.heap.push(fixnum_as_cell!(Fixnum::build_with(redo as i64)));
+
+                let result = self.machine_st.registers[3];
+                unify_fn!(self.machine_st, str_loc_as_cell!(h), result);
+            }
+        }
+    }
+
+    pub(crate) fn call_native(&mut self) -> CallResult {
+        self.native_predicates.reclaim_redos(self.machine_st.b, false);
+
+        let (id, name) = self.native_predicate_name()?;
+        let args = self.native_args(name)?;
+        let arity = args.len();
+
+        let mut call = NativeCall { machine_st: &mut self.machine_st, name, args, write_error: None };
+        let result = (self.native_predicates.preds[id].f)(&mut call);
+        let write_error = call.write_error;
+
+        self.native_result(id, name, arity, write_error, result);
+        Ok(())
+    }
+
+    pub(crate) fn redo_native(&mut self) -> CallResult {
+        let redo = cell_as_fixnum!(self.deref_register(1)).get_num() as usize;
+
+        let NativeRedo { id, mut f, .. } =
+            match self.native_predicates.redos.get_mut(redo).and_then(Option::take) {
+                Some(entry) => entry,
+                None => {
+                    self.machine_st.fail = true;
+                    return Ok(());
+                }
+            };
+
+        // the choicepoint of the redo is gone by now, so this drops
+        // the entries of any choicepoints created after it.
+        self.native_predicates.reclaim_redos(self.machine_st.b, false);
+
+        let name = self.native_predicates.preds[id].name;
+        let args = self.native_args(name)?;
+        let arity = args.len();
+
+        let mut call = NativeCall { machine_st: &mut self.machine_st, name, args, write_error: None };
+        let result = f(&mut call);
+        let write_error = call.write_error;
+
+        self.native_result(id, name, arity, write_error, result);
+        Ok(())
+    }
+
+    /// Ties the redo callback `Redo` to the current choicepoint, which
+    /// `'$native_call_result'/2` creates to call it on backtracking.
+    pub(crate) fn native_choicepoint(&mut self) {
+        let redo = cell_as_fixnum!(self.deref_register(1)).get_num() as usize;
+        let b = self.machine_st.b;
+
+        let entry = self.native_predicates.redos.get_mut(redo).and_then(Option::take);
+
+        // a new choicepoint at b means the one previously there, and
+        // everything above it, is gone.
+        self.native_predicates.reclaim_redos(b, true);
+
+        if let Some(mut entry) = entry {
+            entry.b = Some(b);
+
+            if self.native_predicates.redos.len() <= redo {
+                self.native_predicates.redos.resize_with(redo + 1, || None);
+            }
+
+            self.native_predicates.redos[redo] = Some(entry);
+        }
+    }
+}
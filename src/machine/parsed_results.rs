@@ -0,0 +1,401 @@
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::machine_state::*;
+use crate::machine::streams::*;
+use crate::machine::partial_string::*;
+use crate::parser::ast::*;
+use crate::parser::dashu::{Integer, Rational};
+use crate::types::*;
+
+use indexmap::{IndexMap, IndexSet};
+use ordered_float::OrderedFloat;
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// A Prolog term copied off the heap, so that it stays valid after
+/// the machine backtracks over the bindings it was read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Value {
+    Integer(Integer),
+    Rational(Rational),
+    Float(OrderedFloat<f64>),
+    Atom(String),
+    String(String),
+    List(Vec<Value>),
+    Structure(String, Vec<Value>),
+    Var(String),
+}
+
+impl Value {
+    #[inline]
+    pub fn atom<S: Into<String>>(name: S) -> Self {
+        Value::Atom(name.into())
+    }
+
+    #[inline]
+    pub fn string<S: Into<String>>(string: S) -> Self {
+        Value::String(string.into())
+    }
+
+    #[inline]
+    pub fn var<S: Into<String>>(name: S) -> Self {
+        Value::Var(name.into())
+    }
+
+    #[inline]
+    pub fn structure<S: Into<String>>(name: S, args: Vec<Value>) -> Self {
+        Value::Structure(name.into(), args)
+    }
+
+    /// Converts the value to a parser `Term` so that it can be written
+    /// to the heap by `write_term_to_heap`. Variables of the same name
+    /// are shared, and `_` is anonymous.
+    pub(crate) fn to_term(&self, machine_st: &mut MachineState) -> Term {
+        match self {
+            Value::Integer(n) => {
+                let literal = match i64::try_from(n.clone()) {
+                    Ok(n) => fixnum!(Literal, n, &mut machine_st.arena),
+                    Err(_) => Literal::Integer(arena_alloc!(n.clone(), &mut machine_st.arena)),
+                };
+
+                Term::Literal(Cell::default(), literal)
+            }
+            Value::Rational(r) => {
+                Term::Literal(
+                    Cell::default(),
+                    Literal::Rational(arena_alloc!(r.clone(), &mut machine_st.arena)),
+                )
+            }
+            Value::Float(f) => {
+                Term::Literal(
+                    Cell::default(),
+                    Literal::from(float_alloc!(f.into_inner(), machine_st.arena)),
+                )
+            }
+            Value::Atom(name) => {
+                let atom = machine_st.atom_tbl.build_with(name);
+                Term::Literal(Cell::default(), Literal::Atom(atom))
+            }
+            Value::String(string) if string.is_empty() => {
+                Term::Literal(Cell::default(), Literal::Atom(atom!("[]")))
+            }
+            Value::String(string) => {
+                let atom = machine_st.atom_tbl.build_with(string);
+                Term::CompleteString(Cell::default(), atom)
+            }
+            Value::List(elems) => {
+                let tail = Term::Literal(Cell::default(), Literal::Atom(atom!("[]")));
+
+                elems.iter().rev().fold(tail, |tail, head| {
+                    Term::Cons(Cell::default(), Box::new(head.to_term(machine_st)), Box::new(tail))
+                })
+            }
+            Value::Structure(name, args) if name == "." && args.len() == 2 => {
+                Term::Cons(
+                    Cell::default(),
+                    Box::new(args[0].to_term(machine_st)),
+                    Box::new(args[1].to_term(machine_st)),
+                )
+            }
+            Value::Structure(name, args) => {
+                let name = machine_st.atom_tbl.build_with(name);
+
+                if args.is_empty() {
+                    Term::Literal(Cell::default(), Literal::Atom(name))
+                } else {
+                    let args = args.iter().map(|arg| arg.to_term(machine_st)).collect();
+                    Term::Clause(Cell::default(), name, args)
+                }
+            }
+            Value::Var(name) if name == "_" => {
+                Term::AnonVar
+            }
+            Value::Var(name) => {
+                Term::Var(Cell::default(), VarPtr::from(name.as_str()))
+            }
+        }
+    }
+}
+
+impl From<i64> for Value {
+    #[inline]
+    fn from(n: i64) -> Self {
+        Value::Integer(Integer::from(n))
+    }
+}
+
+impl From<Integer> for Value {
+    #[inline]
+    fn from(n: Integer) -> Self {
+        Value::Integer(n)
+    }
+}
+
+impl From<Rational> for Value {
+    #[inline]
+    fn from(r: Rational) -> Self {
+        Value::Rational(r)
+    }
+}
+
+impl From<f64> for Value {
+    #[inline]
+    fn from(f: f64) -> Self {
+        Value::Float(OrderedFloat(f))
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    #[inline]
+    fn from(elems: Vec<T>) -> Self {
+        Value::List(elems.into_iter().map(Into::into).collect())
+    }
+}
+
+/// A single solution to a query run by `Machine::run_query`. Only the
+/// named, non-anonymous variables of the query appear in `bindings`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueryAnswer {
+    pub bindings: BTreeMap<String, Value>,
+}
+
+impl QueryAnswer {
+    #[inline]
+    pub fn get(&self, var: &str) -> Option<&Value> {
+        self.bindings.get(var)
+    }
+}
+
+pub(crate) struct ValueReader<'a> {
+    machine_st: &'a MachineState,
+    var_names: &'a IndexMap<HeapCellValue, String>,
+    // Str and Lis locations of the compound terms enclosing the
+    // focus. Revisiting one of them means the term is cyclic.
+    path: IndexSet<usize>,
+}
+
+impl<'a> ValueReader<'a> {
+    #[inline]
+    pub(crate) fn new(
+        machine_st: &'a MachineState,
+        var_names: &'a IndexMap<HeapCellValue, String>,
+    ) -> Self {
+        ValueReader { machine_st, var_names, path: IndexSet::new() }
+    }
+
+    fn var_name(&self, var: HeapCellValue) -> String {
+        match self.var_names.get(&var) {
+            Some(name) => name.clone(),
+            None => {
+                read_heap_cell!(var,
+                    (HeapCellValueTag::StackVar, s) => {
+                        format!("_s{}", s)
+                    }
+                    _ => {
+                        format!("_{}", var.get_value())
+                    }
+                )
+            }
+        }
+    }
+
+    pub(crate) fn read(&mut self, cell: HeapCellValue) -> Value {
+        let addr = self.machine_st.deref(cell);
+        let value = self.machine_st.store(addr);
+
+        if value.is_var() {
+            return Value::Var(self.var_name(addr));
+        }
+
+        match Number::try_from(value) {
+            Ok(Number::Fixnum(n)) => return Value::Integer(Integer::from(n.get_num())),
+            Ok(Number::Integer(n)) => return Value::Integer((*n).clone()),
+            Ok(Number::Rational(r)) => return Value::Rational((*r).clone()),
+            Ok(Number::Float(f)) => return Value::Float(f),
+            Err(_) => {}
+        }
+
+        read_heap_cell!(value,
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                if name == atom!("[]") {
+                    Value::List(vec![])
+                } else {
+                    Value::Atom(name.as_str().to_string())
+                }
+            }
+            (HeapCellValueTag::Char, c) => {
+                Value::Atom(c.to_string())
+            }
+            (HeapCellValueTag::CStr, cstr_atom) => {
+                Value::String(cstr_atom.as_str().to_string())
+            }
+            (HeapCellValueTag::Str, s) => {
+                if self.path.contains(&s) {
+                    return Value::Var(format!("_S{}", s));
+                }
+
+                let (name, arity) = cell_as_atom_cell!(self.machine_st.heap[s])
+                    .get_name_and_arity();
+
+                self.path.insert(s);
+
+                let args = (s + 1 ..= s + arity)
+                    .map(|idx| self.read(self.machine_st.heap[idx]))
+                    .collect();
+
+                self.path.pop();
+
+                Value::Structure(name.as_str().to_string(), args)
+            }
+            (HeapCellValueTag::Lis | HeapCellValueTag::PStrLoc) => {
+                self.read_list(value)
+            }
+            (HeapCellValueTag::Cons, c) => {
+                match_untyped_arena_ptr!(c,
+                    (ArenaHeaderTag::Stream, stream) => {
+                        stream_value(stream)
+                    }
+                    (ArenaHeaderTag::OssifiedOpDir, _op_dir) => {
+                        Value::Atom("$ossified_op_dir".to_string())
+                    }
+                    (ArenaHeaderTag::IndexPtr, _index_ptr) => {
+                        Value::Atom("$index_ptr".to_string())
+                    }
+                    _ => {
+                        Value::Atom("$dropped_value".to_string())
+                    }
+                )
+            }
+            _ => {
+                unreachable!()
+            }
+        )
+    }
+
+    // reads the spine of a list or partial string iteratively so that
+    // long lists don't exhaust the Rust stack. Only lists made up
+    // entirely of partial strings are read as strings.
+    fn read_list(&mut self, mut value: HeapCellValue) -> Value {
+        let path_len = self.path.len();
+        let mut elems = vec![];
+        let mut is_string = true;
+
+        let tail = loop {
+            read_heap_cell!(value,
+                (HeapCellValueTag::Lis, l) => {
+                    if self.path.contains(&l) {
+                        break Value::Var(format!("_S{}", l));
+                    }
+
+                    self.path.insert(l);
+                    is_string = false;
+                    elems.push(self.read(self.machine_st.heap[l]));
+                }
+                (HeapCellValueTag::PStrLoc, h) => {
+                    let (h, offset) = pstr_loc_and_offset(&self.machine_st.heap, h);
+                    let offset = offset.get_num() as usize;
+
+                    if self.path.contains(&h) {
+                        break Value::Var(format!("_S{}", h));
+                    }
+
+                    self.path.insert(h);
+
+                    read_heap_cell!(self.machine_st.heap[h],
+                        (HeapCellValueTag::PStr, pstr_atom) => {
+                            let pstr = PartialString::from(pstr_atom);
+
+                            elems.extend(
+                                pstr.as_str_from(offset).chars().map(|c| Value::Atom(c.to_string()))
+                            );
+                        }
+                        (HeapCellValueTag::CStr, cstr_atom) => {
+                            let cstr = PartialString::from(cstr_atom);
+
+                            elems.extend(
+                                cstr.as_str_from(offset).chars().map(|c| Value::Atom(c.to_string()))
+                            );
+
+                            break Value::List(vec![]);
+                        }
+                        _ => {
+                            unreachable!()
+                        }
+                    );
+
+                    // the tail of a PStr is stored immediately after it.
+                    let addr = self.machine_st.deref(self.machine_st.heap[h + 1]);
+                    value = self.machine_st.store(addr);
+
+                    if value.is_var() {
+                        break Value::Var(self.var_name(addr));
+                    }
+
+                    continue;
+                }
+                (HeapCellValueTag::CStr, cstr_atom) => {
+                    elems.extend(cstr_atom.as_str().chars().map(|c| Value::Atom(c.to_string())));
+                    break Value::List(vec![]);
+                }
+                _ => {
+                    break self.read(value);
+                }
+            );
+
+            let l = *self.path.last().unwrap();
+            let addr = self.machine_st.deref(self.machine_st.heap[l + 1]);
+
+            value = self.machine_st.store(addr);
+
+            if value.is_var() {
+                break Value::Var(self.var_name(addr));
+            }
+        };
+
+        self.path.truncate(path_len);
+
+        match tail {
+            Value::List(tail_elems) if is_string && tail_elems.is_empty() => {
+                let mut string = String::new();
+
+                for elem in elems {
+                    if let Value::Atom(c) = elem {
+                        string += &c;
+                    }
+                }
+
+                Value::String(string)
+            }
+            Value::List(tail_elems) => {
+                elems.extend(tail_elems);
+                Value::List(elems)
+            }
+            Value::String(s) => {
+                elems.extend(s.chars().map(|c| Value::Atom(c.to_string())));
+                Value::List(elems)
+            }
+            tail => {
+                // partial lists are represented by nested '.'/2 terms.
+                elems.into_iter().rev().fold(tail, |tail, head| {
+                    Value::Structure(".".to_string(), vec![head, tail])
+                })
+            }
+        }
+    }
+}
+
+fn stream_value(stream: Stream) -> Value {
+    if let Some(alias) = stream.options().get_alias() {
+        Value::Atom(alias.as_str().to_string())
+    } else if stream.is_stdout() || stream.is_stdin() {
+        Value::Atom("user".to_string())
+    } else {
+        Value::Structure(
+            "$stream".to_string(),
+            vec![Value::Integer(Integer::from(stream.as_ptr() as usize))],
+        )
+    }
+}
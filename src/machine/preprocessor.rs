@@ -7,7 +7,7 @@
 use crate::machine::machine_errors::*;
 use crate::parser::ast::*;
 
-use indexmap::IndexSet;
+use indexmap::IndexMap;
 
 use std::cell::Cell;
 use std::convert::TryFrom;
@@ -131,6 +131,51 @@ pub(super) fn setup_module_export_list(
     }
 }
 
+fn setup_module_imports(
+    mut import_list: Term,
+    atom_tbl: &mut AtomTable,
+) -> Result<IndexMap<ModuleExport, Option<Atom>>, CompilationError> {
+    let mut imports = IndexMap::new();
+
+    while let Term::Cons(_, t1, t2) = import_list {
+        match *t1 {
+            Term::Clause(_, name, mut terms) if name == atom!("as") && terms.len() == 2 => {
+                let new_name = match terms.pop().unwrap() {
+                    Term::Literal(_, Literal::Atom(new_name)) => Some(new_name),
+                    _ => None,
+                }.ok_or(CompilationError::InvalidModuleExport)?;
+
+                let key = setup_predicate_indicator(&mut terms.pop().unwrap())?;
+                imports.insert(ModuleExport::PredicateKey(key), Some(new_name));
+            }
+            term => {
+                imports.insert(setup_module_export(term, atom_tbl)?, None);
+            }
+        }
+
+        import_list = *t2;
+    }
+
+    if let Term::Literal(_, Literal::Atom(atom!("[]"))) = import_list {
+        Ok(imports)
+    } else {
+        Err(CompilationError::InvalidModuleDecl)
+    }
+}
+
+pub(super) fn setup_import_list(
+    import_list: Term,
+    atom_tbl: &mut AtomTable,
+) -> Result<ImportList, CompilationError> {
+    match import_list {
+        Term::Clause(_, name, mut terms) if name == atom!("except") && terms.len() == 1 => {
+            let imports = setup_module_imports(terms.pop().unwrap(), atom_tbl)?;
+            Ok(ImportList::Except(imports))
+        }
+        import_list => Ok(ImportList::Only(setup_module_imports(import_list, atom_tbl)?)),
+    }
+}
+
 fn setup_module_decl(
     mut terms: Vec<Term>,
     atom_tbl: &mut AtomTable,
@@ -163,13 +208,13 @@ fn setup_use_module_decl(mut terms: Vec<Term>) -> Result<ModuleSource, Compilati
     }
 }
 
-type UseModuleExport = (ModuleSource, IndexSet<ModuleExport>);
+type UseModuleExport = (ModuleSource, ImportList);
 
 fn setup_qualified_import(
     mut terms: Vec<Term>,
     atom_tbl: &mut AtomTable,
 ) -> Result<UseModuleExport, CompilationError> {
-    let mut export_list = terms.pop().unwrap();
+    let import_list = terms.pop().unwrap();
     let module_src = match terms.pop().unwrap() {
         Term::Clause(_, name, mut terms)
             if name == atom!("library") && terms.len() == 1 =>
@@ -183,18 +228,7 @@ fn setup_qualified_import(
         _ => Err(CompilationError::InvalidUseModuleDecl),
     }?;
 
-    let mut exports = IndexSet::new();
-
-    while let Term::Cons(_, t1, t2) = export_list {
-        exports.insert(setup_module_export(*t1, atom_tbl)?);
-        export_list = *t2;
-    }
-
-    if let Term::Literal(_, Literal::Atom(atom!("[]"))) = export_list {
-        Ok((module_src, exports))
-    } else {
-        Err(CompilationError::InvalidModuleDecl)
-    }
+    Ok((module_src, setup_import_list(import_list, atom_tbl)?))
 }
 
 /*
@@ -324,6 +358,10 @@ pub(super) fn setup_declaration<'a, LS: LoadState<'a>>(
                 let atom_tbl = &mut LS::machine_st(&mut loader.payload).atom_tbl;
                 Ok(Declaration::Op(setup_op_decl(terms, atom_tbl)?))
             }
+            (atom!("fact_table"), 1) => {
+                let (name, arity) = setup_predicate_indicator(&mut terms.pop().unwrap())?;
+                Ok(Declaration::FactTable(name, arity))
+            }
             (atom!("non_counted_backtracking"), 1) => {
                 let (name, arity) = setup_predicate_indicator(&mut terms.pop().unwrap())?;
                 Ok(Declaration::NonCountedBacktracking(name, arity))
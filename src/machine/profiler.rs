@@ -0,0 +1,351 @@
+//! The execution profiler of library(profile).
+//!
+//! While the profiler is on, the machine counts the calls of each
+//! predicate by its code index, the calls made from each call site,
+//! and the choice points resumed at each code location. Every
+//! `SAMPLE_PERIOD` calls, the CPU time spent since the last sample is
+//! charged to the call stack of the moment, read off the continuation
+//! pointers of the environment frames. Code locations are resolved to
+//! the predicates whose clauses hold them only as the profile is
+//! reported.
+
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::partial_string::*;
+use crate::machine::{Machine, JIT_RETRY_LOC};
+use crate::parser::ast::*;
+use crate::types::*;
+
+use cpu_time::ProcessTime;
+use fxhash::FxHashMap;
+use indexmap::{IndexMap, IndexSet};
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::time::Duration;
+
+const SAMPLE_PERIOD: u32 = 1000;
+
+// the most frames of a sampled call stack.
+const MAX_SAMPLE_DEPTH: usize = 512;
+
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    active: bool,
+    // the calls of each predicate, by its code index.
+    calls: FxHashMap<usize, u64>,
+    // the calls made from each call site.
+    call_sites: FxHashMap<usize, u64>,
+    // the choice points resumed at each code location.
+    redos: FxHashMap<usize, u64>,
+    // the CPU time charged to each call stack, from the innermost
+    // code location out.
+    samples: FxHashMap<Vec<usize>, Duration>,
+    countdown: u32,
+    last_sample: Duration,
+    /// The file the folded call stacks are written to as the machine
+    /// halts, set by the `--profile` option.
+    pub(crate) output: Option<Atom>,
+}
+
+impl Profiler {
+    #[inline(always)]
+    pub(crate) fn active(&self) -> bool {
+        self.active
+    }
+
+    fn start(&mut self) {
+        *self = Profiler {
+            active: true,
+            countdown: SAMPLE_PERIOD,
+            last_sample: ProcessTime::now().as_duration(),
+            output: self.output.take(),
+            ..Profiler::default()
+        };
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProfileEntry {
+    calls: u64,
+    redos: u64,
+    inferences: u64,
+    self_time: Duration,
+    total_time: Duration,
+}
+
+type ProfiledPredicate = (Atom, PredicateKey);
+
+fn predicate_name((module_name, (name, arity)): ProfiledPredicate) -> String {
+    format!("{}:{}/{}", module_name.as_str(), name.as_str(), arity)
+}
+
+impl MachineState {
+    // charges the CPU time since the last sample to the call stack.
+    fn profile_sample(&mut self) {
+        let now = ProcessTime::now().as_duration();
+        let elapsed = now.saturating_sub(self.profiler.last_sample);
+
+        self.profiler.last_sample = now;
+
+        let stack = self.call_stack_locs(MAX_SAMPLE_DEPTH);
+
+        *self.profiler.samples.entry(stack).or_default() += elapsed;
+    }
+
+    /// Counts the resumption of the choice point just backtracked to.
+    pub(crate) fn profile_redo(&mut self) {
+        let p = if self.p == JIT_RETRY_LOC {
+            // the alternatives of a just-in-time index are listed at
+            // the end of its choice point.
+            let or_frame = self.stack.index_or_frame(self.b);
+            let n = or_frame.prelude.num_cells - 1;
+
+            read_heap_cell!(or_frame[n],
+                (HeapCellValueTag::Lis, l) => {
+                    cell_as_fixnum!(self.heap[l]).get_num() as usize
+                }
+                _ => {
+                    return;
+                }
+            )
+        } else {
+            self.p
+        };
+
+        *self.profiler.redos.entry(p).or_insert(0) += 1;
+    }
+}
+
+impl Machine {
+    /// Counts the call of the predicate at `idx` from the call site at
+    /// P, and samples the call stack once in `SAMPLE_PERIOD` calls.
+    pub(super) fn profile_call(&mut self, idx: IndexPtr) {
+        if !matches!(idx.tag(), IndexPtrTag::DynamicIndex | IndexPtrTag::Index) {
+            return;
+        }
+
+        let p = self.machine_st.p;
+        let profiler = &mut self.machine_st.profiler;
+
+        *profiler.calls.entry(idx.p() as usize).or_insert(0) += 1;
+        *profiler.call_sites.entry(p).or_insert(0) += 1;
+
+        profiler.countdown -= 1;
+
+        if profiler.countdown == 0 {
+            profiler.countdown = SAMPLE_PERIOD;
+            self.machine_st.profile_sample();
+        }
+    }
+
+    // the predicates of the clauses starting at each code location.
+    fn profiled_predicate_locs(&self) -> BTreeMap<usize, ProfiledPredicate> {
+        self.indices
+            .code_locs()
+            .into_iter()
+            .map(|(p, code_loc)| (p, (code_loc.module_name, code_loc.key)))
+            .collect()
+    }
+
+    fn profile_entries(&self) -> (IndexMap<ProfiledPredicate, ProfileEntry>, Duration) {
+        let locs = self.profiled_predicate_locs();
+        let resolve = |p: usize| locs.range(..=p).next_back().map(|(_, &predicate)| predicate);
+
+        let profiler = &self.machine_st.profiler;
+        let mut entries: IndexMap<ProfiledPredicate, ProfileEntry> = IndexMap::new();
+
+        for (&p, &calls) in &profiler.calls {
+            if let Some(predicate) = resolve(p) {
+                entries.entry(predicate).or_default().calls += calls;
+            }
+        }
+
+        for (&p, &redos) in &profiler.redos {
+            if let Some(predicate) = resolve(p) {
+                entries.entry(predicate).or_default().redos += redos;
+            }
+        }
+
+        for (&p, &inferences) in &profiler.call_sites {
+            if let Some(predicate) = resolve(p) {
+                entries.entry(predicate).or_default().inferences += inferences;
+            }
+        }
+
+        let mut sampled_time = Duration::ZERO;
+
+        for (stack, &time) in &profiler.samples {
+            let mut predicates = stack.iter().filter_map(|&p| resolve(p)).collect::<Vec<_>>();
+            predicates.dedup();
+
+            sampled_time += time;
+
+            if let Some(&leaf) = predicates.first() {
+                entries.entry(leaf).or_default().self_time += time;
+            }
+
+            for predicate in predicates.into_iter().collect::<IndexSet<_>>() {
+                entries.entry(predicate).or_default().total_time += time;
+            }
+        }
+
+        (entries, sampled_time)
+    }
+
+    fn profile_report(&self, top: Option<usize>) -> String {
+        let (entries, sampled_time) = self.profile_entries();
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+
+        entries.sort_by(|(_, e1), (_, e2)| {
+            e2.self_time
+                .cmp(&e1.self_time)
+                .then(e2.calls.cmp(&e1.calls))
+                .then(e2.inferences.cmp(&e1.inferences))
+        });
+
+        let percentage = |time: Duration| {
+            if sampled_time.is_zero() {
+                0.0
+            } else {
+                100.0 * time.as_secs_f64() / sampled_time.as_secs_f64()
+            }
+        };
+
+        let inferences: u64 = entries.iter().map(|(_, entry)| entry.inferences).sum();
+        let mut report = String::new();
+
+        let _ = writeln!(
+            report,
+            "% {} inferences, {:.3} CPU seconds sampled",
+            inferences,
+            sampled_time.as_secs_f64(),
+        );
+
+        let _ = writeln!(
+            report,
+            "{:<40} {:>10} {:>10} {:>12} {:>7} {:>7}",
+            "Predicate", "Calls", "Redos", "Inferences", "Self%", "Total%",
+        );
+
+        for (predicate, entry) in entries.iter().take(top.unwrap_or(entries.len())) {
+            let _ = writeln!(
+                report,
+                "{:<40} {:>10} {:>10} {:>12} {:>7.1} {:>7.1}",
+                predicate_name(*predicate),
+                entry.calls,
+                entry.redos,
+                entry.inferences,
+                percentage(entry.self_time),
+                percentage(entry.total_time),
+            );
+        }
+
+        report
+    }
+
+    // the sampled call stacks in the folded format of flamegraph
+    // tools: the frames from the outermost in, separated by
+    // semicolons, followed by the microseconds charged to the stack.
+    fn folded_call_stacks(&self) -> String {
+        let locs = self.profiled_predicate_locs();
+        let resolve = |p: usize| locs.range(..=p).next_back().map(|(_, &predicate)| predicate);
+
+        let mut stacks: IndexMap<String, Duration> = IndexMap::new();
+
+        for (stack, &time) in &self.machine_st.profiler.samples {
+            let mut predicates = stack.iter().filter_map(|&p| resolve(p)).collect::<Vec<_>>();
+
+            predicates.dedup();
+
+            if predicates.is_empty() {
+                continue;
+            }
+
+            let frames = predicates
+                .into_iter()
+                .rev()
+                .map(predicate_name)
+                .collect::<Vec<_>>()
+                .join(";");
+
+            *stacks.entry(frames).or_default() += time;
+        }
+
+        let mut folded = String::new();
+
+        for (frames, time) in stacks {
+            let _ = writeln!(folded, "{} {}", frames, time.as_micros());
+        }
+
+        folded
+    }
+
+    pub(crate) fn profiler_start(&mut self) {
+        self.machine_st.profiler.start();
+    }
+
+    pub(crate) fn profiler_stop(&mut self) {
+        if self.machine_st.profiler.active {
+            self.machine_st.profile_sample();
+            self.machine_st.profiler.active = false;
+        }
+    }
+
+    /// Unifies the second register with the report of the last profile
+    /// as a string, listing the number of predicates in the first
+    /// register, or all of them if it's `all`.
+    pub(crate) fn profiler_report(&mut self) {
+        let top = match Number::try_from(self.deref_register(1)) {
+            Ok(Number::Fixnum(n)) => Some(n.get_num().max(0) as usize),
+            _ => None,
+        };
+
+        let report = self.profile_report(top);
+        let report = put_complete_string(
+            &mut self.machine_st.heap,
+            &report,
+            &mut self.machine_st.atom_tbl,
+        );
+
+        unify!(self.machine_st, self.machine_st.registers[2], report);
+    }
+
+    /// Writes the call stacks sampled by the last profile to the file
+    /// named by the atom in the first register.
+    pub(crate) fn profiler_write_folded(&mut self) -> CallResult {
+        let file = self.deref_register(1);
+        let path = cell_as_atom!(file);
+
+        match fs::write(path.as_str(), self.folded_call_stacks()) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.machine_st.open_permission_error(file, atom!("profile"), 2)),
+        }
+    }
+
+    /// Profiles the rest of the run, for the `--profile` option, whose
+    /// file is named by the atom in the first register.
+    pub(crate) fn profile_at_halt(&mut self) {
+        let file = cell_as_atom!(self.deref_register(1));
+
+        self.machine_st.profiler.start();
+        self.machine_st.profiler.output = Some(file);
+    }
+
+    /// Reports the profile begun by the `--profile` option on standard
+    /// error as the machine halts, and writes its call stacks.
+    pub(crate) fn write_profile_at_halt(&mut self) {
+        if let Some(file) = self.machine_st.profiler.output.take() {
+            self.profiler_stop();
+
+            eprint!("{}", self.profile_report(None));
+
+            if let Err(err) = fs::write(file.as_str(), self.folded_call_stacks()) {
+                eprintln!("% could not write the profile to {}: {}", file.as_str(), err);
+            }
+        }
+    }
+}
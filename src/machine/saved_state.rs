@@ -0,0 +1,1453 @@
+//! Saved states: the code, indices and flags of a machine written to
+//! a file by `save_program/2`, from which a new machine is restored
+//! without reading or compiling any Prolog source.
+//!
+//! Atoms and code indices are written once and referred to by their
+//! ordinal afterward, so atoms are rebuilt in the atom table of the
+//! restoring machine and code indices shared between code directories
+//! stay shared.
+//!
+//! The files loaded from source are saved with the times they were
+//! modified, so `make/0` reloads them in a restored machine once they
+//! change. Native predicates are closures of the embedding program,
+//! which can't be written, so a machine with native predicates
+//! registered can't be saved at all.
+
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::loader::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::streams::*;
+use crate::machine::{LoadedFile, Machine};
+use crate::parser::ast::*;
+use crate::parser::dashu::integer::UBig;
+use crate::parser::dashu::{Integer, Rational};
+use crate::types::*;
+
+use fxhash::FxBuildHasher;
+use indexmap::{IndexMap, IndexSet};
+use ordered_float::OrderedFloat;
+use tokio::runtime::Runtime;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const MAGIC: &[u8; 8] = b"SCRYERQS";
+const FORMAT_VERSION: u64 = 5;
+
+// an executable written by qsave_program/2 is the runtime followed by
+// the saved state and a trailer of the state's length and
+// EXECUTABLE_MAGIC.
+const EXECUTABLE_MAGIC: &[u8; 8] = b"SCRYEREX";
+const TRAILER_LEN: u64 = 16;
+
+/// The offset and length of the state embedded in the executable `exe`
+/// by `qsave_program/2`, or `None` if it has none.
+///
+/// The trailer is found with a single seek from the end of `exe`, and
+/// nothing else is read from it unless the trailer ends with
+/// `EXECUTABLE_MAGIC`. An executable too short to hold a trailer or
+/// that can't be read has no embedded state.
+pub(crate) fn embedded_state(exe: &mut File) -> io::Result<Option<(u64, u64)>> {
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+
+    let trailer_offset = match exe.seek(SeekFrom::End(-(TRAILER_LEN as i64))) {
+        Ok(offset) => offset,
+        Err(_) => return Ok(None),
+    };
+
+    if exe.read_exact(&mut trailer).is_err() || &trailer[8..] != EXECUTABLE_MAGIC {
+        return Ok(None);
+    }
+
+    let state_len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+
+    match trailer_offset.checked_sub(state_len) {
+        Some(offset) => Ok(Some((offset, state_len))),
+        None => Err(invalid_state("truncated executable")),
+    }
+}
+
+pub(crate) fn invalid_state(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+pub(crate) struct StateWriter<'a> {
+    out: &'a mut dyn Write,
+    atoms: IndexSet<Atom, FxBuildHasher>,
+    code_indices: IndexSet<*const IndexPtr, FxBuildHasher>,
+}
+
+impl<'a> StateWriter<'a> {
+    fn new(out: &'a mut dyn Write) -> Self {
+        StateWriter {
+            out,
+            atoms: IndexSet::with_hasher(FxBuildHasher::default()),
+            code_indices: IndexSet::with_hasher(FxBuildHasher::default()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn write_u8(&mut self, n: u8) -> io::Result<()> {
+        self.out.write_all(&[n])
+    }
+
+    #[inline]
+    pub(crate) fn write_u64(&mut self, n: u64) -> io::Result<()> {
+        self.out.write_all(&n.to_le_bytes())
+    }
+
+    #[inline]
+    pub(crate) fn write_usize(&mut self, n: usize) -> io::Result<()> {
+        self.write_u64(n as u64)
+    }
+
+    pub(crate) fn write_str(&mut self, string: &str) -> io::Result<()> {
+        self.write_usize(string.len())?;
+        self.out.write_all(string.as_bytes())
+    }
+
+    // an atom is written in full the first time it is met, and by its
+    // ordinal after that.
+    pub(crate) fn write_atom(&mut self, atom: Atom) -> io::Result<()> {
+        let (ordinal, is_new) = self.atoms.insert_full(atom);
+
+        self.write_usize(ordinal)?;
+
+        if is_new {
+            self.write_str(atom.as_str())?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn write_code_index(&mut self, code_index: CodeIndex) -> io::Result<()> {
+        let (ordinal, is_new) = self.code_indices.insert_full(code_index.as_ptr());
+
+        self.write_usize(ordinal)?;
+
+        if is_new {
+            self.write_u64(u64::from_le_bytes(code_index.get().into_bytes()))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    input: &'a mut dyn Read,
+    atom_tbl: &'a mut AtomTable,
+    arena: &'a mut Arena,
+    atoms: Vec<Atom>,
+    code_indices: Vec<CodeIndex>,
+}
+
+impl<'a> StateReader<'a> {
+    fn new(input: &'a mut dyn Read, machine_st: &'a mut MachineState) -> Self {
+        StateReader {
+            input,
+            atom_tbl: &mut machine_st.atom_tbl,
+            arena: &mut machine_st.arena,
+            atoms: vec![],
+            code_indices: vec![],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn arena(&mut self) -> &mut Arena {
+        self.arena
+    }
+
+    pub(crate) fn read_u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0u8; 1];
+        self.input.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    pub(crate) fn read_u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        self.input.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    #[inline]
+    pub(crate) fn read_usize(&mut self) -> io::Result<usize> {
+        self.read_u64().map(|n| n as usize)
+    }
+
+    pub(crate) fn read_string(&mut self) -> io::Result<String> {
+        let len = self.read_usize()?;
+        let mut buf = vec![];
+
+        self.input.by_ref().take(len as u64).read_to_end(&mut buf)?;
+
+        if buf.len() < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        String::from_utf8(buf).map_err(|_| invalid_state("malformed string"))
+    }
+
+    pub(crate) fn read_atom(&mut self) -> io::Result<Atom> {
+        let ordinal = self.read_usize()?;
+
+        if ordinal < self.atoms.len() {
+            Ok(self.atoms[ordinal])
+        } else if ordinal == self.atoms.len() {
+            let string = self.read_string()?;
+            let atom = self.atom_tbl.build_with(&string);
+
+            self.atoms.push(atom);
+            Ok(atom)
+        } else {
+            Err(invalid_state("undefined atom"))
+        }
+    }
+
+    pub(crate) fn read_code_index(&mut self) -> io::Result<CodeIndex> {
+        let ordinal = self.read_usize()?;
+
+        if ordinal < self.code_indices.len() {
+            Ok(self.code_indices[ordinal])
+        } else if ordinal == self.code_indices.len() {
+            let ptr = IndexPtr::from_bytes(self.read_u64()?.to_le_bytes());
+            let code_index = CodeIndex::new(ptr, self.arena);
+
+            self.code_indices.push(code_index);
+            Ok(code_index)
+        } else {
+            Err(invalid_state("undefined code index"))
+        }
+    }
+}
+
+/// A value that can be written to a saved state and read back into a
+/// restored machine.
+pub(crate) trait Persist: Sized {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()>;
+    fn restore(reader: &mut StateReader) -> io::Result<Self>;
+}
+
+impl Persist for usize {
+    #[inline]
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(*self)
+    }
+
+    #[inline]
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        reader.read_usize()
+    }
+}
+
+impl Persist for bool {
+    #[inline]
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_u8(*self as u8)
+    }
+
+    #[inline]
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(reader.read_u8()? != 0)
+    }
+}
+
+impl Persist for Atom {
+    #[inline]
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_atom(*self)
+    }
+
+    #[inline]
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        reader.read_atom()
+    }
+}
+
+impl Persist for CodeIndex {
+    #[inline]
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_code_index(*self)
+    }
+
+    #[inline]
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        reader.read_code_index()
+    }
+}
+
+impl<T: Persist> Persist for Option<T> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            Some(value) => {
+                writer.write_u8(1)?;
+                value.save(writer)
+            }
+            None => writer.write_u8(0),
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(T::restore(reader)?)),
+        }
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        self.0.save(writer)?;
+        self.1.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let a = A::restore(reader)?;
+        let b = B::restore(reader)?;
+
+        Ok((a, b))
+    }
+}
+
+impl<T: Persist> Persist for Vec<T> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(self.len())?;
+
+        for value in self {
+            value.save(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let len = reader.read_usize()?;
+        (0 .. len).map(|_| T::restore(reader)).collect()
+    }
+}
+
+impl<T: Persist> Persist for VecDeque<T> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(self.len())?;
+
+        for value in self {
+            value.save(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let len = reader.read_usize()?;
+        (0 .. len).map(|_| T::restore(reader)).collect()
+    }
+}
+
+impl<K: Persist + Hash + Eq, V: Persist> Persist for IndexMap<K, V, FxBuildHasher> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(self.len())?;
+
+        for (key, value) in self {
+            key.save(writer)?;
+            value.save(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let len = reader.read_usize()?;
+        let mut map = IndexMap::with_capacity_and_hasher(len, FxBuildHasher::default());
+
+        for _ in 0 .. len {
+            let key = K::restore(reader)?;
+            let value = V::restore(reader)?;
+
+            map.insert(key, value);
+        }
+
+        Ok(map)
+    }
+}
+
+impl<K: Persist + Ord, V: Persist> Persist for BTreeMap<K, V> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(self.len())?;
+
+        for (key, value) in self {
+            key.save(writer)?;
+            value.save(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let len = reader.read_usize()?;
+        let mut map = BTreeMap::new();
+
+        for _ in 0 .. len {
+            let key = K::restore(reader)?;
+            let value = V::restore(reader)?;
+
+            map.insert(key, value);
+        }
+
+        Ok(map)
+    }
+}
+
+impl<K: Persist + Hash + Eq> Persist for IndexSet<K, FxBuildHasher> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(self.len())?;
+
+        for key in self {
+            key.save(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let len = reader.read_usize()?;
+        let mut set = IndexSet::with_capacity_and_hasher(len, FxBuildHasher::default());
+
+        for _ in 0 .. len {
+            set.insert(K::restore(reader)?);
+        }
+
+        Ok(set)
+    }
+}
+
+impl Persist for OrderedFloat<f64> {
+    #[inline]
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_u64(self.into_inner().to_bits())
+    }
+
+    #[inline]
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(OrderedFloat(f64::from_bits(reader.read_u64()?)))
+    }
+}
+
+impl Persist for F64Offset {
+    #[inline]
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        (*self.as_ptr()).save(writer)
+    }
+
+    #[inline]
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let OrderedFloat(f) = OrderedFloat::restore(reader)?;
+        Ok(float_alloc!(f, reader.arena()).as_offset())
+    }
+}
+
+impl Persist for TypedArenaPtr<Integer> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_str(&(**self).to_string())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let n: Integer = reader.read_string()?
+            .parse()
+            .map_err(|_| invalid_state("malformed integer"))?;
+
+        Ok(arena_alloc!(n, reader.arena()))
+    }
+}
+
+impl Persist for TypedArenaPtr<Rational> {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_str(&self.numerator().to_string())?;
+        writer.write_str(&self.denominator().to_string())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let numerator: Integer = reader.read_string()?
+            .parse()
+            .map_err(|_| invalid_state("malformed rational"))?;
+
+        let denominator: UBig = reader.read_string()?
+            .parse()
+            .map_err(|_| invalid_state("malformed rational"))?;
+
+        let r = Rational::from_parts(numerator, denominator);
+        Ok(arena_alloc!(r, reader.arena()))
+    }
+}
+
+const CELL_RAW: u8 = 0;
+const CELL_ATOM: u8 = 1;
+const CELL_PSTR: u8 = 2;
+const CELL_CSTR: u8 = 3;
+const CELL_F64: u8 = 4;
+const CELL_INTEGER: u8 = 5;
+const CELL_RATIONAL: u8 = 6;
+const CELL_CODE_INDEX: u8 = 7;
+
+impl Persist for HeapCellValue {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        read_heap_cell!(*self,
+            (HeapCellValueTag::Atom, (name, arity)) => {
+                writer.write_u8(CELL_ATOM)?;
+                writer.write_atom(name)?;
+                writer.write_usize(arity)
+            }
+            (HeapCellValueTag::PStr, atom) => {
+                writer.write_u8(CELL_PSTR)?;
+                writer.write_atom(atom)
+            }
+            (HeapCellValueTag::CStr, atom) => {
+                writer.write_u8(CELL_CSTR)?;
+                writer.write_atom(atom)
+            }
+            (HeapCellValueTag::F64, f) => {
+                writer.write_u8(CELL_F64)?;
+                (*f).save(writer)
+            }
+            (HeapCellValueTag::Cons, ptr) => {
+                match_untyped_arena_ptr!(ptr,
+                    (ArenaHeaderTag::Integer, n) => {
+                        writer.write_u8(CELL_INTEGER)?;
+                        n.save(writer)
+                    }
+                    (ArenaHeaderTag::Rational, r) => {
+                        writer.write_u8(CELL_RATIONAL)?;
+                        r.save(writer)
+                    }
+                    (ArenaHeaderTag::IndexPtr, _ip) => {
+                        writer.write_u8(CELL_CODE_INDEX)?;
+                        writer.write_code_index(CodeIndex::from(ptr))
+                    }
+                    _ => {
+                        // streams and the like only live as long as
+                        // the machine that made them.
+                        Err(invalid_state("the program refers to a value that can't be saved"))
+                    }
+                )
+            }
+            _ => {
+                // the remaining cells are addresses and inline
+                // constants, which don't depend on the machine.
+                writer.write_u8(CELL_RAW)?;
+                writer.write_u64(u64::from(*self))
+            }
+        )
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            CELL_RAW => Ok(HeapCellValue::from(reader.read_u64()?)),
+            CELL_ATOM => {
+                let name = reader.read_atom()?;
+                let arity = reader.read_usize()?;
+
+                Ok(atom_as_cell!(name, arity))
+            }
+            CELL_PSTR => Ok(pstr_as_cell!(reader.read_atom()?)),
+            CELL_CSTR => Ok(atom_as_cstr_cell!(reader.read_atom()?)),
+            CELL_F64 => {
+                let OrderedFloat(f) = OrderedFloat::restore(reader)?;
+                Ok(HeapCellValue::from(float_alloc!(f, reader.arena())))
+            }
+            CELL_INTEGER => {
+                let n: TypedArenaPtr<Integer> = Persist::restore(reader)?;
+                Ok(typed_arena_ptr_as_cell!(n))
+            }
+            CELL_RATIONAL => {
+                let r: TypedArenaPtr<Rational> = Persist::restore(reader)?;
+                Ok(typed_arena_ptr_as_cell!(r))
+            }
+            CELL_CODE_INDEX => {
+                let code_index = reader.read_code_index()?;
+                Ok(untyped_arena_ptr_as_cell!(UntypedArenaPtr::from(code_index)))
+            }
+            _ => Err(invalid_state("malformed cell")),
+        }
+    }
+}
+
+impl Persist for Number {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            Number::Float(f) => {
+                writer.write_u8(0)?;
+                f.save(writer)
+            }
+            Number::Integer(n) => {
+                writer.write_u8(1)?;
+                n.save(writer)
+            }
+            Number::Rational(r) => {
+                writer.write_u8(2)?;
+                r.save(writer)
+            }
+            Number::Fixnum(n) => {
+                writer.write_u8(3)?;
+                writer.write_u64(u64::from(*n))
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(Number::Float(Persist::restore(reader)?)),
+            1 => Ok(Number::Integer(Persist::restore(reader)?)),
+            2 => Ok(Number::Rational(Persist::restore(reader)?)),
+            3 => Ok(Number::Fixnum(Fixnum::from(reader.read_u64()?))),
+            _ => Err(invalid_state("malformed number")),
+        }
+    }
+}
+
+impl Persist for Literal {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            Literal::Atom(atom) => {
+                writer.write_u8(0)?;
+                writer.write_atom(*atom)
+            }
+            Literal::Char(c) => {
+                writer.write_u8(1)?;
+                writer.write_u64(*c as u64)
+            }
+            Literal::CodeIndex(code_index) => {
+                writer.write_u8(2)?;
+                writer.write_code_index(*code_index)
+            }
+            Literal::Fixnum(n) => {
+                writer.write_u8(3)?;
+                writer.write_u64(u64::from(*n))
+            }
+            Literal::Integer(n) => {
+                writer.write_u8(4)?;
+                n.save(writer)
+            }
+            Literal::Rational(r) => {
+                writer.write_u8(5)?;
+                r.save(writer)
+            }
+            Literal::Float(f) => {
+                writer.write_u8(6)?;
+                f.save(writer)
+            }
+            Literal::String(atom) => {
+                writer.write_u8(7)?;
+                writer.write_atom(*atom)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(Literal::Atom(reader.read_atom()?)),
+            1 => char::from_u32(reader.read_u64()? as u32)
+                .map(Literal::Char)
+                .ok_or_else(|| invalid_state("malformed char")),
+            2 => Ok(Literal::CodeIndex(reader.read_code_index()?)),
+            3 => Ok(Literal::Fixnum(Fixnum::from(reader.read_u64()?))),
+            4 => Ok(Literal::Integer(Persist::restore(reader)?)),
+            5 => Ok(Literal::Rational(Persist::restore(reader)?)),
+            6 => Ok(Literal::Float(Persist::restore(reader)?)),
+            7 => Ok(Literal::String(reader.read_atom()?)),
+            _ => Err(invalid_state("malformed literal")),
+        }
+    }
+}
+
+impl Persist for RegType {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            RegType::Perm(r) => {
+                writer.write_u8(0)?;
+                writer.write_usize(*r)
+            }
+            RegType::Temp(r) => {
+                writer.write_u8(1)?;
+                writer.write_usize(*r)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(RegType::Perm(reader.read_usize()?)),
+            1 => Ok(RegType::Temp(reader.read_usize()?)),
+            _ => Err(invalid_state("malformed register")),
+        }
+    }
+}
+
+impl Persist for ArithmeticTerm {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            ArithmeticTerm::Reg(r) => {
+                writer.write_u8(0)?;
+                r.save(writer)
+            }
+            ArithmeticTerm::Interm(i) => {
+                writer.write_u8(1)?;
+                writer.write_usize(*i)
+            }
+            ArithmeticTerm::Number(n) => {
+                writer.write_u8(2)?;
+                n.save(writer)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(ArithmeticTerm::Reg(Persist::restore(reader)?)),
+            1 => Ok(ArithmeticTerm::Interm(reader.read_usize()?)),
+            2 => Ok(ArithmeticTerm::Number(Persist::restore(reader)?)),
+            _ => Err(invalid_state("malformed arithmetic term")),
+        }
+    }
+}
+
+impl Persist for Level {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_u8(match self {
+            Level::Deep => 0,
+            Level::Root => 1,
+            Level::Shallow => 2,
+        })
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(Level::Deep),
+            1 => Ok(Level::Root),
+            2 => Ok(Level::Shallow),
+            _ => Err(invalid_state("malformed level")),
+        }
+    }
+}
+
+impl Persist for NextOrFail {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            NextOrFail::Next(o) => {
+                writer.write_u8(0)?;
+                writer.write_usize(*o)
+            }
+            NextOrFail::Fail(o) => {
+                writer.write_u8(1)?;
+                writer.write_usize(*o)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(NextOrFail::Next(reader.read_usize()?)),
+            1 => Ok(NextOrFail::Fail(reader.read_usize()?)),
+            _ => Err(invalid_state("malformed dynamic clause offset")),
+        }
+    }
+}
+
+impl Persist for Death {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            Death::Finite(clock) => {
+                writer.write_u8(0)?;
+                writer.write_usize(*clock)
+            }
+            Death::Infinity => writer.write_u8(1),
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(Death::Finite(reader.read_usize()?)),
+            1 => Ok(Death::Infinity),
+            _ => Err(invalid_state("malformed clause death")),
+        }
+    }
+}
+
+impl Persist for IndexingCodePtr {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            IndexingCodePtr::External(o) => {
+                writer.write_u8(0)?;
+                writer.write_usize(*o)
+            }
+            IndexingCodePtr::DynamicExternal(o) => {
+                writer.write_u8(1)?;
+                writer.write_usize(*o)
+            }
+            IndexingCodePtr::Fail => writer.write_u8(2),
+            IndexingCodePtr::Internal(o) => {
+                writer.write_u8(3)?;
+                writer.write_usize(*o)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(IndexingCodePtr::External(reader.read_usize()?)),
+            1 => Ok(IndexingCodePtr::DynamicExternal(reader.read_usize()?)),
+            2 => Ok(IndexingCodePtr::Fail),
+            3 => Ok(IndexingCodePtr::Internal(reader.read_usize()?)),
+            _ => Err(invalid_state("malformed indexing pointer")),
+        }
+    }
+}
+
+impl Persist for IndexedChoiceInstruction {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_u8(match self {
+            IndexedChoiceInstruction::Retry(_) => 0,
+            IndexedChoiceInstruction::Trust(_) => 1,
+            IndexedChoiceInstruction::Try(_) => 2,
+        })?;
+
+        writer.write_usize(self.offset())
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let tag = reader.read_u8()?;
+        let offset = reader.read_usize()?;
+
+        match tag {
+            0 => Ok(IndexedChoiceInstruction::Retry(offset)),
+            1 => Ok(IndexedChoiceInstruction::Trust(offset)),
+            2 => Ok(IndexedChoiceInstruction::Try(offset)),
+            _ => Err(invalid_state("malformed indexed choice instruction")),
+        }
+    }
+}
+
+impl Persist for IndexingInstruction {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            IndexingInstruction::SwitchOnTerm(arg, v, c, l, s) => {
+                writer.write_u8(0)?;
+                writer.write_usize(*arg)?;
+                v.save(writer)?;
+                c.save(writer)?;
+                l.save(writer)?;
+                s.save(writer)
+            }
+            IndexingInstruction::SwitchOnConstant(constants) => {
+                writer.write_u8(1)?;
+                constants.save(writer)
+            }
+            IndexingInstruction::SwitchOnStructure(structures) => {
+                writer.write_u8(2)?;
+                structures.save(writer)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(IndexingInstruction::SwitchOnTerm(
+                reader.read_usize()?,
+                Persist::restore(reader)?,
+                Persist::restore(reader)?,
+                Persist::restore(reader)?,
+                Persist::restore(reader)?,
+            )),
+            1 => Ok(IndexingInstruction::SwitchOnConstant(Persist::restore(reader)?)),
+            2 => Ok(IndexingInstruction::SwitchOnStructure(Persist::restore(reader)?)),
+            _ => Err(invalid_state("malformed indexing instruction")),
+        }
+    }
+}
+
+impl Persist for IndexingLine {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            IndexingLine::Indexing(instr) => {
+                writer.write_u8(0)?;
+                instr.save(writer)
+            }
+            IndexingLine::IndexedChoice(choices) => {
+                writer.write_u8(1)?;
+                choices.save(writer)
+            }
+            IndexingLine::DynamicIndexedChoice(choices) => {
+                writer.write_u8(2)?;
+                choices.save(writer)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(IndexingLine::Indexing(Persist::restore(reader)?)),
+            1 => Ok(IndexingLine::IndexedChoice(Persist::restore(reader)?)),
+            2 => Ok(IndexingLine::DynamicIndexedChoice(Persist::restore(reader)?)),
+            _ => Err(invalid_state("malformed indexing line")),
+        }
+    }
+}
+
+impl Persist for Fixity {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_u8(match self {
+            Fixity::In => 0,
+            Fixity::Post => 1,
+            Fixity::Pre => 2,
+        })
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(Fixity::In),
+            1 => Ok(Fixity::Post),
+            2 => Ok(Fixity::Pre),
+            _ => Err(invalid_state("malformed fixity")),
+        }
+    }
+}
+
+impl Persist for OpDesc {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        let (prec, spec) = self.get();
+
+        writer.write_u64(prec as u64)?;
+        writer.write_u8(spec)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let prec = reader.read_u64()? as u16;
+        let spec = reader.read_u8()?;
+
+        Ok(OpDesc::build_with(prec, spec))
+    }
+}
+
+impl Persist for MetaSpec {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            MetaSpec::Minus => writer.write_u8(0),
+            MetaSpec::Plus => writer.write_u8(1),
+            MetaSpec::Either => writer.write_u8(2),
+            MetaSpec::Colon => writer.write_u8(3),
+            MetaSpec::RequiresExpansionWithArgument(n) => {
+                writer.write_u8(4)?;
+                writer.write_usize(*n)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(MetaSpec::Minus),
+            1 => Ok(MetaSpec::Plus),
+            2 => Ok(MetaSpec::Either),
+            3 => Ok(MetaSpec::Colon),
+            4 => Ok(MetaSpec::RequiresExpansionWithArgument(reader.read_usize()?)),
+            _ => Err(invalid_state("malformed meta-predicate specifier")),
+        }
+    }
+}
+
+impl Persist for ModuleExport {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            ModuleExport::OpDecl(op_decl) => {
+                writer.write_u8(0)?;
+                op_decl.op_desc.save(writer)?;
+                writer.write_atom(op_decl.name)
+            }
+            ModuleExport::PredicateKey(key) => {
+                writer.write_u8(1)?;
+                key.save(writer)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => {
+                let op_desc = Persist::restore(reader)?;
+                Ok(ModuleExport::OpDecl(OpDecl::new(op_desc, reader.read_atom()?)))
+            }
+            1 => Ok(ModuleExport::PredicateKey(Persist::restore(reader)?)),
+            _ => Err(invalid_state("malformed module export")),
+        }
+    }
+}
+
+impl Persist for ListingSource {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            ListingSource::DynamicallyGenerated => writer.write_u8(0),
+            ListingSource::File(filename, path_buf) => {
+                writer.write_u8(1)?;
+                writer.write_atom(*filename)?;
+                writer.write_str(&path_buf.to_string_lossy())
+            }
+            ListingSource::User => writer.write_u8(2),
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(ListingSource::DynamicallyGenerated),
+            1 => {
+                let filename = reader.read_atom()?;
+                let path_buf = PathBuf::from(reader.read_string()?);
+
+                Ok(ListingSource::File(filename, path_buf))
+            }
+            2 => Ok(ListingSource::User),
+            _ => Err(invalid_state("malformed listing source")),
+        }
+    }
+}
+
+impl Persist for CompilationTarget {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            CompilationTarget::Module(module_name) => {
+                writer.write_u8(0)?;
+                writer.write_atom(*module_name)
+            }
+            CompilationTarget::User => writer.write_u8(1),
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(CompilationTarget::Module(reader.read_atom()?)),
+            1 => Ok(CompilationTarget::User),
+            _ => Err(invalid_state("malformed compilation target")),
+        }
+    }
+}
+
+impl Persist for OptArgIndexKey {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        match self {
+            OptArgIndexKey::Literal(arg, loc, literal, alternatives) => {
+                writer.write_u8(0)?;
+                writer.write_usize(*arg)?;
+                writer.write_usize(*loc)?;
+                literal.save(writer)?;
+                alternatives.save(writer)
+            }
+            OptArgIndexKey::List(arg, loc) => {
+                writer.write_u8(1)?;
+                writer.write_usize(*arg)?;
+                writer.write_usize(*loc)
+            }
+            OptArgIndexKey::None => writer.write_u8(2),
+            OptArgIndexKey::Structure(arg, loc, name, arity) => {
+                writer.write_u8(3)?;
+                writer.write_usize(*arg)?;
+                writer.write_usize(*loc)?;
+                writer.write_atom(*name)?;
+                writer.write_usize(*arity)
+            }
+        }
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        match reader.read_u8()? {
+            0 => Ok(OptArgIndexKey::Literal(
+                reader.read_usize()?,
+                reader.read_usize()?,
+                Persist::restore(reader)?,
+                Persist::restore(reader)?,
+            )),
+            1 => Ok(OptArgIndexKey::List(reader.read_usize()?, reader.read_usize()?)),
+            2 => Ok(OptArgIndexKey::None),
+            3 => Ok(OptArgIndexKey::Structure(
+                reader.read_usize()?,
+                reader.read_usize()?,
+                reader.read_atom()?,
+                reader.read_usize()?,
+            )),
+            _ => Err(invalid_state("malformed argument index key")),
+        }
+    }
+}
+
+impl Persist for ClauseIndexInfo {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_usize(self.clause_start)?;
+        self.opt_arg_index_key.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(ClauseIndexInfo {
+            clause_start: reader.read_usize()?,
+            opt_arg_index_key: Persist::restore(reader)?,
+        })
+    }
+}
+
+impl Persist for ClauseSource {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_atom(self.module_name)?;
+        self.key.save(writer)?;
+        self.file.save(writer)?;
+        self.line.save(writer)?;
+        self.col.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(ClauseSource {
+            module_name: reader.read_atom()?,
+            key: Persist::restore(reader)?,
+            file: Persist::restore(reader)?,
+            line: Persist::restore(reader)?,
+            col: Persist::restore(reader)?,
+        })
+    }
+}
+
+impl Persist for LocalPredicateSkeleton {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        self.is_discontiguous.save(writer)?;
+        self.is_dynamic.save(writer)?;
+        self.is_multifile.save(writer)?;
+        self.clause_clause_locs.save(writer)?;
+        self.clause_assert_margin.save(writer)?;
+        self.retracted_dynamic_clauses.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(LocalPredicateSkeleton {
+            is_discontiguous: Persist::restore(reader)?,
+            is_dynamic: Persist::restore(reader)?,
+            is_multifile: Persist::restore(reader)?,
+            clause_clause_locs: Persist::restore(reader)?,
+            clause_assert_margin: Persist::restore(reader)?,
+            retracted_dynamic_clauses: Persist::restore(reader)?,
+        })
+    }
+}
+
+impl Persist for PredicateSkeleton {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        self.core.save(writer)?;
+        self.clauses.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(PredicateSkeleton {
+            core: Persist::restore(reader)?,
+            clauses: Persist::restore(reader)?,
+        })
+    }
+}
+
+impl Persist for Module {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_atom(self.module_decl.name)?;
+        self.module_decl.exports.save(writer)?;
+        self.code_dir.save(writer)?;
+        self.op_dir.save(writer)?;
+        self.meta_predicates.save(writer)?;
+        self.extensible_predicates.save(writer)?;
+        self.local_extensible_predicates.save(writer)?;
+        self.listing_src.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        Ok(Module {
+            module_decl: ModuleDecl {
+                name: reader.read_atom()?,
+                exports: Persist::restore(reader)?,
+            },
+            code_dir: Persist::restore(reader)?,
+            op_dir: Persist::restore(reader)?,
+            meta_predicates: Persist::restore(reader)?,
+            extensible_predicates: Persist::restore(reader)?,
+            local_extensible_predicates: Persist::restore(reader)?,
+            listing_src: Persist::restore(reader)?,
+        })
+    }
+}
+
+impl Persist for MachineFlags {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        writer.write_u8(match self.double_quotes {
+            DoubleQuotes::Atom => 0,
+            DoubleQuotes::Chars => 1,
+            DoubleQuotes::Codes => 2,
+        })?;
+
+        writer.write_u8(match self.unknown {
+            Unknown::Error => 0,
+            Unknown::Fail => 1,
+            Unknown::Warn => 2,
+        })?;
+
+        writer.write_u8(self.backtrace as u8)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let double_quotes = match reader.read_u8()? {
+            0 => DoubleQuotes::Atom,
+            1 => DoubleQuotes::Chars,
+            2 => DoubleQuotes::Codes,
+            _ => return Err(invalid_state("malformed double_quotes flag")),
+        };
+
+        let unknown = match reader.read_u8()? {
+            0 => Unknown::Error,
+            1 => Unknown::Fail,
+            2 => Unknown::Warn,
+            _ => return Err(invalid_state("malformed unknown flag")),
+        };
+
+        let backtrace = reader.read_u8()? != 0;
+
+        Ok(MachineFlags { double_quotes, unknown, backtrace })
+    }
+}
+
+impl Persist for SystemTime {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        let since_epoch = self.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+
+        writer.write_u64(since_epoch.as_secs())?;
+        writer.write_u64(since_epoch.subsec_nanos() as u64)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let secs = reader.read_u64()?;
+        let nanos = reader.read_u64()?;
+
+        u32::try_from(nanos)
+            .ok()
+            .and_then(|nanos| SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos)))
+            .ok_or_else(|| invalid_state("malformed modification time"))
+    }
+}
+
+impl Persist for LoadedFile {
+    fn save(&self, writer: &mut StateWriter) -> io::Result<()> {
+        self.modified.save(writer)?;
+        self.parent.save(writer)
+    }
+
+    fn restore(reader: &mut StateReader) -> io::Result<Self> {
+        let modified = Persist::restore(reader)?;
+        let parent = Persist::restore(reader)?;
+
+        Ok(LoadedFile { modified, parent })
+    }
+}
+
+impl Machine {
+    // the closures of native predicates can't be saved, so neither can
+    // a machine with any registered.
+    fn check_savable(&mut self, name: Atom) -> CallResult {
+        match self.native_predicates.names().next() {
+            Some(native_name) => {
+                let err = self.machine_st.permission_error(
+                    Permission::Save,
+                    atom!("native_predicate"),
+                    atom_as_cell!(native_name),
+                );
+
+                Err(self.machine_st.error_form(err, functor_stub(name, 2)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Saves the state of the machine to the file named by the atom in
+    /// the first register.
+    pub(crate) fn save_program(&mut self) -> CallResult {
+        self.check_savable(atom!("save_program"))?;
+
+        let file = self.deref_register(1);
+        let path = cell_as_atom!(file);
+
+        let result = File::create(path.as_str())
+            .and_then(|file| self.save_state(&mut BufWriter::new(file)));
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.machine_st.open_permission_error(file, atom!("save_program"), 2)),
+        }
+    }
+
+    /// Writes an executable to the file named by the atom in the first
+    /// register: the runtime of the running executable, followed by
+    /// the state of the machine.
+    pub(crate) fn save_executable(&mut self) -> CallResult {
+        self.check_savable(atom!("qsave_program"))?;
+
+        let file = self.deref_register(1);
+        let path = cell_as_atom!(file);
+
+        match self.write_executable(path.as_str()) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.machine_st.open_permission_error(file, atom!("qsave_program"), 2)),
+        }
+    }
+
+    fn write_executable(&self, path: &str) -> io::Result<()> {
+        let mut exe = File::open(env::current_exe()?)?;
+
+        // an executable written by an executable embeds only the
+        // latest state.
+        let runtime_len = match embedded_state(&mut exe)? {
+            Some((offset, _)) => offset,
+            None => exe.metadata()?.len(),
+        };
+
+        let mut state = vec![];
+        self.save_state(&mut state)?;
+
+        let mut out = BufWriter::new(File::create(path)?);
+
+        exe.seek(SeekFrom::Start(0))?;
+        io::copy(&mut exe.take(runtime_len), &mut out)?;
+
+        out.write_all(&state)?;
+        out.write_all(&(state.len() as u64).to_le_bytes())?;
+        out.write_all(EXECUTABLE_MAGIC)?;
+        out.flush()?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the code, indices, flags and loaded files of the machine
+    /// to `out`. Streams, global variables, coroutines, engines and
+    /// answer tables aren't saved.
+    pub(crate) fn save_state(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+
+        let mut writer = StateWriter::new(out);
+
+        writer.write_u64(FORMAT_VERSION)?;
+        writer.write_str(env!("CARGO_PKG_VERSION"))?;
+        writer.write_u64(INSTRUCTIONS_DIGEST)?;
+
+        self.code.save(&mut writer)?;
+
+        self.indices.code_dir.save(&mut writer)?;
+        self.indices.clause_sources.save(&mut writer)?;
+        self.indices.extensible_predicates.save(&mut writer)?;
+        self.indices.local_extensible_predicates.save(&mut writer)?;
+        self.indices.goal_expansion_indices.save(&mut writer)?;
+        self.indices.meta_predicates.save(&mut writer)?;
+        self.indices.op_dir.save(&mut writer)?;
+
+        self.indices.modules.save(&mut writer)?;
+        self.indices.fact_tables.save(&mut writer)?;
+        self.tables.tabled.save(&mut writer)?;
+        self.loaded_files.save(&mut writer)?;
+
+        self.machine_st.flags.save(&mut writer)?;
+
+        writer.write_u8(
+            if self.machine_st.unify_fn as usize == MachineState::unify_with_occurs_check as usize {
+                1
+            } else if self.machine_st.unify_fn as usize
+                == MachineState::unify_with_occurs_check_with_error as usize
+            {
+                2
+            } else {
+                0
+            },
+        )?;
+
+        writer.write_usize(self.machine_st.global_clock)?;
+        writer.write_usize(self.machine_st.attr_var_init.verify_attrs_loc)?;
+
+        writer.out.flush()
+    }
+
+    /// Builds a machine from the state read from `input`, as written
+    /// by `save_state`.
+    pub(crate) fn restore(
+        machine_st: MachineState,
+        user_input: Stream,
+        user_output: Stream,
+        user_error: Stream,
+        runtime: Runtime,
+        input: &mut dyn Read,
+    ) -> io::Result<Self> {
+        let mut wam = Machine::unloaded(machine_st, user_input, user_output, user_error, runtime);
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(invalid_state("not a saved state"));
+        }
+
+        let mut reader = StateReader::new(input, &mut wam.machine_st);
+
+        if reader.read_u64()? != FORMAT_VERSION
+            || reader.read_string()? != env!("CARGO_PKG_VERSION")
+            || reader.read_u64()? != INSTRUCTIONS_DIGEST
+        {
+            return Err(invalid_state("saved by a different version of Scryer Prolog"));
+        }
+
+        let code = Persist::restore(&mut reader)?;
+
+        let mut indices = IndexStore::new();
+
+        indices.code_dir = Persist::restore(&mut reader)?;
+        indices.clause_sources = Persist::restore(&mut reader)?;
+        indices.extensible_predicates = Persist::restore(&mut reader)?;
+        indices.local_extensible_predicates = Persist::restore(&mut reader)?;
+        indices.goal_expansion_indices = Persist::restore(&mut reader)?;
+        indices.meta_predicates = Persist::restore(&mut reader)?;
+        indices.op_dir = Persist::restore(&mut reader)?;
+
+        indices.modules = Persist::restore(&mut reader)?;
+        indices.fact_tables = Persist::restore(&mut reader)?;
+        let tabled = Persist::restore(&mut reader)?;
+        let loaded_files = Persist::restore(&mut reader)?;
+
+        let flags = Persist::restore(&mut reader)?;
+        let occurs_check = reader.read_u8()?;
+        let global_clock = reader.read_usize()?;
+        let verify_attrs_loc = reader.read_usize()?;
+
+        wam.code = code;
+        wam.indices = indices;
+        wam.tables.tabled = tabled;
+        wam.loaded_files = loaded_files;
+
+        wam.machine_st.flags = flags;
+        wam.machine_st.global_clock = global_clock;
+        wam.machine_st.attr_var_init.verify_attrs_loc = verify_attrs_loc;
+
+        match occurs_check {
+            1 => wam.set_sto_as_unify(),
+            2 => wam.set_sto_with_error_as_unify(),
+            _ => wam.set_nsto_as_unify(),
+        }
+
+        wam.configure_streams();
+
+        Ok(wam)
+    }
+}
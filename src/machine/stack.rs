@@ -265,6 +265,12 @@ pub(crate) fn index_or_frame_mut(&mut self, b: usize) -> &mut OrFrame {
         }
     }
 
+    /// The offset of the top of the stack, past its last frame.
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        self.buf.ptr as usize - self.buf.base as usize
+    }
+
     #[inline(always)]
     pub(crate) fn truncate(&mut self, b: usize) {
         let base = self.buf.base as usize + b;
@@ -393,6 +393,8 @@ fn default() -> Self {
 pub struct StreamLayout<T> {
     pub options: StreamOptions,
     pub lines_read: usize,
+    // the characters read since the last line break.
+    pub col_num: usize,
     past_end_of_stream: bool,
     stream: T,
 }
@@ -403,6 +405,7 @@ pub fn new(stream: T) -> Self {
         Self {
             options: StreamOptions::default(),
             lines_read: 0,
+            col_num: 0,
             past_end_of_stream: false,
             stream,
         }
@@ -675,6 +678,54 @@ pub(crate) fn lines_read(&self) -> usize {
             Stream::StandardError(ptr) => ptr.lines_read,
         }
     }
+
+    #[inline]
+    pub(crate) fn col_num(&self) -> usize {
+        match self {
+            Stream::Byte(ptr) => ptr.col_num,
+            Stream::InputFile(ptr) => ptr.col_num,
+            Stream::OutputFile(ptr) => ptr.col_num,
+            Stream::StaticString(ptr) => ptr.col_num,
+            Stream::NamedTcp(ptr) => ptr.col_num,
+            Stream::NamedTls(ptr) => ptr.col_num,
+            Stream::HttpRead(ptr) => ptr.col_num,
+            Stream::HttpWrite(_) => 0,
+            Stream::Null(_) => 0,
+            Stream::Readline(ptr) => ptr.col_num,
+            Stream::StandardOutput(ptr) => ptr.col_num,
+            Stream::StandardError(ptr) => ptr.col_num,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_col_num(&mut self, value: usize) {
+        match self {
+            Stream::Byte(ptr) => ptr.col_num = value,
+            Stream::InputFile(ptr) => ptr.col_num = value,
+            Stream::OutputFile(ptr) => ptr.col_num = value,
+            Stream::StaticString(ptr) => ptr.col_num = value,
+            Stream::NamedTcp(ptr) => ptr.col_num = value,
+            Stream::NamedTls(ptr) => ptr.col_num = value,
+            Stream::HttpRead(ptr) => ptr.col_num = value,
+            Stream::HttpWrite(_) => {}
+            Stream::Null(_) => {}
+            Stream::Readline(ptr) => ptr.col_num = value,
+            Stream::StandardOutput(ptr) => ptr.col_num = value,
+            Stream::StandardError(ptr) => ptr.col_num = value,
+        }
+    }
+
+    /// Advances the column of the stream past text read by a parser
+    /// that started at the column, and crossed `lines_read` line
+    /// breaks to end at `col_num`.
+    #[inline]
+    pub(crate) fn advance_col_num(&mut self, lines_read: usize, col_num: usize) {
+        if lines_read > 0 {
+            self.set_col_num(col_num);
+        } else {
+            self.set_col_num(self.col_num() + col_num);
+        }
+    }
 }
 
 impl CharRead for Stream {
@@ -1271,6 +1322,7 @@ pub(crate) fn is_output_stream(&self) -> bool {
     #[inline]
     pub(super) fn reset(&mut self) -> bool {
         self.set_lines_read(0);
+        self.set_col_num(0);
         self.set_past_end_of_stream(false);
 
         loop {
@@ -4168,6 +4168,96 @@ pub(crate) fn cpu_now(&mut self) {
         self.machine_st.unify_f64(secs, self.machine_st.registers[1]);
     }
 
+    fn statistics_list(&mut self, values: Vec<usize>) -> HeapCellValue {
+        let h = iter_to_heap_list(
+            &mut self.machine_st.heap,
+            values.into_iter().map(|n| fixnum_as_cell!(Fixnum::build_with(n as i64))),
+        );
+
+        heap_loc_as_cell!(h)
+    }
+
+    #[inline(always)]
+    pub(crate) fn statistics(&mut self) {
+        let key = cell_as_atom!(self.deref_register(1));
+        let cell_size = mem::size_of::<HeapCellValue>();
+
+        let value = match key {
+            atom!("runtime") => {
+                let runtime = ProcessTime::now().as_duration();
+                let since_last = runtime.saturating_sub(self.machine_st.stats.last_runtime);
+
+                self.machine_st.stats.last_runtime = runtime;
+                self.statistics_list(vec![runtime.as_millis() as usize, since_last.as_millis() as usize])
+            }
+            atom!("walltime") => {
+                let walltime = self.machine_st.stats.start.elapsed();
+                let since_last = walltime.saturating_sub(self.machine_st.stats.last_walltime);
+
+                self.machine_st.stats.last_walltime = walltime;
+                self.statistics_list(vec![walltime.as_millis() as usize, since_last.as_millis() as usize])
+            }
+            atom!("cputime") => {
+                let secs = ProcessTime::now().as_duration().as_secs_f64();
+                HeapCellValue::from(float_alloc!(secs, self.machine_st.arena))
+            }
+            atom!("inferences") => {
+                fixnum_as_cell!(Fixnum::build_with(self.machine_st.stats.inferences as i64))
+            }
+            atom!("heapused") => {
+                let bytes = self.machine_st.heap.len() * cell_size;
+                fixnum_as_cell!(Fixnum::build_with(bytes as i64))
+            }
+            atom!("lifted_heap") => {
+                let bytes = self.machine_st.lifted_heap.len() * cell_size;
+                fixnum_as_cell!(Fixnum::build_with(bytes as i64))
+            }
+            atom!("stack") => {
+                fixnum_as_cell!(Fixnum::build_with(self.machine_st.stack.len() as i64))
+            }
+            atom!("trail") => {
+                let bytes = self.machine_st.trail.len() * mem::size_of::<TrailEntry>();
+                fixnum_as_cell!(Fixnum::build_with(bytes as i64))
+            }
+            atom!("code") => {
+                let values = vec![self.code.len(), self.code.len() * mem::size_of::<Instruction>()];
+                self.statistics_list(values)
+            }
+            atom!("arena") => {
+                let (allocations, bytes) = self.machine_st.arena.usage();
+                self.statistics_list(vec![allocations, bytes])
+            }
+            atom!("atoms") => {
+                let atom_tbl = &self.machine_st.atom_tbl;
+                let values = vec![atom_tbl.atom_count(), atom_tbl.bytes_used()];
+
+                self.statistics_list(values)
+            }
+            atom!("garbage_collection") => {
+                let gc = &self.machine_st.gc;
+                let values = vec![gc.collections, gc.reclaimed_cells, gc.time.as_millis() as usize];
+
+                self.statistics_list(values)
+            }
+            atom!("atom_garbage_collection") => {
+                let gc = &self.machine_st.gc;
+                let values = vec![
+                    gc.atom_collections,
+                    gc.collected_atoms,
+                    gc.atom_time.as_millis() as usize,
+                ];
+
+                self.statistics_list(values)
+            }
+            _ => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        unify!(self.machine_st, value, self.machine_st.registers[2]);
+    }
+
     #[inline(always)]
     pub(crate) fn det_length_rundown(&mut self) -> CallResult {
         let stub_gen = || functor_stub(atom!("length"), 2);
@@ -5220,6 +5310,20 @@ pub(crate) fn get_unknown(&mut self) {
         );
     }
 
+    #[inline(always)]
+    pub(crate) fn get_backtrace(&mut self) {
+        let a1 = self.deref_register(1);
+
+        self.machine_st.unify_atom(
+            if self.machine_st.flags.backtrace {
+                atom!("true")
+            } else {
+                atom!("false")
+            },
+            a1,
+        );
+    }
+
     #[inline(always)]
     pub(crate) fn get_scc_cleaner(&mut self) {
         let dest = self.machine_st.registers[1];
@@ -5260,6 +5364,9 @@ pub(crate) fn halt(&mut self) -> std::process::ExitCode {
             }
         };
 
+        self.write_profile_at_halt();
+        self.write_coverage_at_halt();
+
         std::process::ExitCode::from(code)
     }
 
@@ -5590,6 +5697,20 @@ pub(crate) fn set_unknown(&mut self) {
         };
     }
 
+    #[inline(always)]
+    pub(crate) fn set_backtrace(&mut self) {
+        let atom = cell_as_atom!(self.deref_register(1));
+
+        self.machine_st.flags.backtrace = match atom {
+            atom!("true") => true,
+            atom!("false") => false,
+            _ => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+    }
+
     #[inline(always)]
     pub(crate) fn inference_level(&mut self) {
         let a1 = self.deref_register(1);
@@ -5649,7 +5770,13 @@ pub(crate) fn push_ball_stack(&mut self) {
 
     #[inline(always)]
     pub(crate) fn pop_ball_stack(&mut self) {
-        self.machine_st.ball_stack.pop();
+        // the ball is caught. its backtrace is kept in case it's
+        // thrown again.
+        self.machine_st.caught_ball = self
+            .machine_st
+            .ball_stack
+            .pop()
+            .filter(|ball| !ball.backtrace.is_empty());
     }
 
     #[inline(always)]
@@ -7563,9 +7690,11 @@ pub(crate) fn devour_whitespace(&mut self) -> CallResult {
         match devour_whitespace(&mut parser) {
             Ok(false) => { // not at EOF.
                 stream.add_lines_read(parser.lines_read());
+                stream.advance_col_num(parser.lines_read(), parser.col_num());
             }
             Ok(true) => {
                 stream.add_lines_read(parser.lines_read());
+                stream.advance_col_num(parser.lines_read(), parser.col_num());
                 self.machine_st.fail = true;
             }
             Err(err) => {
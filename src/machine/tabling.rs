@@ -0,0 +1,1087 @@
+//! The answer tables of tabled predicates, and the completion of the
+//! tables under evaluation, as driven by library(tabling).
+//!
+//! Tables are found by the variant of their call, and answers are
+//! added to a table only if no variant of them is already there. Both
+//! lookups go through tries whose nodes are allocated in one arena,
+//! the nodes of the table store, which is dropped as a whole by
+//! abolish_all_tables/0.
+//!
+//! The nodes aren't allocated in the arena of the machine. Its
+//! allocations are freed only when the machine is dropped, so trie
+//! nodes there would outlive the tables abolished by
+//! abolish_all_tables/0, and each of them would be a separate
+//! allocation with a tagged header that only heap cells need. Trie
+//! nodes are never referred to by heap cells, only by their index in
+//! the table store, whose vector of them is released all at once like
+//! an arena while giving the memory back when the tables are abolished.
+//!
+//! The tables of predicates with a moded argument keep one answer for
+//! each variant of their other arguments. The answer is replaced as
+//! better ones are found, and the replacement is returned again to the
+//! continuations suspended on the table.
+//!
+//! Answers found by way of tnot/1, whose truth under the well-founded
+//! semantics depends on tables not yet complete, are conditional on the
+//! literals of their delay lists. Those literals are resolved when the
+//! tables are completed, after which the answers left conditional are
+//! undefined.
+
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::heap::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::partial_string::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::parser::dashu::integer::UBig;
+use crate::parser::dashu::Integer;
+use crate::types::*;
+
+use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
+use indexmap::{IndexMap, IndexSet};
+use ordered_float::OrderedFloat;
+
+use std::collections::VecDeque;
+use std::mem;
+
+/// A node label of a variant trie: the principal functor of a subterm,
+/// or the number of a variable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum TrieKey {
+    // the n-th distinct variable of the term, in preorder.
+    Var(usize),
+    Atom(Atom),
+    Char(char),
+    Fixnum(i64),
+    Float(OrderedFloat<f64>),
+    Integer(Integer),
+    Rational(Integer, UBig),
+    List,
+    Structure(Atom, usize),
+    // streams and other constants, which are only equal to themselves.
+    Cell(HeapCellValue),
+}
+
+impl TrieKey {
+    fn from_atom(name: Atom) -> Self {
+        match name.as_char() {
+            Some(c) => TrieKey::Char(c),
+            None => TrieKey::Atom(name),
+        }
+    }
+}
+
+fn push_char_keys(chars: &str, keys: &mut Vec<TrieKey>) {
+    for c in chars.chars() {
+        keys.push(TrieKey::List);
+        keys.push(TrieKey::Char(c));
+    }
+}
+
+// pushes the keys of the characters of the partial string at heap[h],
+// keyed as the list of them, and returns its tail.
+fn push_pstr_keys(heap: &[HeapCellValue], h: usize, keys: &mut Vec<TrieKey>) -> HeapCellValue {
+    read_heap_cell!(heap[h],
+        (HeapCellValueTag::PStr, pstr_atom) => {
+            push_char_keys(PartialString::from(pstr_atom).as_str_from(0), keys);
+            heap[h + 1]
+        }
+        (HeapCellValueTag::CStr, cstr_atom) => {
+            push_char_keys(cstr_atom.as_str(), keys);
+            empty_list_as_cell!()
+        }
+        (HeapCellValueTag::PStrOffset, pstr_loc) => {
+            let n = cell_as_fixnum!(heap[h + 1]).get_num() as usize;
+            let pstr_atom = cell_as_atom!(heap[pstr_loc]);
+
+            push_char_keys(PartialString::from(pstr_atom).as_str_from(n), keys);
+
+            if heap[pstr_loc].get_tag() == HeapCellValueTag::CStr {
+                empty_list_as_cell!()
+            } else {
+                heap[pstr_loc + 1]
+            }
+        }
+        _ => {
+            heap[h]
+        }
+    )
+}
+
+/// The keys of the term at `cell` in preorder, by which the term is
+/// found in a variant trie. Strings are keyed as lists of characters,
+/// and variables by the order of their first occurrence, so variant
+/// terms have the same keys.
+pub(crate) fn variant_keys(machine_st: &MachineState, cell: HeapCellValue) -> Vec<TrieKey> {
+    let mut keys = vec![];
+    let mut vars: FxHashMap<HeapCellValue, usize> = FxHashMap::default();
+    let mut stack = vec![cell];
+
+    while let Some(cell) = stack.pop() {
+        let cell = machine_st.store(machine_st.deref(cell));
+
+        read_heap_cell!(cell,
+            (HeapCellValueTag::Var | HeapCellValueTag::AttrVar | HeapCellValueTag::StackVar) => {
+                let n = vars.len();
+                keys.push(TrieKey::Var(*vars.entry(cell).or_insert(n)));
+            }
+            (HeapCellValueTag::Str, s) => {
+                let (name, arity) = cell_as_atom_cell!(machine_st.heap[s]).get_name_and_arity();
+
+                if name == atom!(".") && arity == 2 {
+                    keys.push(TrieKey::List);
+                } else {
+                    keys.push(TrieKey::Structure(name, arity));
+                }
+
+                for i in (1 ..= arity).rev() {
+                    stack.push(machine_st.heap[s + i]);
+                }
+            }
+            (HeapCellValueTag::Lis, l) => {
+                keys.push(TrieKey::List);
+                stack.push(machine_st.heap[l + 1]);
+                stack.push(machine_st.heap[l]);
+            }
+            (HeapCellValueTag::PStrLoc, h) => {
+                let tail = push_pstr_keys(&machine_st.heap, h, &mut keys);
+                stack.push(tail);
+            }
+            (HeapCellValueTag::CStr, cstr_atom) => {
+                push_char_keys(cstr_atom.as_str(), &mut keys);
+                keys.push(TrieKey::Atom(atom!("[]")));
+            }
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                keys.push(TrieKey::from_atom(name));
+            }
+            (HeapCellValueTag::Char, c) => {
+                keys.push(TrieKey::Char(c));
+            }
+            (HeapCellValueTag::Fixnum, n) => {
+                keys.push(TrieKey::Fixnum(n.get_num()));
+            }
+            (HeapCellValueTag::F64, f) => {
+                keys.push(TrieKey::Float(*f));
+            }
+            (HeapCellValueTag::Cons, ptr) => {
+                match_untyped_arena_ptr!(ptr,
+                    (ArenaHeaderTag::Integer, n) => {
+                        keys.push(TrieKey::Integer((*n).clone()));
+                    }
+                    (ArenaHeaderTag::Rational, r) => {
+                        keys.push(TrieKey::Rational(r.numerator().clone(), r.denominator().clone()));
+                    }
+                    _ => {
+                        keys.push(TrieKey::Cell(cell));
+                    }
+                )
+            }
+            _ => {
+                keys.push(TrieKey::Cell(cell));
+            }
+        );
+    }
+
+    keys
+}
+
+// the arguments of the moded answer `Args-Value` at `cell`.
+fn moded_answer_args(
+    machine_st: &MachineState,
+    cell: HeapCellValue,
+) -> (HeapCellValue, HeapCellValue) {
+    let cell = machine_st.store(machine_st.deref(cell));
+
+    read_heap_cell!(cell,
+        (HeapCellValueTag::Str, s) => {
+            (machine_st.heap[s + 1], machine_st.heap[s + 2])
+        }
+        _ => {
+            unreachable!()
+        }
+    )
+}
+
+// the literals of the delay list at `cell`, which are pos(Table, Answer)
+// and neg(Table) terms.
+fn read_delays(machine_st: &MachineState, cell: HeapCellValue) -> Vec<Delay> {
+    let mut delays = vec![];
+    let mut cell = machine_st.store(machine_st.deref(cell));
+
+    while let HeapCellValueTag::Lis = cell.get_tag() {
+        let l = cell.get_value() as usize;
+        let literal = machine_st.store(machine_st.deref(machine_st.heap[l]));
+
+        read_heap_cell!(literal,
+            (HeapCellValueTag::Str, s) => {
+                let arg = |i: usize| {
+                    let cell = machine_st.store(machine_st.deref(machine_st.heap[s + i]));
+                    cell_as_fixnum!(cell).get_num() as usize
+                };
+
+                match cell_as_atom_cell!(machine_st.heap[s]).get_name_and_arity() {
+                    (atom!("pos"), 2) => delays.push(Delay::Positive(arg(1), arg(2))),
+                    (atom!("neg"), 1) => delays.push(Delay::Negative(arg(1))),
+                    _ => unreachable!(),
+                }
+            }
+            _ => {
+                unreachable!()
+            }
+        );
+
+        cell = machine_st.store(machine_st.deref(machine_st.heap[l + 1]));
+    }
+
+    delays.sort_unstable();
+    delays.dedup();
+    delays
+}
+
+fn delay_to_heap(heap: &mut Heap, delay: Delay) -> HeapCellValue {
+    let h = heap.len();
+
+    match delay {
+        Delay::Positive(id, idx) => heap.extend(functor!(atom!("pos"), [fixnum(id), fixnum(idx)])),
+        Delay::Negative(id) => heap.extend(functor!(atom!("neg"), [fixnum(id)])),
+    }
+
+    str_loc_as_cell!(h)
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: IndexMap<TrieKey, usize, FxBuildHasher>,
+    // the table of a call, or the index of an answer in its table.
+    value: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableStatus {
+    Fresh,
+    Active,
+    Complete,
+}
+
+impl TableStatus {
+    fn as_atom(self) -> Atom {
+        match self {
+            TableStatus::Fresh => atom!("fresh"),
+            TableStatus::Active => atom!("active"),
+            TableStatus::Complete => atom!("complete"),
+        }
+    }
+}
+
+/// A literal of a delay list whose truth isn't yet known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Delay {
+    // an answer of a table, which is itself conditional.
+    Positive(usize, usize),
+    // the negation of the call of a table.
+    Negative(usize),
+}
+
+#[derive(Debug)]
+struct Answer {
+    term: Ball,
+    // the delay lists of the answer, which holds if all the literals of
+    // one of them do. The answer is unconditional if there are none.
+    conditions: Vec<Vec<Delay>>,
+}
+
+impl Answer {
+    #[inline]
+    fn is_unconditional(&self) -> bool {
+        self.conditions.is_empty()
+    }
+}
+
+// a continuation suspended on a table until it's run with each of the
+// table's answers.
+#[derive(Debug)]
+struct Dependency {
+    continuation: Ball,
+    consumed: usize,
+}
+
+#[derive(Debug)]
+struct Table {
+    variant: Ball,
+    // the tabled predicate, on behalf of which errors in the handling of
+    // its answers are thrown.
+    key: PredicateKey,
+    status: TableStatus,
+    answer_trie: usize,
+    // replaced and false answers are left as None, so the indices of
+    // the others don't change.
+    answers: Vec<Option<Answer>>,
+    dependencies: Vec<Dependency>,
+    // the first dependency that may not have consumed every answer.
+    cursor: usize,
+    in_worklist: bool,
+}
+
+impl Table {
+    #[inline]
+    fn has_work(&self) -> bool {
+        self.dependencies[self.cursor ..]
+            .iter()
+            .any(|dependency| dependency.consumed < self.answers.len())
+    }
+
+    // the literal the answer at `idx` is conditional on when consumed,
+    // or `true` if it's unconditional.
+    fn condition_to_heap(&self, id: usize, idx: usize, heap: &mut Heap) -> HeapCellValue {
+        match &self.answers[idx] {
+            Some(answer) if !answer.is_unconditional() => {
+                delay_to_heap(heap, Delay::Positive(id, idx))
+            }
+            _ => atom_as_cell!(atom!("true")),
+        }
+    }
+}
+
+// the root of the call trie, whose values are table ids.
+const CALL_TRIE: usize = 0;
+
+/// The tables of a machine. The tables under evaluation are all
+/// completed together, once their leader, the first tabled call made
+/// outside of any evaluation, has no work left.
+#[derive(Debug)]
+pub(crate) struct TableStore {
+    nodes: Vec<TrieNode>,
+    tables: Vec<Table>,
+    incomplete: Vec<usize>,
+    // the tables with answers not yet returned to every continuation
+    // suspended on them.
+    worklist: VecDeque<usize>,
+    leader: bool,
+    calls: usize,
+    completions: usize,
+    /// The tabled predicates of each module, whose clauses are renamed
+    /// by the loader so that calls go through library(tabling).
+    pub(crate) tabled: IndexSet<(Atom, PredicateKey), FxBuildHasher>,
+}
+
+impl Default for TableStore {
+    fn default() -> Self {
+        TableStore {
+            nodes: vec![TrieNode::default()],
+            tables: vec![],
+            incomplete: vec![],
+            worklist: VecDeque::new(),
+            leader: false,
+            calls: 0,
+            completions: 0,
+            tabled: IndexSet::with_hasher(FxBuildHasher::default()),
+        }
+    }
+}
+
+impl TableStore {
+    fn new_node(&mut self) -> usize {
+        self.nodes.push(TrieNode::default());
+        self.nodes.len() - 1
+    }
+
+    // the node at the end of the path of `keys` from `root`, created if
+    // there's none.
+    fn insert_keys(&mut self, root: usize, keys: &[TrieKey]) -> usize {
+        let mut node = root;
+
+        for key in keys {
+            node = match self.nodes[node].children.get(key) {
+                Some(&child) => child,
+                None => {
+                    let child = self.new_node();
+                    self.nodes[node].children.insert(key.clone(), child);
+                    child
+                }
+            };
+        }
+
+        node
+    }
+
+    fn find_keys(&self, root: usize, keys: &[TrieKey]) -> Option<usize> {
+        keys.iter()
+            .try_fold(root, |node, key| self.nodes[node].children.get(key).copied())
+    }
+
+    fn new_table(&mut self, variant: Ball, key: PredicateKey) -> usize {
+        let answer_trie = self.new_node();
+
+        self.tables.push(Table {
+            variant,
+            key,
+            status: TableStatus::Fresh,
+            answer_trie,
+            answers: vec![],
+            dependencies: vec![],
+            cursor: 0,
+            in_worklist: false,
+        });
+
+        self.tables.len() - 1
+    }
+
+    fn schedule(&mut self, id: usize) {
+        let table = &mut self.tables[id];
+
+        if !table.in_worklist && table.has_work() {
+            table.in_worklist = true;
+            self.worklist.push_back(id);
+        }
+    }
+
+    /// The atoms of the keys of the tries, for the atom collector.
+    pub(crate) fn trie_atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.nodes.iter().flat_map(|node| node.children.keys()).filter_map(|key| match key {
+            &TrieKey::Atom(name) | &TrieKey::Structure(name, _) => Some(name),
+            _ => None,
+        })
+    }
+
+    // the truth of `delay`, or None if it's still unknown.
+    fn delay_truth(&self, delay: Delay) -> Option<bool> {
+        match delay {
+            Delay::Positive(id, idx) => match &self.tables[id].answers[idx] {
+                None => Some(false),
+                Some(answer) if answer.is_unconditional() => Some(true),
+                Some(_) => None,
+            },
+            Delay::Negative(id) => {
+                let mut answers = self.tables[id].answers.iter().flatten().peekable();
+
+                if answers.peek().is_none() {
+                    Some(true)
+                } else if answers.any(Answer::is_unconditional) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // removes the literals of known truth from the delay lists of the
+    // answers of the tables in `scc`, returning true if any answer
+    // became unconditional or false.
+    fn simplify_answers(&mut self, scc: &[usize]) -> bool {
+        let mut changed = false;
+
+        for &id in scc {
+            for idx in 0 .. self.tables[id].answers.len() {
+                let conditions = match &self.tables[id].answers[idx] {
+                    Some(answer) if !answer.is_unconditional() => &answer.conditions,
+                    _ => continue,
+                };
+
+                let mut simplified = vec![];
+                let mut unconditional = false;
+
+                'conditions: for condition in conditions {
+                    let mut kept = vec![];
+
+                    for &delay in condition {
+                        match self.delay_truth(delay) {
+                            Some(true) => {}
+                            Some(false) => continue 'conditions,
+                            None => kept.push(delay),
+                        }
+                    }
+
+                    if kept.is_empty() {
+                        unconditional = true;
+                        break;
+                    }
+
+                    simplified.push(kept);
+                }
+
+                if unconditional {
+                    simplified.clear();
+                } else if simplified.is_empty() {
+                    self.tables[id].answers[idx] = None;
+                    changed = true;
+                    continue;
+                }
+
+                let answer = self.tables[id].answers[idx].as_mut().unwrap();
+
+                changed |= simplified.is_empty();
+                answer.conditions = simplified;
+            }
+        }
+
+        changed
+    }
+
+    // makes false the conditional answers of the tables in `scc` that
+    // only hold if they do, as none of their delay lists can be
+    // satisfied without them. Returns true if there were any.
+    fn remove_unfounded_answers(&mut self, scc: &[usize]) -> bool {
+        let in_scc: FxHashSet<usize> = scc.iter().copied().collect();
+        let mut supported: FxHashSet<(usize, usize)> = FxHashSet::default();
+
+        loop {
+            let mut grew = false;
+
+            for &id in scc {
+                for (idx, answer) in self.tables[id].answers.iter().enumerate() {
+                    let answer = match answer {
+                        Some(answer) if !supported.contains(&(id, idx)) => answer,
+                        _ => continue,
+                    };
+
+                    let is_supported = answer.is_unconditional()
+                        || answer.conditions.iter().any(|condition| {
+                            condition.iter().all(|&delay| match delay {
+                                Delay::Positive(id, idx) => {
+                                    !in_scc.contains(&id) || supported.contains(&(id, idx))
+                                }
+                                Delay::Negative(_) => true,
+                            })
+                        });
+
+                    if is_supported {
+                        supported.insert((id, idx));
+                        grew = true;
+                    }
+                }
+            }
+
+            if !grew {
+                break;
+            }
+        }
+
+        let mut changed = false;
+
+        for &id in scc {
+            for (idx, answer) in self.tables[id].answers.iter_mut().enumerate() {
+                if answer.is_some() && !supported.contains(&(id, idx)) {
+                    *answer = None;
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// The terms held by the tables, for the atom collector.
+    pub(crate) fn balls(&self) -> impl Iterator<Item = &Ball> + '_ {
+        self.tables.iter().flat_map(|table| {
+            std::iter::once(&table.variant)
+                .chain(table.answers.iter().flatten().map(|answer| &answer.term))
+                .chain(table.dependencies.iter().map(|dependency| &dependency.continuation))
+        })
+    }
+}
+
+impl Machine {
+    #[inline]
+    fn table_id(&self, r: usize) -> usize {
+        cell_as_fixnum!(self.deref_register(r)).get_num() as usize
+    }
+
+    // throws a representation error on behalf of the tabled predicate
+    // `key` if the term at `cell` is cyclic, as it has no end of
+    // variant keys.
+    fn check_acyclic(&mut self, cell: HeapCellValue, key: PredicateKey) -> CallResult {
+        if self.machine_st.is_cyclic_term(cell) {
+            let stub = functor_stub(key.0, key.1);
+            let err = self.machine_st.representation_error(RepFlag::Term);
+
+            return Err(self.machine_st.error_form(err, stub));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn add_tabled_predicate(&mut self) {
+        let module_name = cell_as_atom!(self.deref_register(1));
+        let key = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[2], self.machine_st.registers[3]);
+
+        self.tables.tabled.insert((module_name, key));
+    }
+
+    pub(crate) fn tabled_predicate(&mut self) {
+        let module_name = cell_as_atom!(self.deref_register(1));
+        let key = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[2], self.machine_st.registers[3]);
+
+        self.machine_st.fail = !self.tables.tabled.contains(&(module_name, key));
+    }
+
+    pub(crate) fn module_has_tabled_predicates(&mut self) {
+        let module_name = cell_as_atom!(self.deref_register(1));
+
+        self.machine_st.fail = !self
+            .tables
+            .tabled
+            .iter()
+            .any(|&(module, _)| module == module_name);
+    }
+
+    /// Unifies the id and the status of the table of the call variant
+    /// in the first register with the second and third registers. The
+    /// table is created if there's none.
+    pub(crate) fn tbl_variant_table(&mut self) -> CallResult {
+        let variant = self.machine_st.registers[1];
+
+        let (goal, _) = self.machine_st.strip_module(
+            self.machine_st.store(self.machine_st.deref(variant)),
+            empty_list_as_cell!(),
+        );
+        let key = self
+            .machine_st
+            .name_and_arity_from_heap(goal)
+            .unwrap_or((atom!("call"), 1));
+
+        self.check_acyclic(variant, key)?;
+
+        let keys = variant_keys(&self.machine_st, variant);
+        let node = self.tables.insert_keys(CALL_TRIE, &keys);
+
+        let id = match self.tables.nodes[node].value {
+            Some(id) => id,
+            None => {
+                let variant = self.machine_st.copy_to_ball(variant);
+                let id = self.tables.new_table(variant, key);
+
+                self.tables.nodes[node].value = Some(id);
+                id
+            }
+        };
+
+        self.tables.calls += 1;
+
+        let status = self.tables.tables[id].status.as_atom();
+        let a2 = self.machine_st.registers[2];
+        let a3 = self.machine_st.registers[3];
+
+        self.machine_st.unify_fixnum(Fixnum::build_with(id as i64), a2);
+
+        if !self.machine_st.fail {
+            self.machine_st.unify_atom(status, a3);
+        }
+
+        Ok(())
+    }
+
+    /// Marks the table in the first register active. It's completed
+    /// with the other tables under evaluation.
+    pub(crate) fn tbl_set_active(&mut self) {
+        let id = self.table_id(1);
+
+        self.tables.tables[id].status = TableStatus::Active;
+        self.tables.incomplete.push(id);
+    }
+
+    /// Adds the answer in the second register to the table in the
+    /// first, conditional on the delay list in the third. Fails if a
+    /// variant of it is already there, in which case the delay list is
+    /// added to its conditions, or the answer is made unconditional if
+    /// the delay list is empty.
+    pub(crate) fn tbl_add_answer(&mut self) -> CallResult {
+        let id = self.table_id(1);
+        let answer = self.machine_st.registers[2];
+        let delays = read_delays(&self.machine_st, self.machine_st.registers[3]);
+
+        self.check_acyclic(answer, self.tables.tables[id].key)?;
+
+        let keys = variant_keys(&self.machine_st, answer);
+        let node = self.tables.insert_keys(self.tables.tables[id].answer_trie, &keys);
+
+        if let Some(idx) = self.tables.nodes[node].value {
+            if let Some(answer) = &mut self.tables.tables[id].answers[idx] {
+                if delays.is_empty() {
+                    answer.conditions.clear();
+                } else if !answer.is_unconditional() && !answer.conditions.contains(&delays) {
+                    answer.conditions.push(delays);
+                }
+            }
+
+            self.machine_st.fail = true;
+            return Ok(());
+        }
+
+        let table = &mut self.tables.tables[id];
+
+        self.tables.nodes[node].value = Some(table.answers.len());
+
+        table.answers.push(Some(Answer {
+            term: self.machine_st.copy_to_ball(answer),
+            conditions: if delays.is_empty() { vec![] } else { vec![delays] },
+        }));
+
+        table.cursor = 0;
+
+        self.tables.schedule(id);
+
+        Ok(())
+    }
+
+    /// Unifies the third register with the moded argument of the answer
+    /// of the table in the first register for the variant of the other
+    /// arguments in the second. Fails if there's no such answer.
+    pub(crate) fn tbl_moded_answer(&mut self) -> CallResult {
+        let id = self.table_id(1);
+        let args = self.machine_st.registers[2];
+
+        self.check_acyclic(args, self.tables.tables[id].key)?;
+
+        let keys = variant_keys(&self.machine_st, args);
+
+        let answer = match self
+            .tables
+            .find_keys(self.tables.tables[id].answer_trie, &keys)
+            .and_then(|node| self.tables.nodes[node].value)
+            .and_then(|idx| self.tables.tables[id].answers[idx].as_ref())
+        {
+            Some(answer) => self.machine_st.ball_to_heap(&answer.term),
+            None => {
+                self.machine_st.fail = true;
+                return Ok(());
+            }
+        };
+
+        let (_, value) = moded_answer_args(&self.machine_st, answer);
+        unify!(self.machine_st, value, self.machine_st.registers[3]);
+
+        Ok(())
+    }
+
+    /// Makes the answer `Args-Value` in the second register the answer
+    /// of the table in the first register for the variant of `Args`,
+    /// conditional on the delay list in the third, and replacing the
+    /// previous one. Fails if a variant of it is already the answer.
+    pub(crate) fn tbl_set_moded_answer(&mut self) -> CallResult {
+        let id = self.table_id(1);
+        let answer = self.machine_st.registers[2];
+        let delays = read_delays(&self.machine_st, self.machine_st.registers[3]);
+
+        self.check_acyclic(answer, self.tables.tables[id].key)?;
+
+        let (args, _) = moded_answer_args(&self.machine_st, answer);
+        let keys = variant_keys(&self.machine_st, args);
+        let node = self.tables.insert_keys(self.tables.tables[id].answer_trie, &keys);
+
+        if let Some(idx) = self.tables.nodes[node].value {
+            if let Some(previous) = &self.tables.tables[id].answers[idx] {
+                let previous = self.machine_st.ball_to_heap(&previous.term);
+
+                if variant_keys(&self.machine_st, previous) == variant_keys(&self.machine_st, answer) {
+                    self.machine_st.fail = true;
+                    return Ok(());
+                }
+            }
+
+            self.tables.tables[id].answers[idx] = None;
+        }
+
+        let table = &mut self.tables.tables[id];
+
+        self.tables.nodes[node].value = Some(table.answers.len());
+
+        table.answers.push(Some(Answer {
+            term: self.machine_st.copy_to_ball(answer),
+            conditions: if delays.is_empty() { vec![] } else { vec![delays] },
+        }));
+
+        table.cursor = 0;
+
+        self.tables.schedule(id);
+
+        Ok(())
+    }
+
+    pub(crate) fn tbl_answer_count(&mut self) {
+        let id = self.table_id(1);
+        let count = self.tables.tables[id].answers.len();
+        let a2 = self.machine_st.registers[2];
+
+        self.machine_st.unify_fixnum(Fixnum::build_with(count as i64), a2);
+    }
+
+    /// Unifies the answer of the table in the first register at the
+    /// index in the second with the third register, and the fourth
+    /// with the literal the answer is conditional on, or `true`.
+    pub(crate) fn tbl_answer(&mut self) {
+        let id = self.table_id(1);
+        let idx = self.table_id(2);
+
+        // the tables may have been abolished since the answers were
+        // counted.
+        let answer = match self
+            .tables
+            .tables
+            .get(id)
+            .and_then(|table| table.answers.get(idx))
+            .and_then(Option::as_ref)
+        {
+            Some(answer) => self.machine_st.ball_to_heap(&answer.term),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let condition = self.tables.tables[id].condition_to_heap(id, idx, &mut self.machine_st.heap);
+
+        unify!(self.machine_st, answer, self.machine_st.registers[3]);
+
+        if !self.machine_st.fail {
+            unify!(self.machine_st, condition, self.machine_st.registers[4]);
+        }
+    }
+
+    /// Unifies the delay lists of the answer of the table in the first
+    /// register at the index in the second with the third register.
+    pub(crate) fn tbl_answer_conditions(&mut self) {
+        let id = self.table_id(1);
+        let idx = self.table_id(2);
+
+        let conditions = match self
+            .tables
+            .tables
+            .get(id)
+            .and_then(|table| table.answers.get(idx))
+            .and_then(Option::as_ref)
+        {
+            Some(answer) => answer.conditions.clone(),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let heap = &mut self.machine_st.heap;
+
+        let lists: Vec<_> = conditions
+            .into_iter()
+            .map(|condition| {
+                let delays: Vec<_> = condition
+                    .into_iter()
+                    .map(|delay| delay_to_heap(heap, delay))
+                    .collect();
+
+                heap_loc_as_cell!(iter_to_heap_list(heap, delays.into_iter()))
+            })
+            .collect();
+
+        let lists = heap_loc_as_cell!(iter_to_heap_list(heap, lists.into_iter()));
+        unify!(self.machine_st, lists, self.machine_st.registers[3]);
+    }
+
+    /// Unifies the second register with the truth of the negation of
+    /// the call of the table in the first register: `false` if the
+    /// table has an unconditional answer, `true` if it's complete and
+    /// has no answers, and `undefined` otherwise.
+    pub(crate) fn tbl_negation(&mut self) {
+        let id = self.table_id(1);
+        let table = &self.tables.tables[id];
+
+        let truth = if table.answers.iter().flatten().any(Answer::is_unconditional) {
+            atom!("false")
+        } else if table.status == TableStatus::Complete && table.answers.iter().flatten().next().is_none() {
+            atom!("true")
+        } else {
+            atom!("undefined")
+        };
+
+        let a2 = self.machine_st.registers[2];
+        self.machine_st.unify_atom(truth, a2);
+    }
+
+    /// Suspends the dependency in the second register on the table in
+    /// the first, to be run with each of the table's answers.
+    pub(crate) fn tbl_add_dependency(&mut self) {
+        let id = self.table_id(1);
+        let continuation = self.machine_st.copy_to_ball(self.machine_st.registers[2]);
+
+        self.tables.tables[id].dependencies.push(Dependency { continuation, consumed: 0 });
+        self.tables.schedule(id);
+    }
+
+    /// Unifies the first register with the next answer not yet returned
+    /// to a dependency, the second with the dependency, and the third
+    /// with the literal the answer is conditional on, or `true`. Fails
+    /// if there's no work left.
+    pub(crate) fn tbl_pop_work(&mut self) {
+        while let Some(&id) = self.tables.worklist.front() {
+            let table = &mut self.tables.tables[id];
+
+            while table.cursor < table.dependencies.len() {
+                let dependency = &mut table.dependencies[table.cursor];
+
+                if dependency.consumed < table.answers.len() {
+                    let idx = dependency.consumed;
+
+                    let answer = match &table.answers[idx] {
+                        Some(answer) => self.machine_st.ball_to_heap(&answer.term),
+                        None => {
+                            dependency.consumed += 1;
+                            continue;
+                        }
+                    };
+
+                    let continuation = self.machine_st.ball_to_heap(&dependency.continuation);
+
+                    dependency.consumed += 1;
+
+                    let condition = table.condition_to_heap(id, idx, &mut self.machine_st.heap);
+
+                    unify!(self.machine_st, answer, self.machine_st.registers[1]);
+
+                    if !self.machine_st.fail {
+                        unify!(self.machine_st, continuation, self.machine_st.registers[2]);
+                    }
+
+                    if !self.machine_st.fail {
+                        unify!(self.machine_st, condition, self.machine_st.registers[3]);
+                    }
+
+                    return;
+                }
+
+                table.cursor += 1;
+            }
+
+            table.in_worklist = false;
+            self.tables.worklist.pop_front();
+        }
+
+        self.machine_st.fail = true;
+    }
+
+    /// Succeeds if no tables are under evaluation, making the caller
+    /// the leader of the next evaluation.
+    pub(crate) fn tbl_create_scc(&mut self) {
+        if self.tables.leader {
+            self.machine_st.fail = true;
+        } else {
+            self.tables.leader = true;
+        }
+    }
+
+    /// Completes the tables under evaluation, dropping the
+    /// continuations suspended on them. The delay lists of their
+    /// answers are simplified until the answers left conditional are
+    /// undefined.
+    pub(crate) fn tbl_complete_scc(&mut self) {
+        let scc = mem::take(&mut self.tables.incomplete);
+
+        while self.tables.simplify_answers(&scc) || self.tables.remove_unfounded_answers(&scc) {}
+
+        for id in scc {
+            let table = &mut self.tables.tables[id];
+
+            table.status = TableStatus::Complete;
+            table.dependencies = vec![];
+            table.cursor = 0;
+            table.in_worklist = false;
+        }
+
+        self.tables.worklist.clear();
+        self.tables.leader = false;
+        self.tables.completions += 1;
+    }
+
+    /// Resets the tables under evaluation to fresh ones, after their
+    /// evaluation is abandoned by an exception.
+    pub(crate) fn tbl_abandon_scc(&mut self) {
+        for id in mem::take(&mut self.tables.incomplete) {
+            let answer_trie = self.tables.new_node();
+            let table = &mut self.tables.tables[id];
+
+            table.status = TableStatus::Fresh;
+            table.answer_trie = answer_trie;
+            table.answers = vec![];
+            table.dependencies = vec![];
+            table.cursor = 0;
+            table.in_worklist = false;
+        }
+
+        self.tables.worklist.clear();
+        self.tables.leader = false;
+    }
+
+    /// Removes all tables, failing if tables are under evaluation.
+    pub(crate) fn tbl_abolish_all_tables(&mut self) {
+        if self.tables.leader {
+            self.machine_st.fail = true;
+        } else {
+            let tabled = mem::take(&mut self.tables.tabled);
+            self.tables = TableStore { tabled, ..TableStore::default() };
+        }
+    }
+
+    /// Unifies the call variant of the table in the first register
+    /// with the second register.
+    pub(crate) fn tbl_variant(&mut self) {
+        let id = self.table_id(1);
+
+        let variant = match self.tables.tables.get(id) {
+            Some(table) => self.machine_st.ball_to_heap(&table.variant),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        unify!(self.machine_st, variant, self.machine_st.registers[2]);
+    }
+
+    /// Unifies the statistic of the tables named by the atom in the
+    /// first register with the second register.
+    pub(crate) fn tbl_statistics(&mut self) {
+        let key = cell_as_atom!(self.deref_register(1));
+        let tables = &self.tables;
+
+        let value = match key {
+            atom!("tables") => tables.tables.len(),
+            atom!("complete_tables") => tables
+                .tables
+                .iter()
+                .filter(|table| table.status == TableStatus::Complete)
+                .count(),
+            atom!("answers") => tables
+                .tables
+                .iter()
+                .map(|table| table.answers.iter().flatten().count())
+                .sum(),
+            atom!("conditional_answers") => tables
+                .tables
+                .iter()
+                .map(|table| {
+                    table
+                        .answers
+                        .iter()
+                        .flatten()
+                        .filter(|answer| !answer.is_unconditional())
+                        .count()
+                })
+                .sum(),
+            atom!("trie_nodes") => tables.nodes.len(),
+            atom!("calls") => tables.calls,
+            atom!("completions") => tables.completions,
+            _ => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let a2 = self.machine_st.registers[2];
+        self.machine_st.unify_fixnum(Fixnum::build_with(value as i64), a2);
+    }
+}
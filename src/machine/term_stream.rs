@@ -3,6 +3,7 @@
 use crate::machine::load_state::*;
 use crate::machine::loader::*;
 use crate::machine::machine_errors::*;
+use crate::machine::machine_indices::TermPosition;
 use crate::parser::ast::*;
 use crate::parser::parser::*;
 use crate::read::devour_whitespace;
@@ -20,6 +21,7 @@ pub struct LoadStatePayload<TS> {
     pub(super) retraction_info: RetractionInfo,
     pub(super) module_op_exports: ModuleOpExports,
     pub(super) non_counted_bt_preds: IndexSet<PredicateKey>,
+    pub(super) fact_table_preds: IndexSet<PredicateKey>,
     pub(super) predicates: PredicateQueue,
     pub(super) clause_clauses: Vec<(Term, Term)>,
 }
@@ -28,12 +30,16 @@ pub trait TermStream: Sized {
     fn next(&mut self, op_dir: &CompositeOpDir) -> Result<Term, CompilationError>;
     fn eof(&mut self) -> Result<bool, CompilationError>;
     fn listing_src(&self) -> &ListingSource;
+    /// The position at which the term last read by `next` starts, if
+    /// known.
+    fn term_position(&self) -> Option<TermPosition>;
 }
 
 #[derive(Debug)]
 pub struct BootstrappingTermStream<'a> {
     listing_src: ListingSource,
     pub(super) parser: Parser<'a, Stream>,
+    term_position: TermPosition,
 }
 
 impl<'a> BootstrappingTermStream<'a> {
@@ -44,13 +50,21 @@ pub(super) fn from_char_reader(
         listing_src: ListingSource,
     ) -> Self {
         let parser = Parser::new(stream, machine_st);
-        Self { parser, listing_src }
+        let term_position = TermPosition { line: 1, col: 1 };
+        Self { parser, listing_src, term_position }
     }
 }
 
 impl<'a> TermStream for BootstrappingTermStream<'a> {
     #[inline]
     fn next(&mut self, op_dir: &CompositeOpDir) -> Result<Term, CompilationError> {
+        // eof() has skipped the layout before the term, so the parser
+        // is at its first token.
+        self.term_position = TermPosition {
+            line: self.parser.line_num() + 1,
+            col: self.parser.col_num() + 1,
+        };
+
         self.parser.reset();
         self.parser
             .read_term(op_dir, Tokens::Default)
@@ -67,11 +81,18 @@ fn eof(&mut self) -> Result<bool, CompilationError> {
     fn listing_src(&self) -> &ListingSource {
         &self.listing_src
     }
+
+    #[inline]
+    fn term_position(&self) -> Option<TermPosition> {
+        Some(self.term_position)
+    }
 }
 
 pub struct LiveTermStream {
-    pub(super) term_queue: VecDeque<Term>,
+    // the terms to be loaded, with the positions they were read from.
+    pub(super) term_queue: VecDeque<(Term, Option<TermPosition>)>,
     pub(super) listing_src: ListingSource,
+    term_position: Option<TermPosition>,
 }
 
 impl LiveTermStream {
@@ -80,6 +101,7 @@ pub(super) fn new(listing_src: ListingSource) -> Self {
         Self {
             term_queue: VecDeque::new(),
             listing_src,
+            term_position: None,
         }
     }
 }
@@ -98,6 +120,7 @@ pub(super) fn new(code_repo_len: usize, term_stream: TS) -> Self {
             retraction_info: RetractionInfo::new(code_repo_len),
             module_op_exports: vec![],
             non_counted_bt_preds: IndexSet::new(),
+            fact_table_preds: IndexSet::new(),
             predicates: predicate_queue![],
             clause_clauses: vec![],
         }
@@ -107,7 +130,9 @@ pub(super) fn new(code_repo_len: usize, term_stream: TS) -> Self {
 impl TermStream for LiveTermStream {
     #[inline]
     fn next(&mut self, _: &CompositeOpDir) -> Result<Term, CompilationError> {
-        Ok(self.term_queue.pop_front().unwrap())
+        let (term, position) = self.term_queue.pop_front().unwrap();
+        self.term_position = position;
+        Ok(term)
     }
 
     #[inline]
@@ -119,6 +144,11 @@ fn eof(&mut self) -> Result<bool, CompilationError> {
     fn listing_src(&self) -> &ListingSource {
         &self.listing_src
     }
+
+    #[inline]
+    fn term_position(&self) -> Option<TermPosition> {
+        self.term_position
+    }
 }
 
 pub struct InlineTermStream {
@@ -136,5 +166,9 @@ fn eof(&mut self) -> Result<bool, CompilationError> {
     fn listing_src(&self) -> &ListingSource {
 	    &ListingSource::User
     }
+
+    fn term_position(&self) -> Option<TermPosition> {
+	    None
+    }
 }
 
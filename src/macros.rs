@@ -586,10 +586,14 @@ macro_rules! index_store {
     ($code_dir:expr, $op_dir:expr, $modules:expr) => {
         IndexStore {
             code_dir: $code_dir,
+            clause_sources: ClauseSourceDir::new(),
             extensible_predicates: ExtensiblePredicates::with_hasher(FxBuildHasher::default()),
+            fact_tables: vec![],
             local_extensible_predicates: LocalExtensiblePredicates::with_hasher(FxBuildHasher::default()),
             global_variables: GlobalVarDir::with_hasher(FxBuildHasher::default()),
             goal_expansion_indices: GoalExpansionIndices::with_hasher(FxBuildHasher::default()),
+            jit_indices: JitIndexDir::with_hasher(FxBuildHasher::default()),
+            jit_unindexable: BitSet::default(),
             meta_predicates: MetaPredicateDir::with_hasher(FxBuildHasher::default()),
             modules: $modules,
             op_dir: $op_dir,
@@ -304,6 +304,7 @@ pub fn arity(self) -> usize {
 pub struct MachineFlags {
     pub double_quotes: DoubleQuotes,
     pub unknown: Unknown,
+    pub backtrace: bool,
 }
 
 impl Default for MachineFlags {
@@ -311,6 +312,7 @@ fn default() -> Self {
         MachineFlags {
             double_quotes: DoubleQuotes::default(),
             unknown: Unknown::default(),
+            backtrace: false,
         }
     }
 }
@@ -62,7 +62,7 @@ pub(crate) fn read(
         mut inner: Stream,
         op_dir: &OpDir,
     ) -> Result<TermWriteResult, CompilationError> {
-        let (term, num_lines_read) = {
+        let (term, num_lines_read, col_num) = {
             let prior_num_lines_read = inner.lines_read();
             let mut parser = Parser::new(inner, self);
             let op_dir = CompositeOpDir::new(op_dir, None);
@@ -72,10 +72,11 @@ pub(crate) fn read(
             let term = parser.read_term(&op_dir, Tokens::Default)
                 .map_err(|err| error_after_read_term(err, prior_num_lines_read, &parser))?; // CompilationError::from
 
-            (term, parser.lines_read() - prior_num_lines_read)
+            (term, parser.lines_read() - prior_num_lines_read, parser.col_num())
         };
 
         inner.add_lines_read(num_lines_read);
+        inner.advance_col_num(num_lines_read, col_num);
         write_term_to_heap(&term, &mut self.heap, &mut self.atom_tbl)
     }
 }
@@ -0,0 +1,56 @@
+:- module(tests_on_backtrace, []).
+
+:- use_module(library(iso_ext)).
+
+bt_inner(1, 0).
+bt_inner(N, L) :-
+    N > 1,
+    atom_length(_, L0),
+    L is L0 + 1.
+
+bt_outer(L) :-
+    bt_inner(2, L0),
+    L is L0 + 1.
+
+bt_choice(X, Backtrace) :-
+    (  X = 1
+    ;  X = 2
+    ),
+    current_prolog_flag(backtrace, Backtrace).
+
+test_queries_on_backtrace :-
+    current_prolog_flag(backtrace, false),
+    catch(set_prolog_flag(backtrace, on),
+          error(domain_error(flag_value, backtrace + on), _),
+          true),
+    catch_with_backtrace(bt_outer(_),
+                         error(instantiation_error, backtrace(atom_length/2, Frames)),
+                         true),
+    Frames = [Frame,
+              frame(tests_on_backtrace:bt_outer/1, 1, File, 11)|_],
+    Frame = frame(tests_on_backtrace:bt_inner/2, 2, File, 6),
+    sub_atom(File, _, _, 0, 'backtrace.pl'),
+    current_prolog_flag(backtrace, false),
+    % a ball caught and thrown again keeps its backtrace.
+    catch_with_backtrace(catch(bt_outer(_), E, throw(E)),
+                         error(_, backtrace(_, [Frame|_])),
+                         true),
+    % balls are unchanged by catch/3, and by catch_with_backtrace/3 if
+    % they don't unify with its catcher.
+    catch(catch_with_backtrace(bt_outer(_), ball, true),
+          error(instantiation_error, atom_length/2),
+          true),
+    % the flag is on in the goal, on backtracking into it too, and
+    % restored at each of its exits.
+    findall(X-InGoal-AfterGoal,
+            (  catch_with_backtrace(bt_choice(X, InGoal), _, true),
+               current_prolog_flag(backtrace, AfterGoal)
+            ),
+            Solutions),
+    Solutions == [1-true-false, 2-true-false],
+    current_prolog_flag(backtrace, false),
+    set_prolog_flag(backtrace, true),
+    catch(bt_outer(_), error(instantiation_error, atom_length/2), true),
+    set_prolog_flag(backtrace, false).
+
+:- initialization(test_queries_on_backtrace).
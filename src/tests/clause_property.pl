@@ -0,0 +1,47 @@
+:- module(tests_on_clause_property, []).
+
+cp_fact(a).
+cp_fact(b).
+
+cp_rule(X) :-
+    cp_fact(X).
+
+:- dynamic(cp_dynamic/1).
+
+cp_dynamic(1).
+
+:- dynamic(cp_abolished/1).
+
+cp_abolished(1).
+
+test_queries_on_clause_property :-
+    M = tests_on_clause_property,
+    findall(N, nth_clause(M:cp_fact(_), N, _), [1, 2]),
+    nth_clause(M:cp_fact(_), 2, Ref),
+    clause_property(Ref, line_count(4)),
+    clause_property(Ref, file(File)),
+    sub_atom(File, _, _, 0, 'clause_property.pl'),
+    clause_property(Ref, predicate(M:cp_fact/1)),
+    predicate_property(M:cp_rule(_), line_count(6)),
+    predicate_property(M:cp_rule(_), file(File)),
+    % asserted clauses have no file or line.
+    assertz(M:cp_dynamic(2)),
+    nth_clause(M:cp_dynamic(_), 1, Ref1),
+    clause_property(Ref1, line_count(11)),
+    nth_clause(M:cp_dynamic(_), 2, Ref2),
+    clause_property(Ref2, predicate(M:cp_dynamic/1)),
+    \+ clause_property(Ref2, line_count(_)),
+    \+ clause_property(Ref2, file(_)),
+    % retracted and abolished clauses have no properties.
+    retract(M:cp_dynamic(1)),
+    \+ clause_property(Ref1, _),
+    nth_clause(M:cp_abolished(_), 1, Ref3),
+    clause_property(Ref3, line_count(15)),
+    abolish(M:cp_abolished/1),
+    \+ clause_property(Ref3, _),
+    catch(clause_property(_, _), error(instantiation_error, _), true),
+    catch(clause_property(cp_fact, _),
+          error(type_error(clause_reference, cp_fact), _),
+          true).
+
+:- initialization(test_queries_on_clause_property).
@@ -0,0 +1,28 @@
+:- module(tests_on_coroutines, []).
+
+:- use_module(library(coroutines)).
+:- use_module(library(lists)).
+
+sum_to(N, Main) :-
+    numlist(1, N, Ns),
+    sum_list(Ns, S),
+    coroutine_send_message(Main, sum(N, S)).
+
+test_queries_on_coroutines :-
+    coroutine_self(Main),
+    Main == main,
+    findall(Id, (member(N, [10, 20, 30]), coroutine_create(sum_to(N, Main), Id, [])), Ids),
+    findall(N-S, (member(_, Ids), coroutine_get_message(sum(N, S))), Sums),
+    msort(Sums, [10-55, 20-210, 30-465]),
+    maplist(coroutine_join, Ids, [true, true, true]),
+    coroutine_create(fail, A, []),
+    coroutine_join(A, false),
+    coroutine_create(throw(oops), B, [alias(thrower)]),
+    B == thrower,
+    coroutine_join(thrower, exception(oops)),
+    % no other coroutine is left to send a message.
+    catch((coroutine_get_message(_), false),
+          error(system_error(deadlock), _),
+          true).
+
+:- initialization(test_queries_on_coroutines).
@@ -0,0 +1,44 @@
+:- module(tests_on_coverage, []).
+
+:- use_module(library(coverage)).
+:- use_module(library(dcgs)).
+:- use_module(library(files)).
+:- use_module(library(lists)).
+:- use_module(library(pio)).
+
+cov_color(red).
+cov_color(green).
+cov_color(blue).
+
+cov_warm(C) :-
+    cov_color(C),
+    C \== blue.
+
+lcov_coverage(Goal, Info) :-
+    coverage(Goal, [lcov("coverage.info")]),
+    phrase_from_file(seq(Info), "coverage.info"),
+    delete_file("coverage.info").
+
+lcov_record(Info, Record) :-
+    append(_, Suffix, Info),
+    append(Record, _, Suffix),
+    !.
+
+test_queries_on_coverage :-
+    lcov_coverage(cov_warm(green), Info),
+    lcov_record(Info, "coverage.pl\n"),
+    lcov_record(Info, "DA:9,0\nDA:10,1\nDA:11,0\nDA:13,1\n"),
+    lcov_record(Info, "FNDA:1,tests_on_coverage:cov_color/1\n"),
+    % cov_warm/1 exits without failing.
+    lcov_record(Info, "BRDA:13,3,0,1\nBRDA:13,3,1,0\n"),
+    lcov_record(Info, "BRDA:9,0,0,-\n"),
+    lcov_coverage(findall(C, cov_warm(C), _), Info1),
+    % the clauses of cov_color/1 fail as its choice point is resumed
+    % at the next clause, but for the last.
+    lcov_record(Info1, "BRDA:9,0,0,1\nBRDA:9,0,1,1\nBRDA:10,1,0,1\nBRDA:10,1,1,1\nBRDA:11,2,0,1\nBRDA:11,2,1,0\n"),
+    lcov_record(Info1, "BRDA:13,3,0,2\nBRDA:13,3,1,0\n"),
+    \+ coverage(false),
+    catch(coverage(throw(ball)), ball, true),
+    catch(coverage(true, [lcov(1)]), error(domain_error(coverage_option, lcov(1)), _), true).
+
+:- initialization(test_queries_on_coverage).
@@ -0,0 +1,47 @@
+:- module(tests_on_debugger, []).
+
+:- use_module(library(debug)).
+:- use_module(library(lists)).
+
+:- dynamic(fact/1).
+
+fact(a).
+fact(b).
+
+app([], Ys, Ys).
+app([X|Xs], Ys, [X|Zs]) :-
+    app(Xs, Ys, Zs).
+
+first_fact(X) :-
+    fact(X),
+    !.
+
+facts(Xs) :-
+    findall(X, fact(X), Xs).
+
+ball :-
+    throw(ball).
+
+test_queries_on_debugger :-
+    visible(none),
+    leash(none),
+    trace,
+    findall(Xs-Ys, app(Xs, Ys, [a,b]), Splits),
+    first_fact(X),
+    maplist(fact, [a, b]),
+    catch(ball, E, true),
+    assertz(fact(c)),
+    facts(Facts),
+    notrace(\+ fact(d)),
+    \+ fact(d),
+    notrace,
+    Splits == [[]-[a,b], [a]-[b], [a,b]-[]],
+    X == a,
+    E == ball,
+    Facts == [a, b, c],
+    catch(leash(nowhere), error(domain_error(port, nowhere), leash/1), true),
+    catch(visible(_), error(instantiation_error, visible/1), true),
+    visible(all),
+    leash(all).
+
+:- initialization(test_queries_on_debugger).
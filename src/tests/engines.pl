@@ -0,0 +1,21 @@
+:- module(tests_on_engines, []).
+
+:- use_module(library(between)).
+:- use_module(library(engines)).
+:- use_module(library(lists)).
+
+test_queries_on_engines :-
+    engine_create(X, member(X, [a,b,c]), E1),
+    engine_create(Y, (between(1, 3, Y), engine_yield(before(Y))), E2),
+    findall(T, (member(E, [E1,E2,E1,E2,E1,E2]), engine_next(E, T)), Ts),
+    Ts == [a, before(1), b, 1, c, before(2)],
+    findall(T, engine_next(E1, T), []),
+    engine_destroy(E2),
+    catch((engine_next(E2, _), false),
+          error(existence_error(engine, E2), _),
+          true),
+    engine_create(_, throw(oops), E3),
+    catch((engine_next(E3, _), false), oops, true),
+    \+ engine_next(E3, _).
+
+:- initialization(test_queries_on_engines).
@@ -0,0 +1,46 @@
+:- module(tests_on_fact_tables, []).
+
+:- use_module(library(between)).
+
+user:term_expansion(nums, Facts) :-
+    findall(num(N), between(1, 256, N), Facts).
+
+:- fact_table(edge/2).
+
+edge(a, b).
+edge(a, c).
+edge(b, c).
+edge(c, d).
+edge(d, f(a, [1,2])).
+edge("str", 1.5).
+
+:- fact_table(rule/1).
+
+rule(X) :- X = 1.
+
+nums.
+
+test_queries_on_fact_tables :-
+    findall(X-Y, edge(X, Y), [a-b, a-c, b-c, c-d, d-f(a, [1,2]), "str"-1.5]),
+    findall(Y, edge(a, Y), [b, c]),
+    findall(X, edge(X, c), [a, b]),
+    \+ edge(a, d),
+    edge(d, f(A, [B|_])),
+    A == a,
+    B == 1,
+    \+ edge(d, f(b, _)),
+    edge("str", F),
+    F == 1.5,
+    findall(X, edge(X, 1.5), ["str"]),
+    findall(Body, clause(tests_on_fact_tables:edge(a, _), Body), [true, true]),
+    \+ clause(tests_on_fact_tables:edge(a, _), fail),
+    predicate_property(tests_on_fact_tables:edge(_, _), fact_table),
+    \+ predicate_property(tests_on_fact_tables:rule(_), fact_table),
+    rule(1),
+    num(256),
+    \+ predicate_property(tests_on_fact_tables:num(_), fact_table),
+    catch((clause(tests_on_fact_tables:num(_), _), false),
+          error(permission_error(access, private_procedure, num/1), _),
+          true).
+
+:- initialization(test_queries_on_fact_tables).
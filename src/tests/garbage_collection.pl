@@ -0,0 +1,71 @@
+:- module(tests_on_garbage_collection, []).
+
+:- use_module(library(dif)).
+:- use_module(library(engines)).
+:- use_module(library(lists)).
+:- use_module(library(coroutines)).
+
+% builds and drops a list of 100 elements on each iteration.
+churn(0) :- !.
+churn(N) :-
+    length(Xs, 100),
+    maplist(=(x), Xs),
+    N1 is N - 1,
+    churn(N1).
+
+test_queries_on_garbage_collection :-
+    statistics(garbage_collection, [C0, _, _]),
+    X = f(Y, "a partial string", [a,b|T]),
+    churn(10),
+    garbage_collect,
+    statistics(garbage_collection, [C1, R1, _]),
+    C1 =:= C0 + 1,
+    R1 > 0,
+    X == f(Y, "a partial string", [a,b|T]),
+    Y = y,
+    X == f(y, "a partial string", [a,b|T]),
+    G = g(V1, V2),
+    compare(O1, V1, V2),
+    garbage_collect,
+    compare(O2, V1, V2),
+    O1 == O2,
+    G == g(V1, V2),
+    findall(Z, (member(Z, [1,2,3]), churn(10), garbage_collect), [1,2,3]),
+    dif(A, b),
+    garbage_collect,
+    \+ A = b,
+    A = c,
+    churn(20000),
+    statistics(garbage_collection, [C2, _, _]),
+    C2 > C1 + 1,
+    catch(statistics(no_such_key, _),
+          error(domain_error(statistics_key, no_such_key), _),
+          true).
+
+% the atoms are built by atom_concat/3 so that no clause refers to
+% them.
+parked_atoms(X) :-
+    atom_concat(parked_, atom, A),
+    member(X, [first, A]).
+
+test_atom_collection_with_engines_and_coroutines :-
+    engine_create(X, parked_atoms(X), E),
+    engine_next(E, first),
+    coroutine_self(Self),
+    (  atom_concat(queued_, atom, Q),
+       coroutine_send_message(Self, Q),
+       fail
+    ;  true
+    ),
+    statistics(atom_garbage_collection, [C0, _, _]),
+    garbage_collect_atoms,
+    statistics(atom_garbage_collection, [C1, _, _]),
+    C1 =:= C0 + 1,
+    engine_next(E, A),
+    atom_concat(parked_, atom, A),
+    coroutine_get_message(M),
+    atom_concat(queued_, atom, M),
+    engine_destroy(E).
+
+:- initialization(test_queries_on_garbage_collection).
+:- initialization(test_atom_collection_with_engines_and_coroutines).
@@ -0,0 +1,97 @@
+:- module(tests_on_jit_index, []).
+
+:- use_module(library(between)).
+:- use_module(library(lists)).
+
+:- dynamic(p/3).
+:- dynamic(q/2).
+:- dynamic(e/1).
+:- dynamic(g/2).
+
+test_jit_index_follows_clause_changes :-
+    (  between(1, 20, N), K is N mod 4, assertz(p(N, K, N)), false
+    ;  true
+    ),
+    findall(N, (member(K, [0,2,3]), p(N, K, _)), _),
+    findall(N, p(N, 1, _), [1,5,9,13,17]),
+    predicate_property(tests_on_jit_index:p(_, _, _), indexed([2])),
+    findall(N-K, p(N, K, 10), [10-2]),
+    assertz(p(21, 1, 21)),
+    asserta(p(0, 1, 0)),
+    retract(p(5, 1, _)),
+    \+ predicate_property(tests_on_jit_index:p(_, _, _), indexed(_)),
+    findall(N, p(N, 1, _), [0,1,9,13,17,21]),
+    findall(N, (p(N, 1, _), assertz(p(22, 1, 22))), [0,1,9,13,17,21]),
+    findall(N, (member(K, [0,2,3]), p(N, K, _)), _),
+    findall(N, p(N, 1, _), [0,1,9,13,17,21,22,22,22,22,22,22]),
+    predicate_property(tests_on_jit_index:p(_, _, _), indexed([2])).
+
+test_jit_index_leaves_deterministic_calls_to_first_argument_index :-
+    (  between(1, 20, N), K is N mod 4, assertz(q(N, K)), false
+    ;  true
+    ),
+    findall(K, (between(1, 20, N), q(N, K)), Ks),
+    length(Ks, 20),
+    \+ predicate_property(tests_on_jit_index:q(_, _), indexed(_)).
+
+test_jit_index_looks_into_compound_arguments :-
+    (  between(1, 16, N), K is N mod 4,
+       (  N < 9 -> T = op(K, N)
+       ;  T = [K|N]
+       ),
+       assertz(e(T)),
+       false
+    ;  true
+    ),
+    findall(N, (member(K, [0,2,3]), e(op(K, N))), _),
+    findall(N, e(op(1, N)), [1,5]),
+    findall(N, e([1|N]), [9,13]),
+    findall(K, e(op(K, 6)), [2]),
+    predicate_property(tests_on_jit_index:e(_), indexed([1])).
+
+small_eval(op(add, X, Y), Z) :- Z is X + Y.
+small_eval(op(mul, X, Y), Z) :- Z is X * Y.
+small_eval([neg|X], Y) :- Y is -X.
+small_eval([abs|X], Y) :- Y is abs(X).
+
+rare_eval(op(0, X), X).
+rare_eval(op(1, X), X).
+rare_eval(op(2, X), X).
+rare_eval(op(3, X), X).
+rare_eval(op(4, X), X).
+rare_eval(op(5, X), X).
+rare_eval(op(6, X), X).
+rare_eval(op(7, X), X).
+
+% compound arguments are only looked into by the just-in-time index,
+% which predicates with few clauses or few calls go without.
+test_jit_index_leaves_small_and_rarely_called_predicates_alone :-
+    findall(Z, (between(1, 5, _), small_eval(op(mul, 3, 4), Z)), [12,12,12,12,12]),
+    findall(Y, small_eval([abs|-2], Y), [2]),
+    findall(Z, small_eval(op(sub, 1, 2), Z), []),
+    \+ predicate_property(tests_on_jit_index:small_eval(_, _), indexed(_)),
+    findall(Y, rare_eval(op(6, a), Y), [a]),
+    findall(K-Y, rare_eval(op(K, b), Y), [0-b,1-b,2-b,3-b,4-b,5-b,6-b,7-b]),
+    \+ predicate_property(tests_on_jit_index:rare_eval(_, _), indexed(_)),
+    findall(Y, rare_eval(op(2, c), Y), [c]),
+    findall(Y, rare_eval(op(3, d), Y), [d]),
+    predicate_property(tests_on_jit_index:rare_eval(_, _), indexed([1])).
+
+test_jit_index_reconsiders_predicates_once_they_grow :-
+    (  between(1, 3, N), K is N mod 4, assertz(g(N, K)), false
+    ;  true
+    ),
+    findall(N, (between(1, 5, _), g(N, 1)), [1,1,1,1,1]),
+    \+ predicate_property(tests_on_jit_index:g(_, _), indexed(_)),
+    (  between(4, 12, N), K is N mod 4, assertz(g(N, K)), false
+    ;  true
+    ),
+    findall(N, (member(K, [0,2,3]), g(N, K)), _),
+    findall(N, g(N, 1), [1,5,9]),
+    predicate_property(tests_on_jit_index:g(_, _), indexed([2])).
+
+:- initialization(test_jit_index_follows_clause_changes).
+:- initialization(test_jit_index_leaves_deterministic_calls_to_first_argument_index).
+:- initialization(test_jit_index_looks_into_compound_arguments).
+:- initialization(test_jit_index_reconsiders_predicates_once_they_grow).
+:- initialization(test_jit_index_leaves_small_and_rarely_called_predicates_alone).
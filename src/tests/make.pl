@@ -0,0 +1,66 @@
+:- module(tests_on_make, []).
+
+:- use_module(library(between)).
+:- use_module(library(files)).
+:- use_module(library(format)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+:- use_module(library(os)).
+:- use_module(library(time)).
+
+write_source(File, Source) :-
+    open(File, write, Stream),
+    format(Stream, "~s", [Source]),
+    close(Stream).
+
+make_temp_directory(Dir) :-
+    (  getenv("TMPDIR", Tmp) ->
+       true
+    ;  Tmp = "/tmp"
+    ),
+    append(Tmp, "/scryer_tests_on_make", Dir),
+    make_directory_path(Dir).
+
+delete_temp_directory(Dir) :-
+    directory_files(Dir, Names),
+    maplist(delete_temp_file(Dir), Names),
+    delete_directory(Dir).
+
+delete_temp_file(Dir, Name) :-
+    append(Dir, [/|Name], File),
+    delete_file(File).
+
+test_queries_on_make :-
+    setup_call_cleanup(make_temp_directory(Dir),
+                       make_queries(Dir),
+                       delete_temp_directory(Dir)).
+
+make_queries(Dir) :-
+    append(Dir, "/make_source.pl", File),
+    atom_chars(Path, File),
+    write_source(File, ":- module(make_source, [make_value/1, make_other/1]).\nmake_value(1).\nmake_other(1).\n"),
+    use_module(Path, [make_value/1]),
+    user:make_value(1),
+    % nothing is reloaded if nothing is modified.
+    make,
+    user:make_value(1),
+    % file times may only be kept to the second.
+    sleep(1),
+    write_source(File, ":- module(make_source, [make_value/1, make_other/1]).\nmake_value(2).\nmake_other(2).\n"),
+    % the loaded files are kept by their names through a collection
+    % that reuses the slots of the atoms it collects.
+    garbage_collect_atoms,
+    forall(between(1, 1000, N), (number_codes(N, Cs), atom_codes(_, [0'm|Cs]))),
+    make,
+    user:make_value(2),
+    \+ user:make_value(1),
+    % the file is imported again with its import list.
+    catch((user:make_other(_), false),
+          error(existence_error(procedure, make_other/1), _),
+          true),
+    % files that no longer exist are left as they were loaded.
+    delete_file(File),
+    make,
+    user:make_value(2).
+
+:- initialization(test_queries_on_make).
@@ -0,0 +1,8 @@
+:- module(colors, [describe/2, primary/1]).
+
+describe(red, warm).
+describe(blue, cold).
+
+primary(red).
+primary(yellow).
+primary(blue).
@@ -0,0 +1,6 @@
+:- module(facade, [facade_version/1]).
+
+:- reexport(shapes).
+:- reexport(colors, [primary/1, describe/2 as color_description]).
+
+facade_version(1).
@@ -0,0 +1,9 @@
+:- module(shapes, [area/2, describe/2, op(200, xfx, by)]).
+
+area(square(S), A) :-
+    A is S * S.
+area(W by H, A) :-
+    A is W * H.
+
+describe(square(_), square).
+describe(_ by _, rectangle).
@@ -0,0 +1,38 @@
+:- module(tests_on_modules, []).
+
+:- use_module(library(lists)).
+
+:- use_module(facade).
+:- use_module(colors, except([describe/2])).
+
+members([], _).
+members([X | Xs], Ys) :-
+    memberchk(X, Ys),
+    members(Xs, Ys).
+
+test_queries_on_modules :-
+    % the exports of shapes and colors are imported through facade.
+    area(square(3), 9),
+    area(by(2, 3), 6),
+    describe(by(2, 3), rectangle),
+    color_description(red, warm),
+    primary(yellow),
+    facade_version(1),
+    module_property(facade, exports(PIs)),
+    members([facade_version/1, area/2, describe/2, color_description/2, primary/1], PIs),
+    \+ memberchk(op(_, _, _), PIs),
+    module_property(facade, exported_operators([op(200, xfx, by)])),
+    \+ module_property(colors, exported_operators(_)),
+    module_property(facade, file(File)),
+    atom_concat(_, 'facade.pl', File),
+    module_property(facade, class(user)),
+    module_property(lists, class(library)),
+    module_property(loader, class(system)),
+    findall(Module, current_module(Module), Modules),
+    members([facade, shapes, colors, lists], Modules),
+    \+ current_module(no_such_module),
+    catch(module_property(facade, color(_)),
+          error(domain_error(module_property, color(_)), _),
+          true).
+
+:- initialization(test_queries_on_modules).
@@ -0,0 +1,32 @@
+:- module(tests_on_profile, []).
+
+:- use_module(library(between)).
+:- use_module(library(dcgs)).
+:- use_module(library(files)).
+:- use_module(library(lists)).
+:- use_module(library(pio)).
+:- use_module(library(profile)).
+
+app([], Ys, Ys).
+app([X|Xs], Ys, [X|Zs]) :-
+    app(Xs, Ys, Zs).
+
+nrev([], []).
+nrev([X|Xs], Ys) :-
+    nrev(Xs, Ys0),
+    app(Ys0, [X], Ys).
+
+test_queries_on_profile :-
+    numlist(1, 200, Ns),
+    profile(nrev(Ns, Rs), [top(0), folded("profile.folded")]),
+    reverse(Ns, Rs),
+    phrase_from_file(seq(Folded), "profile.folded"),
+    delete_file("profile.folded"),
+    append(_, "tests_on_profile:app/3", Prefix),
+    append(Prefix, _, Folded),
+    !,
+    \+ profile(false, [top(0)]),
+    catch(profile(throw(ball), [top(0)]), ball, true),
+    catch(profile(true, [top(-1)]), error(domain_error(profile_option, top(-1)), _), true).
+
+:- initialization(test_queries_on_profile).
@@ -0,0 +1,45 @@
+:- module(tests_on_statistics, []).
+
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+count_down(0) :- !.
+count_down(N) :-
+    N1 is N - 1,
+    count_down(N1).
+
+test_queries_on_statistics :-
+    statistics(runtime, [T0, _]),
+    statistics(walltime, [W0, _]),
+    statistics(inferences, I0),
+    count_down(1000),
+    statistics(inferences, I1),
+    I1 - I0 >= 1000,
+    statistics(runtime, [T1, _]),
+    T1 >= T0,
+    statistics(walltime, [W1, _]),
+    W1 >= W0,
+    statistics(cputime, C),
+    float(C),
+    forall(member(Key, [heapused, lifted_heap, stack, trail]),
+           (  statistics(Key, Bytes),
+              integer(Bytes),
+              Bytes >= 0
+           )),
+    statistics(heapused, Heap),
+    Heap > 0,
+    statistics(code, [Instructions, CodeBytes]),
+    Instructions > 0,
+    CodeBytes > Instructions,
+    statistics(arena, [_, ArenaBytes]),
+    ArenaBytes > 0,
+    statistics(atoms, [Atoms, _]),
+    Atoms > 0,
+    catch(statistics(_, _),
+          error(instantiation_error, _),
+          true),
+    catch(statistics(1, _),
+          error(type_error(atom, 1), _),
+          true).
+
+:- initialization(test_queries_on_statistics).
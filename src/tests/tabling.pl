@@ -0,0 +1,120 @@
+:- module(tests_on_tabling, []).
+
+:- use_module(library(lists)).
+:- use_module(library(tabling)).
+
+:- table path/2.
+
+path(X, Y) :- path(X, Z), edge(Z, Y).
+path(X, Y) :- edge(X, Y).
+
+edge(a, b).
+edge(b, c).
+edge(c, a).
+edge(c, "d").
+
+:- table even/1, odd/1.
+
+even(0).
+even(N) :- odd(M), M < 10, N is M + 1.
+
+odd(N) :- even(M), M < 10, N is M + 1.
+
+:- table loop/0.
+
+loop :- loop.
+
+:- table dist(_,_,min).
+
+dist(X, Y, D) :- dist(X, Z, D0), weighted_edge(Z, Y, D1), D is D0 + D1.
+dist(X, Y, D) :- weighted_edge(X, Y, D).
+
+weighted_edge(a, b, 1).
+weighted_edge(b, c, 2).
+weighted_edge(a, c, 5).
+weighted_edge(c, a, 1).
+
+:- table route(_,_,lattice(shorter/3)).
+
+route(X, Y, [X, Y]) :- weighted_edge(X, Y, _).
+route(X, Y, P) :- route(X, Z, P0), weighted_edge(Z, Y, _), append(P0, [Y], P).
+
+shorter(P0, P1, P) :-
+    length(P0, N0),
+    length(P1, N1),
+    (  N1 < N0 ->
+       P = P1
+    ;  P = P0
+    ).
+
+:- table first_edge(_,first), last_edge(_,last), heaviest_edge(_,po((<)/2)).
+
+first_edge(X, Y) :- weighted_edge(X, Y, _).
+last_edge(X, Y) :- weighted_edge(X, Y, _).
+heaviest_edge(X, W) :- weighted_edge(X, _, W).
+
+:- table win/1.
+
+win(X) :- move(X, Y), tnot(win(Y)).
+
+move(a, b).
+move(b, a).
+move(b, c).
+move(c, d).
+
+:- table supported/0, blocker/0, unfounded/0, unfounded_loop/0.
+
+supported :- unfounded.
+supported.
+
+blocker :- supported.
+
+unfounded :- tnot(blocker).
+unfounded :- unfounded_loop.
+
+unfounded_loop :- unfounded.
+
+test_queries_on_tabling :-
+    findall(Y, path(a, Y), Ys0),
+    sort(Ys0, [a, b, c, "d"]),
+    findall(X-Y, path(X, Y), Paths),
+    length(Paths, 12),
+    \+ path("d", _),
+    findall(N, even(N), Evens0),
+    sort(Evens0, [0, 2, 4, 6, 8, 10]),
+    \+ loop,
+    dist(a, c, 3),
+    \+ dist(a, c, 5),
+    findall(Y-D, dist(a, Y, D), Dists0),
+    sort(Dists0, [a-4, b-1, c-3]),
+    route(a, c, [a, c]),
+    route(a, a, [a, c, a]),
+    findall(Y, first_edge(a, Y), [b]),
+    findall(Y, last_edge(a, Y), [c]),
+    findall(W, heaviest_edge(a, W), [5]),
+    call_delays(win(c), true),
+    \+ win(d),
+    call_delays(win(a), tests_on_tabling:win(a)),
+    call_delays(win(b), tests_on_tabling:win(b)),
+    call_delays(tnot(win(d)), true),
+    \+ tnot(win(c)),
+    call_delays(tnot(win(a)), tnot(tests_on_tabling:win(a))),
+    call_delays(supported, true),
+    call_delays(blocker, true),
+    \+ unfounded,
+    \+ unfounded_loop,
+    predicate_property(tests_on_tabling:path(_, _), tabled),
+    \+ predicate_property(tests_on_tabling:edge(_, _), tabled),
+    current_table(tests_on_tabling:path(a, _), _),
+    table_statistics(answers, Answers),
+    Answers > 0,
+    abolish_all_tables,
+    table_statistics(tables, 0),
+    findall(Y, path(b, Y), Ys1),
+    sort(Ys1, [a, b, c, "d"]),
+    C = f(C),
+    catch((path(C, _), false),
+          error(representation_error(term), path/2),
+          true).
+
+:- initialization(test_queries_on_tabling).
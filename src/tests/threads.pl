@@ -0,0 +1,30 @@
+:- module(tests_on_threads, []).
+
+:- use_module(library(lists)).
+:- use_module(library(threads)).
+
+sum_to(N, Main) :-
+    numlist(1, N, Ns),
+    sum_list(Ns, S),
+    thread_send_message(Main, sum(N, S)).
+
+echo :-
+    thread_get_message(ping(From)),
+    thread_send_message(From, pong).
+
+test_queries_on_threads :-
+    thread_self(Main),
+    Main == main,
+    findall(Id, (member(N, [10, 20, 30]), thread_create(sum_to(N, Main), Id, [])), Ids),
+    findall(N-S, (member(_, Ids), thread_get_message(sum(N, S))), Sums),
+    msort(Sums, [10-55, 20-210, 30-465]),
+    maplist(thread_join, Ids, [true, true, true]),
+    thread_create(echo, Echo, [alias(echo)]),
+    Echo == echo,
+    thread_send_message(echo, ping(Main)),
+    thread_get_message(pong),
+    thread_join(echo, true),
+    thread_create(throw(oops), Thrower, []),
+    thread_join(Thrower, exception(oops)).
+
+:- initialization(test_queries_on_threads).
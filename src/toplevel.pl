@@ -28,6 +28,19 @@ load_scryerrc :-
 
 :- dynamic(argv/1).
 
+%% main_goal(Module:Goal) is asserted by initialization(Goal, main).
+:- dynamic(main_goal/1).
+
+%% stand_alone is true in an executable written by qsave_program/2.
+:- dynamic(stand_alone/0).
+
+'$repl'([_|Args]) :-
+    stand_alone,
+    !,
+    % the arguments of an executable are all the program's.
+    retractall(argv(_)),
+    asserta('$toplevel':argv(Args)),
+    main_or_repl.
 '$repl'([_|Args0]) :-
     \+ argv(_),
     (   append(Args1, ["--"|Args2], Args0) ->
@@ -38,7 +51,7 @@ load_scryerrc :-
     ),
     delegate_task(Args, []),
     (\+ disabled_init_file -> load_scryerrc ; true),
-    repl.
+    main_or_repl.
 '$repl'(_) :-
     (   \+ argv(_) -> asserta('$toplevel':argv([]))
     ;   true
@@ -51,10 +64,55 @@ delegate_task([], Goals0) :-
     reverse(Goals0, Goals),
     (\+ disabled_init_file -> load_scryerrc ; true),
     run_goals(Goals),
-    repl.
+    main_or_repl.
+
+%% A program declaring initialization(Goal, main) runs Goal in place of
+%% the REPL, and halts with status 0 if it succeeds and status 1 if it
+%% fails or throws an exception. Goal may halt with a status of its own.
+main_or_repl :-
+    (  main_goal(Goal) ->
+       (  catch(Goal, E, (print_exception(E), halt(1))) ->
+          halt(0)
+       ;  halt(1)
+       )
+    ;  repl
+    ).
 
+delegate_task([Arg0, Size|Args], Goals0) :-
+    limit_flag_arg(Arg0, Flag),
+    !,
+    set_limit_flag(Flag, Size),
+    delegate_task(Args, Goals0).
+delegate_task(["--compile", File, "-o", Output|_], _) :-
+    !,
+    atom_chars(Mod, File),
+    atom_chars(Executable, Output),
+    catch(( consult(Mod),
+            use_module(library(saved_states)),
+            saved_states:qsave_program(Executable, [])
+          ),
+          E,
+          (print_exception(E), halt(1))),
+    halt.
+delegate_task(["--profile", File|Args], Goals0) :-
+    !,
+    atom_chars(FileAtom, File),
+    '$profile_at_halt'(FileAtom),
+    delegate_task(Args, Goals0).
+delegate_task(["--coverage", File|Args], Goals0) :-
+    !,
+    atom_chars(FileAtom, File),
+    '$coverage_at_halt'(FileAtom),
+    delegate_task(Args, Goals0).
+delegate_task(["--restore", _|Args], Goals0) :-
+    !,
+    % the machine was restored from the saved state before the
+    % toplevel started.
+    delegate_task(Args, Goals0).
 delegate_task([Arg0|Args], Goals0) :-
     (   member(Arg0, ["-h", "--help"]) -> print_help
+    ;   member(Arg0, ["--restore", "--compile", "--profile", "--coverage"]) -> print_help
+    ;   limit_flag_arg(Arg0, _) -> print_help
     ;   member(Arg0, ["-v", "--version"]) -> print_version
     ;   member(Arg0, ["-g", "--goal"]) -> gather_goal(g, Args, Goals0)
     ;   member(Arg0, ["-f"]) -> disable_init_file
@@ -78,6 +136,24 @@ print_help :-
     write('Fast startup. Do not load initialization file (~/.scryerrc)'), nl,
     write('   --no-add-history       '),
     write('Prevent adding input to history file (~/.scryer_history)'), nl,
+    write('   --restore FILE         '),
+    write('Start from the state saved to FILE by save_program/2'), nl,
+    write('   --compile FILE -o OUT  '),
+    write('Write FILE and the runtime to the executable OUT'), nl,
+    write('   --profile FILE         '),
+    write('Profile the run, writing its call stacks to FILE'), nl,
+    write('   --coverage FILE        '),
+    write('Cover the clauses run, writing LCOV records to FILE'), nl,
+    write('   --max-heap-size SIZE   '),
+    write('Limit the heap to SIZE bytes'), nl,
+    write('   --max-stack-size SIZE  '),
+    write('Limit the stack to SIZE bytes'), nl,
+    write('   --max-trail-size SIZE  '),
+    write('Limit the trail to SIZE bytes'), nl,
+    write('   --max-atoms N          '),
+    write('Limit the atom table to N atoms'), nl,
+    write('                          '),
+    write('SIZE may end in K, M or G'), nl,
     % write('                        '),
     halt.
 
@@ -95,6 +171,33 @@ gather_goal(Type, Args0, Goals) :-
     Gs =.. [Type, Gs1],
     delegate_task(Args, [Gs|Goals]).
 
+limit_flag_arg("--max-heap-size", max_heap_size).
+limit_flag_arg("--max-stack-size", max_stack_size).
+limit_flag_arg("--max-trail-size", max_trail_size).
+limit_flag_arg("--max-atoms", max_atoms).
+
+set_limit_flag(Flag, Size) :-
+    (   Size == "infinite" ->
+        set_prolog_flag(Flag, infinite)
+    ;   append(Digits, [Suffix], Size),
+        size_suffix(Suffix, Multiplier) ->
+        size_limit(Digits, Multiplier, Flag)
+    ;   size_limit(Size, 1, Flag)
+    ).
+
+size_suffix('K', 1024).
+size_suffix('M', 1048576).
+size_suffix('G', 1073741824).
+
+size_limit(Digits, Multiplier, Flag) :-
+    (   catch(number_chars(N, Digits), _, false),
+        integer(N),
+        N >= 0 ->
+        Limit is N * Multiplier,
+        set_prolog_flag(Flag, Limit)
+    ;   print_help
+    ).
+
 disable_init_file :-
     asserta('disabled_init_file').
 
@@ -203,6 +306,29 @@ submit_query_and_print_results_(_, _) :-
     nl.
 
 
+% The entry point of queries run from Rust by Machine::run_query.
+'$run_query'(Term) :-
+    expand_goal(Term, user, Term0),
+    call(user:Term0).
+
+% Defines Module:Name/Arity as a predicate implemented by the Rust
+% closure registered by Machine::register_predicate under Id.
+'$register_native_predicate'(Module, Name, Arity, Id) :-
+    functor(Head, Name, Arity),
+    Head =.. [_ | Args],
+    assertz(Module:(Head :- '$toplevel':'$native_call'(Id, Args))).
+
+'$native_call'(Id, Args) :-
+    '$call_native'(Id, Args, Result),
+    '$native_call_result'(Result, Args).
+
+'$native_call_result'(true, _).
+'$native_call_result'(redo(Redo), _) :-
+    '$native_choicepoint'(Redo).
+'$native_call_result'(redo(Redo), Args) :-
+    '$redo_native'(Redo, Args, Result),
+    '$native_call_result'(Result, Args).
+
 submit_query_and_print_results(Term, VarList) :-
     % (  functor(Term0, call, _) ->
     %    Term = Term0 % prevent pre-mature expansion of incomplete goal
@@ -315,7 +441,8 @@ write_eqs_and_read_input(B, VarList, AttrVars) :-
     charsio:extend_var_list(Vars, VarList, NewVarList, fabricated),
     '$get_b_value'(B0),
     gather_equations(NewVarList, OrigVars, Equations),
-    append(Equations, AttrGoals, Goals),
+    tabling_residue(ResidueGoals, Program),
+    append([Equations, AttrGoals, ResidueGoals], Goals),
     % one layer of depth added for (=/2) functor
     maplist(\Term^Vs^term_variables_under_max_depth(Term, 22, Vs), Equations, EquationVars),
     % maplist(term_variables_under_max_depth(22), Equations, EquationVars),
@@ -323,6 +450,7 @@ write_eqs_and_read_input(B, VarList, AttrVars) :-
     term_variables(Vars1, Vars2), % deduplicate vars of Vars1 but preserve their order.
     charsio:extend_var_list(Vars2, VarList, NewVarList0, fabricated),
     bb_get('$answer_count', Count),
+    write_residual_program(Program, Count),
     (   Count =:= 0 ->
         write('   ')
     ;   true
@@ -342,6 +470,39 @@ write_eqs_and_read_input(B, VarList, AttrVars) :-
        read_input(ThreadedGoals, NewVarList0)
     ).
 
+% the goals an answer that's undefined under the well-founded semantics
+% is conditional on, and the residual program they depend on.
+tabling_residue(Goals, Program) :-
+    (  '$module_exists'(tabling),
+       tabling:residual_program(Goals0, Program0) ->
+       Goals = Goals0,
+       Program = Program0
+    ;  Goals = [],
+       Program = []
+    ).
+
+write_residual_program(Program, Count) :-
+    (  Program == [] ->
+       true
+    ;  (  Count > 0 ->
+          nl
+       ;  true
+       ),
+       write('% WFS residual program'), nl,
+       double_quotes_option(DQ),
+       forall(member((Head :- Body), Program),
+              (  write('    '),
+                 write_term(Head, [quoted(true), double_quotes(DQ)]),
+                 write(' :- '),
+                 write_term(Body, [quoted(true), double_quotes(DQ)]),
+                 write('.'), nl
+              )),
+       (  Count > 0 ->
+          write('   ')
+       ;  true
+       )
+    ).
+
 read_input(ThreadedGoals, NewVarList) :-
     (  bb_get('$report_all', true) ->
        C = n
@@ -432,6 +593,25 @@ gather_equations([Var = Value | Pairs], OrigVarList, Goals) :-
     ).
 
 print_exception(E) :-
+    (  E == '$aborted' ->
+       % thrown by the abort command of the tracer.
+       write('% Execution aborted'),
+       nl
+    ;  print_exception_(E)
+    ).
+
+print_exception_(E0) :-
+    (  '$caught_backtrace'(Frames0) ->
+       true
+    ;  Frames0 = []
+    ),
+    (  nonvar(E0),
+       E0 = error(Formal, backtrace(Context, Frames)) ->
+       % thrown through catch_with_backtrace/3.
+       E = error(Formal, Context)
+    ;  E = E0,
+       Frames = Frames0
+    ),
     (  E == error('$interrupt_thrown', repl) -> nl % print the
                                                    % exception on a
                                                    % newline to evade
@@ -439,7 +619,30 @@ print_exception(E) :-
     ;  true
     ),
     loader:write_error(E),
-    nl.
+    nl,
+    print_backtrace(Frames).
+
+print_backtrace([]).
+print_backtrace([frame(PI, N, File, Line)|Frames]) :-
+    write('%   '),
+    writeq(PI),
+    (  N > 0 ->
+       write(', clause '),
+       write(N)
+    ;  true
+    ),
+    (  File == [] ->
+       true
+    ;  write(', '),
+       write(File),
+       (  Line == [] ->
+          true
+       ;  write(':'),
+          write(Line)
+       )
+    ),
+    nl,
+    print_backtrace(Frames).
 
 print_exception_with_check(E) :-
     (  E = error(_, _:_) -> true % if the error source contains a line
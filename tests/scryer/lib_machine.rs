@@ -0,0 +1,713 @@
+use scryer_prolog::machine::Machine;
+use scryer_prolog::machine::parsed_results::*;
+use serial_test::serial;
+
+#[serial]
+#[test]
+fn run_query_binds_variables() {
+    let mut machine = Machine::with_test_streams();
+
+    let answers: Vec<_> = machine.run_query("X = f(a, \"bc\", [1,2|T]), Y = 3.5, Z = [b,c].")
+        .unwrap()
+        .collect();
+
+    assert_eq!(answers.len(), 1);
+
+    let answer = answers[0].as_ref().unwrap();
+
+    assert_eq!(
+        answer.get("X"),
+        Some(&Value::Structure(
+            "f".to_string(),
+            vec![
+                Value::Atom("a".to_string()),
+                Value::String("bc".to_string()),
+                Value::Structure(
+                    ".".to_string(),
+                    vec![
+                        Value::Integer(1.into()),
+                        Value::Structure(
+                            ".".to_string(),
+                            vec![Value::Integer(2.into()), Value::Var("T".to_string())],
+                        ),
+                    ],
+                ),
+            ],
+        )),
+    );
+
+    assert_eq!(answer.get("Y"), Some(&Value::Float(3.5.into())));
+
+    // lists of characters are only read as strings if they are strings.
+    assert_eq!(
+        answer.get("Z"),
+        Some(&Value::List(vec![Value::Atom("b".to_string()), Value::Atom("c".to_string())])),
+    );
+}
+
+#[serial]
+#[test]
+fn run_query_reads_cyclic_partial_strings() {
+    let mut machine = Machine::with_test_streams();
+
+    assert_eq!(machine.run_query("use_module(library(iso_ext)).").unwrap().count(), 1);
+
+    let answers: Vec<_> = machine
+        .run_query("partial_string(\"ab\", X, X).")
+        .unwrap()
+        .collect();
+
+    assert_eq!(answers.len(), 1);
+
+    match answers[0].as_ref().unwrap().get("X") {
+        Some(Value::Structure(name, args)) => {
+            assert_eq!(name, ".");
+            assert_eq!(args[0], Value::Atom("a".to_string()));
+
+            match &args[1] {
+                Value::Structure(name, args) => {
+                    assert_eq!(name, ".");
+                    assert_eq!(args[0], Value::Atom("b".to_string()));
+                    assert!(matches!(&args[1], Value::Var(var) if var.starts_with("_S")));
+                }
+                value => panic!("unexpected tail {:?}", value),
+            }
+        }
+        value => panic!("unexpected binding {:?}", value),
+    }
+}
+
+#[serial]
+#[test]
+fn run_query_backtracks_on_demand() {
+    let mut machine = Machine::with_test_streams();
+
+    let mut solutions = machine.run_query("(X = a ; X = b ; X = c)").unwrap();
+
+    let first = solutions.next().unwrap().unwrap();
+    assert_eq!(first.get("X"), Some(&Value::Atom("a".to_string())));
+
+    let rest: Vec<_> = solutions
+        .map(|answer| answer.unwrap().bindings["X"].clone())
+        .collect();
+
+    assert_eq!(rest, vec![Value::Atom("b".to_string()), Value::Atom("c".to_string())]);
+
+    assert_eq!(machine.run_query("false.").unwrap().count(), 0);
+}
+
+#[serial]
+#[test]
+fn run_query_reports_exceptions() {
+    let mut machine = Machine::with_test_streams();
+
+    let answers: Vec<_> = machine.run_query("X = 1 ; throw(oops)").unwrap().collect();
+
+    assert_eq!(answers.len(), 2);
+    assert!(answers[0].is_ok());
+    assert_eq!(answers[1], Err(Value::Atom("oops".to_string())));
+
+    let caught: Vec<_> = machine.run_query("catch(throw(oops), E, true)").unwrap().collect();
+    assert_eq!(caught[0].as_ref().unwrap().get("E"), Some(&Value::Atom("oops".to_string())));
+
+    assert!(machine.run_query("X = ").is_err());
+}
+
+#[serial]
+#[test]
+fn run_query_bindings_survive_garbage_collection() {
+    let mut machine = Machine::with_test_streams();
+
+    let answers: Vec<_> = machine
+        .run_query("atom_codes(abcdefghij, _), X = f(Y), (Z = a ; Z = b), garbage_collect, Y = [1, Z]")
+        .unwrap()
+        .map(|answer| answer.unwrap().bindings["X"].clone())
+        .collect();
+
+    assert_eq!(
+        answers,
+        vec![
+            Value::structure("f", vec![Value::List(vec![Value::from(1i64), Value::atom("a")])]),
+            Value::structure("f", vec![Value::List(vec![Value::from(1i64), Value::atom("b")])]),
+        ],
+    );
+}
+
+#[serial]
+#[test]
+fn run_query_unreferenced_atoms_are_collected() {
+    let mut machine = Machine::with_test_streams();
+
+    for i in 0..1000 {
+        let query = format!("atom_codes(_, \"dynamic_atom_{}\")", i);
+        assert_eq!(machine.run_query(&query).unwrap().count(), 1);
+    }
+
+    let answers: Vec<_> = machine
+        .run_query(
+            "statistics(atoms, [N0, _]), \
+             statistics(atom_garbage_collection, [_, C0, _]), \
+             garbage_collect_atoms, \
+             statistics(atoms, [N1, _]), \
+             statistics(atom_garbage_collection, [_, C1, _]), \
+             N0 - N1 >= 900, C1 - C0 >= 900, \
+             atom_codes(A, \"dynamic_atom_7\"), atom_length(A, 14)",
+        )
+        .unwrap()
+        .map(|answer| answer.unwrap().bindings["A"].clone())
+        .collect();
+
+    assert_eq!(answers, vec![Value::atom("dynamic_atom_7")]);
+}
+
+#[serial]
+#[test]
+fn resource_limits_throw_memory_errors() {
+    let mut machine = Machine::with_test_streams();
+
+    let clauses = "assertz((deep(N) :- N1 is N + 1, deep(N1), N1 > 0)), \
+                   assertz((long(L) :- long([x|L])))";
+
+    assert_eq!(machine.run_query(clauses).unwrap().count(), 1);
+
+    // length/2 builds the whole list in one builtin call.
+    let goals = [
+        ("max_stack_size", "deep(0)"),
+        ("max_heap_size", "long([])"),
+        ("max_heap_size", "length(_, 1000000)"),
+    ];
+
+    for (flag, goal) in goals {
+        let query = format!(
+            "set_prolog_flag({flag}, 1048576), \
+             catch({goal}, error(resource_error(memory), _), E = caught), \
+             set_prolog_flag({flag}, infinite), \
+             current_prolog_flag({flag}, Limit)",
+        );
+
+        let answers: Vec<_> = machine
+            .run_query(&query)
+            .unwrap()
+            .map(|answer| answer.unwrap())
+            .collect();
+
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].get("E"), Some(&Value::atom("caught")));
+        assert_eq!(answers[0].get("Limit"), Some(&Value::atom("infinite")));
+    }
+
+    let answers: Vec<_> = machine
+        .run_query("catch(set_prolog_flag(max_atoms, -1), error(E, _), true)")
+        .unwrap()
+        .map(|answer| answer.unwrap().bindings["E"].clone())
+        .collect();
+
+    assert_eq!(
+        answers,
+        vec![Value::structure(
+            "domain_error",
+            vec![
+                Value::atom("flag_value"),
+                Value::structure("+", vec![Value::atom("max_atoms"), Value::from(-1)]),
+            ],
+        )],
+    );
+}
+
+#[serial]
+#[test]
+fn statistics_prints_summary() {
+    let mut machine = Machine::with_test_streams();
+
+    assert_eq!(machine.run_query("statistics").unwrap().count(), 1);
+
+    let output = String::from_utf8(machine.read_user_output()).unwrap();
+    let labels: Vec<_> = output
+        .lines()
+        .map(|line| line.split(':').next().unwrap())
+        .collect();
+
+    assert_eq!(
+        labels,
+        vec![
+            "% Runtime", "% Inferences", "% Heap", "% Lifted heap", "% Stack",
+            "% Trail", "% Atoms", "% Code", "% Arena", "% Heap GC", "% Atom GC",
+        ],
+    );
+}
+
+#[serial]
+#[test]
+fn run_query_with_bindings_passes_arguments() {
+    let mut machine = Machine::with_test_streams();
+
+    let args = [
+        ("X", Value::from(21i64)),
+        ("L", Value::from(vec![Value::atom("a"), Value::string("bc"), Value::var("T")])),
+    ];
+
+    let answers: Vec<_> = machine
+        .run_query_with_bindings("Y is X * 2, L = [A, B|_], length(B, N)", args)
+        .unwrap()
+        .collect();
+
+    let answer = answers[0].as_ref().unwrap();
+
+    assert_eq!(answer.get("Y"), Some(&Value::from(42i64)));
+    assert_eq!(answer.get("A"), Some(&Value::atom("a")));
+    assert_eq!(answer.get("B"), Some(&Value::string("bc")));
+    assert_eq!(answer.get("N"), Some(&Value::from(2i64)));
+}
+
+#[serial]
+#[test]
+fn run_query_with_bindings_reports_unwritable_bindings() {
+    let mut machine = Machine::with_test_streams();
+
+    let args = [("X", Value::Structure("f".to_string(), vec![Value::from(0i64); 2000]))];
+
+    let err = match machine.run_query_with_bindings("X = _", args) {
+        Ok(_) => panic!("the binding was written"),
+        Err(err) => err,
+    };
+
+    // bindings beyond the maximum arity aren't syntax errors.
+    match err {
+        Value::Structure(name, args) => {
+            assert_eq!(name, "error");
+            assert_eq!(
+                args[0],
+                Value::Structure(
+                    "representation_error".to_string(),
+                    vec![Value::atom("max_arity")],
+                ),
+            );
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[serial]
+#[test]
+fn machine_builder_configures_streams_libraries_and_flags() {
+    use scryer_prolog::machine::config::*;
+
+    let mut machine = MachineBuilder::default()
+        .with_user_input(StreamConfig::from_string("hello."))
+        .with_user_output(StreamConfig::in_memory())
+        .with_add_history(false)
+        .with_current_thread_runtime()
+        .with_preloaded_library("assoc")
+        .with_use_module("lists")
+        .with_prolog_flag("double_quotes", Value::atom("codes"))
+        .build()
+        .unwrap();
+
+    let answers: Vec<_> = machine
+        .run_query("read(T), write(T), length(L, 2), X = \"ab\"")
+        .unwrap()
+        .collect();
+
+    let answer = answers[0].as_ref().unwrap();
+
+    assert_eq!(answer.get("T"), Some(&Value::atom("hello")));
+    assert_eq!(answer.get("X"), Some(&Value::from(vec![97i64, 98i64])));
+    assert_eq!(machine.read_user_output(), b"hello");
+
+    // the preloaded library is compiled but not imported.
+    let answers: Vec<_> = machine
+        .run_query("assoc:list_to_assoc([a-1], A), assoc:get_assoc(a, A, V)")
+        .unwrap()
+        .collect();
+
+    assert_eq!(answers[0].as_ref().unwrap().get("V"), Some(&Value::from(1i64)));
+
+    let answers: Vec<_> = machine.run_query("empty_assoc(A)").unwrap().collect();
+
+    assert!(answers[0].is_err());
+
+    // the atom table is shared by the process, so only one machine
+    // can be alive at a time.
+    drop(machine);
+
+    let err = MachineBuilder::default()
+        .with_user_input(StreamConfig::null())
+        .with_current_thread_runtime()
+        .with_prolog_flag("no_such_flag", Value::atom("true"))
+        .build();
+
+    assert!(err.is_err());
+}
+
+#[serial]
+#[test]
+fn debugger_reports_ports_and_obeys_commands() {
+    use scryer_prolog::machine::config::*;
+
+    // the commands read at the leashed ports of the second query.
+    let mut machine = MachineBuilder::default()
+        .with_user_input(StreamConfig::from_string("srcccccc"))
+        .with_user_output(StreamConfig::in_memory())
+        .with_user_error(StreamConfig::in_memory())
+        .with_add_history(false)
+        .with_current_thread_runtime()
+        .with_use_module("debug")
+        .build()
+        .unwrap();
+
+    let clauses = "assertz(fact(a)), assertz(fact(b)), \
+                   assertz((test :- fact(a), fact(c))), \
+                   assertz((first :- fact(a), fact(b))), \
+                   assertz((ball :- throw(ball)))";
+
+    assert_eq!(machine.run_query(clauses).unwrap().count(), 1);
+
+    let answers: Vec<_> = machine
+        .run_query("visible(all), leash(none), trace, \\+ test, catch(ball, _, true), notrace")
+        .unwrap()
+        .collect();
+
+    assert_eq!(answers.len(), 1);
+    assert!(answers[0].is_ok());
+
+    let output = String::from_utf8(machine.read_user_error()).unwrap();
+
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        vec![
+            "   Call: (1) test",
+            "   Call: (2) fact(a)",
+            "   Exit: (2) fact(a)",
+            "   Call: (2) fact(c)",
+            "   Fail: (2) fact(c)",
+            "   Redo: (2) fact(a)",
+            "   Fail: (2) fact(a)",
+            "   Fail: (1) test",
+            "   Call: (1) ball",
+            "   Exception: (1) ball raised ball",
+        ],
+    );
+
+    // skip first to its exit, then retry it and creep through it.
+    let answers: Vec<_> = machine
+        .run_query("leash([call, exit]), trace, first, notrace")
+        .unwrap()
+        .collect();
+
+    assert_eq!(answers.len(), 1);
+    assert!(answers[0].is_ok());
+
+    let output = String::from_utf8(machine.read_user_error()).unwrap();
+
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        vec![
+            "   Call: (1) first ? skip",
+            "   Exit: (1) first ? retry",
+            "   Call: (1) first ? creep",
+            "   Call: (2) fact(a) ? creep",
+            "   Exit: (2) fact(a) ? creep",
+            "   Call: (2) fact(b) ? creep",
+            "   Exit: (2) fact(b) ? creep",
+            "   Exit: (1) first ? creep",
+        ],
+    );
+}
+
+#[serial]
+#[test]
+fn register_native_predicates() {
+    use scryer_prolog::machine::native_predicates::*;
+
+    let mut machine = Machine::with_test_streams();
+
+    machine
+        .register_predicate("user", "double", 2, |call| match call.arg(0) {
+            Value::Integer(n) => {
+                if call.unify(1, &Value::Integer(&n + &n)) {
+                    NativeResult::True
+                } else {
+                    NativeResult::False
+                }
+            }
+            Value::Var(_) => call.instantiation_error(),
+            culprit => call.type_error("integer", culprit),
+        })
+        .unwrap();
+
+    fn count_from(n: i64, max: i64) -> NativeFn {
+        Box::new(move |call| {
+            if n > max || !call.unify(1, &Value::from(n)) {
+                NativeResult::False
+            } else if n == max {
+                NativeResult::True
+            } else {
+                NativeResult::Redo(count_from(n + 1, max))
+            }
+        })
+    }
+
+    machine
+        .register_predicate("user", "upto", 2, |call| match call.arg(0) {
+            Value::Integer(max) => match i64::try_from(max) {
+                Ok(max) => count_from(1, max)(call),
+                Err(_) => call.representation_error("max_integer"),
+            },
+            _ => call.instantiation_error(),
+        })
+        .unwrap();
+
+    let answer = machine.run_query("double(21, X)").unwrap().next().unwrap().unwrap();
+    assert_eq!(answer.get("X"), Some(&Value::from(42i64)));
+
+    assert_eq!(machine.run_query("double(21, 43)").unwrap().count(), 0);
+
+    let xs: Vec<_> = machine
+        .run_query("upto(3, X)")
+        .unwrap()
+        .map(|answer| answer.unwrap().bindings["X"].clone())
+        .collect();
+
+    assert_eq!(xs, vec![Value::from(1i64), Value::from(2i64), Value::from(3i64)]);
+
+    let answer = machine.run_query("findall(X, (upto(4, X), X > 2), Xs)").unwrap().next();
+    assert_eq!(
+        answer.unwrap().unwrap().get("Xs"),
+        Some(&Value::from(vec![3i64, 4i64])),
+    );
+
+    let err = machine.run_query("double(a, X)").unwrap().next().unwrap();
+    assert_eq!(
+        err,
+        Err(Value::structure(
+            "error",
+            vec![
+                Value::structure("type_error", vec![Value::atom("integer"), Value::atom("a")]),
+                Value::structure("/", vec![Value::atom("double"), Value::from(2i64)]),
+            ],
+        )),
+    );
+}
+
+#[serial]
+#[test]
+fn native_predicates_report_unwritable_values_and_unknown_ids() {
+    use scryer_prolog::machine::native_predicates::*;
+
+    let mut machine = Machine::with_test_streams();
+
+    machine
+        .register_predicate("user", "wide", 1, |call| {
+            let wide = Value::Structure("f".to_string(), vec![Value::from(0i64); 2000]);
+
+            if call.unify(0, &wide) {
+                NativeResult::True
+            } else {
+                NativeResult::False
+            }
+        })
+        .unwrap();
+
+    match machine.run_query("wide(X)").unwrap().next().unwrap() {
+        Err(Value::Structure(name, args)) => {
+            assert_eq!(name, "error");
+            assert!(matches!(&args[0], Value::Structure(name, _) if name == "resource_error"));
+        }
+        answer => panic!("unexpected answer {:?}", answer),
+    }
+
+    // ids that no predicate was registered under, as in a restored
+    // saved state, raise an existence error.
+    match machine.run_query("'$toplevel':'$native_call'(100, [])").unwrap().next().unwrap() {
+        Err(Value::Structure(name, args)) => {
+            assert_eq!(name, "error");
+            assert_eq!(
+                args[0],
+                Value::structure(
+                    "existence_error",
+                    vec![Value::atom("native_predicate"), Value::from(100i64)],
+                ),
+            );
+        }
+        answer => panic!("unexpected answer {:?}", answer),
+    }
+}
+
+#[serial]
+#[test]
+fn cut_native_choicepoints_free_their_redos() {
+    use scryer_prolog::machine::native_predicates::*;
+    use std::rc::Rc;
+
+    let mut machine = Machine::with_test_streams();
+    let tracker = Rc::new(());
+
+    fn tick(tracker: Rc<()>) -> NativeFn {
+        Box::new(move |call| {
+            if call.unify(0, &Value::atom("tick")) {
+                NativeResult::Redo(tick(tracker.clone()))
+            } else {
+                NativeResult::False
+            }
+        })
+    }
+
+    let ticks_tracker = tracker.clone();
+
+    machine
+        .register_predicate("user", "ticks", 1, move |call| tick(ticks_tracker.clone())(call))
+        .unwrap();
+
+    let live_tracker = tracker.clone();
+
+    machine
+        .register_predicate("user", "live_redos", 1, move |call| {
+            let live = Rc::strong_count(&live_tracker) as i64;
+
+            if call.unify(0, &Value::from(live)) {
+                NativeResult::True
+            } else {
+                NativeResult::False
+            }
+        })
+        .unwrap();
+
+    let answer = machine
+        .run_query("live_redos(Before), forall(between(1, 100, _), once(ticks(_))), live_redos(After)")
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(answer.get("After"), answer.get("Before"));
+}
+
+#[serial]
+#[test]
+fn saved_states_restore_programs() {
+    use scryer_prolog::machine::config::*;
+
+    let path = std::env::temp_dir().join("scryer_prolog_saved_state_test.qls");
+    let mut machine = Machine::with_test_streams();
+
+    let query = format!(
+        "use_module(library(lists)), use_module(library(saved_states)), \
+         op(700, xfx, ===>), set_prolog_flag(double_quotes, atom), \
+         assertz(p(1, 1.5, 123456789012345678901234567890)), \
+         assertz(p(2, f(X, X), \"two\")), \
+         R is 1 rdiv 3, assertz(p(3, R, [a|b])), \
+         retract(p(2, _, _)), \
+         save_program('{}', [])",
+        path.display(),
+    );
+
+    assert_eq!(machine.run_query(&query).unwrap().count(), 1);
+
+    drop(machine);
+
+    let mut machine = MachineBuilder::default()
+        .with_user_input(StreamConfig::null())
+        .with_user_output(StreamConfig::in_memory())
+        .with_current_thread_runtime()
+        .restore(&path)
+        .unwrap();
+
+    let query = "findall(N, p(N, _, _), Ns), \
+                 p(1, F, I), F =:= 1.5, I =:= 123456789012345678901234567890, \
+                 p(3, R, [a|b]), R =:= 1 rdiv 3, \
+                 append(Ns, [4], Ms), T = (a ===> b), \
+                 current_prolog_flag(double_quotes, DQ)";
+
+    let answers: Vec<_> = machine
+        .run_query(query)
+        .unwrap()
+        .map(|answer| answer.unwrap())
+        .collect();
+
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].get("Ms"), Some(&Value::from(vec![1i64, 3, 4])));
+    assert_eq!(answers[0].get("DQ"), Some(&Value::atom("atom")));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[serial]
+#[test]
+fn saved_states_refuse_machines_with_native_predicates() {
+    use scryer_prolog::machine::native_predicates::*;
+
+    let path = std::env::temp_dir().join("scryer_prolog_native_saved_state_test.qls");
+    let mut machine = Machine::with_test_streams();
+
+    machine
+        .register_predicate("user", "native_true", 0, |_| NativeResult::True)
+        .unwrap();
+
+    let query = format!(
+        "use_module(library(saved_states)), save_program('{}', [])",
+        path.display(),
+    );
+
+    match machine.run_query(&query).unwrap().next().unwrap() {
+        Err(Value::Structure(name, args)) => {
+            assert_eq!(name, "error");
+            assert_eq!(
+                args[0],
+                Value::structure(
+                    "permission_error",
+                    vec![
+                        Value::atom("save"),
+                        Value::atom("native_predicate"),
+                        Value::atom("native_true"),
+                    ],
+                ),
+            );
+        }
+        answer => panic!("unexpected answer {:?}", answer),
+    }
+
+    assert!(!path.exists());
+}
+
+#[serial]
+#[test]
+fn qsave_program_writes_executables() {
+    let dir = std::env::temp_dir();
+    let source = dir.join("scryer_prolog_executable_test.pl");
+    let executable = dir.join("scryer_prolog_executable_test");
+
+    std::fs::write(
+        &source,
+        ":- initialization(main, main).\n\nmain :- argv(Args), length(Args, N), halt(N).\n",
+    )
+    .unwrap();
+
+    let mut machine = Machine::with_test_streams();
+
+    let query = format!(
+        "use_module(library(saved_states)), consult('{}'), \
+         '$toplevel':main_goal(Module:main), \
+         qsave_program('{}', []), \
+         \\+ '$toplevel':stand_alone",
+        source.display(),
+        executable.display(),
+    );
+
+    let answers: Vec<_> = machine
+        .run_query(&query)
+        .unwrap()
+        .map(|answer| answer.unwrap())
+        .collect();
+
+    assert_eq!(answers.len(), 1);
+    assert_eq!(answers[0].get("Module"), Some(&Value::atom("user")));
+
+    let runtime = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let written = std::fs::read(&executable).unwrap();
+
+    assert!(written.starts_with(&runtime));
+    assert!(written.ends_with(b"SCRYEREX"));
+
+    std::fs::remove_file(&source).unwrap();
+    std::fs::remove_file(&executable).unwrap();
+}
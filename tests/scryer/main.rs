@@ -1,3 +1,4 @@
 mod helper;
 mod issues;
+mod lib_machine;
 mod src_tests;
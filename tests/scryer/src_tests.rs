@@ -1,6 +1,12 @@
 use crate::helper::{load_module_test, run_top_level_test_with_args};
 use serial_test::serial;
 
+#[serial]
+#[test]
+fn backtrace() {
+    load_module_test("src/tests/backtrace.pl", "");
+}
+
 #[serial]
 #[test]
 fn builtins() {
@@ -13,18 +19,157 @@ fn call_with_inference_limit() {
     load_module_test("src/tests/call_with_inference_limit.pl", "");
 }
 
+#[serial]
+#[test]
+fn clause_property() {
+    load_module_test("src/tests/clause_property.pl", "");
+}
+
+#[test]
+fn compiled_executables_take_their_arguments() {
+    let dir = std::env::temp_dir();
+    let source = dir.join("scryer_tests_on_executables.pl");
+    let executable = dir.join("scryer_tests_on_executables");
+
+    std::fs::write(
+        &source,
+        ":- use_module(library(lists)).\n\
+         :- initialization(main, main).\n\n\
+         main :- argv(Args), maplist(atom_chars, As, Args), write(As), nl, \
+         length(Args, N), halt(N).\n",
+    )
+    .unwrap();
+
+    run_top_level_test_with_args(
+        &[
+            "--compile",
+            source.to_str().unwrap(),
+            "-o",
+            executable.to_str().unwrap(),
+        ],
+        "",
+        "",
+    );
+
+    // all the arguments of an executable, including those after `--`,
+    // are the program's, and halt/1 sets its exit code.
+    assert_cmd::Command::new(&executable)
+        .args(["a", "--", "-g", "b"])
+        .assert()
+        .stdout("[a,--,-g,b]\n")
+        .code(4);
+
+    std::fs::remove_file(&source).unwrap();
+    std::fs::remove_file(&executable).unwrap();
+}
+
+#[serial]
+#[test]
+fn coroutines() {
+    load_module_test("src/tests/coroutines.pl", "");
+}
+
+#[serial]
+#[test]
+fn coverage() {
+    load_module_test("src/tests/coverage.pl", "");
+}
+
+#[test]
+fn coverage_output_survives_atom_collection() {
+    let path = std::env::temp_dir().join("scryer_tests_on_coverage_atoms.info");
+    let _ = std::fs::remove_file(&path);
+
+    // the atoms built after the collection reuse the slots of the
+    // atoms it collected.
+    run_top_level_test_with_args(
+        &[
+            "--coverage",
+            path.to_str().unwrap(),
+            "-g",
+            "use_module(library(between))",
+            "-g",
+            "garbage_collect_atoms, \
+             forall(between(1, 1000, N), (number_codes(N, Cs), atom_codes(_, [0'c|Cs])))",
+            "-g",
+            "halt",
+        ],
+        "",
+        "",
+    );
+
+    // the coverage is written to the file named on the command line,
+    // not to the name of an atom that took over its slot.
+    assert!(path.exists());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[serial]
+#[test]
+fn debugger() {
+    load_module_test("src/tests/debugger.pl", "");
+}
+
+#[serial]
+#[test]
+fn engines() {
+    load_module_test("src/tests/engines.pl", "");
+}
+
+#[serial]
+#[test]
+fn fact_tables() {
+    load_module_test("src/tests/fact_tables.pl", "");
+}
+
 #[serial]
 #[test]
 fn facts() {
     load_module_test("src/tests/facts.pl", "");
 }
 
+#[serial]
+#[test]
+fn garbage_collection() {
+    load_module_test("src/tests/garbage_collection.pl", "");
+}
+
 #[serial]
 #[test]
 fn hello_world() {
     load_module_test("src/tests/hello_world.pl", "Hello World!\n");
 }
 
+#[serial]
+#[test]
+fn jit_index() {
+    load_module_test("src/tests/jit_index.pl", "");
+}
+
+#[serial]
+#[test]
+fn make() {
+    load_module_test("src/tests/make.pl", "");
+}
+
+#[serial]
+#[test]
+fn modules() {
+    load_module_test("src/tests/modules/test_modules.pl", "");
+}
+
+#[serial]
+#[test]
+fn profile() {
+    load_module_test("src/tests/profile.pl", "");
+}
+
+#[serial]
+#[test]
+fn statistics() {
+    load_module_test("src/tests/statistics.pl", "");
+}
+
 #[serial]
 #[test]
 fn syntax_error() {
@@ -34,6 +179,18 @@ fn syntax_error() {
     );
 }
 
+#[serial]
+#[test]
+fn tabling() {
+    load_module_test("src/tests/tabling.pl", "");
+}
+
+#[serial]
+#[test]
+fn threads() {
+    load_module_test("src/tests/threads.pl", "");
+}
+
 #[serial]
 #[test]
 fn predicates() {